@@ -14,9 +14,7 @@ use configuration::{
 use net_packets::{ButtonEntry, ButtonValue, FaceData, TrackingData};
 use shared::{
     DeviceMotion, NANVR_HIGH_NAME, NanvrCodecType, NanvrFov, NanvrPose, NanvrQuat, NanvrViewParams,
-    Pose, ViewParams,
-    anyhow::Result,
-    debug, error,
+    Pose, ViewParams, debug, error,
     glam::{UVec2, Vec2, Vec3},
     info,
     parking_lot::Mutex,
@@ -288,7 +286,9 @@ pub extern "C" fn nanvr_poll_event(out_event: *mut NanvrEvent) -> bool {
                 frequency,
                 amplitude,
             },
-            ClientCoreEvent::DecoderConfig { codec, config_nal } => {
+            ClientCoreEvent::DecoderConfig {
+                codec, config_nal, ..
+            } => {
                 *DECODER_CONFIG_BUFFER.lock() = config_nal;
 
                 NanvrEvent::DecoderConfig {
@@ -885,22 +885,10 @@ pub extern "C" fn nanvr_create_decoder(config: NanvrDecoderConfig) {
         },
     };
 
-    let (mut sink, source) =
-        video_decoder::create_decoder(config, |maybe_timestamp: Result<Duration>| {
-            if let Some(context) = &*CLIENT_CORE_CONTEXT.lock() {
-                match maybe_timestamp {
-                    Ok(timestamp) => context.report_frame_decoded(timestamp),
-                    Err(e) => context.report_fatal_decoder_error(&e.to_string()),
-                }
-            }
-        });
-
-    *DECODER_SOURCE.lock() = Some(source);
-
     if let Some(context) = &*CLIENT_CORE_CONTEXT.lock() {
-        context.set_decoder_input_callback(Box::new(move |timestamp, buffer| {
-            sink.push_nal(timestamp, buffer)
-        }));
+        *DECODER_SOURCE.lock() = shared::show_err(
+            context.attach_video_decoder(video_decoder::default_decoder(), config),
+        );
     }
 }
 
@@ -7,7 +7,7 @@ use crate::{
     statistics::StatisticsManager,
     storage::Config,
 };
-use configuration::{SocketProtocol, settings_schema::Switch};
+use configuration::{CustomAudioDeviceConfig, SocketProtocol, settings_schema::Switch};
 use const_format::formatcp;
 use net_packets::{
     AUDIO, ClientConnectionResult, ClientControlPacket, ClientStatistics, HAPTICS, Haptics,
@@ -20,12 +20,15 @@ use net_sockets::{
 };
 use shared::{
     AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState, NANVR_NAME,
-    NANVR_VERSION, ViewParams, dbg_connection, debug, error, info,
+    NANVR_VERSION, ViewParams,
+    anyhow::Result,
+    dbg_connection, debug, error, info,
     parking_lot::{Condvar, Mutex, RwLock},
     wait_rwlock, warn,
 };
 use std::{
     collections::VecDeque,
+    net::IpAddr,
     sync::{Arc, mpsc},
     thread,
     time::{Duration, Instant},
@@ -70,11 +73,32 @@ pub struct ConnectionContext {
     pub max_prediction: RwLock<Duration>,
 }
 
+impl ConnectionContext {
+    pub fn report_frame_decoded(&self, timestamp: Duration) {
+        if let Some(stats) = &mut *self.statistics_manager.lock() {
+            stats.report_frame_decoded(timestamp);
+        }
+    }
+
+    pub fn report_fatal_decoder_error(&self, error: &str) {
+        error!("Fatal decoder error, restarting connection: {error}");
+
+        // The connection loop observes changes on this value
+        *self.state.write() = ConnectionState::Disconnecting;
+    }
+}
+
 fn set_hud_message(event_queue: &Mutex<VecDeque<ClientCoreEvent>>, message: &str) {
+    let config = Config::load();
+    let pinned_server_line = config
+        .pinned_server()
+        .map(|server| format!("\npinned streamer: {}", server.name))
+        .unwrap_or_default();
+
     let message = format!(
-        "{NANVR_NAME} {}\nhostname: {}\nIP: {}\n\n{message}",
+        "{NANVR_NAME} {}\nhostname: {}\nIP: {}{pinned_server_line}\n\n{message}",
         NANVR_VERSION.to_owned(),
-        Config::load().hostname,
+        config.hostname,
         system_info::local_ip(),
     );
 
@@ -83,6 +107,47 @@ fn set_hud_message(event_queue: &Mutex<VecDeque<ClientCoreEvent>>, message: &str
         .push_back(ClientCoreEvent::UpdateHudMessage(message));
 }
 
+fn server_refused_message(server_ip: IpAddr, reason: &str) -> String {
+    format!(
+        "Refused connection from {server_ip}:\n\
+        {reason}.\n\
+        Unpin or forget it in the server list to allow it."
+    )
+}
+
+// Falls back to the default device if the selected one is not available
+fn output_device(config: Option<&CustomAudioDeviceConfig>) -> Result<sound::Device> {
+    if let Some(config) = config {
+        match sound::new_output(Some(config)) {
+            Ok(device) => return Ok(device),
+            Err(e) => warn!("{e}, using the default audio output"),
+        }
+    }
+
+    sound::new_output(None)
+}
+
+// The microphone sample rate has been negotiated with the default device, other devices can be
+// used only if they match it
+fn input_device(
+    config: Option<CustomAudioDeviceConfig>,
+    sample_rate: u32,
+) -> Result<sound::Device> {
+    if let Some(config) = config {
+        match sound::new_input(Some(config)) {
+            Ok(device) if sound::input_sample_rate(&device).ok() == Some(sample_rate) => {
+                return Ok(device);
+            }
+            Ok(_) => {
+                warn!("Selected microphone does not run at {sample_rate} Hz, using the default one")
+            }
+            Err(e) => warn!("{e}, using the default microphone"),
+        }
+    }
+
+    sound::new_input(None)
+}
+
 fn is_streaming(ctx: &ConnectionContext) -> bool {
     *ctx.state.read() == ConnectionState::Streaming
 }
@@ -143,12 +208,12 @@ fn connection_pipeline(
 
             announcer_socket.announce().ok();
 
-            if let Ok(pair) = ProtoControlSocket::connect_to(
+            if let Ok((socket, server_ip)) = ProtoControlSocket::connect_to(
                 SOCKET_INIT_RETRY_INTERVAL,
                 PeerType::Server(&listener_socket),
             ) {
                 set_hud_message(&event_queue, SUCCESS_CONNECT_MESSAGE);
-                break pair;
+                break (socket, server_ip);
             }
         }
     };
@@ -162,6 +227,14 @@ fn connection_pipeline(
     let microphone_sample_rate =
         sound::input_sample_rate(&sound::new_input(None).to_con()?).to_con()?;
 
+    // The server is identified only once it answered the challenge. Reload in case the known
+    // servers have been edited in the meantime
+    let server_challenge = rand::random::<[u8; 16]>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let request_server_key = Config::load().needs_server_key();
+
     dbg_connection!("connection_pipeline: Send stream capabilities");
     proto_control_socket
         .send(&ClientConnectionResult::ConnectionAccepted {
@@ -182,7 +255,10 @@ fn connection_pipeline(
                     prefer_hdr: capabilities.prefer_hdr,
                     ext_str: String::new(),
                 }
-                .with_ext(VideoStreamingCapabilitiesExt {}),
+                .with_ext(VideoStreamingCapabilitiesExt {
+                    server_challenge: Some(server_challenge.clone()),
+                    request_server_key,
+                }),
             ),
         })
         .to_con()?;
@@ -192,6 +268,30 @@ fn connection_pipeline(
 
     let stream_config = config_packet.to_stream_config().to_con()?;
 
+    {
+        let ext = stream_config.negotiated_config.ext().unwrap_or_default();
+
+        // Servers that don't report an ID are identified by their address
+        let server_id = ext.server_id.unwrap_or_else(|| server_ip.to_string());
+
+        let mut config = Config::load();
+        if let Err(e) =
+            config.verify_server(&server_id, &server_challenge, ext.server_proof.as_deref())
+        {
+            warn!("Refused connection from server {server_id}: {e}");
+            set_hud_message(
+                &event_queue,
+                &server_refused_message(server_ip, &e.to_string()),
+            );
+
+            // Dropping the socket closes the connection
+            return Ok(());
+        }
+
+        config.register_connection(&server_id, server_ip, ext.server_key);
+        config.store();
+    }
+
     let streaming_start_event = ClientCoreEvent::StreamingStarted(Box::new(stream_config.clone()));
 
     let settings = stream_config.settings;
@@ -287,7 +387,7 @@ fn connection_pipeline(
                 };
 
                 if let Some(stats) = &mut *ctx.statistics_manager.lock() {
-                    stats.report_video_packet_received(header.timestamp);
+                    stats.report_video_packet_received(header.timestamp, data.lost_packets_count());
                 }
 
                 if header.is_idr {
@@ -321,6 +421,14 @@ fn connection_pipeline(
                         .as_mut()
                         .is_some_and(|callback| callback(header.timestamp, nal));
 
+                    if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                        if submitted {
+                            stats.report_decoder_input(header.timestamp);
+                        } else {
+                            stats.report_frame_dropped();
+                        }
+                    }
+
                     if !submitted {
                         stream_corrupted = true;
                         if let Some(sender) = &mut *ctx.control_sender.lock() {
@@ -329,6 +437,9 @@ fn connection_pipeline(
                         warn!("Dropped video packet. Reason: Decoder saturation")
                     }
                 } else {
+                    if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                        stats.report_frame_dropped();
+                    }
                     if let Some(sender) = &mut *ctx.control_sender.lock() {
                         sender.send(&ClientControlPacket::RequestIdr).ok();
                     }
@@ -338,14 +449,31 @@ fn connection_pipeline(
         }
     });
 
+    // Updated by RealTimeConfig packets. Audio loops are restarted when the device changes
+    let game_audio_device = Arc::new(Mutex::new(None));
+    let microphone_device = Arc::new(Mutex::new(None));
+
     let game_audio_thread = if let Switch::Enabled(config) = settings.audio.game_audio {
-        let device = sound::new_output(None).to_con()?;
+        let mut device_config = config.device.clone();
+        let mut device = output_device(device_config.as_ref()).to_con()?;
+        *game_audio_device.lock() = device_config.clone();
+
         thread::spawn({
             let ctx = Arc::clone(&ctx);
+            let game_audio_device = Arc::clone(&game_audio_device);
             move || {
                 while is_streaming(&ctx) {
+                    let new_device_config = game_audio_device.lock().clone();
+                    if new_device_config != device_config {
+                        match output_device(new_device_config.as_ref()) {
+                            Ok(new_device) => device = new_device,
+                            Err(e) => error!("Audio output error: {e}"),
+                        }
+                        device_config = new_device_config;
+                    }
+
                     shared::show_err(audio::play_audio_loop(
-                        || is_streaming(&ctx),
+                        || is_streaming(&ctx) && *game_audio_device.lock() == device_config,
                         &device,
                         2,
                         negotiated_config.game_audio_sample_rate,
@@ -359,29 +487,42 @@ fn connection_pipeline(
         thread::spawn(|| ())
     };
 
-    let microphone_thread = if matches!(settings.audio.microphone, Switch::Enabled(_)) {
-        let device = sound::new_input(None).to_con()?;
+    let microphone_thread = if let Switch::Enabled(config) = settings.audio.microphone {
+        let mut device_config = config.device.clone();
+        let mut device = input_device(device_config.clone(), microphone_sample_rate).to_con()?;
+        *microphone_device.lock() = device_config.clone();
 
         let microphone_sender = stream_socket.request_stream(AUDIO);
 
         thread::spawn({
             let ctx = Arc::clone(&ctx);
+            let microphone_device = Arc::clone(&microphone_device);
             move || {
                 while is_streaming(&ctx) {
-                    let ctx = Arc::clone(&ctx);
-                    match audio::record_audio_blocking(
-                        Arc::new(move || is_streaming(&ctx)),
+                    let new_device_config = microphone_device.lock().clone();
+                    if new_device_config != device_config {
+                        match input_device(new_device_config.clone(), microphone_sample_rate) {
+                            Ok(new_device) => device = new_device,
+                            Err(e) => error!("Audio record error: {e}"),
+                        }
+                        device_config = new_device_config;
+                    }
+
+                    let is_running = {
+                        let ctx = Arc::clone(&ctx);
+                        let microphone_device = Arc::clone(&microphone_device);
+                        let device_config = device_config.clone();
+                        move || is_streaming(&ctx) && *microphone_device.lock() == device_config
+                    };
+                    // Returns without error when the stream stops or the device changes
+                    if let Err(e) = audio::record_audio_blocking(
+                        Arc::new(is_running),
                         microphone_sender.clone(),
                         &device,
                         1,
                         false,
                     ) {
-                        Ok(()) => break,
-                        Err(e) => {
-                            error!("Audio record error: {e}");
-
-                            continue;
-                        }
+                        error!("Audio record error: {e}");
                     }
                 }
             }
@@ -425,6 +566,8 @@ fn connection_pipeline(
 
             #[cfg(target_os = "android")]
             let mut battery_deadline = Instant::now();
+            #[cfg(target_os = "android")]
+            let mut telemetry_deadline = Instant::now();
 
             while is_streaming(&ctx) && *lifecycle_state.read() == LifecycleState::Resumed {
                 if let Ok(packet) = log_channel_receiver.recv_timeout(STREAMING_RECV_TIMEOUT)
@@ -460,6 +603,42 @@ fn connection_pipeline(
 
                     battery_deadline = Instant::now() + Duration::from_secs(5);
                 }
+
+                #[cfg(target_os = "android")]
+                if Instant::now() > telemetry_deadline {
+                    use net_packets::{ThermalStatus, WifiLinkInfo};
+
+                    let wifi_link_info = system_info::get_wifi_link_info();
+                    let thermal_status = system_info::get_thermal_status().and_then(|status| {
+                        Some(match status {
+                            0 => ThermalStatus::None,
+                            1 => ThermalStatus::Light,
+                            2 => ThermalStatus::Moderate,
+                            3 => ThermalStatus::Severe,
+                            4 => ThermalStatus::Critical,
+                            5 => ThermalStatus::Emergency,
+                            6 => ThermalStatus::Shutdown,
+                            _ => return None,
+                        })
+                    });
+
+                    if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                        if let Some((rssi_dbm, link_speed_mbps, frequency_mhz)) = wifi_link_info {
+                            stats.report_wifi_link(WifiLinkInfo {
+                                rssi_dbm,
+                                link_speed_mbps,
+                                frequency_mhz,
+                                channel: frequency_mhz
+                                    .and_then(WifiLinkInfo::channel_from_frequency),
+                            });
+                        }
+                        if let Some(status) = thermal_status {
+                            stats.report_thermal_status(status);
+                        }
+                    }
+
+                    telemetry_deadline = Instant::now() + Duration::from_secs(1);
+                }
             }
 
             disconnect_notif.notify_one();
@@ -477,11 +656,14 @@ fn connection_pipeline(
 
                 match maybe_packet {
                     Ok(ServerControlPacket::DecoderConfig(config)) => {
+                        let view_resolution = config.ext().ok().and_then(|ext| ext.view_resolution);
+
                         event_queue
                             .lock()
                             .push_back(ClientCoreEvent::DecoderConfig {
                                 codec: config.codec,
                                 config_nal: config.config_buffer,
+                                view_resolution,
                             });
                     }
                     Ok(ServerControlPacket::Restarting) => {
@@ -490,10 +672,20 @@ fn connection_pipeline(
                         disconnect_notif.notify_one();
                     }
                     Ok(ServerControlPacket::RealTimeConfig(config)) => {
+                        if let Ok(ext) = config.ext() {
+                            *game_audio_device.lock() = ext.game_audio_device;
+                            *microphone_device.lock() = ext.microphone_device;
+                        }
+
                         event_queue
                             .lock()
                             .push_back(ClientCoreEvent::RealTimeConfig(config));
                     }
+                    Ok(ServerControlPacket::TraceCapture(enabled)) => {
+                        if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                            stats.set_trace_capture(enabled);
+                        }
+                    }
                     Ok(ServerControlPacket::StartStream) => {
                         error!("Unexpected StartStream paceket");
                     }
@@ -20,10 +20,13 @@ pub mod video_decoder;
 use configuration::CodecType;
 use connection::{ConnectionContext, DecoderCallback};
 use net_packets::{
-    BatteryInfo, ButtonEntry, ClientControlPacket, RealTimeConfig, StreamConfig, TrackingData,
+    BatteryInfo, ButtonEntry, ClientControlPacket, PerformanceDomain, PerformanceLevel,
+    RealTimeConfig, StreamConfig, TrackingData,
 };
 use shared::{
-    ConnectionState, LifecycleState, ViewParams, dbg_client_core, error,
+    ConnectionState, LifecycleState, ViewParams,
+    anyhow::Result,
+    dbg_client_core,
     glam::{UVec2, Vec2},
     parking_lot::{Mutex, RwLock},
     warn,
@@ -35,8 +38,10 @@ use std::{
     time::Duration,
 };
 use storage::Config;
+use video_decoder::{VideoDecoder, VideoDecoderConfig, VideoDecoderSource};
 
 pub use logging_backend::init_logging;
+pub use storage::KnownServer;
 
 pub enum ClientCoreEvent {
     UpdateHudMessage(String),
@@ -52,6 +57,8 @@ pub enum ClientCoreEvent {
     DecoderConfig {
         codec: CodecType,
         config_nal: Vec<u8>,
+        // Set if the stream resolution changed after negotiation
+        view_resolution: Option<UVec2>,
     },
     RealTimeConfig(RealTimeConfig),
 }
@@ -144,6 +151,70 @@ impl ClientCoreContext {
         }
     }
 
+    /// Servers that have connected at least once, ordered by user preference.
+    pub fn known_servers(&self) -> Vec<KnownServer> {
+        dbg_client_core!("known_servers");
+
+        Config::load().known_servers
+    }
+
+    pub fn pinned_server_id(&self) -> Option<String> {
+        dbg_client_core!("pinned_server_id");
+
+        Config::load().pinned_server_id
+    }
+
+    /// While a server is pinned, connections from any other server are refused. Pass None to
+    /// accept any server.
+    pub fn set_pinned_server(&self, server_id: Option<&str>) {
+        dbg_client_core!("set_pinned_server");
+
+        let mut config = Config::load();
+        if let Some(id) = server_id
+            && !config
+                .known_servers
+                .iter()
+                .any(|server| server.server_id == id)
+        {
+            warn!("Cannot pin unknown server {id}");
+            return;
+        }
+
+        config.pinned_server_id = server_id.map(ToOwned::to_owned);
+        config.store();
+    }
+
+    pub fn rename_server(&self, server_id: &str, name: &str) {
+        dbg_client_core!("rename_server");
+
+        let mut config = Config::load();
+        if let Some(server) = config
+            .known_servers
+            .iter_mut()
+            .find(|server| server.server_id == server_id)
+        {
+            server.name = name.to_owned();
+            config.store();
+        }
+    }
+
+    /// Removes the server from the list. If it was pinned, pinning is disabled.
+    pub fn forget_server(&self, server_id: &str) {
+        dbg_client_core!("forget_server");
+
+        let mut config = Config::load();
+        config.forget_server(server_id);
+        config.store();
+    }
+
+    pub fn move_server(&self, server_id: &str, new_index: usize) {
+        dbg_client_core!("move_server");
+
+        let mut config = Config::load();
+        config.move_server(server_id, new_index);
+        config.store();
+    }
+
     pub fn poll_event(&self) -> Option<ClientCoreEvent> {
         dbg_client_core!("poll_event");
 
@@ -164,6 +235,14 @@ impl ClientCoreContext {
         }
     }
 
+    pub fn report_performance_level(&self, domain: PerformanceDomain, level: PerformanceLevel) {
+        dbg_client_core!("report_performance_level");
+
+        if let Some(stats) = &mut *self.connection_context.statistics_manager.lock() {
+            stats.report_performance_level(domain, level);
+        }
+    }
+
     pub fn send_playspace(&self, area: Option<Vec2>) {
         dbg_client_core!("send_playspace");
 
@@ -246,19 +325,38 @@ impl ClientCoreContext {
         }
     }
 
+    /// Configure the decoder and route the video stream to it. Decoded frames and errors are
+    /// reported automatically. Frames should be pulled from the returned source.
+    pub fn attach_video_decoder(
+        &self,
+        decoder: Box<dyn VideoDecoder>,
+        config: VideoDecoderConfig,
+    ) -> Result<VideoDecoderSource> {
+        dbg_client_core!("attach_video_decoder");
+
+        let (mut sink, source) = video_decoder::create_decoder(decoder, config, {
+            let connection_context = Arc::clone(&self.connection_context);
+            move |maybe_timestamp: Result<Duration>| match maybe_timestamp {
+                Ok(timestamp) => connection_context.report_frame_decoded(timestamp),
+                Err(e) => connection_context.report_fatal_decoder_error(&e.to_string()),
+            }
+        })?;
+
+        self.set_decoder_input_callback(Box::new(move |timestamp, buffer| {
+            sink.push_nal(timestamp, buffer)
+        }));
+
+        Ok(source)
+    }
+
     pub fn report_frame_decoded(&self, timestamp: Duration) {
         dbg_client_core!("report_frame_decoded");
 
-        if let Some(stats) = &mut *self.connection_context.statistics_manager.lock() {
-            stats.report_frame_decoded(timestamp);
-        }
+        self.connection_context.report_frame_decoded(timestamp);
     }
 
     pub fn report_fatal_decoder_error(&self, error: &str) {
-        error!("Fatal decoder error, restarting connection: {error}");
-
-        // The connection loop observes changes on this value
-        *self.connection_context.state.write() = ConnectionState::Disconnecting;
+        self.connection_context.report_fatal_decoder_error(error);
     }
 
     pub fn report_compositor_start(&self, timestamp: Duration) -> [ViewParams; 2] {
@@ -1,10 +1,24 @@
-use net_packets::ClientStatistics;
+use net_packets::{
+    ClientStatistics, ClientStatisticsExt, ClientTraceEvent, PerformanceDomain, PerformanceLevel,
+    ThermalStatus, WifiLinkInfo,
+};
 use shared::SlidingWindowAverage;
 use std::{
     collections::VecDeque,
+    mem,
     time::{Duration, Instant},
 };
 
+const TELEMETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_PENDING_TRACE_EVENTS: usize = 1000;
+
+struct TraceEvent {
+    name: &'static str,
+    track: &'static str,
+    start: Instant,
+    duration: Option<Duration>,
+}
+
 struct HistoryFrame {
     input_acquired: Instant,
     video_packet_received: Instant,
@@ -16,6 +30,21 @@ pub struct StatisticsManager {
     max_history_size: usize,
     prev_vsync: Instant,
     total_pipeline_latency_average: SlidingWindowAverage<Duration>,
+    // Packets of frames that were not submitted are accounted in the next report
+    video_packets_received: u32,
+    video_packets_lost: u32,
+    // Frames submitted to the decoder, oldest first
+    decoder_queue: VecDeque<Duration>,
+    dropped_frames: u64,
+    submitted_frames: u32,
+    wifi: Option<WifiLinkInfo>,
+    thermal_status: Option<ThermalStatus>,
+    cpu_level: Option<PerformanceLevel>,
+    gpu_level: Option<PerformanceLevel>,
+    rendering_fps: Option<f32>,
+    last_telemetry_instant: Instant,
+    // Some while a trace capture is running. Events are sent with the next frame statistics
+    trace_events: Option<VecDeque<TraceEvent>>,
 }
 
 impl StatisticsManager {
@@ -28,6 +57,42 @@ impl StatisticsManager {
                 Duration::ZERO,
                 max_history_size,
             ),
+            video_packets_received: 0,
+            video_packets_lost: 0,
+            decoder_queue: VecDeque::new(),
+            dropped_frames: 0,
+            submitted_frames: 0,
+            wifi: None,
+            thermal_status: None,
+            cpu_level: None,
+            gpu_level: None,
+            rendering_fps: None,
+            last_telemetry_instant: Instant::now(),
+            trace_events: None,
+        }
+    }
+
+    pub fn set_trace_capture(&mut self, enabled: bool) {
+        self.trace_events = enabled.then(VecDeque::new);
+    }
+
+    fn report_trace_event(
+        &mut self,
+        track: &'static str,
+        name: &'static str,
+        start: Instant,
+        duration: Option<Duration>,
+    ) {
+        if let Some(events) = &mut self.trace_events {
+            events.push_back(TraceEvent {
+                name,
+                track,
+                start,
+                duration,
+            });
+            if events.len() > MAX_PENDING_TRACE_EVENTS {
+                events.pop_front();
+            }
         }
     }
 
@@ -53,7 +118,14 @@ impl StatisticsManager {
         }
     }
 
-    pub fn report_video_packet_received(&mut self, target_timestamp: Duration) {
+    pub fn report_video_packet_received(&mut self, target_timestamp: Duration, lost_packets: u32) {
+        self.video_packets_received += 1;
+        self.video_packets_lost += lost_packets;
+
+        if lost_packets > 0 {
+            self.report_trace_event("Network", "Packet loss", Instant::now(), None);
+        }
+
         if let Some(frame) = self
             .history_buffer
             .iter_mut()
@@ -63,7 +135,35 @@ impl StatisticsManager {
         }
     }
 
+    pub fn report_decoder_input(&mut self, target_timestamp: Duration) {
+        self.decoder_queue.push_back(target_timestamp);
+        if self.decoder_queue.len() > self.max_history_size {
+            self.decoder_queue.pop_front();
+            self.dropped_frames += 1;
+        }
+    }
+
+    // The frame was received but never reached the decoder
+    pub fn report_frame_dropped(&mut self) {
+        self.dropped_frames += 1;
+        self.report_trace_event("Decoder", "Frame dropped", Instant::now(), None);
+    }
+
     pub fn report_frame_decoded(&mut self, target_timestamp: Duration) {
+        // Older frames still in the queue have been skipped by the decoder
+        if let Some(index) = self
+            .decoder_queue
+            .iter()
+            .position(|timestamp| *timestamp == target_timestamp)
+        {
+            self.dropped_frames += index as u64;
+            self.decoder_queue.drain(..=index);
+
+            if index > 0 {
+                self.report_trace_event("Decoder", "Frames skipped", Instant::now(), None);
+            }
+        }
+
         if let Some(frame) = self
             .history_buffer
             .iter_mut()
@@ -86,6 +186,21 @@ impl StatisticsManager {
         }
     }
 
+    pub fn report_wifi_link(&mut self, wifi: WifiLinkInfo) {
+        self.wifi = Some(wifi);
+    }
+
+    pub fn report_thermal_status(&mut self, status: ThermalStatus) {
+        self.thermal_status = Some(status);
+    }
+
+    pub fn report_performance_level(&mut self, domain: PerformanceDomain, level: PerformanceLevel) {
+        match domain {
+            PerformanceDomain::Cpu => self.cpu_level = Some(level),
+            PerformanceDomain::Gpu => self.gpu_level = Some(level),
+        }
+    }
+
     // vsync_queue is the latency between this call and the vsync. it cannot be measured by NaNVR and
     // should be reported by the VR runtime
     pub fn report_submit(&mut self, target_timestamp: Duration, vsync_queue: Duration) {
@@ -102,6 +217,8 @@ impl StatisticsManager {
                     + frame.client_stats.video_decoder_queue,
             );
             frame.client_stats.vsync_queue = vsync_queue;
+            frame.client_stats.video_packets_received = mem::take(&mut self.video_packets_received);
+            frame.client_stats.video_packets_lost = mem::take(&mut self.video_packets_lost);
             frame.client_stats.total_pipeline_latency =
                 now.saturating_duration_since(frame.input_acquired) + vsync_queue;
             self.total_pipeline_latency_average
@@ -110,6 +227,81 @@ impl StatisticsManager {
             let vsync = now + vsync_queue;
             frame.client_stats.frame_interval = vsync.saturating_duration_since(self.prev_vsync);
             self.prev_vsync = vsync;
+
+            self.submitted_frames += 1;
+
+            if let Some(events) = &mut self.trace_events {
+                let decoded = frame.video_packet_received + frame.client_stats.video_decode;
+                let compositor_start = decoded + frame.client_stats.video_decoder_queue;
+                for (track, name, start, duration) in [
+                    (
+                        "Decoder",
+                        "Decode",
+                        frame.video_packet_received,
+                        frame.client_stats.video_decode,
+                    ),
+                    (
+                        "Decoder",
+                        "Queued",
+                        decoded,
+                        frame.client_stats.video_decoder_queue,
+                    ),
+                    (
+                        "Compositor",
+                        "Render",
+                        compositor_start,
+                        frame.client_stats.rendering,
+                    ),
+                    ("Display", "VSync queue", now, vsync_queue),
+                ] {
+                    events.push_back(TraceEvent {
+                        name,
+                        track,
+                        start,
+                        duration: Some(duration),
+                    });
+                }
+            }
+
+            let telemetry_interval = now.saturating_duration_since(self.last_telemetry_instant);
+            let telemetry_due = telemetry_interval > TELEMETRY_INTERVAL;
+            if telemetry_due {
+                self.rendering_fps = Some(
+                    mem::take(&mut self.submitted_frames) as f32 / telemetry_interval.as_secs_f32(),
+                );
+                self.last_telemetry_instant = now;
+            }
+
+            if telemetry_due || self.trace_events.is_some() {
+                let input_acquired = frame.input_acquired;
+                let trace_events = self.trace_events.as_mut().map(|events| {
+                    events
+                        .drain(..)
+                        .map(|event| ClientTraceEvent {
+                            name: event.name.into(),
+                            track: event.track.into(),
+                            start_offset_us: if event.start >= input_acquired {
+                                (event.start - input_acquired).as_micros() as i64
+                            } else {
+                                -((input_acquired - event.start).as_micros() as i64)
+                            },
+                            duration_us: event.duration.map(|duration| duration.as_micros() as u64),
+                        })
+                        .collect()
+                });
+
+                frame.client_stats =
+                    mem::take(&mut frame.client_stats).with_ext(ClientStatisticsExt {
+                        wifi: self.wifi.clone(),
+                        decoder_queue_frames: Some(self.decoder_queue.len() as u32),
+                        dropped_frames: Some(self.dropped_frames),
+                        thermal_status: self.thermal_status,
+                        cpu_level: self.cpu_level,
+                        gpu_level: self.gpu_level,
+                        rendering_fps: self.rendering_fps,
+                        trace_events,
+                    });
+            }
         }
     }
 
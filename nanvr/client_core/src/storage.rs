@@ -2,8 +2,20 @@ use app_dirs2::{AppDataType, AppInfo};
 use const_format::formatcp;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use shared::{NANVR_NAME, error, info};
-use std::{fs, path::PathBuf};
+use shared::{
+    NANVR_NAME,
+    anyhow::{Result, bail},
+    error, info,
+};
+use std::{
+    fs,
+    net::IpAddr,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Only the most recent addresses are kept, in case the server IP changes frequently
+const MAX_ADDRESSES_PER_SERVER: usize = 4;
 
 fn config_path() -> PathBuf {
     app_dirs2::app_root(
@@ -17,10 +29,29 @@ fn config_path() -> PathBuf {
     .join("session.json")
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KnownServer {
+    // Identity reported by the server on the first connection (trust on first use)
+    pub server_id: String,
+    pub name: String,
+    // Most recent first
+    pub addresses: Vec<IpAddr>,
+    pub last_connected_unix_secs: u64,
+    // Given by the server on the first connection, used to authenticate it afterwards
+    #[serde(default)]
+    pub server_key: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub hostname: String,
     pub protocol_id: String,
+    // Ordered by user preference
+    #[serde(default)]
+    pub known_servers: Vec<KnownServer>,
+    // If set, connections from any other server are refused
+    #[serde(default)]
+    pub pinned_server_id: Option<String>,
 }
 
 impl Default for Config {
@@ -36,6 +67,8 @@ impl Default for Config {
                 rng.random_range(0..10),
             ),
             protocol_id: shared::protocol_id(),
+            known_servers: vec![],
+            pinned_server_id: None,
         }
     }
 }
@@ -66,4 +99,204 @@ impl Config {
             error!("Error writing {NANVR_NAME} config: {e}")
         }
     }
+
+    pub fn pinned_server(&self) -> Option<&KnownServer> {
+        let pinned_id = self.pinned_server_id.as_ref()?;
+
+        self.known_servers
+            .iter()
+            .find(|server| server.server_id == *pinned_id)
+    }
+
+    // The key is requested only on the first connection after pinning a server, so that it is
+    // sent at most once and is hard to sniff
+    pub fn needs_server_key(&self) -> bool {
+        self.pinned_server()
+            .is_some_and(|server| server.server_key.is_none())
+    }
+
+    // Fails if pinning is enabled and the server is not the pinned one, or if the server cannot
+    // prove that it holds the key stored on the first connection. Identities are checked by key,
+    // not address, as the server address can change.
+    pub fn verify_server(
+        &self,
+        server_id: &str,
+        challenge: &str,
+        proof: Option<&str>,
+    ) -> Result<()> {
+        if self
+            .pinned_server_id
+            .as_ref()
+            .is_some_and(|pinned_id| pinned_id != server_id)
+        {
+            bail!("it is not the pinned streamer");
+        }
+
+        if let Some(server_key) = self
+            .known_servers
+            .iter()
+            .find(|server| server.server_id == server_id)
+            .and_then(|server| server.server_key.as_ref())
+            && !proof.is_some_and(|proof| {
+                net_packets::is_valid_server_proof(server_key, challenge, proof)
+            })
+        {
+            bail!("it failed to authenticate");
+        }
+
+        Ok(())
+    }
+
+    // The key is stored only if none is known for this server, it must be verified beforehand
+    pub fn register_connection(
+        &mut self,
+        server_id: &str,
+        address: IpAddr,
+        server_key: Option<String>,
+    ) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        if let Some(server) = self
+            .known_servers
+            .iter_mut()
+            .find(|server| server.server_id == server_id)
+        {
+            server.addresses.retain(|a| *a != address);
+            server.addresses.insert(0, address);
+            server.addresses.truncate(MAX_ADDRESSES_PER_SERVER);
+            server.last_connected_unix_secs = now;
+            if server.server_key.is_none() {
+                server.server_key = server_key;
+            }
+        } else {
+            self.known_servers.push(KnownServer {
+                server_id: server_id.to_owned(),
+                name: address.to_string(),
+                addresses: vec![address],
+                last_connected_unix_secs: now,
+                server_key,
+            });
+        }
+    }
+
+    pub fn forget_server(&mut self, server_id: &str) {
+        self.known_servers
+            .retain(|server| server.server_id != server_id);
+
+        if self.pinned_server_id.as_deref() == Some(server_id) {
+            self.pinned_server_id = None;
+        }
+    }
+
+    pub fn move_server(&mut self, server_id: &str, new_index: usize) {
+        if let Some(index) = self
+            .known_servers
+            .iter()
+            .position(|server| server.server_id == server_id)
+        {
+            let server = self.known_servers.remove(index);
+            let new_index = usize::min(new_index, self.known_servers.len());
+            self.known_servers.insert(new_index, server);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(last_byte: u8) -> IpAddr {
+        IpAddr::from([192, 168, 1, last_byte])
+    }
+
+    #[test]
+    fn test_register_connection() {
+        let mut config = Config::default();
+        config.register_connection("a", address(1), Some("key".into()));
+        assert_eq!(config.known_servers.len(), 1);
+        assert_eq!(config.known_servers[0].addresses, [address(1)]);
+        assert_eq!(config.known_servers[0].server_key.as_deref(), Some("key"));
+
+        // The key is never replaced
+        config.register_connection("a", address(2), Some("other key".into()));
+        assert_eq!(config.known_servers.len(), 1);
+        assert_eq!(config.known_servers[0].addresses, [address(2), address(1)]);
+        assert_eq!(config.known_servers[0].server_key.as_deref(), Some("key"));
+
+        for last_byte in 3..10 {
+            config.register_connection("a", address(last_byte), None);
+        }
+        assert_eq!(
+            config.known_servers[0].addresses.len(),
+            MAX_ADDRESSES_PER_SERVER
+        );
+        assert_eq!(config.known_servers[0].addresses[0], address(9));
+
+        config.register_connection("b", address(1), None);
+        assert_eq!(config.known_servers.len(), 2);
+        assert_eq!(config.known_servers[1].server_id, "b");
+    }
+
+    #[test]
+    fn test_move_and_forget_server() {
+        let mut config = Config::default();
+        for id in ["a", "b", "c"] {
+            config.register_connection(id, address(1), None);
+        }
+        let ids = |config: &Config| {
+            config
+                .known_servers
+                .iter()
+                .map(|server| server.server_id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        config.move_server("c", 0);
+        assert_eq!(ids(&config), ["c", "a", "b"]);
+        config.move_server("c", 10);
+        assert_eq!(ids(&config), ["a", "b", "c"]);
+        config.move_server("d", 0);
+        assert_eq!(ids(&config), ["a", "b", "c"]);
+
+        config.pinned_server_id = Some("b".into());
+        config.forget_server("b");
+        assert_eq!(ids(&config), ["a", "c"]);
+        assert!(config.pinned_server_id.is_none());
+    }
+
+    #[test]
+    fn test_verify_server() {
+        let key = net_packets::client_server_key("secret", "1234.client.local.");
+        let proof = net_packets::server_proof(&key, "challenge");
+
+        let mut config = Config::default();
+        assert!(!config.needs_server_key());
+        assert!(config.verify_server("a", "challenge", None).is_ok());
+
+        config.register_connection("a", address(1), None);
+        config.pinned_server_id = Some("a".into());
+        assert!(config.needs_server_key());
+        assert!(config.verify_server("a", "challenge", None).is_ok());
+
+        config.register_connection("a", address(1), Some(key));
+        assert!(!config.needs_server_key());
+        assert!(config.verify_server("a", "challenge", Some(&proof)).is_ok());
+        assert!(
+            config
+                .verify_server("a", "other challenge", Some(&proof))
+                .is_err()
+        );
+        assert!(config.verify_server("a", "challenge", None).is_err());
+
+        // Pinned servers are identified by key, even if their address changed
+        config.register_connection("a", address(2), None);
+        assert!(config.verify_server("a", "challenge", Some(&proof)).is_ok());
+        assert!(config.verify_server("b", "challenge", None).is_err());
+
+        config.pinned_server_id = None;
+        assert!(config.verify_server("b", "challenge", None).is_ok());
+    }
 }
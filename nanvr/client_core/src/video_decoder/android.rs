@@ -1,4 +1,7 @@
-use super::VideoDecoderConfig;
+use super::{
+    FrameHandleType, FrameResultCallback, VideoDecoder, VideoDecoderCapabilities,
+    VideoDecoderConfig, VideoDecoderInput, VideoDecoderOutput,
+};
 use configuration::{CodecType, MediacodecPropType};
 use ndk::{
     hardware_buffer::HardwareBufferUsage,
@@ -426,3 +429,44 @@ pub fn video_decoder_split(
 
     Ok((sink, source))
 }
+
+impl VideoDecoderInput for VideoDecoderSink {
+    fn push_nal(&mut self, timestamp: Duration, nal: &[u8]) -> Result<bool> {
+        self.push_frame_nal(timestamp, nal)
+    }
+}
+
+impl VideoDecoderOutput for VideoDecoderSource {
+    fn pull_frame(&mut self) -> Option<(Duration, *mut c_void)> {
+        self.dequeue_frame()
+    }
+}
+
+#[derive(Default)]
+pub struct MediaCodecDecoder {
+    // Of the last configured session
+    force_software_decoder: bool,
+}
+
+impl VideoDecoder for MediaCodecDecoder {
+    fn capabilities(&self) -> VideoDecoderCapabilities {
+        VideoDecoderCapabilities {
+            codecs: vec![CodecType::H264, CodecType::Hevc, CodecType::AV1],
+            hardware_accelerated: !self.force_software_decoder,
+            frame_handle_type: FrameHandleType::HardwareBuffer,
+        }
+    }
+
+    fn configure(
+        &mut self,
+        config: VideoDecoderConfig,
+        frame_result_callback: FrameResultCallback,
+    ) -> Result<(Box<dyn VideoDecoderInput>, Box<dyn VideoDecoderOutput>)> {
+        self.force_software_decoder = config.force_software_decoder;
+
+        let csd_0 = config.config_buffer.clone();
+        let (sink, source) = video_decoder_split(config, csd_0, frame_result_callback)?;
+
+        Ok((Box::new(sink), Box::new(source)))
+    }
+}
@@ -0,0 +1,92 @@
+use super::{
+    FrameHandleType, FrameResultCallback, VideoDecoder, VideoDecoderCapabilities,
+    VideoDecoderConfig, VideoDecoderInput, VideoDecoderOutput,
+};
+use configuration::CodecType;
+use shared::{anyhow::Result, parking_lot::Mutex};
+use std::{
+    collections::VecDeque,
+    ffi::c_void,
+    ptr,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+/// Decoder that does not decode anything: every NAL is immediately reported as a decoded frame
+/// with a null handle. Useful for testing the streaming pipeline.
+#[derive(Default)]
+pub struct MockDecoder {
+    config: Option<VideoDecoderConfig>,
+}
+
+impl MockDecoder {
+    pub fn config(&self) -> Option<&VideoDecoderConfig> {
+        self.config.as_ref()
+    }
+}
+
+// The input holds a weak reference, so it stops accepting NALs once the output is dropped
+struct MockDecoderInput {
+    frame_queue: Weak<Mutex<VecDeque<Duration>>>,
+    queue_size: usize,
+    frame_result_callback: FrameResultCallback,
+}
+
+impl VideoDecoderInput for MockDecoderInput {
+    fn push_nal(&mut self, timestamp: Duration, _: &[u8]) -> Result<bool> {
+        let Some(frame_queue) = self.frame_queue.upgrade() else {
+            return Ok(false);
+        };
+
+        let mut frame_queue_lock = frame_queue.lock();
+        if frame_queue_lock.len() >= self.queue_size {
+            return Ok(false);
+        }
+
+        frame_queue_lock.push_back(timestamp);
+        (self.frame_result_callback)(Ok(timestamp));
+
+        Ok(true)
+    }
+}
+
+struct MockDecoderOutput {
+    frame_queue: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+impl VideoDecoderOutput for MockDecoderOutput {
+    fn pull_frame(&mut self) -> Option<(Duration, *mut c_void)> {
+        self.frame_queue
+            .lock()
+            .pop_front()
+            .map(|timestamp| (timestamp, ptr::null_mut()))
+    }
+}
+
+impl VideoDecoder for MockDecoder {
+    fn capabilities(&self) -> VideoDecoderCapabilities {
+        VideoDecoderCapabilities {
+            codecs: vec![CodecType::H264, CodecType::Hevc, CodecType::AV1],
+            hardware_accelerated: false,
+            frame_handle_type: FrameHandleType::Opaque,
+        }
+    }
+
+    fn configure(
+        &mut self,
+        config: VideoDecoderConfig,
+        frame_result_callback: FrameResultCallback,
+    ) -> Result<(Box<dyn VideoDecoderInput>, Box<dyn VideoDecoderOutput>)> {
+        let frame_queue = Arc::new(Mutex::new(VecDeque::new()));
+
+        let input = MockDecoderInput {
+            frame_queue: Arc::downgrade(&frame_queue),
+            // Same queue size as the real decoders
+            queue_size: (2. * config.max_buffering_frames).ceil() as usize,
+            frame_result_callback,
+        };
+        self.config = Some(config);
+
+        Ok((Box::new(input), Box::new(MockDecoderOutput { frame_queue })))
+    }
+}
@@ -1,9 +1,18 @@
 #[cfg(target_os = "android")]
 mod android;
+mod mock;
+#[cfg(all(not(target_os = "android"), feature = "software-decoder"))]
+mod software;
+
+#[cfg(target_os = "android")]
+pub use android::MediaCodecDecoder;
+pub use mock::MockDecoder;
+#[cfg(all(not(target_os = "android"), feature = "software-decoder"))]
+pub use software::{SoftwareDecoder, SoftwareFrame};
 
 use configuration::{CodecType, MediacodecProperty};
 use shared::anyhow::Result;
-use std::time::Duration;
+use std::{ffi::c_void, time::Duration};
 
 #[derive(Clone, Default, PartialEq)]
 pub struct VideoDecoderConfig {
@@ -15,61 +24,218 @@ pub struct VideoDecoderConfig {
     pub config_buffer: Vec<u8>,
 }
 
-pub struct VideoDecoderSink {
+/// Meaning of the pointer returned by VideoDecoderOutput::pull_frame()
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameHandleType {
+    /// AHardwareBuffer
+    HardwareBuffer,
+    /// SoftwareFrame, only available with the software-decoder feature
+    SoftwareFrame,
+    /// Only meaningful to the decoder implementation. Might be null.
+    Opaque,
+}
+
+#[derive(Clone, Debug)]
+pub struct VideoDecoderCapabilities {
+    pub codecs: Vec<CodecType>,
+    pub hardware_accelerated: bool,
+    pub frame_handle_type: FrameHandleType,
+}
+
+/// Called with the timestamp of each decoded frame, or with an error if the decoder failed
+/// irrecoverably.
+pub type FrameResultCallback = Box<dyn Fn(Result<Duration>) + Send + Sync>;
+
+/// Input half of a decoding session, fed by the connection thread.
+pub trait VideoDecoderInput: Send {
+    /// Must not block. Returns true if the NAL has been successfully enqueued. Returns false once
+    /// the output has been dropped.
+    fn push_nal(&mut self, timestamp: Duration, nal: &[u8]) -> Result<bool>;
+}
+
+/// Output half of a decoding session, polled by the render thread. Dropping it ends the session.
+pub trait VideoDecoderOutput: Send {
+    /// If a frame is available, return the timestamp and the frame handle. The application MUST
+    /// finish using the returned frame before calling this function again.
+    fn pull_frame(&mut self) -> Option<(Duration, *mut c_void)>;
+}
+
+pub trait VideoDecoder: Send {
+    fn capabilities(&self) -> VideoDecoderCapabilities;
+
+    /// Create a decoding session, split into an input and an output used from different threads.
+    /// The two halves must share only the internal queues, so that pushing NALs and pulling
+    /// frames never wait on each other.
+    fn configure(
+        &mut self,
+        config: VideoDecoderConfig,
+        frame_result_callback: FrameResultCallback,
+    ) -> Result<(Box<dyn VideoDecoderInput>, Box<dyn VideoDecoderOutput>)>;
+}
+
+// Used when no decoder implementation is available for the target
+#[cfg(not(any(target_os = "android", feature = "software-decoder")))]
+struct NoopDecoder;
+
+#[cfg(not(any(target_os = "android", feature = "software-decoder")))]
+impl VideoDecoderInput for NoopDecoder {
+    fn push_nal(&mut self, _: Duration, _: &[u8]) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(not(any(target_os = "android", feature = "software-decoder")))]
+impl VideoDecoderOutput for NoopDecoder {
+    fn pull_frame(&mut self) -> Option<(Duration, *mut c_void)> {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "android", feature = "software-decoder")))]
+impl VideoDecoder for NoopDecoder {
+    fn capabilities(&self) -> VideoDecoderCapabilities {
+        VideoDecoderCapabilities {
+            codecs: vec![],
+            hardware_accelerated: false,
+            frame_handle_type: FrameHandleType::Opaque,
+        }
+    }
+
+    fn configure(
+        &mut self,
+        _: VideoDecoderConfig,
+        _: FrameResultCallback,
+    ) -> Result<(Box<dyn VideoDecoderInput>, Box<dyn VideoDecoderOutput>)> {
+        Ok((Box::new(NoopDecoder), Box::new(NoopDecoder)))
+    }
+}
+
+/// Returns the platform decoder: MediaCodec on Android, the software decoder on desktop if the
+/// software-decoder feature is enabled, otherwise a decoder that drops everything.
+pub fn default_decoder() -> Box<dyn VideoDecoder> {
     #[cfg(target_os = "android")]
-    inner: android::VideoDecoderSink,
+    {
+        Box::new(MediaCodecDecoder::default())
+    }
+    #[cfg(all(not(target_os = "android"), feature = "software-decoder"))]
+    {
+        Box::new(SoftwareDecoder::default())
+    }
+    #[cfg(not(any(target_os = "android", feature = "software-decoder")))]
+    {
+        Box::new(NoopDecoder)
+    }
+}
+
+pub struct VideoDecoderSink {
+    input: Box<dyn VideoDecoderInput>,
 }
 
 impl VideoDecoderSink {
     // returns true if frame has been successfully enqueued
-    #[allow(unused_variables)]
     pub fn push_nal(&mut self, timestamp: Duration, nal: &[u8]) -> bool {
-        #[cfg(target_os = "android")]
-        {
-            shared::show_err(self.inner.push_frame_nal(timestamp, nal)).unwrap_or(false)
-        }
-        #[cfg(not(target_os = "android"))]
-        false
+        shared::show_err(self.input.push_nal(timestamp, nal)).unwrap_or(false)
     }
 }
 
+// The decoding session is destroyed together with the source
 pub struct VideoDecoderSource {
-    #[cfg(target_os = "android")]
-    inner: android::VideoDecoderSource,
+    output: Box<dyn VideoDecoderOutput>,
+    frame_handle_type: FrameHandleType,
 }
 
 impl VideoDecoderSource {
-    /// If a frame is available, return the timestamp and the AHardwareBuffer.
-    pub fn get_frame(&mut self) -> Option<(Duration, *mut std::ffi::c_void)> {
-        #[cfg(target_os = "android")]
-        {
-            self.inner.dequeue_frame()
+    pub fn frame_handle_type(&self) -> FrameHandleType {
+        self.frame_handle_type
+    }
+
+    /// If a frame is available, return the timestamp and the frame handle, which for the default
+    /// decoder on Android is an AHardwareBuffer.
+    pub fn get_frame(&mut self) -> Option<(Duration, *mut c_void)> {
+        self.output.pull_frame()
+    }
+
+    /// If a frame is available, return the timestamp and the decoded pixels. The frame stays
+    /// valid until the next call. Returns None if the decoder does not produce software frames.
+    #[cfg(all(not(target_os = "android"), feature = "software-decoder"))]
+    pub fn get_software_frame(&mut self) -> Option<(Duration, &SoftwareFrame)> {
+        if self.frame_handle_type != FrameHandleType::SoftwareFrame {
+            return None;
         }
-        #[cfg(not(target_os = "android"))]
-        None
+
+        self.get_frame().map(|(timestamp, frame_ptr)| {
+            (timestamp, unsafe { &*frame_ptr.cast::<SoftwareFrame>() })
+        })
     }
 }
 
-// report_frame_decoded: (target_timestamp: Duration) -> ()
-#[allow(unused_variables)]
+// Configure the decoder and split it into a sink (fed by the connection thread) and a source
+// (polled by the render thread).
 pub fn create_decoder(
+    mut decoder: Box<dyn VideoDecoder>,
     config: VideoDecoderConfig,
-    report_frame_decoded: impl Fn(Result<Duration>) + Send + Sync + 'static,
-) -> (VideoDecoderSink, VideoDecoderSource) {
-    #[cfg(target_os = "android")]
-    {
-        let (sink, source) = android::video_decoder_split(
-            config.clone(),
-            config.config_buffer,
-            report_frame_decoded,
-        )
+    frame_result_callback: impl Fn(Result<Duration>) + Send + Sync + 'static,
+) -> Result<(VideoDecoderSink, VideoDecoderSource)> {
+    let (input, output) = decoder.configure(config, Box::new(frame_result_callback))?;
+
+    Ok((
+        VideoDecoderSink { input },
+        VideoDecoderSource {
+            output,
+            frame_handle_type: decoder.capabilities().frame_handle_type,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> VideoDecoderConfig {
+        VideoDecoderConfig {
+            max_buffering_frames: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mock_decoder_roundtrip() {
+        let (mut sink, mut source) =
+            create_decoder(Box::new(MockDecoder::default()), test_config(), |_| ()).unwrap();
+
+        assert_eq!(source.frame_handle_type(), FrameHandleType::Opaque);
+        assert!(sink.push_nal(Duration::from_millis(1), &[0, 0, 1]));
+        assert!(sink.push_nal(Duration::from_millis(2), &[0, 0, 1]));
+        // Only 2 frames can be buffered
+        assert!(!sink.push_nal(Duration::from_millis(3), &[0, 0, 1]));
+
+        assert_eq!(source.get_frame().unwrap().0, Duration::from_millis(1));
+        assert_eq!(source.get_frame().unwrap().0, Duration::from_millis(2));
+        assert!(source.get_frame().is_none());
+    }
+
+    #[test]
+    fn test_sink_on_other_thread() {
+        let (mut sink, mut source) =
+            create_decoder(Box::new(MockDecoder::default()), test_config(), |_| ()).unwrap();
+
+        // The sink and the source share no lock besides the frame queue
+        std::thread::spawn(move || {
+            assert!(sink.push_nal(Duration::from_millis(1), &[0, 0, 1]));
+        })
+        .join()
         .unwrap();
 
-        (
-            VideoDecoderSink { inner: sink },
-            VideoDecoderSource { inner: source },
-        )
+        assert_eq!(source.get_frame().unwrap().0, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_sink_after_source_drop() {
+        let (mut sink, source) =
+            create_decoder(Box::new(MockDecoder::default()), test_config(), |_| ()).unwrap();
+
+        drop(source);
+
+        assert!(!sink.push_nal(Duration::ZERO, &[0, 0, 1]));
     }
-    #[cfg(not(target_os = "android"))]
-    (VideoDecoderSink {}, VideoDecoderSource {})
 }
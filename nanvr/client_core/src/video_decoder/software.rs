@@ -0,0 +1,468 @@
+use super::{
+    FrameHandleType, FrameResultCallback, VideoDecoder, VideoDecoderCapabilities,
+    VideoDecoderConfig, VideoDecoderInput, VideoDecoderOutput,
+};
+use configuration::CodecType;
+use ffmpeg::{
+    Packet,
+    codec::{self, decoder},
+    error::EAGAIN,
+    format::Pixel,
+    frame,
+    software::scaling,
+};
+use ffmpeg_next as ffmpeg;
+use shared::{
+    RelaxedAtomic,
+    anyhow::{Result, anyhow, bail},
+    error, info,
+    parking_lot::{Condvar, Mutex},
+    warn,
+};
+use std::{
+    collections::VecDeque,
+    ffi::c_void,
+    sync::{
+        Arc,
+        mpsc::{self, RecvTimeoutError, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+const NAL_RECV_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Decoded picture converted to tightly packed RGBA8 pixels.
+pub struct SoftwareFrame {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+pub struct VideoDecoderSink {
+    nal_sender: SyncSender<(Duration, Vec<u8>)>,
+}
+
+impl VideoDecoderSink {
+    // Does not block. Returns false if the decoder input queue is full.
+    pub fn push_frame_nal(&mut self, timestamp: Duration, data: &[u8]) -> Result<bool> {
+        match self.nal_sender.try_send((timestamp, data.to_vec())) {
+            Ok(()) => Ok(true),
+            Err(TrySendError::Full(_)) => Ok(false),
+            // This might happen only during destruction or after a fatal decoder error, which has
+            // already been reported
+            Err(TrySendError::Disconnected(_)) => Ok(false),
+        }
+    }
+}
+
+// The frame is boxed so that the pointer given to the application stays valid when the queue is
+// reallocated
+struct QueuedFrame {
+    timestamp: Duration,
+    frame: Box<SoftwareFrame>,
+    in_use: bool,
+}
+
+// The frame in use by the application is skipped, it is removed only on the next dequeue
+fn evict_oldest_unused_frame(frame_queue: &mut VecDeque<QueuedFrame>) {
+    if let Some(index) = frame_queue.iter().position(|queued| !queued.in_use) {
+        frame_queue.remove(index);
+    }
+}
+
+fn enqueue_frame(
+    frame_queue: &mut VecDeque<QueuedFrame>,
+    timestamp: Duration,
+    frame: SoftwareFrame,
+    available_buffering_frames: usize,
+) {
+    if frame_queue.len() > available_buffering_frames {
+        warn!("Video frame queue overflow!");
+        evict_oldest_unused_frame(frame_queue);
+    }
+
+    frame_queue.push_back(QueuedFrame {
+        timestamp,
+        frame: Box::new(frame),
+        in_use: false,
+    });
+}
+
+// Access the frame queue synchronously.
+pub struct VideoDecoderSource {
+    running: Arc<RelaxedAtomic>,
+    decoder_thread: Option<JoinHandle<()>>,
+    frame_queue: Arc<Mutex<VecDeque<QueuedFrame>>>,
+    config: VideoDecoderConfig,
+    buffering_running_average: f32,
+}
+
+impl VideoDecoderSource {
+    // The application MUST finish using the returned frame before calling this function again.
+    // The pointer refers to a SoftwareFrame.
+    pub fn dequeue_frame(&mut self) -> Option<(Duration, *mut c_void)> {
+        let mut frame_queue_lock = self.frame_queue.lock();
+
+        if let Some(queued_frame) = frame_queue_lock.front()
+            && queued_frame.in_use
+        {
+            // The frame has been consumed by the application
+            frame_queue_lock.pop_front();
+        }
+
+        // use running average to give more weight to recent samples
+        self.buffering_running_average = self.buffering_running_average
+            * self.config.buffering_history_weight
+            + frame_queue_lock.len() as f32 * (1. - self.config.buffering_history_weight);
+        if self.buffering_running_average > self.config.max_buffering_frames {
+            evict_oldest_unused_frame(&mut frame_queue_lock);
+        }
+
+        if let Some(queued_frame) = frame_queue_lock.front_mut() {
+            queued_frame.in_use = true;
+
+            Some((
+                queued_frame.timestamp,
+                (&mut *queued_frame.frame as *mut SoftwareFrame).cast(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for VideoDecoderSource {
+    fn drop(&mut self) {
+        self.running.set(false);
+
+        self.decoder_thread.take().map(|t| t.join());
+    }
+}
+
+fn codec_id(codec: CodecType) -> codec::Id {
+    match codec {
+        CodecType::H264 => codec::Id::H264,
+        CodecType::Hevc => codec::Id::HEVC,
+        CodecType::AV1 => codec::Id::AV1,
+    }
+}
+
+fn convert_to_rgba(
+    scaler: &mut Option<scaling::Context>,
+    decoded: &frame::Video,
+) -> Result<SoftwareFrame> {
+    let width = decoded.width();
+    let height = decoded.height();
+
+    let input_definition = scaling::Definition {
+        format: decoded.format(),
+        width,
+        height,
+    };
+    if scaler
+        .as_ref()
+        .is_none_or(|scaler| *scaler.input() != input_definition)
+    {
+        *scaler = Some(scaling::Context::get(
+            decoded.format(),
+            width,
+            height,
+            Pixel::RGBA,
+            width,
+            height,
+            scaling::Flags::BILINEAR,
+        )?);
+    }
+
+    let mut rgba = frame::Video::empty();
+    scaler.as_mut().unwrap().run(decoded, &mut rgba)?;
+
+    // Remove the row padding
+    let stride = rgba.stride(0);
+    let row_size = width as usize * 4;
+    let data = rgba
+        .data(0)
+        .chunks(stride)
+        .take(height as usize)
+        .flat_map(|row| &row[..row_size])
+        .copied()
+        .collect();
+
+    Ok(SoftwareFrame {
+        width,
+        height,
+        data,
+    })
+}
+
+fn decoder_lifecycle(
+    config: VideoDecoderConfig,
+    csd_0: Vec<u8>,
+    frame_result_callback: &impl Fn(Result<Duration>),
+    running: Arc<RelaxedAtomic>,
+    decoder_ready: Arc<(Mutex<bool>, Condvar)>,
+    nal_receiver: mpsc::Receiver<(Duration, Vec<u8>)>,
+    frame_queue: Arc<Mutex<VecDeque<QueuedFrame>>>,
+) -> Result<()> {
+    // 2x: keep the target buffering in the middle of the max amount of queuable frames
+    let available_buffering_frames = (2. * config.max_buffering_frames).ceil() as usize;
+
+    ffmpeg::init()?;
+
+    let codec = decoder::find(codec_id(config.codec))
+        .ok_or_else(|| anyhow!("No software decoder available for {:?}", config.codec))?;
+
+    let mut context = codec::Context::new_with_codec(codec);
+    context.set_flags(codec::Flags::LOW_DELAY);
+    let mut decoder = context.decoder().video()?;
+
+    info!("Using software decoder: {}", codec.name());
+
+    // The config NAL is sent ahead of any frame, like the csd-0 buffer on MediaCodec
+    if !csd_0.is_empty() {
+        decoder.send_packet(&Packet::copy(&csd_0))?;
+    }
+
+    {
+        let (ready_lock, ready_notifier) = &*decoder_ready;
+        *ready_lock.lock() = true;
+        ready_notifier.notify_one();
+    }
+
+    let mut scaler = None;
+    let mut decoded = frame::Video::empty();
+    let mut error_counter = 0;
+    while running.value() {
+        let (timestamp, nal) = match nal_receiver.recv_timeout(NAL_RECV_TIMEOUT) {
+            Ok(pair) => pair,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let mut packet = Packet::copy(&nal);
+        // Use nanoseconds as presentation time to have complete precision, so when converted back
+        // to Duration it can compare correctly to other Durations
+        packet.set_pts(Some(timestamp.as_nanos() as _));
+
+        if let Err(e) = decoder.send_packet(&packet) {
+            error!("Decoder enqueue error: {e}");
+
+            error_counter += 1;
+            if error_counter > 10 {
+                bail!("Too many decoder errors: {e}");
+            }
+
+            continue;
+        }
+
+        loop {
+            match decoder.receive_frame(&mut decoded) {
+                Ok(()) => (),
+                Err(ffmpeg::Error::Other { errno: EAGAIN }) => break,
+                Err(e) => bail!("Decoder dequeue error: {e}"),
+            }
+
+            let timestamp = decoded
+                .pts()
+                .map(|pts| Duration::from_nanos(pts as _))
+                .unwrap_or(timestamp);
+            let frame = convert_to_rgba(&mut scaler, &decoded)?;
+
+            frame_result_callback(Ok(timestamp));
+
+            enqueue_frame(
+                &mut frame_queue.lock(),
+                timestamp,
+                frame,
+                available_buffering_frames,
+            );
+        }
+
+        error_counter = 0;
+    }
+
+    Ok(())
+}
+
+// Create a sink/source pair
+pub fn video_decoder_split(
+    config: VideoDecoderConfig,
+    csd_0: Vec<u8>,
+    frame_result_callback: impl Fn(Result<Duration>) + Send + Sync + 'static,
+) -> Result<(VideoDecoderSink, VideoDecoderSource)> {
+    let running = Arc::new(RelaxedAtomic::new(true));
+    let decoder_ready = Arc::new((Mutex::new(false), Condvar::new()));
+    let frame_queue = Arc::new(Mutex::new(VecDeque::<QueuedFrame>::new()));
+
+    // The input queue has the same size as the output queue, so a saturated decoder is signaled
+    // to the connection loop, which will then request a new IDR
+    let (nal_sender, nal_receiver) = mpsc::sync_channel(usize::max(
+        (2. * config.max_buffering_frames).ceil() as usize,
+        1,
+    ));
+
+    let decoder_thread = thread::spawn({
+        let config = config.clone();
+        let running = Arc::clone(&running);
+        let decoder_ready = Arc::clone(&decoder_ready);
+        let frame_queue = Arc::clone(&frame_queue);
+        move || {
+            if let Err(e) = decoder_lifecycle(
+                config,
+                csd_0,
+                &frame_result_callback,
+                running,
+                Arc::clone(&decoder_ready),
+                nal_receiver,
+                Arc::clone(&frame_queue),
+            ) {
+                frame_result_callback(Err(e));
+            }
+
+            // The application might still be reading the frame in use
+            frame_queue.lock().retain(|queued| queued.in_use);
+
+            // Unblock the creator thread in case the decoder failed to initialize
+            let (ready_lock, ready_notifier) = &*decoder_ready;
+            *ready_lock.lock() = true;
+            ready_notifier.notify_one();
+        }
+    });
+
+    // Make sure the decoder is ready: we don't want to try to enqueue frame and lose them, to avoid
+    // image corruption.
+    {
+        let (ready_lock, ready_notifier) = &*decoder_ready;
+        let mut ready_lock = ready_lock.lock();
+
+        if !*ready_lock {
+            // No spurious wakeups
+            ready_notifier.wait(&mut ready_lock);
+        }
+    }
+
+    let sink = VideoDecoderSink { nal_sender };
+    let source = VideoDecoderSource {
+        running,
+        decoder_thread: Some(decoder_thread),
+        frame_queue,
+        config,
+        buffering_running_average: 0.0,
+    };
+
+    Ok((sink, source))
+}
+
+impl VideoDecoderInput for VideoDecoderSink {
+    fn push_nal(&mut self, timestamp: Duration, nal: &[u8]) -> Result<bool> {
+        self.push_frame_nal(timestamp, nal)
+    }
+}
+
+impl VideoDecoderOutput for VideoDecoderSource {
+    fn pull_frame(&mut self) -> Option<(Duration, *mut c_void)> {
+        self.dequeue_frame()
+    }
+}
+
+#[derive(Default)]
+pub struct SoftwareDecoder;
+
+impl VideoDecoder for SoftwareDecoder {
+    fn capabilities(&self) -> VideoDecoderCapabilities {
+        let codecs = [CodecType::H264, CodecType::Hevc, CodecType::AV1]
+            .into_iter()
+            .filter(|codec| decoder::find(codec_id(*codec)).is_some())
+            .collect();
+
+        VideoDecoderCapabilities {
+            codecs,
+            hardware_accelerated: false,
+            frame_handle_type: FrameHandleType::SoftwareFrame,
+        }
+    }
+
+    fn configure(
+        &mut self,
+        config: VideoDecoderConfig,
+        frame_result_callback: FrameResultCallback,
+    ) -> Result<(Box<dyn VideoDecoderInput>, Box<dyn VideoDecoderOutput>)> {
+        let csd_0 = config.config_buffer.clone();
+        let (sink, source) = video_decoder_split(config, csd_0, frame_result_callback)?;
+
+        Ok((Box::new(sink), Box::new(source)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_frame(value: u8) -> SoftwareFrame {
+        SoftwareFrame {
+            width: 1,
+            height: 1,
+            data: vec![value; 4],
+        }
+    }
+
+    #[test]
+    fn test_overflow_keeps_frame_in_use() {
+        let config = VideoDecoderConfig {
+            max_buffering_frames: 1.0,
+            buffering_history_weight: 0.9,
+            ..Default::default()
+        };
+        let available_buffering_frames = (2. * config.max_buffering_frames).ceil() as usize;
+        let mut source = VideoDecoderSource {
+            running: Arc::new(RelaxedAtomic::new(true)),
+            decoder_thread: None,
+            frame_queue: Arc::new(Mutex::new(VecDeque::new())),
+            config,
+            buffering_running_average: 0.0,
+        };
+
+        enqueue_frame(
+            &mut source.frame_queue.lock(),
+            Duration::ZERO,
+            test_frame(0),
+            available_buffering_frames,
+        );
+        let (timestamp, frame_ptr) = source.dequeue_frame().unwrap();
+        assert_eq!(timestamp, Duration::ZERO);
+
+        // Overflow the queue and force reallocations while the frame is held
+        for value in 1..100 {
+            enqueue_frame(
+                &mut source.frame_queue.lock(),
+                Duration::from_millis(value),
+                test_frame(value as u8),
+                available_buffering_frames,
+            );
+        }
+
+        {
+            let frame_queue = source.frame_queue.lock();
+            assert_eq!(frame_queue.len(), available_buffering_frames + 1);
+            assert!(frame_queue[0].in_use);
+            assert!(std::ptr::eq(
+                &*frame_queue[0].frame,
+                frame_ptr.cast::<SoftwareFrame>()
+            ));
+        }
+        let frame = unsafe { &*frame_ptr.cast::<SoftwareFrame>() };
+        assert_eq!(frame.data, [0; 4]);
+
+        // The held frame is released only by the next dequeue, which returns the newest frames
+        let (timestamp, _) = source.dequeue_frame().unwrap();
+        assert!(timestamp > Duration::ZERO);
+        assert!(
+            !source
+                .frame_queue
+                .lock()
+                .iter()
+                .any(|queued| queued.timestamp.is_zero())
+        );
+    }
+}
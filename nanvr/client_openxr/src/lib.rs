@@ -18,6 +18,7 @@ use extra_extensions::{
 };
 use interaction::{InteractionContext, InteractionSourcesConfig};
 use lobby::Lobby;
+use net_packets::{PerformanceDomain, PerformanceLevel};
 use openxr as xr;
 use passthrough::PassthroughLayer;
 use shared::{
@@ -288,6 +289,7 @@ pub fn entry_point() {
         let mut lobby = Lobby::new(
             xr_session.clone(),
             Rc::clone(&graphics_context),
+            Arc::clone(&core_context),
             Arc::clone(&interaction_context),
             platform,
             default_view_resolution,
@@ -373,6 +375,27 @@ pub fn entry_point() {
                             event.domain(),
                             event.sub_domain(),
                         );
+
+                        let domain = match event.domain() {
+                            xr::PerfSettingsDomainEXT::CPU => Some(PerformanceDomain::Cpu),
+                            xr::PerfSettingsDomainEXT::GPU => Some(PerformanceDomain::Gpu),
+                            _ => None,
+                        };
+                        let level = match event.to_level() {
+                            xr::PerfSettingsNotificationLevelEXT::NORMAL => {
+                                Some(PerformanceLevel::Normal)
+                            }
+                            xr::PerfSettingsNotificationLevelEXT::WARNING => {
+                                Some(PerformanceLevel::Warning)
+                            }
+                            xr::PerfSettingsNotificationLevelEXT::IMPAIRED => {
+                                Some(PerformanceLevel::Impaired)
+                            }
+                            _ => None,
+                        };
+                        if let (Some(domain), Some(level)) = (domain, level) {
+                            core_context.report_performance_level(domain, level);
+                        }
                     }
                     xr::Event::InteractionProfileChanged(_)
                     | xr::Event::PassthroughStateChangedFB(_) => {
@@ -443,9 +466,13 @@ pub fn entry_point() {
                             )
                             .unwrap();
                     }
-                    ClientCoreEvent::DecoderConfig { codec, config_nal } => {
+                    ClientCoreEvent::DecoderConfig {
+                        codec,
+                        config_nal,
+                        view_resolution,
+                    } => {
                         if let Some(stream) = &mut stream_context {
-                            stream.maybe_initialize_decoder(codec, config_nal);
+                            stream.maybe_initialize_decoder(codec, config_nal, view_resolution);
                         }
                     }
                     ClientCoreEvent::RealTimeConfig(config) => {
@@ -2,15 +2,64 @@ use crate::{
     graphics::{self, ProjectionLayerAlphaConfig, ProjectionLayerBuilder},
     interaction::{self, InteractionContext},
 };
+use client_core::{ClientCoreContext, KnownServer};
 use client_graphics::{GraphicsContext, LobbyRenderer, LobbyViewParams, SDR_FORMAT_GL};
+use net_packets::ButtonValue;
 use openxr as xr;
-use shared::{Pose, ViewParams, glam::UVec2, parking_lot::RwLock};
-use std::{rc::Rc, sync::Arc, time::Duration};
+use shared::{
+    LEFT_X_CLICK_ID, LEFT_Y_CLICK_ID, Pose, RIGHT_A_CLICK_ID, RIGHT_B_CLICK_ID, ViewParams,
+    glam::UVec2, parking_lot::RwLock,
+};
+use std::{
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use system_info::Platform;
 
-// todo: add interaction?
+const SERVER_LIST_HELP: &str = "Y: select, X: pin/unpin, A: move up, B: forget";
+// Forgetting a server deletes its key, so it must be confirmed by pressing B again
+const FORGET_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(3);
+
+fn server_list_text(
+    servers: &[KnownServer],
+    pinned_server_id: Option<&str>,
+    selected_index: usize,
+    pending_forget_id: Option<&str>,
+) -> String {
+    if servers.is_empty() {
+        return String::new();
+    }
+
+    let mut text = "\n\nKnown servers:".to_owned();
+    for (index, server) in servers.iter().enumerate() {
+        let cursor = if index == selected_index { "> " } else { "" };
+        let pinned = if pinned_server_id == Some(server.server_id.as_str()) {
+            " (pinned)"
+        } else {
+            ""
+        };
+        text += &format!("\n{cursor}{}{pinned}", server.name);
+    }
+
+    if let Some(server) =
+        pending_forget_id.and_then(|id| servers.iter().find(|server| server.server_id == id))
+    {
+        text += &format!(
+            "\nPress B again within {} s to forget {}",
+            FORGET_CONFIRMATION_TIMEOUT.as_secs(),
+            server.name
+        );
+    } else {
+        text += &format!("\n{SERVER_LIST_HELP}");
+    }
+
+    text
+}
+
 pub struct Lobby {
     xr_session: xr::Session<xr::OpenGlEs>,
+    core_context: Arc<ClientCoreContext>,
     interaction_ctx: Arc<RwLock<InteractionContext>>,
     platform: Platform,
     reference_space: xr::Space,
@@ -18,12 +67,17 @@ pub struct Lobby {
     view_resolution: UVec2,
     reference_space_type: xr::ReferenceSpaceType,
     renderer: LobbyRenderer,
+    hud_message: String,
+    selected_server_index: usize,
+    // Server ID and deadline
+    pending_forget: Option<(String, Instant)>,
 }
 
 impl Lobby {
     pub fn new(
         xr_session: xr::Session<xr::OpenGlEs>,
         gfx_ctx: Rc<GraphicsContext>,
+        core_context: Arc<ClientCoreContext>,
         interaction_ctx: Arc<RwLock<InteractionContext>>,
         platform: Platform,
         view_resolution: UVec2,
@@ -64,8 +118,9 @@ impl Lobby {
             initial_hud_message,
         );
 
-        Self {
+        let mut this = Self {
             xr_session,
+            core_context,
             interaction_ctx,
             platform,
             reference_space,
@@ -73,7 +128,13 @@ impl Lobby {
             view_resolution,
             reference_space_type,
             renderer,
-        }
+            hud_message: initial_hud_message.to_owned(),
+            selected_server_index: 0,
+            pending_forget: None,
+        };
+        this.refresh_hud();
+
+        this
     }
 
     pub fn update_reference_space(&mut self) {
@@ -81,8 +142,98 @@ impl Lobby {
             interaction::get_reference_space(&self.xr_session, self.reference_space_type);
     }
 
-    pub fn update_hud_message(&self, message: &str) {
-        self.renderer.update_hud_message(message);
+    // The known servers are reloaded here too, as they change when a server connects
+    pub fn update_hud_message(&mut self, message: &str) {
+        message.clone_into(&mut self.hud_message);
+        self.refresh_hud();
+    }
+
+    fn refresh_hud(&mut self) {
+        let servers = self.core_context.known_servers();
+        let pinned_server_id = self.core_context.pinned_server_id();
+        self.selected_server_index = self
+            .selected_server_index
+            .min(servers.len().saturating_sub(1));
+
+        let server_list = server_list_text(
+            &servers,
+            pinned_server_id.as_deref(),
+            self.selected_server_index,
+            self.pending_forget.as_ref().map(|(id, _)| id.as_str()),
+        );
+        self.renderer
+            .update_hud_message(&format!("{}{server_list}", self.hud_message));
+    }
+
+    fn handle_server_list_buttons(&mut self) {
+        let pressed_buttons = interaction::update_buttons(
+            &self.xr_session,
+            &self.interaction_ctx.read().button_actions,
+        )
+        .into_iter()
+        .filter(|entry| matches!(entry.value, ButtonValue::Binary(true)))
+        .map(|entry| entry.path_id)
+        .collect::<Vec<_>>();
+
+        if self
+            .pending_forget
+            .as_ref()
+            .is_some_and(|(_, deadline)| Instant::now() > *deadline)
+        {
+            self.pending_forget = None;
+            self.refresh_hud();
+        }
+
+        if pressed_buttons.is_empty() {
+            return;
+        }
+
+        let servers = self.core_context.known_servers();
+        let Some(selected_server) = servers.get(self.selected_server_index) else {
+            return;
+        };
+        let server_id = selected_server.server_id.as_str();
+
+        for id in pressed_buttons {
+            if id == *LEFT_Y_CLICK_ID {
+                self.selected_server_index = (self.selected_server_index + 1) % servers.len();
+            } else if id == *LEFT_X_CLICK_ID {
+                if self.core_context.pinned_server_id().as_deref() == Some(server_id) {
+                    self.core_context.set_pinned_server(None);
+                } else {
+                    self.core_context.set_pinned_server(Some(server_id));
+                }
+            } else if id == *RIGHT_A_CLICK_ID {
+                let new_index = self.selected_server_index.saturating_sub(1);
+                self.core_context.move_server(server_id, new_index);
+                self.selected_server_index = new_index;
+            } else if id == *RIGHT_B_CLICK_ID {
+                if self
+                    .pending_forget
+                    .take()
+                    .is_some_and(|(pending_id, _)| pending_id == server_id)
+                {
+                    self.core_context.forget_server(server_id);
+                } else {
+                    self.pending_forget = Some((
+                        server_id.to_owned(),
+                        Instant::now() + FORGET_CONFIRMATION_TIMEOUT,
+                    ));
+                }
+
+                break;
+            } else {
+                continue;
+            }
+
+            // Any other action cancels the pending forget
+            self.pending_forget = None;
+
+            // Apply one action per frame, the selection may be stale afterwards
+            break;
+        }
+
+        self.refresh_hud();
     }
 
     pub fn render(&mut self, vsync_time: Duration) -> ProjectionLayerBuilder<'_> {
@@ -107,6 +258,8 @@ impl Lobby {
             .sync_actions(&[(&self.interaction_ctx.read().action_set).into()])
             .ok();
 
+        self.handle_server_list_buttons();
+
         // future_time doesn't have to be any particular value, just something after vsync_time
         let future_time = vsync_time + Duration::from_millis(80);
         let left_hand_data = interaction::get_hand_data(
@@ -14,9 +14,7 @@ use configuration::{
 use net_packets::{RealTimeConfig, StreamConfig, TrackingData};
 use openxr as xr;
 use shared::{
-    HAND_LEFT_ID, HAND_RIGHT_ID, HEAD_ID, Pose, RelaxedAtomic, ViewParams,
-    anyhow::Result,
-    error,
+    HAND_LEFT_ID, HAND_RIGHT_ID, HEAD_ID, Pose, RelaxedAtomic, ViewParams, error,
     glam::{UVec2, Vec2},
     parking_lot::RwLock,
 };
@@ -95,6 +93,9 @@ pub struct StreamContext {
     input_thread_running: Arc<RelaxedAtomic>,
     config: ParsedStreamConfig,
     target_view_resolution: UVec2,
+    gfx_context: Rc<GraphicsContext>,
+    platform: Platform,
+    swapchain_format: u32,
     renderer: StreamRenderer,
     decoder: Option<(VideoDecoderConfig, VideoDecoderSource)>,
     use_custom_reprojection: bool,
@@ -173,30 +174,13 @@ impl StreamContext {
             ),
         ];
 
-        let renderer = StreamRenderer::new(
-            gfx_ctx,
-            config.view_resolution,
+        let renderer = create_renderer(
+            Rc::clone(&gfx_ctx),
+            &config,
             target_view_resolution,
-            [
-                swapchains[0]
-                    .enumerate_images()
-                    .unwrap()
-                    .iter()
-                    .map(|i| *i as _)
-                    .collect(),
-                swapchains[1]
-                    .enumerate_images()
-                    .unwrap()
-                    .iter()
-                    .map(|i| *i as _)
-                    .collect(),
-            ],
+            &swapchains,
             format,
-            config.foveated_encoding_config.clone(),
-            platform != Platform::Lynx && !((platform.is_pico()) && config.enable_hdr),
-            !config.enable_hdr,
-            config.encoding_gamma,
-            config.upscaling.clone(),
+            platform,
         );
 
         {
@@ -236,6 +220,9 @@ impl StreamContext {
             input_thread_running,
             config,
             target_view_resolution,
+            gfx_context: gfx_ctx,
+            platform,
+            swapchain_format: format,
             renderer,
             decoder: None,
             use_custom_reprojection: platform.is_yvr(),
@@ -297,7 +284,29 @@ impl StreamContext {
         }));
     }
 
-    pub fn maybe_initialize_decoder(&mut self, codec: CodecType, config_nal: Vec<u8>) {
+    pub fn maybe_initialize_decoder(
+        &mut self,
+        codec: CodecType,
+        config_nal: Vec<u8>,
+        view_resolution: Option<UVec2>,
+    ) {
+        // With dynamic resolution the server changes the encoding resolution while streaming. The
+        // renderer is recreated to sample the new frame size, and it upscales it to the swapchains.
+        if let Some(view_resolution) = view_resolution
+            && view_resolution != self.config.view_resolution
+        {
+            self.config.view_resolution = view_resolution;
+
+            self.renderer = create_renderer(
+                Rc::clone(&self.gfx_context),
+                &self.config,
+                self.target_view_resolution,
+                &self.swapchains,
+                self.swapchain_format,
+                self.platform,
+            );
+        }
+
         let new_config = VideoDecoderConfig {
             codec,
             force_software_decoder: self.config.force_software_decoder,
@@ -313,25 +322,38 @@ impl StreamContext {
             Some(new_config)
         };
 
-        if let Some(config) = maybe_config {
-            let (mut sink, source) = video_decoder::create_decoder(config.clone(), {
-                let ctx = Arc::clone(&self.core_context);
-                move |maybe_timestamp: Result<Duration>| match maybe_timestamp {
-                    Ok(timestamp) => ctx.report_frame_decoded(timestamp),
-                    Err(e) => ctx.report_fatal_decoder_error(&e.to_string()),
-                }
-            });
+        if let Some(config) = maybe_config
+            && let Some(source) = shared::show_err(
+                self.core_context
+                    .attach_video_decoder(video_decoder::default_decoder(), config.clone()),
+            )
+        {
             self.decoder = Some((config, source));
-
-            self.core_context.set_decoder_input_callback(Box::new(
-                move |timestamp, buffer| -> bool { sink.push_nal(timestamp, buffer) },
-            ));
         }
     }
 
     pub fn update_real_time_config(&mut self, config: &RealTimeConfig) {
         self.config.passthrough = config.passthrough.clone();
         self.config.clientside_post_processing = config.clientside_post_processing.clone();
+
+        // Foveated encoding cannot be toggled without renegotiating the stream, only its
+        // parameters can change
+        if let Ok(ext) = config.ext()
+            && self.config.foveated_encoding_config.is_some()
+            && ext.foveated_encoding.is_some()
+            && ext.foveated_encoding != self.config.foveated_encoding_config
+        {
+            self.config.foveated_encoding_config = ext.foveated_encoding;
+
+            self.renderer = create_renderer(
+                Rc::clone(&self.gfx_context),
+                &self.config,
+                self.target_view_resolution,
+                &self.swapchains,
+                self.swapchain_format,
+                self.platform,
+            );
+        }
     }
 
     pub fn render(
@@ -510,6 +532,41 @@ impl Drop for StreamContext {
     }
 }
 
+fn create_renderer(
+    gfx_ctx: Rc<GraphicsContext>,
+    config: &ParsedStreamConfig,
+    target_view_resolution: UVec2,
+    swapchains: &[xr::Swapchain<xr::OpenGlEs>; 2],
+    format: u32,
+    platform: Platform,
+) -> StreamRenderer {
+    StreamRenderer::new(
+        gfx_ctx,
+        config.view_resolution,
+        target_view_resolution,
+        [
+            swapchains[0]
+                .enumerate_images()
+                .unwrap()
+                .iter()
+                .map(|i| *i as _)
+                .collect(),
+            swapchains[1]
+                .enumerate_images()
+                .unwrap()
+                .iter()
+                .map(|i| *i as _)
+                .collect(),
+        ],
+        format,
+        config.foveated_encoding_config.clone(),
+        platform != Platform::Lynx && !((platform.is_pico()) && config.enable_hdr),
+        !config.enable_hdr,
+        config.encoding_gamma,
+        config.upscaling.clone(),
+    )
+}
+
 fn stream_input_loop(
     core_ctx: &ClientCoreContext,
     xr_session: xr::Session<xr::OpenGlEs>,
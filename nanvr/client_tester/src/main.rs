@@ -1,4 +1,7 @@
-use client_core::{ClientCapabilities, ClientCoreContext, ClientCoreEvent};
+use client_core::{
+    ClientCapabilities, ClientCoreContext, ClientCoreEvent, KnownServer,
+    video_decoder::{self, VideoDecoderConfig, VideoDecoderSource},
+};
 use configuration::CodecType;
 use eframe::{
     CreationContext, Frame, NativeOptions,
@@ -12,6 +15,7 @@ use shared::{
 };
 use std::{
     f32::consts::{FRAC_PI_2, PI},
+    hash::{DefaultHasher, Hash, Hasher},
     sync::{
         Arc,
         mpsc::{self, TryRecvError},
@@ -20,6 +24,8 @@ use std::{
     time::{Duration, Instant},
 };
 
+const SERVERS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone, PartialEq)]
 struct WindowInput {
     height: f32,
@@ -45,6 +51,12 @@ impl Default for WindowInput {
     }
 }
 
+enum ServerAction {
+    Pin(Option<String>),
+    Move { server_id: String, new_index: usize },
+    Forget(String),
+}
+
 #[derive(Clone)]
 struct WindowOutput {
     hud_message: String,
@@ -53,6 +65,12 @@ struct WindowOutput {
     resolution: UVec2,
     decoder_codec: Option<CodecType>,
     current_frame_timestamp: Duration,
+    decoded_frames: u64,
+    decoded_resolution: UVec2,
+    // Hash of the pixels of the last decoded frame, used to check stream integrity
+    decoded_frame_hash: u64,
+    known_servers: Vec<KnownServer>,
+    pinned_server_id: Option<String>,
 }
 
 impl Default for WindowOutput {
@@ -64,6 +82,11 @@ impl Default for WindowOutput {
             resolution: UVec2::ZERO,
             decoder_codec: None,
             current_frame_timestamp: Duration::ZERO,
+            decoded_frames: 0,
+            decoded_resolution: UVec2::ZERO,
+            decoded_frame_hash: 0,
+            known_servers: vec![],
+            pinned_server_id: None,
         }
     }
 }
@@ -73,6 +96,7 @@ pub struct Window {
     input_sender: mpsc::Sender<WindowInput>,
     output: WindowOutput,
     output_receiver: mpsc::Receiver<WindowOutput>,
+    server_action_sender: mpsc::Sender<ServerAction>,
 }
 
 impl Window {
@@ -80,6 +104,7 @@ impl Window {
         creation_context: &CreationContext<'_>,
         input_sender: mpsc::Sender<WindowInput>,
         output_receiver: mpsc::Receiver<WindowOutput>,
+        server_action_sender: mpsc::Sender<ServerAction>,
     ) -> Self {
         gui_shared::font::add_fonts(&creation_context.egui_ctx);
         Self {
@@ -87,6 +112,7 @@ impl Window {
             input_sender,
             output: WindowOutput::default(),
             output_receiver,
+            server_action_sender,
         }
     }
 }
@@ -111,6 +137,15 @@ impl eframe::App for Window {
                 "Current frame: {:?}",
                 self.output.current_frame_timestamp
             ));
+            ui.label(format!("Decoded frames: {}", self.output.decoded_frames));
+            ui.label(format!(
+                "Decoded resolution: {}",
+                self.output.decoded_resolution
+            ));
+            ui.label(format!(
+                "Decoded frame hash: {:016x}",
+                self.output.decoded_frame_hash
+            ));
             ui.add_space(10.0);
             ui.horizontal(|ui| {
                 ui.label("Height:");
@@ -128,6 +163,42 @@ impl eframe::App for Window {
                 &mut input.use_random_orientation,
                 "Use randomized orientation offset",
             );
+            ui.add_space(10.0);
+            ui.label("Known servers:");
+            for (index, server) in self.output.known_servers.iter().enumerate() {
+                let pinned = self.output.pinned_server_id.as_ref() == Some(&server.server_id);
+
+                ui.horizontal(|ui| {
+                    ui.label(&server.name);
+                    if ui.selectable_label(pinned, "Pin").clicked() {
+                        let server_id = (!pinned).then(|| server.server_id.clone());
+                        self.server_action_sender
+                            .send(ServerAction::Pin(server_id))
+                            .ok();
+                    }
+                    if ui.button("Up").clicked() {
+                        self.server_action_sender
+                            .send(ServerAction::Move {
+                                server_id: server.server_id.clone(),
+                                new_index: index.saturating_sub(1),
+                            })
+                            .ok();
+                    }
+                    if ui.button("Down").clicked() {
+                        self.server_action_sender
+                            .send(ServerAction::Move {
+                                server_id: server.server_id.clone(),
+                                new_index: index + 1,
+                            })
+                            .ok();
+                    }
+                    if ui.button("Forget").clicked() {
+                        self.server_action_sender
+                            .send(ServerAction::Forget(server.server_id.clone()))
+                            .ok();
+                    }
+                });
+            }
         });
 
         if input != self.input {
@@ -190,6 +261,7 @@ fn tracking_thread(
 fn client_thread(
     output_sender: mpsc::Sender<WindowOutput>,
     input_receiver: mpsc::Receiver<WindowInput>,
+    server_action_receiver: mpsc::Receiver<ServerAction>,
 ) {
     let capabilities = ClientCapabilities {
         default_view_resolution: UVec2::new(1920, 1832),
@@ -210,10 +282,14 @@ fn client_thread(
     let streaming = Arc::new(RelaxedAtomic::new(false));
     let got_decoder_config = Arc::new(RelaxedAtomic::new(false));
     let mut maybe_tracking_thread = None;
+    let mut maybe_stream_config = None;
+    let mut maybe_decoder = None::<(VideoDecoderConfig, VideoDecoderSource)>;
 
     let mut window_output = WindowOutput::default();
     let window_input = Arc::new(RwLock::new(WindowInput::default()));
 
+    let mut servers_refresh_deadline = Instant::now();
+
     let mut deadline = Instant::now();
     'main_loop: loop {
         let input_lock = window_input.read();
@@ -233,14 +309,12 @@ fn client_thread(
                     let context = Arc::clone(&client_core_context);
                     let streaming = Arc::clone(&streaming);
                     let input = Arc::clone(&window_input);
+                    let refresh_rate = config.negotiated_config.refresh_rate_hint;
                     maybe_tracking_thread = Some(thread::spawn(move || {
-                        tracking_thread(
-                            context,
-                            streaming,
-                            config.negotiated_config.refresh_rate_hint,
-                            input,
-                        )
+                        tracking_thread(context, streaming, refresh_rate, input)
                     }));
+
+                    maybe_stream_config = Some(config);
                 }
                 ClientCoreEvent::StreamingStopped => {
                     streaming.set(false);
@@ -254,8 +328,46 @@ fn client_thread(
                     window_output.connected = false;
                     window_output.resolution = UVec2::ZERO;
                     window_output.decoder_codec = None;
+
+                    maybe_stream_config = None;
+                    maybe_decoder = None;
                 }
-                ClientCoreEvent::DecoderConfig { codec, .. } => {
+                ClientCoreEvent::DecoderConfig {
+                    codec,
+                    config_nal,
+                    view_resolution,
+                } => {
+                    // With dynamic resolution the frame size changes while streaming
+                    if let Some(view_resolution) = view_resolution {
+                        window_output.resolution = view_resolution;
+                    }
+
+                    // Like the headset client, the decoder is recreated whenever its config
+                    // changes, which includes resolution and codec changes
+                    if let Some(config) = &maybe_stream_config {
+                        let video = &config.settings.video;
+                        let new_config = VideoDecoderConfig {
+                            codec,
+                            force_software_decoder: true,
+                            max_buffering_frames: video.max_buffering_frames,
+                            buffering_history_weight: video.buffering_history_weight,
+                            options: vec![],
+                            config_buffer: config_nal,
+                        };
+
+                        if maybe_decoder
+                            .as_ref()
+                            .is_none_or(|(config, _)| *config != new_config)
+                            && let Some(source) =
+                                shared::show_err(client_core_context.attach_video_decoder(
+                                    video_decoder::default_decoder(),
+                                    new_config.clone(),
+                                ))
+                        {
+                            maybe_decoder = Some((new_config, source));
+                        }
+                    }
+
                     got_decoder_config.set(true);
 
                     window_output.decoder_codec = Some(codec);
@@ -266,6 +378,44 @@ fn client_thread(
             output_sender.send(window_output.clone()).ok();
         }
 
+        if let Some((_, source)) = &mut maybe_decoder
+            && let Some((timestamp, frame)) = source.get_software_frame()
+        {
+            let mut hasher = DefaultHasher::new();
+            frame.data.hash(&mut hasher);
+
+            window_output.current_frame_timestamp = timestamp;
+            window_output.decoded_frames += 1;
+            window_output.decoded_resolution = UVec2::new(frame.width, frame.height);
+            window_output.decoded_frame_hash = hasher.finish();
+
+            output_sender.send(window_output.clone()).ok();
+        }
+
+        while let Ok(action) = server_action_receiver.try_recv() {
+            match action {
+                ServerAction::Pin(server_id) => {
+                    client_core_context.set_pinned_server(server_id.as_deref())
+                }
+                ServerAction::Move {
+                    server_id,
+                    new_index,
+                } => client_core_context.move_server(&server_id, new_index),
+                ServerAction::Forget(server_id) => client_core_context.forget_server(&server_id),
+            }
+
+            servers_refresh_deadline = Instant::now();
+        }
+
+        if Instant::now() >= servers_refresh_deadline {
+            window_output.known_servers = client_core_context.known_servers();
+            window_output.pinned_server_id = client_core_context.pinned_server_id();
+
+            output_sender.send(window_output.clone()).ok();
+
+            servers_refresh_deadline = Instant::now() + SERVERS_REFRESH_INTERVAL;
+        }
+
         thread::sleep(Duration::from_millis(3));
 
         client_core_context.report_compositor_start(window_output.current_frame_timestamp);
@@ -294,6 +444,8 @@ fn client_thread(
         thread.join().unwrap();
     }
 
+    drop(maybe_decoder);
+
     client_core_context.pause()
 
     // client_core_context destroy is called here on drop
@@ -304,15 +456,16 @@ fn main() {
 
     let (input_sender, input_receiver) = mpsc::channel::<WindowInput>();
     let (output_sender, output_receiver) = mpsc::channel::<WindowOutput>();
+    let (server_action_sender, server_action_receiver) = mpsc::channel::<ServerAction>();
 
     let client_thread = thread::spawn(|| {
-        client_thread(output_sender, input_receiver);
+        client_thread(output_sender, input_receiver, server_action_receiver);
     });
 
     eframe::run_native(
         "Mock client",
         NativeOptions {
-            viewport: ViewportBuilder::default().with_inner_size((400.0, 400.0)),
+            viewport: ViewportBuilder::default().with_inner_size((400.0, 600.0)),
             ..Default::default()
         },
         Box::new(|creation_context| {
@@ -320,6 +473,7 @@ fn main() {
                 creation_context,
                 input_sender,
                 output_receiver,
+                server_action_sender,
             )))
         }),
     )
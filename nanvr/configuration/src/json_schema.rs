@@ -0,0 +1,320 @@
+// JSON Schema (draft 2020-12) of the session settings, the representation stored in session.json
+// and accepted by the web API. Every field is required, so partial settings (like profiles and
+// overrides) must be validated ignoring "required". Ranges are the ones of the dashboard sliders.
+// Setting flags are listed in "x-flags", the unit of numbers in "x-unit".
+
+use crate::{Settings, settings::session_settings_default};
+use serde_json as json;
+use settings_schema::{NumberType, NumericGuiType, SchemaEntry, SchemaNode};
+use std::collections::{HashMap, HashSet};
+
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+fn object_schema(properties: json::Map<String, json::Value>) -> json::Value {
+    let required = properties.keys().cloned().collect::<Vec<_>>();
+
+    json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn annotate(schema: &mut json::Value, strings: &HashMap<String, String>, flags: &HashSet<String>) {
+    let Some(schema) = schema.as_object_mut() else {
+        return;
+    };
+
+    if let Some(display_name) = strings.get("display_name") {
+        schema.insert("title".into(), display_name.clone().into());
+    }
+    if let Some(help) = strings.get("help") {
+        schema.insert("description".into(), help.clone().into());
+    }
+    if let Some(notice) = strings.get("notice") {
+        schema.insert("x-notice".into(), notice.clone().into());
+    }
+    if !flags.is_empty() {
+        let mut flags = flags.iter().cloned().collect::<Vec<_>>();
+        flags.sort();
+        schema.insert("x-flags".into(), flags.into());
+    }
+}
+
+fn collapsible_schema(mut properties: json::Map<String, json::Value>) -> json::Value {
+    properties.insert("gui_collapsed".into(), json::json!({ "type": "boolean" }));
+
+    object_schema(properties)
+}
+
+fn node_schema(schema: &SchemaNode) -> json::Value {
+    match schema {
+        SchemaNode::Section {
+            entries,
+            gui_collapsible,
+        } => {
+            let mut properties = entries
+                .iter()
+                .map(|entry: &SchemaEntry<SchemaNode>| {
+                    let mut schema = node_schema(&entry.content);
+                    annotate(&mut schema, &entry.strings, &entry.flags);
+
+                    (entry.name.clone(), schema)
+                })
+                .collect::<json::Map<_, _>>();
+            if *gui_collapsible {
+                properties.insert("gui_collapsed".into(), json::json!({ "type": "boolean" }));
+            }
+
+            object_schema(properties)
+        }
+        SchemaNode::Choice {
+            default, variants, ..
+        } => {
+            let variant_names = variants
+                .iter()
+                .map(|entry| entry.name.clone())
+                .collect::<Vec<_>>();
+            let variant_descriptions = variants
+                .iter()
+                .map(|entry| {
+                    let mut schema = json::json!({ "const": entry.name });
+                    annotate(&mut schema, &entry.strings, &entry.flags);
+
+                    schema
+                })
+                .collect::<Vec<_>>();
+
+            let mut properties = json::Map::new();
+            properties.insert(
+                "variant".into(),
+                json::json!({
+                    "type": "string",
+                    "enum": variant_names,
+                    "oneOf": variant_descriptions,
+                    "default": default,
+                }),
+            );
+            for entry in variants {
+                if let Some(content) = &entry.content {
+                    let mut schema = node_schema(content);
+                    annotate(&mut schema, &entry.strings, &entry.flags);
+                    properties.insert(entry.name.clone(), schema);
+                }
+            }
+
+            object_schema(properties)
+        }
+        SchemaNode::Optional {
+            default_set,
+            content,
+        } => {
+            let mut properties = json::Map::new();
+            properties.insert(
+                "set".into(),
+                json::json!({ "type": "boolean", "default": default_set }),
+            );
+            properties.insert("content".into(), node_schema(content));
+
+            object_schema(properties)
+        }
+        SchemaNode::Switch {
+            default_enabled,
+            content,
+        } => {
+            let mut properties = json::Map::new();
+            properties.insert(
+                "enabled".into(),
+                json::json!({ "type": "boolean", "default": default_enabled }),
+            );
+            properties.insert("content".into(), node_schema(content));
+
+            object_schema(properties)
+        }
+        SchemaNode::Boolean { default } => json::json!({ "type": "boolean", "default": default }),
+        SchemaNode::Number {
+            default,
+            ty,
+            gui,
+            suffix,
+        } => {
+            let mut schema = match ty {
+                NumberType::UnsignedInteger => json::json!({
+                    "type": "integer",
+                    "minimum": 0,
+                    "default": *default as u64,
+                }),
+                NumberType::SignedInteger => {
+                    json::json!({ "type": "integer", "default": *default as i64 })
+                }
+                NumberType::Float => json::json!({ "type": "number", "default": default }),
+            };
+
+            if let NumericGuiType::Slider { range, step, .. } = gui {
+                schema["minimum"] = (*range.start()).into();
+                schema["maximum"] = (*range.end()).into();
+                if let Some(step) = step {
+                    schema["x-step"] = (*step).into();
+                }
+            }
+            if let Some(suffix) = suffix {
+                schema["x-unit"] = suffix.trim().into();
+            }
+
+            schema
+        }
+        SchemaNode::Text { default } => json::json!({ "type": "string", "default": default }),
+        SchemaNode::Array(array_schema) => {
+            let element_schemas = array_schema.iter().map(node_schema).collect::<Vec<_>>();
+
+            let mut properties = json::Map::new();
+            properties.insert(
+                "content".into(),
+                json::json!({
+                    "type": "array",
+                    "prefixItems": element_schemas,
+                    "items": false,
+                    "minItems": array_schema.len(),
+                }),
+            );
+
+            collapsible_schema(properties)
+        }
+        SchemaNode::Vector {
+            default_element, ..
+        } => {
+            let element_schema = node_schema(default_element);
+
+            let mut properties = json::Map::new();
+            properties.insert("element".into(), element_schema.clone());
+            properties.insert(
+                "content".into(),
+                json::json!({ "type": "array", "items": element_schema }),
+            );
+
+            collapsible_schema(properties)
+        }
+        SchemaNode::Dictionary { default_value, .. } => {
+            let value_schema = node_schema(default_value);
+
+            let mut properties = json::Map::new();
+            properties.insert("key".into(), json::json!({ "type": "string" }));
+            properties.insert("value".into(), value_schema.clone());
+            // Entries are [key, value] pairs
+            properties.insert(
+                "content".into(),
+                json::json!({
+                    "type": "array",
+                    "items": {
+                        "type": "array",
+                        "prefixItems": [{ "type": "string" }, value_schema],
+                        "items": false,
+                        "minItems": 2,
+                    },
+                }),
+            );
+
+            collapsible_schema(properties)
+        }
+        // Unknown nodes accept any value
+        _ => json::json!({}),
+    }
+}
+
+pub fn settings_json_schema() -> json::Value {
+    let mut schema = node_schema(&Settings::schema(session_settings_default()));
+
+    let object = schema.as_object_mut().unwrap();
+    object.insert("$schema".into(), JSON_SCHEMA_DIALECT.into());
+    object.insert("title".into(), "Session settings".into());
+
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Supports the subset of keywords generated above, ignoring annotations and ranges
+    fn validate(value: &json::Value, schema: &json::Value, path: &str) {
+        let valid_type = match schema["type"].as_str() {
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("boolean") => value.is_boolean(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            Some("string") => value.is_string(),
+            _ => true,
+        };
+        assert!(valid_type, "{path}: expected {}", schema["type"]);
+
+        if let Some(variants) = schema["enum"].as_array() {
+            assert!(variants.contains(value), "{path}: unknown variant {value}");
+        }
+
+        if let (Some(object), Some(properties)) =
+            (value.as_object(), schema["properties"].as_object())
+        {
+            for (key, value) in object {
+                let Some(property_schema) = properties.get(key) else {
+                    panic!("{path}.{key}: unknown field");
+                };
+                validate(value, property_schema, &format!("{path}.{key}"));
+            }
+            for required in schema["required"].as_array().unwrap() {
+                let required = required.as_str().unwrap();
+                assert!(object.contains_key(required), "{path}.{required}: missing");
+            }
+        }
+
+        if let Some(elements) = value.as_array() {
+            let prefix_items = schema["prefixItems"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            if let Some(min_items) = schema["minItems"].as_u64() {
+                assert!(
+                    elements.len() as u64 >= min_items,
+                    "{path}: too few elements"
+                );
+            }
+            for (idx, element) in elements.iter().enumerate() {
+                let element_schema = prefix_items.get(idx).unwrap_or(&schema["items"]);
+                assert_ne!(
+                    element_schema,
+                    &json::Value::Bool(false),
+                    "{path}: extra element"
+                );
+                validate(element, element_schema, &format!("{path}[{idx}]"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_settings_match_schema() {
+        let schema = settings_json_schema();
+        let session_settings = json::to_value(session_settings_default()).unwrap();
+
+        validate(&session_settings, &schema, "session_settings");
+    }
+
+    #[test]
+    fn test_schema_flags() {
+        let schema = settings_json_schema();
+        let video = &schema["properties"]["video"]["properties"];
+
+        assert_eq!(video["passthrough"]["x-flags"], json::json!(["real-time"]));
+        assert!(video["preferred_codec"]["description"].is_string());
+        assert_eq!(
+            video["preferred_codec"]["x-flags"],
+            json::json!(["steamvr-restart"])
+        );
+        assert!(
+            video["preferred_codec"]["properties"]["variant"]["enum"]
+                .as_array()
+                .unwrap()
+                .contains(&json::json!("H264"))
+        );
+    }
+}
@@ -1,18 +1,22 @@
+mod json_schema;
 mod settings;
 
+pub use json_schema::*;
 pub use settings::*;
 pub use settings_schema;
 
+use rand::{TryRngCore, rngs::OsRng};
 use serde::{Deserialize, Serialize};
 use serde_json as json;
 use settings_schema::{NumberType, SchemaNode};
 use shared::{
     ConnectionState, NANVR_VERSION, ToAny,
-    anyhow::{Result, bail},
+    anyhow::{Result, anyhow, bail},
+    warn,
 };
 use std::{
     collections::{HashMap, HashSet},
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr},
 };
 
 // SessionSettings is similar to Settings but it contains every branch, even unused ones. This is
@@ -22,7 +26,8 @@ pub type SessionSettings = settings::SettingsDefault;
 // This structure is used to store the minimum configuration data that NaNVR driver needs to
 // initialize OpenVR before having the chance to communicate with a client. When a client is
 // connected, a new OpenvrConfig instance is generated, then the connection is accepted only if that
-// instance is equivalent to the one stored in the session, otherwise SteamVR is restarted.
+// instance is equivalent to the one stored in the session, otherwise SteamVR is restarted (or the
+// stream is renegotiated, see OpenvrConfig::requires_driver_reload()).
 // Other components (like the encoder, audio recorder) don't need this treatment and are initialized
 // dynamically.
 // todo: properties that can be set after the OpenVR initialization should be removed and set with
@@ -32,6 +37,10 @@ pub type SessionSettings = settings::SettingsDefault;
 pub struct OpenvrConfig {
     pub eye_resolution_width: u32,
     pub eye_resolution_height: u32,
+    // Resolution of the encoded stream. It can be lower than eye_resolution, which is the render
+    // size reported to SteamVR
+    pub encoding_eye_resolution_width: u32,
+    pub encoding_eye_resolution_height: u32,
     pub target_eye_resolution_width: u32,
     pub target_eye_resolution_height: u32,
     pub tracking_ref_only: bool,
@@ -107,6 +116,72 @@ pub struct OpenvrConfig {
     pub _decoder_debug: bool,
 }
 
+impl OpenvrConfig {
+    // Copy of the config with the fields read only when (re)creating the encoder reset
+    fn without_encoder_fields(&self) -> Self {
+        Self {
+            encoding_eye_resolution_width: 0,
+            encoding_eye_resolution_height: 0,
+            minimum_idr_interval_ms: 0,
+            codec: 0,
+            h264_profile: 0,
+            use_10bit_encoder: false,
+            encoding_gamma: 0.0,
+            enable_hdr: false,
+            force_hdr_srgb_correction: false,
+            clamp_hdr_extended_range: false,
+            enable_vbaq: false,
+            encoder_quality_preset: 0,
+            filler_data: false,
+            entropy_coding: 0,
+            force_sw_encoding: false,
+            sw_thread_count: 0,
+            enable_foveated_encoding: false,
+            foveation_center_size_x: 0.0,
+            foveation_center_size_y: 0.0,
+            foveation_center_shift_x: 0.0,
+            foveation_center_shift_y: 0.0,
+            foveation_edge_ratio_x: 0.0,
+            foveation_edge_ratio_y: 0.0,
+            enable_color_correction: false,
+            brightness: 0.0,
+            contrast: 0.0,
+            saturation: 0.0,
+            gamma: 0.0,
+            sharpening: 0.0,
+            nvenc_quality_preset: 0,
+            nvenc_tuning_preset: 0,
+            nvenc_multi_pass: 0,
+            nvenc_adaptive_quantization_mode: 0,
+            nvenc_low_delay_key_frame_scale: 0,
+            nvenc_refresh_rate: 0,
+            enable_intra_refresh: false,
+            intra_refresh_period: 0,
+            intra_refresh_count: 0,
+            max_num_ref_frames: 0,
+            gop_length: 0,
+            p_frame_strategy: 0,
+            nvenc_rate_control_mode: 0,
+            rc_buffer_size: 0,
+            rc_initial_delay: 0,
+            rc_max_bitrate: 0,
+            rc_average_bitrate: 0,
+            nvenc_enable_weighted_prediction: false,
+            capture_frame_dir: String::new(),
+            amd_bitrate_corruption_fix: false,
+            _encoder_debug: false,
+            ..self.clone()
+        }
+    }
+
+    // Changes to encoder-only fields can be applied by recreating the encoder and renegotiating
+    // the stream with the client. Any other change (including the render size reported to
+    // SteamVR) requires reloading the driver.
+    pub fn requires_driver_reload(&self, new_config: &OpenvrConfig) -> bool {
+        self.without_encoder_fields() != new_config.without_encoder_fields()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ClientConnectionConfig {
     pub display_name: String,
@@ -114,26 +189,51 @@ pub struct ClientConnectionConfig {
     pub manual_ips: HashSet<IpAddr>,
     pub trusted: bool,
     pub connection_state: ConnectionState,
+    // Partial session settings applied on top of the global ones when this client connects. It has
+    // the same format as SessionConfig::session_settings.
+    #[serde(default)]
+    pub settings_overrides: Option<json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SessionConfig {
     pub server_version: String,
+    // Stable identity of this server installation, used by clients to pin it
+    pub server_id: String,
+    // Required as a bearer token by the web server for mutating requests and websockets
+    pub api_token: String,
+    // Used to derive the key each client stores to authenticate this server. Never sent as is
+    pub server_secret: String,
     pub openvr_config: OpenvrConfig,
     // The hashmap key is the hostname
     pub client_connections: HashMap<String, ClientConnectionConfig>,
     pub session_settings: SessionSettings,
 }
 
+// The server ID is public, so it is generated separately from the secrets
+fn random_hex(bytes_count: usize) -> String {
+    let mut bytes = vec![0; bytes_count];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .expect("Failed to read the OS random number generator");
+
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
 impl Default for SessionConfig {
     fn default() -> Self {
         Self {
             server_version: NANVR_VERSION.to_owned(),
+            server_id: random_hex(8),
+            api_token: random_hex(32),
+            server_secret: random_hex(32),
             openvr_config: OpenvrConfig {
                 // avoid realistic resolutions, as on first start, on Linux, it
                 // could trigger direct mode on an existing monitor
                 eye_resolution_width: 800,
                 eye_resolution_height: 900,
+                encoding_eye_resolution_width: 800,
+                encoding_eye_resolution_height: 900,
                 target_eye_resolution_width: 800,
                 target_eye_resolution_height: 900,
                 refresh_rate: 60,
@@ -151,6 +251,17 @@ impl Default for SessionConfig {
     }
 }
 
+impl WebServerBindAddress {
+    // None if the custom address is not valid
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            WebServerBindAddress::Localhost => Some(Ipv4Addr::LOCALHOST.into()),
+            WebServerBindAddress::AllInterfaces => Some(Ipv4Addr::UNSPECIFIED.into()),
+            WebServerBindAddress::Custom(address) => address.trim().parse().ok(),
+        }
+    }
+}
+
 impl SessionConfig {
     // If json_value is not a valid representation of SessionConfig (because of version upgrade),
     // use some fuzzy logic to extrapolate as much information as possible.
@@ -214,6 +325,51 @@ impl SessionConfig {
         }
     }
 
+    // Copy of the session that can be handed outside the server. The API token grants access to
+    // the web server and the server secret authenticates the server.
+    pub fn without_secrets(&self) -> SessionConfig {
+        SessionConfig {
+            api_token: String::new(),
+            server_secret: String::new(),
+            ..self.clone()
+        }
+    }
+
+    // Copy of the session where the settings overrides of the client, if any, are merged on top of
+    // the global settings.
+    pub fn with_client_overrides(&self, hostname: &str) -> SessionConfig {
+        let mut session = self.clone();
+
+        if let Some(overrides) = self
+            .client_connections
+            .get(hostname)
+            .and_then(|client| client.settings_overrides.as_ref())
+        {
+            // Overrides are validated when set, but the schema could have changed since then
+            match self.session_settings_with_overrides(overrides) {
+                Ok(session_settings) => session.session_settings = session_settings,
+                Err(e) => warn!("Failed to apply settings overrides for {hostname}: {e}"),
+            }
+        }
+
+        session
+    }
+
+    // Merge partial session settings on top of the current ones. Fields that do not match the
+    // schema are ignored.
+    pub fn session_settings_with_overrides(
+        &self,
+        overrides: &json::Value,
+    ) -> Result<SessionSettings> {
+        let merged_json = extrapolate_session_settings_from_session_settings(
+            &json::to_value(&self.session_settings)?,
+            overrides,
+            &Settings::schema(settings::session_settings_default()),
+        );
+
+        Ok(json::from_value(merged_json)?)
+    }
+
     pub fn to_settings(&self) -> Settings {
         let session_settings_json = json::to_value(&self.session_settings).unwrap();
         let schema = Settings::schema(settings::session_settings_default());
@@ -227,6 +383,341 @@ impl SessionConfig {
     }
 }
 
+// Check that a partial session settings value only contains fields known to the settings schema,
+// with values of the correct type.
+pub fn validate_settings_overrides(overrides: &json::Value) -> Result<()> {
+    validate_session_settings_overlay(
+        overrides,
+        &Settings::schema(settings::session_settings_default()),
+        "session_settings",
+    )
+}
+
+fn validate_session_settings_overlay(
+    overlay: &json::Value,
+    schema: &SchemaNode,
+    path: &str,
+) -> Result<()> {
+    fn as_object<'a>(
+        value: &'a json::Value,
+        path: &str,
+    ) -> Result<&'a json::Map<String, json::Value>> {
+        value
+            .as_object()
+            .ok_or_else(|| anyhow!("{path}: expected an object"))
+    }
+
+    fn check_bool(value: &json::Value, path: &str) -> Result<()> {
+        if !value.is_boolean() {
+            bail!("{path}: expected a boolean");
+        }
+
+        Ok(())
+    }
+
+    match schema {
+        SchemaNode::Section {
+            entries,
+            gui_collapsible,
+        } => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                if key == "gui_collapsed" && *gui_collapsible {
+                    check_bool(value, &path)?;
+                } else if let Some(named_entry) = entries.iter().find(|entry| entry.name == *key) {
+                    validate_session_settings_overlay(value, &named_entry.content, &path)?;
+                } else {
+                    bail!("{path}: unknown field");
+                }
+            }
+        }
+        SchemaNode::Choice { variants, .. } => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                if key == "variant" {
+                    if !variants
+                        .iter()
+                        .any(|named_entry| Some(named_entry.name.as_str()) == value.as_str())
+                    {
+                        bail!("{path}: unknown variant {value}");
+                    }
+                } else if let Some(data_schema) = variants
+                    .iter()
+                    .find(|named_entry| named_entry.name == *key)
+                    .and_then(|named_entry| named_entry.content.as_ref())
+                {
+                    validate_session_settings_overlay(value, data_schema, &path)?;
+                } else {
+                    bail!("{path}: unknown field");
+                }
+            }
+        }
+        SchemaNode::Optional { content, .. } => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                match key.as_str() {
+                    "set" => check_bool(value, &path)?,
+                    "content" => validate_session_settings_overlay(value, content, &path)?,
+                    _ => bail!("{path}: unknown field"),
+                }
+            }
+        }
+        SchemaNode::Switch { content, .. } => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                match key.as_str() {
+                    "enabled" => check_bool(value, &path)?,
+                    "content" => validate_session_settings_overlay(value, content, &path)?,
+                    _ => bail!("{path}: unknown field"),
+                }
+            }
+        }
+        SchemaNode::Boolean { .. } => check_bool(overlay, path)?,
+        SchemaNode::Number { ty, .. } => {
+            let valid = match ty {
+                NumberType::UnsignedInteger => overlay.is_u64(),
+                NumberType::SignedInteger => overlay.is_i64(),
+                NumberType::Float => overlay.is_number(),
+            };
+            if !valid {
+                bail!("{path}: expected a number of the correct type");
+            }
+        }
+        SchemaNode::Text { .. } => {
+            if !overlay.is_string() {
+                bail!("{path}: expected a string");
+            }
+        }
+        SchemaNode::Array(array_schema) => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                match key.as_str() {
+                    "gui_collapsed" => check_bool(value, &path)?,
+                    "content" => {
+                        let Some(elements) = value.as_array() else {
+                            bail!("{path}: expected an array");
+                        };
+                        if elements.len() > array_schema.len() {
+                            bail!("{path}: expected at most {} elements", array_schema.len());
+                        }
+                        for (idx, (element, schema)) in
+                            elements.iter().zip(array_schema).enumerate()
+                        {
+                            validate_session_settings_overlay(
+                                element,
+                                schema,
+                                &format!("{path}[{idx}]"),
+                            )?;
+                        }
+                    }
+                    _ => bail!("{path}: unknown field"),
+                }
+            }
+        }
+        SchemaNode::Vector {
+            default_element, ..
+        } => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                match key.as_str() {
+                    "gui_collapsed" => check_bool(value, &path)?,
+                    "element" => validate_session_settings_overlay(value, default_element, &path)?,
+                    "content" => {
+                        let Some(elements) = value.as_array() else {
+                            bail!("{path}: expected an array");
+                        };
+                        for (idx, element) in elements.iter().enumerate() {
+                            validate_session_settings_overlay(
+                                element,
+                                default_element,
+                                &format!("{path}[{idx}]"),
+                            )?;
+                        }
+                    }
+                    _ => bail!("{path}: unknown field"),
+                }
+            }
+        }
+        SchemaNode::Dictionary { default_value, .. } => {
+            for (key, value) in as_object(overlay, path)? {
+                let path = format!("{path}.{key}");
+                match key.as_str() {
+                    "gui_collapsed" => check_bool(value, &path)?,
+                    "key" => {
+                        if !value.is_string() {
+                            bail!("{path}: expected a string");
+                        }
+                    }
+                    "value" => validate_session_settings_overlay(value, default_value, &path)?,
+                    "content" => {
+                        let Ok(entries) =
+                            json::from_value::<Vec<(String, json::Value)>>(value.clone())
+                        else {
+                            bail!("{path}: expected an array of key-value pairs");
+                        };
+                        for (key, value) in entries {
+                            validate_session_settings_overlay(
+                                &value,
+                                default_value,
+                                &format!("{path}[{key}]"),
+                            )?;
+                        }
+                    }
+                    _ => bail!("{path}: unknown field"),
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+// Paths of the settings flagged with "steamvr-restart" that differ between the two session
+// settings.
+pub fn steamvr_restart_paths(
+    old_session_settings: &SessionSettings,
+    new_session_settings: &SessionSettings,
+) -> Vec<String> {
+    let mut paths = vec![];
+    collect_steamvr_restart_paths(
+        &json::to_value(old_session_settings).unwrap(),
+        &json::to_value(new_session_settings).unwrap(),
+        &Settings::schema(settings::session_settings_default()),
+        "session_settings",
+        &mut paths,
+    );
+
+    paths
+}
+
+fn collect_steamvr_restart_paths(
+    old_session_settings: &json::Value,
+    new_session_settings: &json::Value,
+    schema: &SchemaNode,
+    path: &str,
+    paths: &mut Vec<String>,
+) {
+    match schema {
+        SchemaNode::Section { entries, .. } => {
+            for named_entry in entries {
+                let name = &named_entry.name;
+                if old_session_settings[name] == new_session_settings[name] {
+                    continue;
+                }
+
+                let path = format!("{path}.{name}");
+                if named_entry.flags.contains("steamvr-restart") {
+                    if !is_real_time_change(
+                        &old_session_settings[name],
+                        &new_session_settings[name],
+                        &named_entry.content,
+                    ) {
+                        paths.push(path);
+                    }
+                } else {
+                    collect_steamvr_restart_paths(
+                        &old_session_settings[name],
+                        &new_session_settings[name],
+                        &named_entry.content,
+                        &path,
+                        paths,
+                    );
+                }
+            }
+        }
+        SchemaNode::Choice { variants, .. } => {
+            for named_entry in variants {
+                if let Some(data_schema) = &named_entry.content {
+                    collect_steamvr_restart_paths(
+                        &old_session_settings[&named_entry.name],
+                        &new_session_settings[&named_entry.name],
+                        data_schema,
+                        &format!("{path}.{}", named_entry.name),
+                        paths,
+                    );
+                }
+            }
+        }
+        SchemaNode::Optional { content, .. } | SchemaNode::Switch { content, .. } => {
+            collect_steamvr_restart_paths(
+                &old_session_settings["content"],
+                &new_session_settings["content"],
+                content,
+                &format!("{path}.content"),
+                paths,
+            );
+        }
+        SchemaNode::Array(array_schema) => {
+            for (idx, schema) in array_schema.iter().enumerate() {
+                collect_steamvr_restart_paths(
+                    &old_session_settings["content"][idx],
+                    &new_session_settings["content"][idx],
+                    schema,
+                    &format!("{path}.content[{idx}]"),
+                    paths,
+                );
+            }
+        }
+        // Flags inside vectors and dictionaries are not supported
+        _ => (),
+    }
+}
+
+// Whether the only differences are in real-time settings, which are applied while streaming even
+// when nested inside a setting that requires a SteamVR restart
+fn is_real_time_change(
+    old_session_settings: &json::Value,
+    new_session_settings: &json::Value,
+    schema: &SchemaNode,
+) -> bool {
+    match schema {
+        SchemaNode::Section { entries, .. } => entries.iter().all(|named_entry| {
+            let name = &named_entry.name;
+            old_session_settings[name] == new_session_settings[name]
+                || named_entry.flags.contains("real-time")
+                || is_real_time_change(
+                    &old_session_settings[name],
+                    &new_session_settings[name],
+                    &named_entry.content,
+                )
+        }),
+        SchemaNode::Choice { variants, .. } => {
+            old_session_settings["variant"] == new_session_settings["variant"]
+                && variants.iter().all(|named_entry| {
+                    let name = &named_entry.name;
+                    old_session_settings[name] == new_session_settings[name]
+                        || named_entry.content.as_ref().is_some_and(|data_schema| {
+                            is_real_time_change(
+                                &old_session_settings[name],
+                                &new_session_settings[name],
+                                data_schema,
+                            )
+                        })
+                })
+        }
+        SchemaNode::Optional { content, .. } => {
+            old_session_settings["set"] == new_session_settings["set"]
+                && (old_session_settings["content"] == new_session_settings["content"]
+                    || is_real_time_change(
+                        &old_session_settings["content"],
+                        &new_session_settings["content"],
+                        content,
+                    ))
+        }
+        SchemaNode::Switch { content, .. } => {
+            old_session_settings["enabled"] == new_session_settings["enabled"]
+                && (old_session_settings["content"] == new_session_settings["content"]
+                    || is_real_time_change(
+                        &old_session_settings["content"],
+                        &new_session_settings["content"],
+                        content,
+                    ))
+        }
+        _ => old_session_settings == new_session_settings,
+    }
+}
+
 // Current data extrapolation strategy: match both field name and value type exactly.
 // Integer bounds are not validated, if they do not match the schema, deserialization will fail and
 // all data is lost.
@@ -638,4 +1129,116 @@ mod tests {
         assert_eq!(settings.video.preferred_fps, 60.0);
         assert!(settings.headset.controllers.as_option().is_none());
     }
+
+    #[test]
+    fn test_client_settings_overrides() {
+        let overrides = json::json!({
+            "video": {
+                "preferred_fps": 60.0
+            }
+        });
+        validate_settings_overrides(&overrides).unwrap();
+
+        let mut session = SessionConfig::default();
+        session.client_connections.insert(
+            "client".into(),
+            ClientConnectionConfig {
+                display_name: "Client".into(),
+                current_ip: None,
+                manual_ips: HashSet::new(),
+                trusted: true,
+                connection_state: ConnectionState::Disconnected,
+                settings_overrides: Some(overrides),
+            },
+        );
+
+        let default_fps = session.to_settings().video.preferred_fps;
+        let settings = session.with_client_overrides("client").to_settings();
+        assert_eq!(settings.video.preferred_fps, 60.0);
+        assert_eq!(
+            session
+                .with_client_overrides("other")
+                .to_settings()
+                .video
+                .preferred_fps,
+            default_fps
+        );
+
+        assert!(validate_settings_overrides(&json::json!({ "fjdshfks": false })).is_err());
+        assert!(
+            validate_settings_overrides(&json::json!({ "video": { "preferred_fps": "60" } }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_random_identifiers() {
+        let session = SessionConfig::default();
+        assert_eq!(session.server_id.len(), 16);
+        assert_eq!(session.api_token.len(), 64);
+        assert!(session.api_token.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert!(!session.api_token.contains(&session.server_id));
+        assert_eq!(session.server_secret.len(), 64);
+        assert_ne!(session.server_secret, session.api_token);
+
+        let other_session = SessionConfig::default();
+        assert_ne!(other_session.server_id, session.server_id);
+        assert_ne!(other_session.api_token, session.api_token);
+        assert_ne!(other_session.server_secret, session.server_secret);
+    }
+
+    #[test]
+    fn test_web_server_bind_address_ip() {
+        assert_eq!(
+            WebServerBindAddress::Localhost.ip(),
+            Some(Ipv4Addr::LOCALHOST.into())
+        );
+        assert_eq!(
+            WebServerBindAddress::AllInterfaces.ip(),
+            Some(Ipv4Addr::UNSPECIFIED.into())
+        );
+
+        let custom = |address: &str| WebServerBindAddress::Custom(address.into()).ip();
+        assert_eq!(custom(" 192.168.1.20 "), Some("192.168.1.20".parse().unwrap()));
+        assert_eq!(custom("::1"), Some("::1".parse().unwrap()));
+        assert_eq!(custom("192.168.1.20:8082"), None);
+        assert_eq!(custom("localhost"), None);
+        assert_eq!(custom(""), None);
+    }
+
+    #[test]
+    fn test_steamvr_restart_paths() {
+        let old_session_settings = SessionConfig::default().session_settings;
+
+        // Foveation parameters are real-time, but toggling foveated encoding is not
+        let mut new_session_settings = old_session_settings.clone();
+        new_session_settings
+            .video
+            .foveated_encoding
+            .content
+            .center_size_x += 0.1;
+        assert!(steamvr_restart_paths(&old_session_settings, &new_session_settings).is_empty());
+
+        new_session_settings.video.foveated_encoding.enabled =
+            !old_session_settings.video.foveated_encoding.enabled;
+        assert_eq!(
+            steamvr_restart_paths(&old_session_settings, &new_session_settings),
+            ["session_settings.video.foveated_encoding"]
+        );
+    }
+
+    #[test]
+    fn test_openvr_config_requires_driver_reload() {
+        let config = OpenvrConfig::default();
+
+        let mut new_config = config.clone();
+        new_config.codec = 1;
+        new_config.use_10bit_encoder = true;
+        new_config.foveation_center_size_x = 0.5;
+        new_config.encoding_eye_resolution_width = 1000;
+        assert!(!config.requires_driver_reload(&new_config));
+
+        new_config.eye_resolution_width = 2000;
+        assert!(config.requires_driver_reload(&new_config));
+    }
 }
@@ -313,6 +313,32 @@ pub struct DecoderLatencyLimiter {
     pub latency_overstep_multiplier: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(collapsible)]
+pub struct PacketLossLimiter {
+    #[schema(strings(
+        display_name = "Maximum packet loss",
+        help = "When the video packet loss goes above this threshold, the bitrate will be reduced"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 20.0, step = 0.1)), suffix = "%")]
+    pub max_packet_loss_percent: f32,
+
+    #[schema(strings(
+        help = "Controls how much the bitrate is reduced every second while the packet loss is above the threshold"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.5, max = 1.0, step = 0.01)))]
+    pub backoff_multiplier: f32,
+
+    #[schema(strings(
+        help = "Controls how fast the bitrate limit is relaxed every second while there is no packet loss"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 1.5, step = 0.01)))]
+    pub recovery_multiplier: f32,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(gui = "button_group")]
 pub enum BitrateMode {
@@ -351,6 +377,53 @@ pub enum BitrateMode {
         ))]
         #[schema(flag = "real-time")]
         decoder_latency_limiter: Switch<DecoderLatencyLimiter>,
+
+        #[schema(strings(
+            help = "Reduce the bitrate when the network drops video packets, even if the latency doesn't increase"
+        ))]
+        #[schema(flag = "real-time")]
+        packet_loss_limiter: Switch<PacketLossLimiter>,
+    },
+
+    #[schema(strings(
+        display_name = "Delay gradient",
+        help = "Congestion control based on the trend of the network latency and on packet loss, similar to Google Congestion Control"
+    ))]
+    #[schema(collapsible)]
+    DelayGradient {
+        #[schema(strings(display_name = "Initial bitrate"))]
+        #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "Mbps")]
+        initial_throughput_mbps: u64,
+
+        #[schema(strings(display_name = "Maximum bitrate"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "Mbps")]
+        max_throughput_mbps: u64,
+
+        #[schema(strings(display_name = "Minimum bitrate"))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 1, max = 100, logarithmic)), suffix = "Mbps")]
+        min_throughput_mbps: u64,
+
+        #[schema(strings(
+            help = "Initial threshold for the latency trend above which the network is considered congested. It then adapts to the network jitter"
+        ))]
+        #[schema(gui(slider(min = 1.0, max = 50.0, step = 0.5)), suffix = "ms")]
+        overuse_threshold_ms: f32,
+
+        #[schema(strings(
+            help = "Fraction by which the bitrate is increased every second while the network is not congested"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.01, max = 0.5, step = 0.01)))]
+        increase_rate: f32,
+
+        #[schema(strings(
+            help = "Multiplier applied to the received throughput when the network is congested"
+        ))]
+        #[schema(flag = "real-time")]
+        #[schema(gui(slider(min = 0.5, max = 0.95, step = 0.01)))]
+        decrease_multiplier: f32,
     },
 }
 
@@ -365,6 +438,48 @@ pub struct BitrateAdaptiveFramerateConfig {
     pub framerate_reset_threshold_multiplier: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(collapsible)]
+pub struct DynamicResolutionConfig {
+    #[schema(strings(
+        display_name = "Minimum resolution scale",
+        help = "Lowest fraction of the stream resolution the encoder can downscale to"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.25, max = 1.0, step = 0.05)))]
+    pub min_scale: f32,
+
+    #[schema(strings(help = "Resolution scale change for each step"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.05, max = 0.5, step = 0.05)))]
+    pub scale_step: f32,
+
+    #[schema(strings(
+        display_name = "Low bitrate threshold",
+        help = "The resolution is lowered when the bitrate stays at or below this value"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 100.0, logarithmic)), suffix = "Mbps")]
+    pub low_bitrate_threshold_mbps: f32,
+
+    #[schema(strings(
+        help = "The resolution is raised again when the bitrate stays above the low bitrate threshold times this multiplier"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 3.0, step = 0.1)))]
+    pub upscale_bitrate_multiplier: f32,
+
+    #[schema(strings(display_name = "Downscale delay"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 30.0, step = 1.0)), suffix = "s")]
+    pub downscale_delay_s: f32,
+
+    #[schema(strings(display_name = "Upscale delay"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 60.0, step = 1.0)), suffix = "s")]
+    pub upscale_delay_s: f32,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(collapsible)]
 pub struct BitrateConfig {
@@ -377,6 +492,12 @@ pub struct BitrateConfig {
     #[schema(flag = "real-time")]
     pub adapt_to_framerate: Switch<BitrateAdaptiveFramerateConfig>,
 
+    #[schema(strings(
+        help = "Lower the encoding resolution in steps when an adaptive bitrate stays low, instead of starving the encoder. The client upscales the picture."
+    ))]
+    #[schema(flag = "real-time")]
+    pub dynamic_resolution: Switch<DynamicResolutionConfig>,
+
     #[schema(strings(help = "Controls the smoothness during calculations"))]
     pub history_size: usize,
 
@@ -419,32 +540,32 @@ pub struct FoveatedEncodingConfig {
 
     #[schema(strings(display_name = "Center region width"))]
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_size_x: f32,
 
     #[schema(strings(display_name = "Center region height"))]
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_size_y: f32,
 
     #[schema(strings(display_name = "Center shift X"))]
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_shift_x: f32,
 
     #[schema(strings(display_name = "Center shift Y"))]
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_shift_y: f32,
 
     #[schema(strings(display_name = "Horizontal edge ratio"))]
     #[schema(gui(slider(min = 1.0, max = 10.0, step = 1.0)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub edge_ratio_x: f32,
 
     #[schema(strings(display_name = "Vertical edge ratio"))]
     #[schema(gui(slider(min = 1.0, max = 10.0, step = 1.0)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub edge_ratio_y: f32,
 }
 
@@ -452,23 +573,23 @@ pub struct FoveatedEncodingConfig {
 #[derive(SettingsSchema, Clone, Copy, Serialize, Deserialize, Pod, Zeroable)]
 pub struct ColorCorrectionConfig {
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.001)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub brightness: f32,
 
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.001)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub contrast: f32,
 
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub saturation: f32,
 
     #[schema(gui(slider(min = 0.0, max = 5.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub gamma: f32,
 
     #[schema(gui(slider(min = -1.0, max = 5.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub sharpening: f32,
 }
 
@@ -666,7 +787,7 @@ If you want to reduce the amount of pixelation on the edges, increase the center
     #[schema(flag = "steamvr-restart")]
     pub foveated_encoding: Switch<FoveatedEncodingConfig>,
 
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub color_correction: Switch<ColorCorrectionConfig>,
 
     #[schema(
@@ -728,7 +849,7 @@ If you want to reduce the amount of pixelation on the edges, increase the center
     pub upscaling: Switch<UpscalingConfig>,
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(gui = "button_group")]
 pub enum CustomAudioDeviceConfig {
     #[schema(strings(display_name = "By name (substring)"))]
@@ -752,6 +873,14 @@ pub struct AudioBufferingConfig {
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct GameAudioConfig {
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 2.0, step = 0.01)))]
+    pub gain: f32,
+
+    #[schema(strings(help = "Headset audio device. If not set, the system default is used"))]
+    #[schema(flag = "real-time")]
+    pub device: Option<CustomAudioDeviceConfig>,
+
     pub buffering: AudioBufferingConfig,
 }
 
@@ -760,6 +889,14 @@ pub struct GameAudioConfig {
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct MicrophoneConfig {
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 2.0, step = 0.01)))]
+    pub gain: f32,
+
+    #[schema(strings(help = "Headset audio device. If not set, the system default is used"))]
+    #[schema(flag = "real-time")]
+    pub device: Option<CustomAudioDeviceConfig>,
+
     pub buffering: AudioBufferingConfig,
 }
 
@@ -792,7 +929,7 @@ pub enum FaceTrackingSourcesConfig {
     PreferFullFaceTracking,
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 pub enum FaceTrackingSinkConfig {
     #[schema(strings(display_name = "VRChat Eye OSC"))]
     VrchatEyeOsc { port: u16 },
@@ -804,6 +941,7 @@ pub enum FaceTrackingSinkConfig {
 #[schema(collapsible)]
 pub struct FaceTrackingConfig {
     pub sources: FaceTrackingSourcesConfig,
+    #[schema(flag = "real-time")]
     pub sink: FaceTrackingSinkConfig,
 }
 
@@ -839,7 +977,7 @@ pub struct BodyTrackingSourcesConfig {
     pub bd: BodyTrackingBDConfig,
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 pub enum BodyTrackingSinkConfig {
     #[schema(strings(display_name = "Fake Vive Trackers"))]
     FakeViveTracker,
@@ -851,6 +989,7 @@ pub enum BodyTrackingSinkConfig {
 #[schema(collapsible)]
 pub struct BodyTrackingConfig {
     pub sources: BodyTrackingSourcesConfig,
+    #[schema(flag = "real-time")]
     pub sink: BodyTrackingSinkConfig,
     #[schema(strings(help = "Turn this off to temporarily pause tracking."))]
     #[schema(flag = "real-time")]
@@ -893,7 +1032,7 @@ pub enum ControllersEmulationMode {
     },
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct HysteresisThreshold {
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
     pub value: f32,
@@ -901,7 +1040,7 @@ pub struct HysteresisThreshold {
     pub deviation: f32,
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct BinaryToScalarStates {
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
     pub off: f32,
@@ -910,7 +1049,7 @@ pub struct BinaryToScalarStates {
 }
 
 // Remaps 0..1 to custom range
-#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct Range {
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
     pub min: f32,
@@ -918,7 +1057,7 @@ pub struct Range {
     pub max: f32,
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ButtonMappingType {
     Passthrough,
     HysteresisThreshold(HysteresisThreshold),
@@ -926,14 +1065,14 @@ pub enum ButtonMappingType {
     Remap(Range),
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ButtonBindingTarget {
     pub destination: String,
     pub mapping_type: ButtonMappingType,
     pub binary_conditions: Vec<String>,
 }
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(collapsible)]
 pub struct AutomaticButtonMappingConfig {
     pub click_threshold: HysteresisThreshold,
@@ -1122,9 +1261,11 @@ Currently this cannot be reliably estimated automatically. The correct value sho
     #[schema(gui(slider(min = -180.0, max = 180.0, step = 1.0)), suffix = "°")]
     pub left_hand_tracking_rotation_offset: [f32; 3],
 
+    #[schema(flag = "real-time")]
     #[schema(strings(help = "List of OpenXR-syle paths"))]
     pub button_mappings: Option<Vec<(String, Vec<ButtonBindingTarget>)>>,
 
+    #[schema(flag = "real-time")]
     pub button_mapping_config: AutomaticButtonMappingConfig,
 }
 
@@ -1218,6 +1359,76 @@ pub enum SocketBufferSize {
     Custom(#[schema(suffix = "B")] u32),
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[schema(collapsible)]
+pub struct LatencyDistributionConfig {
+    #[schema(strings(
+        help = "Number of recent frames used to calculate the latency percentiles and histograms"
+    ))]
+    #[schema(gui(slider(min = 100, max = 10000, logarithmic)), suffix = " frames")]
+    pub window_frames: usize,
+
+    #[schema(strings(help = "Frames with a higher total latency are counted as latency spikes"))]
+    #[schema(gui(slider(min = 20.0, max = 200.0, step = 5.0)), suffix = "ms")]
+    pub total_latency_threshold_ms: f32,
+
+    #[schema(strings(
+        help = "Frame intervals longer than this are counted as stutters, on both the client and the streamer"
+    ))]
+    #[schema(gui(slider(min = 5.0, max = 100.0, step = 1.0)), suffix = "ms")]
+    pub frame_interval_threshold_ms: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum WebServerBindAddress {
+    #[schema(strings(display_name = "Localhost only"))]
+    Localhost,
+    #[schema(strings(display_name = "All interfaces (LAN access)"))]
+    AllInterfaces,
+    Custom(String),
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub enum WebServerCertificate {
+    #[schema(strings(
+        help = "A self-signed certificate is generated in the configuration folder on first use"
+    ))]
+    SelfSigned,
+    #[schema(strings(display_name = "PEM files"))]
+    Custom {
+        certificate_path: String,
+        private_key_path: String,
+    },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct HostMonitoringConfig {
+    #[schema(strings(
+        help = "A warning is shown when the CPU usage of the PC stays above this value"
+    ))]
+    #[schema(gui(slider(min = 50.0, max = 100.0, step = 1.0)), suffix = "%")]
+    pub cpu_warning_threshold_percent: f32,
+
+    #[schema(strings(
+        help = "A warning is shown when the GPU or video encoder usage stays above this value"
+    ))]
+    #[schema(gui(slider(min = 50.0, max = 100.0, step = 1.0)), suffix = "%")]
+    pub gpu_warning_threshold_percent: f32,
+
+    #[schema(strings(help = "A warning is shown when the CPU or GPU gets hotter than this"))]
+    #[schema(gui(slider(min = 60.0, max = 110.0, step = 1.0)), suffix = "°C")]
+    pub temperature_warning_threshold_celsius: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct MetricsEndpointConfig {
+    #[schema(strings(
+        help = "Serve metrics to other machines on the network. Otherwise only local requests are allowed."
+    ))]
+    #[schema(flag = "real-time")]
+    pub allow_remote_access: bool,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct ConnectionConfig {
     #[schema(strings(
@@ -1255,6 +1466,12 @@ TCP: Slower than UDP, but more stable. Pick this if you experience video or audi
     #[schema(flag = "real-time")]
     pub allow_untrusted_http: bool,
 
+    #[schema(strings(
+        help = "Serve streaming statistics at /metrics on the web server, in the Prometheus text format"
+    ))]
+    #[schema(flag = "real-time")]
+    pub metrics_endpoint: Switch<MetricsEndpointConfig>,
+
     #[schema(strings(
         help = r#"If the client, server or the network discarded one packet, discard packets until a IDR packet is found."#
     ))]
@@ -1265,6 +1482,21 @@ TCP: Slower than UDP, but more stable. Pick this if you experience video or audi
 
     pub stream_port: u16,
     pub web_server_port: u16,
+
+    #[schema(strings(
+        help = r#"Network address the web server listens on. The dashboard on this PC always has access.
+Remote browsers, metrics scrapers and scripts on other machines need LAN access, and must send the API token stored in session.json."#
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub web_server_bind_address: WebServerBindAddress,
+
+    #[schema(strings(
+        display_name = "Web server TLS",
+        help = r#"Serve the web server over HTTPS and WSS. Plain HTTP is still accepted from this PC, so the dashboard keeps working.
+Remote tools can pin the certificate fingerprint shown in the Installation tab."#
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub web_server_tls: Switch<WebServerCertificate>,
     pub osc_local_port: u16,
 
     #[schema(strings(display_name = "Streamer send buffer size"))]
@@ -1288,6 +1520,13 @@ This could happen on TCP. A IDR frame is requested in this case."#
     #[schema(suffix = " frames")]
     pub statistics_history_size: usize,
 
+    pub latency_distribution: LatencyDistributionConfig,
+
+    #[schema(strings(
+        help = "Monitor the CPU, memory, GPU and video encoder usage and the temperatures of the PC while streaming"
+    ))]
+    pub host_monitoring: Switch<HostMonitoringConfig>,
+
     #[schema(strings(display_name = "Minimum IDR interval"))]
     #[schema(flag = "steamvr-restart")]
     #[schema(gui(slider(min = 5, max = 1000, step = 5)), suffix = "ms")]
@@ -1373,6 +1612,28 @@ pub struct RollingVideoFilesConfig {
     pub duration_s: u64,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[schema(gui = "button_group")]
+pub enum FrameStatisticsFormat {
+    #[schema(strings(display_name = "CSV"))]
+    Csv,
+    #[schema(strings(display_name = "JSONL"))]
+    Jsonl,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct FrameStatisticsLogConfig {
+    pub format: FrameStatisticsFormat,
+
+    #[schema(strings(help = "A new file is started when the current one reaches this size"))]
+    #[schema(gui(slider(min = 1, max = 1000, logarithmic)), suffix = "MB")]
+    pub max_file_size_mb: u64,
+
+    #[schema(strings(help = "Older files of the same logging session are deleted"))]
+    #[schema(gui(slider(min = 1, max = 100)))]
+    pub max_files: usize,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct CaptureConfig {
     #[schema(strings(display_name = "Start video recording at client connection"))]
@@ -1380,6 +1641,14 @@ pub struct CaptureConfig {
 
     pub rolling_video_files: Switch<RollingVideoFilesConfig>,
 
+    #[schema(strings(
+        display_name = "Start frame statistics logging at client connection",
+        help = "Write the timestamps, latencies and size of every frame to a file in the log directory"
+    ))]
+    pub startup_frame_statistics_logging: bool,
+
+    pub frame_statistics_log: FrameStatisticsLogConfig,
+
     #[schema(flag = "steamvr-restart")]
     pub capture_frame_dir: String,
 }
@@ -1403,6 +1672,39 @@ pub struct NewVersionPopupConfig {
     pub hide_while_version: String,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct HookConfig {
+    #[schema(strings(
+        help = "Name of the event that triggers the hook, for example ClientConnected, Battery, ServerRequestsSelfRestart or StatisticsSummary."
+    ))]
+    pub event: String,
+
+    #[schema(strings(
+        help = "Script or executable to run. The event is passed as JSON on stdin, the env var EVENT_NAME is also set."
+    ))]
+    pub script: Option<String>,
+
+    #[schema(strings(
+        help = "Local URL that will receive the event as a JSON HTTP POST request. URLs that don't point to this machine are rejected."
+    ))]
+    pub webhook_url: Option<String>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct HooksConfig {
+    pub hooks: Vec<HookConfig>,
+
+    #[schema(strings(help = "Scripts and requests that take longer than this are aborted."))]
+    #[schema(gui(slider(min = 100, max = 30000, step = 100)), suffix = "ms")]
+    pub timeout_ms: u64,
+
+    #[schema(strings(
+        help = "Maximum number of hooks running at the same time. Further events are dropped."
+    ))]
+    #[schema(gui(slider(min = 1, max = 32)))]
+    pub max_concurrent_hooks: usize,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct ExtraConfig {
     pub steamvr_launcher: SteamvrLauncher,
@@ -1418,6 +1720,10 @@ It does not update in real time.")
 
     pub open_setup_wizard: bool,
     pub new_version_popup: Switch<NewVersionPopupConfig>,
+
+    #[schema(strings(help = "Run scripts or send HTTP requests when server events happen."))]
+    #[schema(flag = "real-time")]
+    pub hooks: Switch<HooksConfig>,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -1441,6 +1747,14 @@ pub fn session_settings_default() -> SettingsDefault {
             },
         },
     };
+    let audio_device = OptionalDefault {
+        set: false,
+        content: CustomAudioDeviceConfigDefault {
+            variant: CustomAudioDeviceConfigDefaultVariant::NameSubstring,
+            NameSubstring: "".into(),
+            Index: 0,
+        },
+    };
     let default_custom_openvr_props = VectorDefault {
         gui_collapsed: true,
         element: OpenvrPropertyDefault {
@@ -1549,6 +1863,24 @@ pub fn session_settings_default() -> SettingsDefault {
                                 latency_overstep_multiplier: 0.99,
                             },
                         },
+                        packet_loss_limiter: SwitchDefault {
+                            enabled: true,
+                            content: PacketLossLimiterDefault {
+                                gui_collapsed: true,
+                                max_packet_loss_percent: 1.0,
+                                backoff_multiplier: 0.85,
+                                recovery_multiplier: 1.05,
+                            },
+                        },
+                    },
+                    DelayGradient: BitrateModeDelayGradientDefault {
+                        gui_collapsed: true,
+                        initial_throughput_mbps: 30,
+                        max_throughput_mbps: 200,
+                        min_throughput_mbps: 5,
+                        overuse_threshold_ms: 12.5,
+                        increase_rate: 0.08,
+                        decrease_multiplier: 0.85,
                     },
                     variant: BitrateModeDefaultVariant::Adaptive,
                 },
@@ -1558,6 +1890,18 @@ pub fn session_settings_default() -> SettingsDefault {
                         framerate_reset_threshold_multiplier: 2.0,
                     },
                 },
+                dynamic_resolution: SwitchDefault {
+                    enabled: false,
+                    content: DynamicResolutionConfigDefault {
+                        gui_collapsed: true,
+                        min_scale: 0.7,
+                        scale_step: 0.15,
+                        low_bitrate_threshold_mbps: 15.0,
+                        upscale_bitrate_multiplier: 1.5,
+                        downscale_delay_s: 3.0,
+                        upscale_delay_s: 10.0,
+                    },
+                },
                 history_size: 256,
                 image_corruption_fix: false,
             },
@@ -1704,6 +2048,8 @@ pub fn session_settings_default() -> SettingsDefault {
                 enabled: true,
                 content: GameAudioConfigDefault {
                     gui_collapsed: true,
+                    gain: 1.0,
+                    device: audio_device.clone(),
                     buffering: AudioBufferingConfigDefault {
                         gui_collapsed: true,
                         average_buffering_ms: 50,
@@ -1715,6 +2061,8 @@ pub fn session_settings_default() -> SettingsDefault {
                 enabled: true,
                 content: MicrophoneConfigDefault {
                     gui_collapsed: true,
+                    gain: 1.0,
+                    device: audio_device.clone(),
                     buffering: AudioBufferingConfigDefault {
                         gui_collapsed: true,
                         average_buffering_ms: 50,
@@ -1921,6 +2269,20 @@ pub fn session_settings_default() -> SettingsDefault {
             },
             wired_client_autolaunch: true,
             web_server_port: 8083,
+            web_server_bind_address: WebServerBindAddressDefault {
+                Custom: "0.0.0.0".into(),
+                variant: WebServerBindAddressDefaultVariant::Localhost,
+            },
+            web_server_tls: SwitchDefault {
+                enabled: false,
+                content: WebServerCertificateDefault {
+                    Custom: WebServerCertificateCustomDefault {
+                        certificate_path: "".into(),
+                        private_key_path: "".into(),
+                    },
+                    variant: WebServerCertificateDefaultVariant::SelfSigned,
+                },
+            },
             stream_port: 9946,
             osc_local_port: 9947,
             dscp: OptionalDefault {
@@ -1946,8 +2308,28 @@ pub fn session_settings_default() -> SettingsDefault {
             enable_on_connect_script: false,
             enable_on_disconnect_script: false,
             allow_untrusted_http: false,
+            metrics_endpoint: SwitchDefault {
+                enabled: false,
+                content: MetricsEndpointConfigDefault {
+                    allow_remote_access: false,
+                },
+            },
             packet_size: 1400,
             statistics_history_size: 256,
+            latency_distribution: LatencyDistributionConfigDefault {
+                gui_collapsed: true,
+                window_frames: 1000,
+                total_latency_threshold_ms: 80.0,
+                frame_interval_threshold_ms: 20.0,
+            },
+            host_monitoring: SwitchDefault {
+                enabled: true,
+                content: HostMonitoringConfigDefault {
+                    cpu_warning_threshold_percent: 95.0,
+                    gpu_warning_threshold_percent: 95.0,
+                    temperature_warning_threshold_celsius: 90.0,
+                },
+            },
         },
         extra: ExtraConfigDefault {
             logging: LoggingConfigDefault {
@@ -1998,6 +2380,14 @@ pub fn session_settings_default() -> SettingsDefault {
                     enabled: false,
                     content: RollingVideoFilesConfigDefault { duration_s: 5 },
                 },
+                startup_frame_statistics_logging: false,
+                frame_statistics_log: FrameStatisticsLogConfigDefault {
+                    format: FrameStatisticsFormatDefault {
+                        variant: FrameStatisticsFormatDefaultVariant::Csv,
+                    },
+                    max_file_size_mb: 50,
+                    max_files: 10,
+                },
                 capture_frame_dir: "/tmp".into(),
             },
             patches: PatchesDefault {
@@ -2012,6 +2402,28 @@ pub fn session_settings_default() -> SettingsDefault {
                     hide_while_version: NANVR_VERSION.to_string(),
                 },
             },
+            hooks: SwitchDefault {
+                enabled: false,
+                content: HooksConfigDefault {
+                    hooks: VectorDefault {
+                        gui_collapsed: false,
+                        element: HookConfigDefault {
+                            event: "ClientConnected".into(),
+                            script: OptionalDefault {
+                                set: false,
+                                content: "".into(),
+                            },
+                            webhook_url: OptionalDefault {
+                                set: false,
+                                content: "http://localhost:8080/".into(),
+                            },
+                        },
+                        content: vec![],
+                    },
+                    timeout_ms: 5000,
+                    max_concurrent_hooks: 4,
+                },
+            },
         },
     }
 }
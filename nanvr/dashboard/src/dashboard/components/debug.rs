@@ -28,5 +28,25 @@ For that, use other means of recording, for example through headset or desktop V
         }
     });
 
+    ui.columns(2, |ui| {
+        if ui[0].button("Start frame statistics log").clicked() {
+            request = Some(ServerRequest::StartFrameStatisticsLogging);
+        }
+
+        if ui[1].button("Stop frame statistics log").clicked() {
+            request = Some(ServerRequest::StopFrameStatisticsLogging);
+        }
+    });
+
+    ui.columns(2, |ui| {
+        if ui[0].button("Start trace capture").clicked() {
+            request = Some(ServerRequest::StartTraceCapture);
+        }
+
+        if ui[1].button("Stop and save trace").clicked() {
+            request = Some(ServerRequest::StopTraceCapture);
+        }
+    });
+
     request
 }
@@ -1,22 +1,69 @@
 use crate::dashboard::ServerRequest;
 use configuration::{ClientConnectionConfig, SessionConfig};
 use eframe::{
-    egui::{self, Frame, Grid, Layout, ProgressBar, RichText, TextEdit, Ui, Window},
+    egui::{
+        self, CollapsingHeader, Frame, Grid, Layout, ProgressBar, RichText, ScrollArea, TextEdit,
+        Ui, Window,
+    },
     emath::{Align, Align2},
     epaint::Color32,
 };
 use gui_shared::theme::{self, log_colors};
 use net_packets::ClientListAction;
 use net_sockets::WIRED_CLIENT_HOSTNAME;
-use shared::{ConnectionState, NANVR_NAME};
+use serde_json as json;
+use shared::{ConnectionState, NANVR_NAME, anyhow::Result};
 
 struct EditPopupState {
     new_devices: bool,
     hostname: String,
     ips: Vec<String>,
+    settings_overrides: String,
+    // Result of the validation of settings_overrides, None if empty
+    parsed_overrides: Option<Result<json::Value>>,
+    // Merged settings in json format, recalculated when the overrides change
+    effective_settings: Option<String>,
+}
+
+impl EditPopupState {
+    fn new(new_devices: bool, hostname: String, ips: Vec<String>) -> Self {
+        Self {
+            new_devices,
+            hostname,
+            ips,
+            settings_overrides: String::new(),
+            parsed_overrides: None,
+            effective_settings: None,
+        }
+    }
+
+    fn update_overrides(&mut self, session: &SessionConfig) {
+        self.parsed_overrides =
+            (!self.settings_overrides.trim().is_empty()).then(|| -> Result<_> {
+                let overrides = json::from_str(&self.settings_overrides)?;
+                configuration::validate_settings_overrides(&overrides)?;
+
+                Ok(overrides)
+            });
+
+        if let Some(Err(_)) = &self.parsed_overrides {
+            return;
+        }
+
+        let mut session = session.clone();
+        if let Some(client) = session.client_connections.get_mut(&self.hostname) {
+            client.settings_overrides = self
+                .parsed_overrides
+                .as_ref()
+                .and_then(|res| res.as_ref().ok().cloned());
+        }
+        let settings = session.with_client_overrides(&self.hostname).to_settings();
+        self.effective_settings = json::to_string_pretty(&settings).ok();
+    }
 }
 
 pub struct DevicesTab {
+    session: Option<SessionConfig>,
     new_devices: Option<Vec<(String, ClientConnectionConfig)>>,
     trusted_devices: Option<Vec<(String, ClientConnectionConfig)>>,
     edit_popup_state: Option<EditPopupState>,
@@ -26,6 +73,7 @@ pub struct DevicesTab {
 impl DevicesTab {
     pub fn new() -> Self {
         Self {
+            session: None,
             new_devices: None,
             trusted_devices: None,
             edit_popup_state: None,
@@ -43,6 +91,7 @@ impl DevicesTab {
 
         self.trusted_devices = Some(trusted_clients);
         self.new_devices = Some(untrusted_clients);
+        self.session = Some(session.clone());
     }
 
     pub fn update_adb_download_progress(&mut self, progress: f32) {
@@ -150,12 +199,22 @@ impl DevicesTab {
                         }
                     });
 
+                    if !state.new_devices
+                        && let Some(session) = &self.session
+                    {
+                        settings_overrides_section(ui, &mut state, session);
+                    }
+
                     ui.columns(2, |ui| {
                         if ui[0].button("Cancel").clicked() {
                             return;
                         }
 
-                        if ui[1].button("Save").clicked() {
+                        let overrides_valid = !matches!(state.parsed_overrides, Some(Err(_)));
+                        if ui[1]
+                            .add_enabled(overrides_valid, egui::Button::new("Save"))
+                            .clicked()
+                        {
                             let manual_ips =
                                 state.ips.iter().filter_map(|s| s.parse().ok()).collect();
 
@@ -169,9 +228,15 @@ impl DevicesTab {
                                 });
                             } else {
                                 requests.push(ServerRequest::UpdateClientList {
-                                    hostname: state.hostname,
+                                    hostname: state.hostname.clone(),
                                     action: ClientListAction::SetManualIps(manual_ips),
                                 });
+                                requests.push(ServerRequest::UpdateClientList {
+                                    hostname: state.hostname,
+                                    action: ClientListAction::SetSettingsOverrides(
+                                        state.parsed_overrides.and_then(Result::ok),
+                                    ),
+                                });
                             }
                         } else {
                             self.edit_popup_state = Some(state);
@@ -315,11 +380,11 @@ fn trusted_clients_section(
 
                 ui.with_layout(Layout::right_to_left(eframe::emath::Align::Center), |ui| {
                     if ui.button("Add device manually").clicked() {
-                        *edit_popup_state = Some(EditPopupState {
-                            hostname: "XXXX.client.local.".into(),
-                            new_devices: true,
-                            ips: Vec::new(),
-                        });
+                        *edit_popup_state = Some(EditPopupState::new(
+                            true,
+                            "XXXX.client.local.".into(),
+                            Vec::new(),
+                        ));
                     }
                 });
             });
@@ -355,15 +420,22 @@ fn trusted_clients_section(
                                         });
                                     }
                                     if ui.button("Edit").clicked() {
-                                        *edit_popup_state = Some(EditPopupState {
-                                            new_devices: false,
-                                            hostname: hostname.to_owned(),
-                                            ips: data
-                                                .manual_ips
+                                        let mut state = EditPopupState::new(
+                                            false,
+                                            hostname.to_owned(),
+                                            data.manual_ips
                                                 .iter()
                                                 .map(|addr| addr.to_string())
                                                 .collect::<Vec<String>>(),
-                                        });
+                                        );
+                                        state.settings_overrides = data
+                                            .settings_overrides
+                                            .as_ref()
+                                            .and_then(|overrides| {
+                                                json::to_string_pretty(overrides).ok()
+                                            })
+                                            .unwrap_or_default();
+                                        *edit_popup_state = Some(state);
                                     }
                                 });
                             });
@@ -374,6 +446,40 @@ fn trusted_clients_section(
     request
 }
 
+fn settings_overrides_section(ui: &mut Ui, state: &mut EditPopupState, session: &SessionConfig) {
+    if state.effective_settings.is_none() {
+        state.update_overrides(session);
+    }
+
+    ui.add_space(5.0);
+    ui.label("Settings overrides (partial session settings in json format):");
+    if ui
+        .add(
+            TextEdit::multiline(&mut state.settings_overrides)
+                .code_editor()
+                .desired_rows(6)
+                .hint_text(r#"{ "video": { "preferred_fps": 90.0 } }"#),
+        )
+        .changed()
+    {
+        state.update_overrides(session);
+    }
+
+    if let Some(Err(e)) = &state.parsed_overrides {
+        ui.colored_label(log_colors::ERROR_LIGHT, format!("Invalid overrides: {e}"));
+    }
+
+    if let Some(effective_settings) = &state.effective_settings {
+        CollapsingHeader::new("Effective settings").show(ui, |ui| {
+            ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                ui.monospace(effective_settings);
+            });
+        });
+    }
+
+    ui.add_space(5.0);
+}
+
 fn connection_label(ui: &mut Ui, connection_state: &ConnectionState) {
     match connection_state {
         ConnectionState::Disconnected => ui.colored_label(Color32::GRAY, "Disconnected"),
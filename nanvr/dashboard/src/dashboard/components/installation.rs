@@ -1,3 +1,4 @@
+use configuration::SessionConfig;
 use eframe::{
     egui::{Frame, Grid, Layout, RichText, Ui},
     emath::Align,
@@ -20,6 +21,8 @@ pub enum InstallationTabRequest {
 pub struct InstallationTab {
     drivers: Vec<PathBuf>,
     last_update_instant: Instant,
+    // None if TLS is disabled. Ok(None) until the web server generates its certificate
+    web_server_fingerprint: Option<Result<Option<String>, String>>,
 }
 
 impl InstallationTab {
@@ -27,6 +30,7 @@ impl InstallationTab {
         Self {
             drivers: vec![],
             last_update_instant: Instant::now(),
+            web_server_fingerprint: None,
         }
     }
 
@@ -34,6 +38,22 @@ impl InstallationTab {
         self.drivers = list;
     }
 
+    // The certificate is only read: generating it here would race with the web server
+    pub fn update_session(&mut self, session: &SessionConfig) {
+        self.web_server_fingerprint = session
+            .to_settings()
+            .connection
+            .web_server_tls
+            .as_option()
+            .map(|certificate| {
+                server_io::web_server_certificate_fingerprint(
+                    &crate::get_filesystem_layout().config_dir,
+                    certificate,
+                )
+                .map_err(|e| e.to_string())
+            });
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) -> Vec<InstallationTabRequest> {
         let mut requests = vec![];
 
@@ -97,6 +117,41 @@ impl InstallationTab {
                         ));
                     }
                 });
+
+            if let Some(fingerprint) = &self.web_server_fingerprint {
+                Frame::group(ui.style())
+                    .fill(theme::SECTION_BG)
+                    .show(ui, |ui| {
+                        ui.vertical_centered(|ui| {
+                            ui.add_space(5.0);
+                            ui.label(RichText::new("Web server certificate").size(18.0));
+                        });
+
+                        match fingerprint {
+                            Ok(Some(fingerprint)) => {
+                                ui.label("SHA-256 fingerprint, for pinning in remote tools:");
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new(fingerprint).monospace());
+                                    if ui.button("Copy").clicked() {
+                                        ui.ctx().copy_text(fingerprint.clone());
+                                    }
+                                });
+                            }
+                            Ok(None) => {
+                                ui.label(
+                                    "The self-signed certificate is generated when the streamer \
+                                    starts",
+                                );
+                            }
+                            Err(e) => {
+                                ui.colored_label(
+                                    theme::KO_RED,
+                                    format!("Failed to load the certificate: {e}"),
+                                );
+                            }
+                        }
+                    });
+            }
         });
 
         requests
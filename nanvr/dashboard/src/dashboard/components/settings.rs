@@ -4,8 +4,12 @@ use super::{
 };
 use crate::dashboard::ServerRequest;
 use configuration::{SessionSettings, Settings};
-use eframe::egui::{self, Align, Frame, Grid, Layout, RichText, ScrollArea, Ui};
-use gui_shared::{DisplayString, theme};
+use eframe::egui::{self, Align, Frame, Grid, Layout, RichText, ScrollArea, TextEdit, Ui};
+use gui_shared::{
+    DisplayString,
+    theme::{self, log_colors},
+};
+use net_packets::SettingsProfileAction;
 use serde_json as json;
 use settings_schema::SchemaNode;
 use std::time::Duration;
@@ -19,9 +23,25 @@ struct TopLevelEntry {
     control: SettingControl,
 }
 
+struct RenameProfileState {
+    name: String,
+    new_name: String,
+}
+
+// User-defined profiles stored in the presets directory
+#[derive(Default)]
+struct ProfilesState {
+    names: Option<Vec<String>>,
+    new_profile_name: String,
+    rename_state: Option<RenameProfileState>,
+    // Name of the profile and settings that need a SteamVR restart
+    last_applied: Option<(String, Vec<String>)>,
+}
+
 pub struct SettingsTab {
     selected_top_tab_id: String,
     presets: Vec<PresetControl>,
+    profiles: ProfilesState,
     top_level_entries: Vec<TopLevelEntry>,
     session_settings_json: Option<json::Value>,
     last_update_instant: Instant,
@@ -69,6 +89,7 @@ impl SettingsTab {
                 PresetControl::new(builtin_schema::hand_tracking_interaction_schema()),
                 PresetControl::new(builtin_schema::eye_face_tracking_schema()),
             ],
+            profiles: ProfilesState::default(),
             top_level_entries,
             session_settings_json: None,
             last_update_instant: Instant::now(),
@@ -85,6 +106,14 @@ impl SettingsTab {
         self.session_settings_json = Some(settings_json);
     }
 
+    pub fn update_profiles(&mut self, names: Vec<String>) {
+        self.profiles.names = Some(names);
+    }
+
+    pub fn update_applied_profile(&mut self, name: String, steamvr_restart_paths: Vec<String>) {
+        self.profiles.last_applied = Some((name, steamvr_restart_paths));
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) -> Vec<ServerRequest> {
         let mut requests = vec![];
 
@@ -93,6 +122,9 @@ impl SettingsTab {
             if self.session_settings_json.is_none() {
                 requests.push(ServerRequest::GetSession);
             }
+            if self.profiles.names.is_none() {
+                requests.push(ServerRequest::SettingsProfile(SettingsProfileAction::List));
+            }
 
             self.last_update_instant = now;
         }
@@ -124,6 +156,12 @@ impl SettingsTab {
             ScrollArea::new([false, true])
                 .id_salt("presets_scroll")
                 .show(ui, |ui| {
+                    requests.extend(profiles_ui(
+                        ui,
+                        &mut self.profiles,
+                        self.session_settings_json.as_ref(),
+                    ));
+
                     Grid::new("presets_grid")
                         .striped(true)
                         .num_columns(2)
@@ -179,3 +217,95 @@ impl SettingsTab {
         requests
     }
 }
+
+fn profiles_ui(
+    ui: &mut Ui,
+    state: &mut ProfilesState,
+    session_settings_json: Option<&json::Value>,
+) -> Option<ServerRequest> {
+    let mut request = None;
+
+    Frame::group(ui.style())
+        .fill(theme::DARKER_BG)
+        .inner_margin(egui::vec2(15.0, 12.0))
+        .show(ui, |ui| {
+            ui.heading("Profiles");
+
+            for name in state.names.iter().flatten() {
+                ui.horizontal(|ui| {
+                    if let Some(rename_state) = &mut state.rename_state
+                        && rename_state.name == *name
+                    {
+                        ui.text_edit_singleline(&mut rename_state.new_name);
+                        if ui.button("Save").clicked() {
+                            request = Some(SettingsProfileAction::Rename {
+                                name: name.clone(),
+                                new_name: rename_state.new_name.clone(),
+                            });
+                        }
+                    } else {
+                        ui.label(name);
+                    }
+
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        if ui.button("Delete").clicked() {
+                            request = Some(SettingsProfileAction::Delete(name.clone()));
+                        }
+                        if ui.button("Rename").clicked() {
+                            state.rename_state = Some(RenameProfileState {
+                                name: name.clone(),
+                                new_name: name.clone(),
+                            });
+                        }
+                        if ui.button("Apply").clicked() {
+                            request = Some(SettingsProfileAction::Apply(name.clone()));
+                        }
+                    });
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.add(TextEdit::singleline(&mut state.new_profile_name).hint_text("Profile name"));
+                if ui
+                    .add_enabled(
+                        session_settings_json.is_some() && !state.new_profile_name.is_empty(),
+                        egui::Button::new("Save current settings"),
+                    )
+                    .clicked()
+                    && let Some(session_settings_json) = session_settings_json
+                {
+                    // Only the settings that differ from the defaults are stored
+                    let default_json =
+                        json::to_value(configuration::session_settings_default()).unwrap();
+
+                    request = Some(SettingsProfileAction::Save {
+                        name: std::mem::take(&mut state.new_profile_name),
+                        profile: server_io::session_settings_diff(
+                            &default_json,
+                            session_settings_json,
+                        ),
+                    });
+                }
+            });
+
+            if let Some((name, steamvr_restart_paths)) = &state.last_applied {
+                if steamvr_restart_paths.is_empty() {
+                    ui.label(format!("Applied profile \"{name}\""));
+                } else {
+                    ui.colored_label(
+                        log_colors::WARNING_LIGHT,
+                        format!(
+                            "Applied profile \"{name}\". SteamVR must be restarted to apply: {}",
+                            steamvr_restart_paths.join(", ")
+                        ),
+                    );
+                }
+            }
+        });
+
+    if request.is_some() {
+        state.rename_state = None;
+    }
+
+    request.map(ServerRequest::SettingsProfile)
+}
@@ -7,8 +7,13 @@ use eframe::{
     emath::RectTransform,
     epaint::Pos2,
 };
-use events::{GraphStatistics, StatisticsSummary};
+use events::{
+    FRAME_INTERVAL_DISTRIBUTIONS, GraphStatistics, LATENCY_COMPONENTS,
+    LATENCY_HISTOGRAM_BUCKET_COUNT, LATENCY_HISTOGRAM_BUCKET_MS, LatencyDistribution,
+    StatisticsSummary,
+};
 use gui_shared::theme;
+use net_packets::PerformanceLevel;
 use shared::NANVR_NAME;
 use statrs::statistics::{self, OrderStatistics};
 use std::{collections::VecDeque, ops::RangeInclusive};
@@ -16,6 +21,14 @@ use std::{collections::VecDeque, ops::RangeInclusive};
 const GRAPH_HISTORY_SIZE: usize = 1000;
 const UPPER_QUANTILE: f64 = 0.90;
 
+fn distribution_label(name: &str) -> &str {
+    LATENCY_COMPONENTS
+        .iter()
+        .chain(&FRAME_INTERVAL_DISTRIBUTIONS)
+        .find(|(distribution, _)| *distribution == name)
+        .map_or(name, |(_, label)| *label)
+}
+
 fn draw_lines(painter: &Painter, points: Vec<Pos2>, color: Color32) {
     painter.add(Shape::line(points, Stroke::new(1.0, color)));
 }
@@ -23,6 +36,7 @@ fn draw_lines(painter: &Painter, points: Vec<Pos2>, color: Color32) {
 pub struct StatisticsTab {
     history: VecDeque<GraphStatistics>,
     last_statistics_summary: Option<StatisticsSummary>,
+    selected_distribution: String,
 }
 
 impl StatisticsTab {
@@ -32,6 +46,7 @@ impl StatisticsTab {
                 .into_iter()
                 .collect(),
             last_statistics_summary: None,
+            selected_distribution: LATENCY_COMPONENTS[0].0.into(),
         }
     }
 
@@ -44,14 +59,17 @@ impl StatisticsTab {
         self.history.push_back(statistics);
     }
 
-    pub fn ui(&self, ui: &mut Ui) -> Option<ServerRequest> {
-        if let Some(stats) = &self.last_statistics_summary {
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<ServerRequest> {
+        if let Some(stats) = self.last_statistics_summary.clone() {
             ScrollArea::new([false, true]).show(ui, |ui| {
                 let available_width = ui.available_width();
                 self.draw_latency_graph(ui, available_width);
                 self.draw_fps_graph(ui, available_width);
                 self.draw_bitrate_graph(ui, available_width);
-                self.draw_statistics_overview(ui, stats);
+                self.draw_packet_loss_graph(ui, available_width);
+                self.draw_latency_histogram(ui, available_width, &stats.latency_distributions);
+                self.draw_statistics_overview(ui, &stats);
+                self.draw_latency_percentiles(ui, &stats.latency_distributions);
             });
         } else {
             ui.heading(
@@ -289,6 +307,7 @@ impl StatisticsTab {
                 let mut decoder_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut network_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut encoder_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut packet_loss_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut max_throughput = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut min_throughput = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut requested_bitrate = Vec::with_capacity(GRAPH_HISTORY_SIZE);
@@ -309,6 +328,9 @@ impl StatisticsTab {
                     if let Some(value) = d.encoder_latency_limiter_bps {
                         encoder_latency_limiter.push(to_screen_trans * pos2(i as f32, value / 1e6))
                     }
+                    if let Some(value) = d.packet_loss_limiter_bps {
+                        packet_loss_limiter.push(to_screen_trans * pos2(i as f32, value / 1e6))
+                    }
                     if let Some(value) = d.manual_max_throughput_bps {
                         max_throughput.push(to_screen_trans * pos2(i as f32, value / 1e6))
                     }
@@ -344,6 +366,11 @@ impl StatisticsTab {
                     decoder_latency_limiter,
                     graph_colors::ENCODER_DECODER_LATENCY_LIMITER,
                 );
+                draw_lines(
+                    painter,
+                    packet_loss_limiter,
+                    graph_colors::PACKET_LOSS_LIMITER,
+                );
                 draw_lines(
                     painter,
                     max_throughput,
@@ -404,6 +431,12 @@ impl StatisticsTab {
                             .filter(|l| *l < stats.throughput_bps),
                         graph_colors::ENCODER_DECODER_LATENCY_LIMITER,
                     );
+                    maybe_label(
+                        ui,
+                        "Packet loss limiter",
+                        td.packet_loss_limiter_bps,
+                        graph_colors::PACKET_LOSS_LIMITER,
+                    );
                     maybe_label(
                         ui,
                         "Manual max throughput",
@@ -441,6 +474,191 @@ impl StatisticsTab {
         )
     }
 
+    fn draw_packet_loss_graph(&self, ui: &mut Ui, available_width: f32) {
+        let max_percent = self
+            .history
+            .iter()
+            .map(|stats| stats.packet_loss_ratio * 100.0)
+            .fold(1.0, f32::max);
+
+        self.draw_graph(
+            ui,
+            available_width,
+            "Packet Loss",
+            0.0..=max_percent * 1.2,
+            |painter, to_screen_trans| {
+                let points = (0..GRAPH_HISTORY_SIZE)
+                    .map(|i| {
+                        to_screen_trans * pos2(i as f32, self.history[i].packet_loss_ratio * 100.0)
+                    })
+                    .collect();
+
+                draw_lines(painter, points, graph_colors::PACKET_LOSS);
+            },
+            |ui, stats| {
+                Grid::new("packet_loss_tooltip")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.colored_label(graph_colors::PACKET_LOSS, "Video packet loss");
+                        ui.colored_label(
+                            graph_colors::PACKET_LOSS,
+                            format!("{:.2}%", stats.packet_loss_ratio * 100.0),
+                        );
+                        ui.end_row();
+                    });
+            },
+        );
+    }
+
+    fn draw_latency_histogram(
+        &mut self,
+        ui: &mut Ui,
+        available_width: f32,
+        distributions: &[LatencyDistribution],
+    ) {
+        ui.add_space(10.0);
+        ui.label(RichText::new("Latency Distribution").size(20.0));
+
+        ui.horizontal_wrapped(|ui| {
+            for distribution in distributions {
+                ui.selectable_value(
+                    &mut self.selected_distribution,
+                    distribution.name.clone(),
+                    distribution_label(&distribution.name),
+                );
+            }
+        });
+
+        let Some(distribution) = distributions
+            .iter()
+            .find(|distribution| distribution.name == self.selected_distribution)
+        else {
+            return;
+        };
+
+        let max_count = distribution.histogram.iter().copied().max().unwrap_or(0);
+        let range_ms = LATENCY_HISTOGRAM_BUCKET_COUNT as f32 * LATENCY_HISTOGRAM_BUCKET_MS;
+
+        let canvas_response = Frame::canvas(ui.style()).show(ui, |ui| {
+            let size = available_width * vec2(1.0, 0.2);
+            let (_id, canvas_rect) = ui.allocate_space(size);
+
+            let data_rect = Rect::from_x_y_ranges(0.0..=range_ms, (max_count as f32 * 1.2)..=0.0);
+            if max_count == 0 {
+                // Drawing using a 0 sized rectangle causes a crash
+                return data_rect;
+            }
+            let to_screen = RectTransform::from_to(data_rect, canvas_rect);
+            let painter = ui.painter().with_clip_rect(canvas_rect);
+
+            for (i, count) in distribution.histogram.iter().enumerate() {
+                let start_ms = i as f32 * LATENCY_HISTOGRAM_BUCKET_MS;
+                painter.rect_filled(
+                    Rect {
+                        min: to_screen * pos2(start_ms, *count as f32),
+                        max: to_screen * pos2(start_ms + LATENCY_HISTOGRAM_BUCKET_MS * 0.8, 0.0),
+                    },
+                    CornerRadius::ZERO,
+                    graph_colors::LATENCY_HISTOGRAM,
+                );
+            }
+
+            for (value_ms, color) in [
+                (distribution.p50_ms, graph_colors::LATENCY_P50),
+                (distribution.p90_ms, graph_colors::LATENCY_P90),
+                (distribution.p99_ms, graph_colors::LATENCY_P99),
+            ] {
+                let x = f32::min(value_ms, range_ms);
+                draw_lines(
+                    &painter,
+                    vec![
+                        to_screen * pos2(x, 0.0),
+                        to_screen * pos2(x, max_count as f32 * 1.2),
+                    ],
+                    color,
+                );
+            }
+
+            painter.text(
+                to_screen * pos2(range_ms, 0.0),
+                Align2::RIGHT_BOTTOM,
+                format!("{range_ms:.0}ms+"),
+                FontId::monospace(20.0),
+                Color32::GRAY,
+            );
+            painter.text(
+                to_screen * pos2(0.0, max_count as f32 * 1.2),
+                Align2::LEFT_TOP,
+                format!("{max_count}"),
+                FontId::monospace(20.0),
+                Color32::GRAY,
+            );
+
+            data_rect
+        });
+
+        if let Some(pos) = canvas_response.response.hover_pos() {
+            let graph_pos =
+                RectTransform::from_to(canvas_response.response.rect, canvas_response.inner) * pos;
+            let bucket = ((graph_pos.x / LATENCY_HISTOGRAM_BUCKET_MS) as usize)
+                .clamp(0, LATENCY_HISTOGRAM_BUCKET_COUNT - 1);
+            let start_ms = bucket as f32 * LATENCY_HISTOGRAM_BUCKET_MS;
+
+            popup::show_tooltip(
+                ui.ctx(),
+                ui.layer_id(),
+                Id::new("latency_histogram_popup"),
+                |ui| {
+                    let range = if bucket == LATENCY_HISTOGRAM_BUCKET_COUNT - 1 {
+                        format!("{start_ms:.0}ms or more")
+                    } else {
+                        format!(
+                            "{start_ms:.0}-{:.0}ms",
+                            start_ms + LATENCY_HISTOGRAM_BUCKET_MS
+                        )
+                    };
+                    let count = distribution.histogram.get(bucket).copied().unwrap_or(0);
+                    ui.label(format!("{range}: {count} frames"));
+                },
+            );
+        }
+    }
+
+    fn draw_latency_percentiles(&self, ui: &mut Ui, distributions: &[LatencyDistribution]) {
+        ui.add_space(10.0);
+
+        Grid::new("latency_percentiles")
+            .num_columns(6)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.colored_label(graph_colors::LATENCY_P50, "p50");
+                ui.colored_label(graph_colors::LATENCY_P90, "p90");
+                ui.colored_label(graph_colors::LATENCY_P99, "p99");
+                ui.label("max");
+                ui.label("over threshold");
+                ui.end_row();
+
+                for distribution in distributions {
+                    ui.label(distribution_label(&distribution.name));
+                    for value_ms in [
+                        distribution.p50_ms,
+                        distribution.p90_ms,
+                        distribution.p99_ms,
+                        distribution.max_ms,
+                    ] {
+                        ui.label(format!("{value_ms:.2} ms"));
+                    }
+                    if let Some(count) = distribution.frames_over_threshold {
+                        ui.label(format!("{count} frames"));
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
     fn draw_statistics_overview(&self, ui: &mut Ui, statistics: &StatisticsSummary) {
         ui.add_space(10.0);
 
@@ -485,6 +703,103 @@ impl StatisticsTab {
                     "unplugged"
                 }
             ));
+
+            if let Some(resources) = &statistics.host_resources {
+                ui[0].label("PC CPU usage:");
+                ui[1].label(format!(
+                    "{:.0}% (streamer {:.0}%)",
+                    resources.system_cpu_percent, resources.process_cpu_percent
+                ));
+
+                ui[0].label("PC memory:");
+                ui[1].label(format!(
+                    "{} / {} MB (streamer {} MB)",
+                    resources.system_memory_used_mb,
+                    resources.system_memory_total_mb,
+                    resources.process_memory_mb
+                ));
+
+                if let Some(gpu) = resources.gpu_percent {
+                    ui[0].label("PC GPU usage:");
+                    ui[1].label(format!("{gpu:.0}%"));
+                }
+
+                if let Some(encoder) = resources.encoder_percent {
+                    ui[0].label("PC encoder usage:");
+                    ui[1].label(format!("{encoder:.0}%"));
+                }
+
+                if resources.cpu_temperature_celsius.is_some()
+                    || resources.gpu_temperature_celsius.is_some()
+                {
+                    let temperature_text = |temperature: Option<f32>| {
+                        temperature.map_or("unknown".to_owned(), |t| format!("{t:.0}°C"))
+                    };
+
+                    ui[0].label("PC CPU/GPU temperature:");
+                    ui[1].label(format!(
+                        "{} / {}",
+                        temperature_text(resources.cpu_temperature_celsius),
+                        temperature_text(resources.gpu_temperature_celsius)
+                    ));
+                }
+            }
+
+            let Some(telemetry) = &statistics.client_telemetry else {
+                return;
+            };
+
+            if let Some(wifi) = &telemetry.wifi {
+                let mut parts = vec![];
+                if let Some(rssi) = wifi.rssi_dbm {
+                    parts.push(format!("{rssi} dBm"));
+                }
+                if let Some(speed) = wifi.link_speed_mbps {
+                    parts.push(format!("{speed} Mbps"));
+                }
+                if let Some(band) = wifi.band_name() {
+                    parts.push(band.to_owned());
+                }
+                if let Some(channel) = wifi.channel {
+                    parts.push(format!("channel {channel}"));
+                }
+
+                ui[0].label("Client Wi-Fi:");
+                ui[1].label(parts.join(", "));
+            }
+
+            if let Some(fps) = telemetry.rendering_fps {
+                ui[0].label("Client rendering FPS:");
+                ui[1].label(format!("{fps:.1} FPS"));
+            }
+
+            if let Some(frames) = telemetry.decoder_queue_frames {
+                ui[0].label("Decoder queue:");
+                ui[1].label(format!("{frames} frames"));
+            }
+
+            if let Some(frames) = telemetry.dropped_frames {
+                ui[0].label("Dropped frames:");
+                ui[1].label(format!("{frames} frames"));
+            }
+
+            if let Some(status) = telemetry.thermal_status {
+                ui[0].label("Headset thermal status:");
+                ui[1].label(format!("{status:?}"));
+            }
+
+            if telemetry.cpu_level.is_some() || telemetry.gpu_level.is_some() {
+                let level_text = |level: Option<PerformanceLevel>| {
+                    level.map_or("unknown".to_owned(), |level| format!("{level:?}"))
+                };
+
+                ui[0].label("Headset CPU/GPU level:");
+                ui[1].label(format!(
+                    "{} / {}",
+                    level_text(telemetry.cpu_level),
+                    level_text(telemetry.gpu_level)
+                ));
+            }
         });
     }
 }
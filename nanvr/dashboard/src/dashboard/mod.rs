@@ -135,6 +135,7 @@ impl eframe::App for Dashboard {
                     let settings = session.to_settings();
 
                     self.connections_tab.update_client_list(&session);
+                    self.installation_tab.update_session(&session);
                     self.settings_tab.update_session(&session.session_settings);
                     self.logs_tab.update_settings(&settings);
                     self.notification_bar.update_settings(&settings);
@@ -156,6 +157,15 @@ impl eframe::App for Dashboard {
                 EventType::NewVersionFound { version, message } => {
                     self.new_version_popup = Some(NewVersionPopup::new(version, message));
                 }
+                EventType::SettingsProfiles(profiles) => {
+                    self.settings_tab.update_profiles(profiles)
+                }
+                EventType::SettingsProfileApplied {
+                    name,
+                    steamvr_restart_paths,
+                } => self
+                    .settings_tab
+                    .update_applied_profile(name, steamvr_restart_paths),
                 EventType::DebugGroup { .. }
                 | EventType::Tracking(_)
                 | EventType::Buttons(_)
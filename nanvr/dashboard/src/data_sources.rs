@@ -9,7 +9,7 @@ use shared::{
 };
 use std::{
     io::ErrorKind,
-    net::{SocketAddr, TcpStream},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
     str::FromStr,
     sync::{Arc, mpsc},
     thread::{self, JoinHandle},
@@ -132,7 +132,15 @@ impl DataSources {
         let server_connected = Arc::new(RelaxedAtomic::new(false));
 
         let session_manager = get_local_session_source();
-        let port = session_manager.settings().connection.web_server_port;
+        let connection = &session_manager.settings().connection;
+        // Use the loopback interface unless the streamer listens on a specific address
+        let web_server_ip = connection
+            .web_server_bind_address
+            .ip()
+            .filter(|ip| !ip.is_unspecified())
+            .unwrap_or(Ipv4Addr::LOCALHOST.into());
+        let web_server_addr = SocketAddr::new(web_server_ip, connection.web_server_port);
+        let authorization = format!("Bearer {}", session_manager.session().api_token);
         let session_source = Arc::new(Mutex::new(SessionSource::Local(Box::new(session_manager))));
 
         let version_check_thread = thread::spawn({
@@ -201,8 +209,9 @@ impl DataSources {
             let context = context.clone();
             let session_source = Arc::clone(&session_source);
             let events_sender = events_sender.clone();
+            let authorization = authorization.clone();
             move || {
-                let uri = format!("http://127.0.0.1:{port}/api/dashboard-request");
+                let uri = format!("http://{web_server_addr}/api/dashboard-request");
                 let request_agent: ureq::Agent = ureq::Agent::config_builder()
                     .timeout_global(Some(LOCAL_REQUEST_TIMEOUT))
                     .build()
@@ -280,10 +289,28 @@ impl DataSources {
                                         )
                                     }
                                 }
+                                ServerRequest::SettingsProfile(action) => {
+                                    match server_io::settings_profile_action(
+                                        session_manager,
+                                        &filesystem_layout.presets_dir(),
+                                        action,
+                                    ) {
+                                        Ok(event) => {
+                                            report_event_local(&context, &events_sender, event)
+                                        }
+                                        Err(e) => error!("Settings profile action failed: {e}"),
+                                    }
+
+                                    report_session_local(&context, &events_sender, session_manager);
+                                }
                                 ServerRequest::CaptureFrame
                                 | ServerRequest::InsertIdr
                                 | ServerRequest::StartRecording
-                                | ServerRequest::StopRecording => {
+                                | ServerRequest::StopRecording
+                                | ServerRequest::StartFrameStatisticsLogging
+                                | ServerRequest::StopFrameStatisticsLogging
+                                | ServerRequest::StartTraceCapture
+                                | ServerRequest::StopTraceCapture => {
                                     warn!(
                                         "Cannot perform action, streamer (SteamVR) is not connected."
                                     )
@@ -297,6 +324,7 @@ impl DataSources {
                             request_agent
                                 .post(&uri)
                                 .header(format!("X-{NANVR_HIGH_NAME}"), "true")
+                                .header("Authorization", &authorization)
                                 .send_json(&request)
                                 .ok();
                         }
@@ -318,12 +346,10 @@ impl DataSources {
                         continue;
                     }
 
-                    let uri = Uri::from_str(&format!("ws://127.0.0.1:{port}/api/events")).unwrap();
+                    let uri = Uri::from_str(&format!("ws://{web_server_addr}/api/events")).unwrap();
 
-                    let maybe_socket = TcpStream::connect_timeout(
-                        &SocketAddr::from_str(&format!("127.0.0.1:{port}")).unwrap(),
-                        Duration::from_millis(500),
-                    );
+                    let maybe_socket =
+                        TcpStream::connect_timeout(&web_server_addr, Duration::from_millis(500));
                     let Ok(socket) = maybe_socket else {
                         thread::sleep(Duration::from_millis(500));
 
@@ -335,6 +361,10 @@ impl DataSources {
                         formatcp!("X-{NANVR_HIGH_NAME}"),
                         HeaderValue::from_str("true").unwrap(),
                     );
+                    req.headers_mut().insert(
+                        "Authorization",
+                        HeaderValue::from_str(&authorization).unwrap(),
+                    );
 
                     let Ok((mut ws, _)) = tungstenite::client(req, socket) else {
                         thread::sleep(Duration::from_millis(500));
@@ -382,7 +412,7 @@ impl DataSources {
             move || {
                 const PING_INTERVAL: Duration = Duration::from_secs(1);
                 let mut deadline = Instant::now();
-                let uri = format!("http://127.0.0.1:{port}/api/version");
+                let uri = format!("http://{web_server_addr}/api/version");
 
                 let request_agent: ureq::Agent = ureq::Agent::config_builder()
                     .timeout_global(Some(LOCAL_REQUEST_TIMEOUT))
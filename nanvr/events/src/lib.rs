@@ -1,5 +1,5 @@
 use configuration::SessionConfig;
-use net_packets::{ButtonValue, FaceData};
+use net_packets::{ButtonValue, ClientStatisticsExt, FaceData};
 use serde::{Deserialize, Serialize};
 use shared::{DeviceMotion, LogEntry, LogSeverity, Pose, info};
 use std::{path::PathBuf, time::Duration};
@@ -18,6 +18,59 @@ pub struct StatisticsSummary {
     pub server_fps: u32,
     pub battery_hmd: u32,
     pub hmd_plugged: bool,
+    pub latency_distributions: Vec<LatencyDistribution>,
+    pub client_telemetry: Option<ClientStatisticsExt>,
+    pub host_resources: Option<HostResources>,
+}
+
+// Resource usage of the streamer PC. Values that can't be read on this system are None
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HostResources {
+    // Percentages of the total capacity of all CPU cores
+    pub process_cpu_percent: f32,
+    pub system_cpu_percent: f32,
+    pub process_memory_mb: u64,
+    pub system_memory_used_mb: u64,
+    pub system_memory_total_mb: u64,
+    pub gpu_percent: Option<f32>,
+    pub encoder_percent: Option<f32>,
+    pub gpu_temperature_celsius: Option<f32>,
+    pub cpu_temperature_celsius: Option<f32>,
+}
+
+// Latency components of GraphStatistics in pipeline order, as (name, label). The names identify
+// the latency distributions and the metrics, the labels are shown in the dashboard.
+pub const LATENCY_COMPONENTS: [(&str, &str); 9] = [
+    ("total", "Motion to Photon"),
+    ("game", "Game Render"),
+    ("server_compositor", "Streamer Compositor"),
+    ("encoder", "Encode"),
+    ("network", "Network"),
+    ("decoder", "Decode"),
+    ("decoder_queue", "Frame Buffering"),
+    ("client_compositor", "Client App Compositor"),
+    ("vsync_queue", "Client System"),
+];
+pub const FRAME_INTERVAL_DISTRIBUTIONS: [(&str, &str); 2] = [
+    ("client_frame_interval", "Client Frame Interval"),
+    ("server_frame_interval", "Streamer Frame Interval"),
+];
+
+pub const LATENCY_HISTOGRAM_BUCKET_MS: f32 = 1.0;
+pub const LATENCY_HISTOGRAM_BUCKET_COUNT: usize = 100;
+
+// Distribution of the recent samples of a latency component or frame interval
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LatencyDistribution {
+    pub name: String,
+    pub p50_ms: f32,
+    pub p90_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+    // Buckets of LATENCY_HISTOGRAM_BUCKET_MS, the last one also counts longer samples
+    pub histogram: Vec<u32>,
+    // Samples over the configured threshold since the stream started
+    pub frames_over_threshold: Option<u64>,
 }
 
 // Bitrate statistics minus the empirical output value
@@ -27,6 +80,7 @@ pub struct BitrateDirectives {
     pub decoder_latency_limiter_bps: Option<f32>,
     pub network_latency_limiter_bps: Option<f32>,
     pub encoder_latency_limiter_bps: Option<f32>,
+    pub packet_loss_limiter_bps: Option<f32>,
     pub manual_max_throughput_bps: Option<f32>,
     pub manual_min_throughput_bps: Option<f32>,
     pub requested_bitrate_bps: f32,
@@ -45,6 +99,7 @@ pub struct GraphStatistics {
     pub vsync_queue_s: f32,
     pub client_fps: f32,
     pub server_fps: f32,
+    pub packet_loss_ratio: f32,
     pub bitrate_directives: BitrateDirectives,
     pub throughput_bps: f32,
     pub bitrate_bps: f32,
@@ -91,6 +146,33 @@ pub enum EventType {
     ServerRequestsSelfRestart,
     Adb(AdbEvent),
     NewVersionFound { version: String, message: String },
+    SettingsProfiles(Vec<String>),
+    SettingsProfileApplied {
+        name: String,
+        steamvr_restart_paths: Vec<String>,
+    },
+}
+
+impl EventType {
+    // Matches the serialized "id" field
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventType::Log(_) => "Log",
+            EventType::DebugGroup { .. } => "DebugGroup",
+            EventType::Session(_) => "Session",
+            EventType::StatisticsSummary(_) => "StatisticsSummary",
+            EventType::GraphStatistics(_) => "GraphStatistics",
+            EventType::Tracking(_) => "Tracking",
+            EventType::Buttons(_) => "Buttons",
+            EventType::Haptics(_) => "Haptics",
+            EventType::DriversList(_) => "DriversList",
+            EventType::ServerRequestsSelfRestart => "ServerRequestsSelfRestart",
+            EventType::Adb(_) => "Adb",
+            EventType::NewVersionFound { .. } => "NewVersionFound",
+            EventType::SettingsProfiles(_) => "SettingsProfiles",
+            EventType::SettingsProfileApplied { .. } => "SettingsProfileApplied",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -119,6 +201,8 @@ impl Event {
             EventType::ServerRequestsSelfRestart => "RESTART".to_string(),
             EventType::Adb(_) => "ADB".to_string(),
             EventType::NewVersionFound { .. } => "NEW VER".to_string(),
+            EventType::SettingsProfiles(_) => "PROFILES".to_string(),
+            EventType::SettingsProfileApplied { .. } => "PROFILE".to_string(),
         }
     }
 
@@ -135,6 +219,8 @@ impl Event {
             EventType::ServerRequestsSelfRestart => "Request for server restart".into(),
             EventType::Adb(adb) => serde_json::to_string(adb).unwrap(),
             EventType::NewVersionFound { version, .. } => version.clone(),
+            EventType::SettingsProfiles(profiles) => serde_json::to_string(profiles).unwrap(),
+            EventType::SettingsProfileApplied { name, .. } => format!("Applied {name}"),
         }
     }
 }
@@ -0,0 +1,419 @@
+use events::Event;
+use net_packets::{EventsSubscription, EventsWebsocketMessage, REST_API_BASE_PATH};
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider, ring},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+use serde_json as json;
+use sha2::{Digest, Sha256};
+use shared::{
+    NANVR_HIGH_NAME, NANVR_NAME,
+    anyhow::{Result, anyhow, bail},
+};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    sync::Arc,
+    time::Duration,
+};
+use tungstenite::{
+    Message, WebSocket,
+    client::IntoClientRequest,
+    http::{HeaderName, HeaderValue},
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Percent-encodes everything but unreserved characters
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+fn format_fingerprint(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// Accepts hex with or without colons, as shown in the dashboard
+fn parse_fingerprint(fingerprint: &str) -> Result<Vec<u8>> {
+    let hex = fingerprint.replace(':', "");
+    if hex.len() != 64 || !hex.is_ascii() {
+        bail!("The certificate fingerprint must be a SHA-256 hash in hex");
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| {
+            u8::from_str_radix(&hex[idx..idx + 2], 16)
+                .map_err(|_| anyhow!("Invalid certificate fingerprint: {fingerprint}"))
+        })
+        .collect()
+}
+
+// The web server uses a self-signed certificate by default, so instead of checking the chain and
+// the host name the certificate must match the pinned fingerprint
+#[derive(Debug)]
+struct PinnedCertificateVerifier {
+    fingerprint: Vec<u8>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let digest = Sha256::digest(end_entity);
+        if digest.as_slice() == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!(
+                "Certificate fingerprint is {}, expected {}",
+                format_fingerprint(&digest),
+                format_fingerprint(&self.fingerprint)
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            certificate,
+            signature,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            certificate,
+            signature,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+struct TlsConfig {
+    config: Arc<ClientConfig>,
+    server_name: ServerName<'static>,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+// Client of the versioned REST API and of the events websocket. Plain HTTP is always accepted
+// from the PC running the streamer, remote connections need TLS with a pinned certificate.
+// Each request uses its own connection.
+pub struct ApiClient {
+    address: String,
+    authorization: Option<String>,
+    tls: Option<TlsConfig>,
+}
+
+impl ApiClient {
+    // The connection uses TLS if the certificate fingerprint is set
+    pub fn new(address: String, token: Option<String>, fingerprint: Option<&str>) -> Result<Self> {
+        let tls = if let Some(fingerprint) = fingerprint {
+            let provider = Arc::new(ring::default_provider());
+            let verifier = PinnedCertificateVerifier {
+                fingerprint: parse_fingerprint(fingerprint)?,
+                provider: Arc::clone(&provider),
+            };
+            let config = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()?
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth();
+
+            // The port is removed, and the brackets of IPv6 addresses
+            let host = address
+                .rsplit_once(':')
+                .map_or(address.as_str(), |(host, _)| host)
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+
+            Some(TlsConfig {
+                config: Arc::new(config),
+                server_name: ServerName::try_from(host.to_owned())
+                    .map_err(|e| anyhow!("Invalid server address {address}: {e}"))?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            address,
+            authorization: token.map(|token| format!("Bearer {token}")),
+            tls,
+        })
+    }
+
+    fn connect(&self, timeout: Option<Duration>) -> Result<Stream> {
+        let stream = TcpStream::connect(&self.address)
+            .map_err(|e| anyhow!("Failed to connect to the {NANVR_NAME} streamer: {e}"))?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+
+        if let Some(tls) = &self.tls {
+            let connection =
+                ClientConnection::new(Arc::clone(&tls.config), tls.server_name.clone())?;
+
+            Ok(Stream::Tls(Box::new(StreamOwned::new(connection, stream))))
+        } else {
+            Ok(Stream::Plain(stream))
+        }
+    }
+
+    // The path segments are percent-encoded
+    fn path(segments: &[&str]) -> String {
+        let mut path = REST_API_BASE_PATH.to_owned();
+        for segment in segments {
+            path.push('/');
+            path.push_str(&encode_path_segment(segment));
+        }
+
+        path
+    }
+
+    // Returns the JSON body, or null if empty. Errors use the message sent by the server
+    fn request(
+        &self,
+        method: &str,
+        segments: &[&str],
+        body: Option<&json::Value>,
+    ) -> Result<json::Value> {
+        let body = body.map(json::to_string).transpose()?.unwrap_or_default();
+
+        let mut head = format!(
+            "{method} {} HTTP/1.1\r\nHost: {}\r\nX-{NANVR_HIGH_NAME}: true\r\n\
+            Connection: close\r\nContent-Length: {}\r\n",
+            Self::path(segments),
+            self.address,
+            body.len()
+        );
+        if !body.is_empty() {
+            head.push_str("Content-Type: application/json\r\n");
+        }
+        if let Some(authorization) = &self.authorization {
+            head.push_str(&format!("Authorization: {authorization}\r\n"));
+        }
+        head.push_str("\r\n");
+
+        let stream = self.connect(Some(REQUEST_TIMEOUT))?;
+        let (status, reason, body) = exchange(stream, &[head.as_bytes(), body.as_bytes()])
+            .map_err(|e| anyhow!("Request to the {NANVR_NAME} streamer failed: {e}"))?;
+
+        if !(200..300).contains(&status) {
+            let message = json::from_str::<json::Value>(&body)
+                .ok()
+                .and_then(|value| value["error"].as_str().map(String::from))
+                .unwrap_or(body);
+            if message.is_empty() {
+                bail!("Server responded with {status} {reason}");
+            } else {
+                bail!("Server responded with {status} {reason}: {message}");
+            }
+        }
+
+        if body.is_empty() {
+            Ok(json::Value::Null)
+        } else {
+            Ok(json::from_str(&body)?)
+        }
+    }
+
+    pub fn get(&self, segments: &[&str]) -> Result<json::Value> {
+        self.request("GET", segments, None)
+    }
+
+    pub fn post(&self, segments: &[&str]) -> Result<json::Value> {
+        self.request("POST", segments, None)
+    }
+
+    pub fn patch(&self, segments: &[&str], body: &json::Value) -> Result<json::Value> {
+        self.request("PATCH", segments, Some(body))
+    }
+
+    pub fn delete(&self, segments: &[&str]) -> Result<json::Value> {
+        self.request("DELETE", segments, None)
+    }
+
+    pub fn subscribe_events(&self, subscription: EventsSubscription) -> Result<EventsStream> {
+        let scheme = if self.tls.is_some() { "wss" } else { "ws" };
+        let mut request = format!("{scheme}://{}/api/events", self.address).into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert(
+            HeaderName::try_from(format!("X-{NANVR_HIGH_NAME}"))?,
+            HeaderValue::from_static("true"),
+        );
+        if let Some(authorization) = &self.authorization {
+            headers.insert("Authorization", HeaderValue::from_str(authorization)?);
+        }
+
+        // Events can be rare, so reads never time out
+        let stream = self.connect(None)?;
+        let (mut socket, _) = tungstenite::client(request, stream)
+            .map_err(|e| anyhow!("Failed to connect to the events websocket: {e}"))?;
+
+        socket.send(Message::text(json::to_string(
+            &EventsWebsocketMessage::Subscribe(subscription),
+        )?))?;
+
+        Ok(EventsStream { socket })
+    }
+}
+
+// Sends the request and returns the status code, the reason phrase and the body of the response.
+// The server always sets Content-Length
+fn exchange(mut stream: Stream, request: &[&[u8]]) -> io::Result<(u16, String, String)> {
+    let invalid_response = || io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response");
+
+    for part in request {
+        stream.write_all(part)?;
+    }
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let mut status_parts = status_line.trim_end().splitn(3, ' ').skip(1);
+    let status = status_parts
+        .next()
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(invalid_response)?;
+    let reason = status_parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse().map_err(|_| invalid_response())?;
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok((
+        status,
+        reason,
+        String::from_utf8(body).map_err(|_| invalid_response())?,
+    ))
+}
+
+pub struct EventsStream {
+    socket: WebSocket<Stream>,
+}
+
+impl EventsStream {
+    // None when the server closes the connection
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => {
+                    // Other messages, like server request replies, are skipped
+                    if let Ok(event) = json::from_str(&text) {
+                        return Ok(Some(event));
+                    }
+                }
+                Ok(Message::Close(_))
+                | Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(None);
+                }
+                Ok(_) => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let fingerprint = (0..32)
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(":");
+        let digest = parse_fingerprint(&fingerprint).unwrap();
+        assert_eq!(digest, (0..32).collect::<Vec<u8>>());
+        assert_eq!(format_fingerprint(&digest), fingerprint.to_uppercase());
+
+        assert_eq!(parse_fingerprint(&fingerprint.replace(':', "")).unwrap(), digest);
+        assert!(parse_fingerprint("AB:CD").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+}
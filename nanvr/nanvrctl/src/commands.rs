@@ -0,0 +1,643 @@
+use crate::{
+    ClientsCommand, Command, RecordingCommand, SettingsCommand,
+    client::{ApiClient, EventsStream},
+};
+use configuration::ClientConnectionConfig;
+use events::{Event, EventType, StatisticsSummary};
+use net_packets::{EventsSubscription, PathSegment, PathValuePair};
+use serde_json as json;
+use shared::{
+    LogSeverity,
+    anyhow::{Result, anyhow},
+};
+use std::{collections::HashMap, io::Write};
+
+fn print_json(out: &mut impl Write, value: &json::Value) -> Result<()> {
+    writeln!(out, "{}", json::to_string_pretty(value)?)?;
+
+    Ok(())
+}
+
+// In JSON mode the response of the server is printed, if any
+fn print_action(
+    out: &mut impl Write,
+    json_output: bool,
+    response: &json::Value,
+    message: &str,
+) -> Result<()> {
+    if !json_output {
+        writeln!(out, "{message}")?;
+    } else if !response.is_null() {
+        print_json(out, response)?;
+    }
+
+    Ok(())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+fn format_statistics(summary: &StatisticsSummary) -> String {
+    format!(
+        "{} fps (client {} fps), {:.1} ms latency (network {:.1} ms, encode {:.1} ms, decode \
+        {:.1} ms), {:.1} Mbps",
+        summary.server_fps,
+        summary.client_fps,
+        summary.total_latency_ms,
+        summary.network_latency_ms,
+        summary.encode_latency_ms,
+        summary.decode_latency_ms,
+        summary.video_mbits_per_sec,
+    )
+}
+
+fn write_client_list(out: &mut impl Write, clients: json::Value, indent: &str) -> Result<()> {
+    let mut clients = json::from_value::<HashMap<String, ClientConnectionConfig>>(clients)?
+        .into_iter()
+        .collect::<Vec<_>>();
+    clients.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if clients.is_empty() {
+        writeln!(out, "{indent}No clients")?;
+    }
+
+    let hostname_width = clients
+        .iter()
+        .map(|(hostname, _)| hostname.len())
+        .max()
+        .unwrap_or_default();
+    for (hostname, client) in clients {
+        let ip = client
+            .current_ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "-".into());
+        writeln!(
+            out,
+            "{indent}{hostname:hostname_width$}  {:13}  {:9}  {ip:15}  {}",
+            format!("{:?}", client.connection_state),
+            if client.trusted {
+                "trusted"
+            } else {
+                "untrusted"
+            },
+            client.display_name,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn status(client: &ApiClient, json_output: bool, out: &mut impl Write) -> Result<()> {
+    let version = client.get(&["version"])?["version"].clone();
+    let recording = client.get(&["recording"])?["recording"].as_bool() == Some(true);
+    let clients = client.get(&["clients"])?;
+    // Not found while no client is streaming
+    let statistics = client.get(&["statistics"]).ok();
+
+    if json_output {
+        return print_json(
+            out,
+            &json::json!({
+                "version": version,
+                "recording": recording,
+                "clients": clients,
+                "statistics": statistics,
+            }),
+        );
+    }
+
+    writeln!(
+        out,
+        "Server version: {}",
+        version.as_str().unwrap_or_default()
+    )?;
+    writeln!(out, "Recording: {}", yes_no(recording))?;
+    if let Some(statistics) = statistics {
+        let summary = json::from_value::<StatisticsSummary>(statistics)?;
+        writeln!(out, "Streaming: {}", format_statistics(&summary))?;
+    } else {
+        writeln!(out, "Streaming: no")?;
+    }
+    writeln!(out, "Clients:")?;
+    write_client_list(out, clients, "  ")
+}
+
+fn settings_path(path: &str) -> Vec<PathSegment> {
+    let mut segments = vec![PathSegment::from("session_settings")];
+    segments.extend(net_packets::parse_path(path));
+
+    segments
+}
+
+fn session_value<'a>(
+    session: &'a json::Value,
+    segments: &[PathSegment],
+    path: &str,
+) -> Result<&'a json::Value> {
+    let mut value = session;
+    for segment in segments {
+        value = match segment {
+            PathSegment::Name(name) => value.get(name),
+            PathSegment::Index(index) => value.get(index),
+        }
+        .ok_or_else(|| anyhow!("Setting \"{path}\" not found"))?;
+    }
+
+    Ok(value)
+}
+
+fn settings(
+    client: &ApiClient,
+    command: &SettingsCommand,
+    json_output: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        SettingsCommand::Get { path } => {
+            let session = client.get(&["session"])?;
+            let value = session_value(&session, &settings_path(path), path)?;
+
+            if let Some(text) = value.as_str()
+                && !json_output
+            {
+                writeln!(out, "{text}")?;
+            } else {
+                print_json(out, value)?;
+            }
+        }
+        SettingsCommand::Set { path, value } => {
+            let value =
+                json::from_str(value).unwrap_or_else(|_| json::Value::String(value.clone()));
+            let segments = settings_path(path);
+
+            let session = client.patch(
+                &["session"],
+                &json::to_value(vec![PathValuePair {
+                    path: segments.clone(),
+                    value,
+                }])?,
+            )?;
+            let new_value = session_value(&session, &segments, path)?;
+
+            if json_output {
+                print_json(out, new_value)?;
+            } else {
+                writeln!(out, "{path} = {new_value}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Prints the events accepted by the format function until the server closes the connection
+fn tail(
+    mut events: EventsStream,
+    count: Option<usize>,
+    json_output: bool,
+    out: &mut impl Write,
+    format: impl Fn(&Event) -> Option<String>,
+) -> Result<()> {
+    let mut printed = 0;
+    while count.is_none_or(|count| printed < count) {
+        let Some(event) = events.next_event()? else {
+            break;
+        };
+        // Events sent before the subscription was processed are not filtered by the server
+        let Some(line) = format(&event) else {
+            continue;
+        };
+
+        if json_output {
+            writeln!(out, "{}", json::to_string(&event)?)?;
+        } else {
+            writeln!(out, "{line}")?;
+        }
+        out.flush()?;
+
+        printed += 1;
+    }
+
+    Ok(())
+}
+
+pub fn run(
+    client: &ApiClient,
+    command: &Command,
+    json_output: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    match command {
+        Command::Status => status(client, json_output, out),
+        Command::Clients { command } => match command {
+            ClientsCommand::List => {
+                let clients = client.get(&["clients"])?;
+                if json_output {
+                    print_json(out, &clients)
+                } else {
+                    write_client_list(out, clients, "")
+                }
+            }
+            ClientsCommand::Trust { hostname } => {
+                let response = client.post(&["clients", hostname, "trust"])?;
+                print_action(out, json_output, &response, &format!("Trusted {hostname}"))
+            }
+            ClientsCommand::Remove { hostname } => {
+                let response = client.delete(&["clients", hostname])?;
+                print_action(out, json_output, &response, &format!("Removed {hostname}"))
+            }
+        },
+        Command::Settings { command } => settings(client, command, json_output, out),
+        Command::Recording { command } => {
+            let response = match command {
+                RecordingCommand::Status => client.get(&["recording"])?,
+                RecordingCommand::Start => client.post(&["recording", "start"])?,
+                RecordingCommand::Stop => client.post(&["recording", "stop"])?,
+            };
+            let recording = response["recording"].as_bool() == Some(true);
+
+            print_action(
+                out,
+                json_output,
+                &response,
+                &format!("Recording: {}", yes_no(recording)),
+            )
+        }
+        Command::CaptureFrame => {
+            let response = client.post(&["video", "capture-frame"])?;
+            print_action(out, json_output, &response, "Frame capture requested")
+        }
+        Command::RequestIdr => {
+            let response = client.post(&["video", "idr"])?;
+            print_action(out, json_output, &response, "IDR frame requested")
+        }
+        Command::RestartSteamvr => {
+            let response = client.post(&["steamvr", "restart"])?;
+            print_action(out, json_output, &response, "SteamVR restart requested")
+        }
+        Command::Logs { level, count } => {
+            let min_severity = LogSeverity::from(*level);
+            let events = client.subscribe_events(EventsSubscription {
+                event_types: Some(vec!["Log".into()]),
+                min_log_severity: Some(min_severity),
+                max_statistics_rate_hz: None,
+            })?;
+
+            tail(events, *count, json_output, out, |event| {
+                match &event.event_type {
+                    EventType::Log(entry) if entry.severity >= min_severity => Some(format!(
+                        "{} [{}] {}",
+                        event.timestamp,
+                        event.event_type_string(),
+                        event.message()
+                    )),
+                    _ => None,
+                }
+            })
+        }
+        Command::Stats { rate, count } => {
+            let events = client.subscribe_events(EventsSubscription {
+                event_types: Some(vec!["StatisticsSummary".into()]),
+                min_log_severity: None,
+                max_statistics_rate_hz: Some(*rate),
+            })?;
+
+            tail(events, *count, json_output, out, |event| {
+                match &event.event_type {
+                    EventType::StatisticsSummary(summary) => Some(format!(
+                        "{} {}",
+                        event.timestamp,
+                        format_statistics(summary)
+                    )),
+                    _ => None,
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogLevel;
+    use server_core::{ServerCoreEvent, test_server::TestWebServer};
+    use shared::LogEntry;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    fn client(server: &TestWebServer) -> ApiClient {
+        ApiClient::new(
+            server.address.to_string(),
+            Some(TestWebServer::api_token()),
+            server.tls_fingerprint.as_deref(),
+        )
+        .unwrap()
+    }
+
+    fn run_command(client: &ApiClient, command: Command, json_output: bool) -> Result<String> {
+        let mut out = vec![];
+        run(client, &command, json_output, &mut out)?;
+
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    // Events are sent repeatedly until the command returns, because they are only delivered
+    // after the websocket is opened
+    fn run_with_events(
+        client: &ApiClient,
+        command: Command,
+        json_output: bool,
+        events: Vec<Event>,
+    ) -> Result<String> {
+        let done = Arc::new(AtomicBool::new(false));
+        let sender_thread = thread::spawn({
+            let done = Arc::clone(&done);
+            move || {
+                while !done.load(Ordering::Relaxed) {
+                    for event in &events {
+                        TestWebServer::send_event(event.clone());
+                    }
+                    thread::sleep(Duration::from_millis(20));
+                }
+            }
+        });
+
+        let res = run_command(client, command, json_output);
+        done.store(true, Ordering::Relaxed);
+        sender_thread.join().unwrap();
+
+        res
+    }
+
+    fn log_event(severity: LogSeverity, content: &str) -> Event {
+        Event {
+            timestamp: "12:00:00.000".into(),
+            event_type: EventType::Log(LogEntry {
+                severity,
+                content: content.into(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let server = TestWebServer::start(false);
+        let client = client(&server);
+
+        let output = run_command(&client, Command::Status, false).unwrap();
+        assert!(output.starts_with(&format!(
+            "Server version: {}\nRecording: no\nStreaming: no\nClients:\n",
+            shared::NANVR_VERSION
+        )));
+
+        let output = run_command(&client, Command::Status, true).unwrap();
+        let status = json::from_str::<json::Value>(&output).unwrap();
+        assert_eq!(status["version"], shared::NANVR_VERSION);
+        assert_eq!(status["recording"], false);
+        assert_eq!(status["statistics"], json::Value::Null);
+    }
+
+    #[test]
+    fn test_clients() {
+        let server = TestWebServer::start(false);
+        let client = client(&server);
+        let hostname = "nanvrctl test.client";
+        TestWebServer::add_client(hostname, false);
+
+        let list = |client: &ApiClient| {
+            json::from_str::<json::Value>(
+                &run_command(
+                    client,
+                    Command::Clients {
+                        command: ClientsCommand::List,
+                    },
+                    true,
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(list(&client)[hostname]["trusted"], false);
+
+        // The hostname contains a space, which must be percent-encoded
+        let output = run_command(
+            &client,
+            Command::Clients {
+                command: ClientsCommand::Trust {
+                    hostname: hostname.into(),
+                },
+            },
+            false,
+        )
+        .unwrap();
+        assert_eq!(output, format!("Trusted {hostname}\n"));
+        assert_eq!(list(&client)[hostname]["trusted"], true);
+
+        run_command(
+            &client,
+            Command::Clients {
+                command: ClientsCommand::Remove {
+                    hostname: hostname.into(),
+                },
+            },
+            false,
+        )
+        .unwrap();
+        assert_eq!(list(&client)[hostname], json::Value::Null);
+
+        let error = run_command(
+            &client,
+            Command::Clients {
+                command: ClientsCommand::Remove {
+                    hostname: hostname.into(),
+                },
+            },
+            false,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("Client not found"));
+    }
+
+    #[test]
+    fn test_actions() {
+        let server = TestWebServer::start(false);
+        let client = client(&server);
+
+        let output = run_command(&client, Command::CaptureFrame, false).unwrap();
+        assert_eq!(output, "Frame capture requested\n");
+        run_command(&client, Command::RequestIdr, false).unwrap();
+        run_command(&client, Command::RestartSteamvr, false).unwrap();
+
+        let events = server.events_receiver.try_iter().collect::<Vec<_>>();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                ServerCoreEvent::CaptureFrame,
+                ServerCoreEvent::RequestIDR,
+                ServerCoreEvent::RestartPending
+            ]
+        ));
+
+        let output = run_command(
+            &client,
+            Command::Recording {
+                command: RecordingCommand::Status,
+            },
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            json::from_str::<json::Value>(&output).unwrap(),
+            json::json!({ "recording": false })
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        let server = TestWebServer::start(false);
+        let client = client(&server);
+
+        let output = run_command(
+            &client,
+            Command::Settings {
+                command: SettingsCommand::Set {
+                    path: "connection.packet_size".into(),
+                    value: "1300".into(),
+                },
+            },
+            false,
+        )
+        .unwrap();
+        assert_eq!(output, "connection.packet_size = 1300\n");
+
+        let get = |path: &str| {
+            run_command(
+                &client,
+                Command::Settings {
+                    command: SettingsCommand::Get { path: path.into() },
+                },
+                false,
+            )
+        };
+        assert_eq!(get("connection.packet_size").unwrap(), "1300\n");
+        assert!(
+            get("connection.missing")
+                .unwrap_err()
+                .to_string()
+                .contains("connection.missing")
+        );
+
+        // The session is validated by the server
+        let error = run_command(
+            &client,
+            Command::Settings {
+                command: SettingsCommand::Set {
+                    path: "connection.packet_size".into(),
+                    value: "not a number".into(),
+                },
+            },
+            false,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("422"));
+    }
+
+    #[test]
+    fn test_missing_token() {
+        let server = TestWebServer::start(false);
+        let client = ApiClient::new(server.address.to_string(), None, None).unwrap();
+
+        // Reading the version doesn't need the token
+        run_command(&client, Command::Status, false).unwrap();
+
+        let error = run_command(&client, Command::RequestIdr, false).unwrap_err();
+        assert!(error.to_string().contains("401"));
+    }
+
+    #[test]
+    fn test_tls() {
+        let server = TestWebServer::start(true);
+
+        let output = run_command(&client(&server), Command::Status, true).unwrap();
+        let status = json::from_str::<json::Value>(&output).unwrap();
+        assert_eq!(status["version"], shared::NANVR_VERSION);
+
+        let wrong_fingerprint = ["00"; 32].join(":");
+        let client = ApiClient::new(
+            server.address.to_string(),
+            Some(TestWebServer::api_token()),
+            Some(&wrong_fingerprint),
+        )
+        .unwrap();
+        let error = run_command(&client, Command::Status, false).unwrap_err();
+        assert!(error.to_string().contains("fingerprint"));
+    }
+
+    // Over TLS, to cover the websocket on the pinned connection
+    #[test]
+    fn test_logs() {
+        let server = TestWebServer::start(true);
+
+        let output = run_with_events(
+            &client(&server),
+            Command::Logs {
+                level: LogLevel::Info,
+                count: Some(2),
+            },
+            false,
+            vec![
+                log_event(LogSeverity::Info, "Client connected"),
+                log_event(LogSeverity::Debug, "Filtered out"),
+                log_event(LogSeverity::Error, "Encoder failed"),
+            ],
+        )
+        .unwrap();
+
+        let mut lines = output.lines().collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "12:00:00.000 [ERROR] Encoder failed",
+                "12:00:00.000 [INFO] Client connected"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_count() {
+        let server = TestWebServer::start(false);
+
+        let output = run_with_events(
+            &client(&server),
+            Command::Stats {
+                rate: 2.0,
+                count: Some(1),
+            },
+            true,
+            vec![Event {
+                timestamp: "12:00:00.000".into(),
+                event_type: EventType::StatisticsSummary(StatisticsSummary {
+                    server_fps: 90,
+                    ..Default::default()
+                }),
+            }],
+        )
+        .unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1);
+
+        let event = json::from_str::<Event>(lines[0]).unwrap();
+        assert!(matches!(
+            event.event_type,
+            EventType::StatisticsSummary(StatisticsSummary { server_fps: 90, .. })
+        ));
+    }
+}
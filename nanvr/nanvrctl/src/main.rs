@@ -0,0 +1,171 @@
+mod client;
+mod commands;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use client::ApiClient;
+use configuration::SessionConfig;
+use serde_json as json;
+use shared::LogSeverity;
+use std::{
+    env, fs, io,
+    net::{Ipv4Addr, SocketAddr},
+    process::ExitCode,
+};
+
+#[derive(Parser)]
+#[command(name = "nanvrctl")]
+#[command(about = "Control a running NaNVR streamer through its web API", long_about = None)]
+struct Cli {
+    /// Web server address. Defaults to the local streamer, using the port in session.json
+    #[arg(long, global = true)]
+    server: Option<String>,
+    /// API token. Defaults to the one in session.json
+    #[arg(long, global = true)]
+    token: Option<String>,
+    /// SHA-256 fingerprint of the web server certificate, shown in the Installation tab of the
+    /// dashboard. Connects with TLS, which is required for remote streamers
+    #[arg(long, global = true)]
+    fingerprint: Option<String>,
+    /// Print JSON instead of human-readable output. Streams print one JSON object per line
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Show the server version, the recording state, the clients and the stream statistics
+    Status,
+    /// Manage the known clients
+    Clients {
+        #[command(subcommand)]
+        command: ClientsCommand,
+    },
+    /// Read or change session settings. Paths are relative to session_settings in session.json,
+    /// like "video.bitrate.mode.variant", numeric segments are array indices
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommand,
+    },
+    /// Record the video stream to a file
+    Recording {
+        #[command(subcommand)]
+        command: RecordingCommand,
+    },
+    /// Save the next encoded frame to a file
+    CaptureFrame,
+    /// Request an IDR frame from the encoder
+    RequestIdr,
+    /// Restart SteamVR
+    RestartSteamvr,
+    /// Print log messages as they are emitted
+    Logs {
+        /// Minimum severity
+        #[arg(long, value_enum, default_value_t = LogLevel::Info)]
+        level: LogLevel,
+        /// Exit after this many messages
+        #[arg(long)]
+        count: Option<usize>,
+    },
+    /// Print statistics summaries while a client is streaming
+    Stats {
+        /// Maximum summaries per second
+        #[arg(long, default_value_t = 1.0)]
+        rate: f32,
+        /// Exit after this many summaries
+        #[arg(long)]
+        count: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ClientsCommand {
+    /// List the known clients with their connection state
+    List,
+    /// Trust a client, allowing it to connect
+    Trust { hostname: String },
+    /// Remove a client
+    Remove { hostname: String },
+}
+
+#[derive(Subcommand)]
+pub enum SettingsCommand {
+    /// Print the value of a setting
+    Get { path: String },
+    /// The value is parsed as JSON, or used as a string if it is not valid JSON
+    Set { path: String, value: String },
+}
+
+#[derive(Subcommand)]
+pub enum RecordingCommand {
+    /// Show whether the video stream is being recorded
+    Status,
+    Start,
+    Stop,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl From<LogLevel> for LogSeverity {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LogSeverity::Error,
+            LogLevel::Warning => LogSeverity::Warning,
+            LogLevel::Info => LogSeverity::Info,
+            LogLevel::Debug => LogSeverity::Debug,
+        }
+    }
+}
+
+// None if the streamer was never launched on this PC
+fn local_session() -> Option<SessionConfig> {
+    let layout = filepaths::filesystem_layout_from_dashboard_exe(&env::current_exe().ok()?)?;
+    let session_json = json::from_str(&fs::read_to_string(layout.session()).ok()?).ok()?;
+
+    let mut session = SessionConfig::default();
+    session.merge_from_json(&session_json).ok()?;
+
+    Some(session)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let session = local_session();
+    let server = cli.server.clone().unwrap_or_else(|| {
+        let connection = session.as_ref().map_or_else(
+            || SessionConfig::default().to_settings().connection,
+            |session| session.to_settings().connection,
+        );
+        // Use the loopback interface unless the streamer listens on a specific address
+        let ip = connection
+            .web_server_bind_address
+            .ip()
+            .filter(|ip| !ip.is_unspecified())
+            .unwrap_or(Ipv4Addr::LOCALHOST.into());
+
+        SocketAddr::new(ip, connection.web_server_port).to_string()
+    });
+    let token = cli
+        .token
+        .clone()
+        .or_else(|| session.map(|session| session.api_token));
+
+    let res = ApiClient::new(server, token, cli.fingerprint.as_deref())
+        .and_then(|client| commands::run(&client, &cli.command, cli.json, &mut io::stdout()));
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+
+            ExitCode::FAILURE
+        }
+    }
+}
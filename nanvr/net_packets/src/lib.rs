@@ -1,6 +1,12 @@
+mod openapi;
+
+pub use openapi::*;
+
 use configuration::{
-    ClientsidePostProcessingConfig, CodecType, PassthroughMode, SessionConfig, Settings,
+    ClientsidePostProcessingConfig, CodecType, CustomAudioDeviceConfig, FoveatedEncodingConfig,
+    PassthroughMode, SessionConfig, Settings,
 };
+use ring::hmac;
 use serde::{Deserialize, Serialize};
 use serde_json as json;
 use shared::{
@@ -24,7 +30,10 @@ pub const STATISTICS: u16 = 4;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct VideoStreamingCapabilitiesExt {
-    // Nothing for now
+    // Random value the server must sign with the key it gave to this client, see server_proof()
+    pub server_challenge: Option<String>,
+    // Set when the client pinned a server it does not hold a key for yet
+    pub request_server_key: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -51,11 +60,21 @@ impl VideoStreamingCapabilities {
     }
 
     pub fn ext(&self) -> Result<VideoStreamingCapabilitiesExt> {
-        let _ext_json = json::from_str::<json::Value>(&self.ext_str)?;
-
-        // decode values here
-
-        Ok(VideoStreamingCapabilitiesExt {})
+        let ext_json = json::from_str::<json::Value>(&self.ext_str)?;
+
+        let server_challenge = ext_json
+            .get("server_challenge")
+            .and_then(|value| value.as_str())
+            .map(ToOwned::to_owned);
+        let request_server_key = ext_json
+            .get("request_server_key")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+
+        Ok(VideoStreamingCapabilitiesExt {
+            server_challenge,
+            request_server_key,
+        })
     }
 }
 
@@ -70,9 +89,14 @@ pub enum ClientConnectionResult {
     ClientStandby,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct NegotiatedStreamingConfigExt {
-    // Nothing for now
+    pub server_id: Option<String>,
+    // Answer to the client challenge
+    pub server_proof: Option<String>,
+    // Sent only if requested. It is specific to the client, which stores it to authenticate the
+    // server on the next connections (trust on first use)
+    pub server_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -96,12 +120,53 @@ impl NegotiatedStreamingConfig {
     }
 
     pub fn ext(&self) -> Result<NegotiatedStreamingConfigExt> {
-        let _ext_json = json::from_str::<json::Value>(&self.ext_str)?;
+        let ext_json = json::from_str::<json::Value>(&self.ext_str)?;
+
+        let get_string = |key| {
+            ext_json
+                .get(key)
+                .and_then(|value| value.as_str())
+                .map(ToOwned::to_owned)
+        };
+
+        Ok(NegotiatedStreamingConfigExt {
+            server_id: get_string("server_id"),
+            server_proof: get_string("server_proof"),
+            server_key: get_string("server_key"),
+        })
+    }
+}
 
-        // decode values here
+fn hmac_hex(key: &str, message: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes());
 
-        Ok(NegotiatedStreamingConfigExt {})
-    }
+    hmac::sign(&key, message.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// Key given by the server to a single client. It is derived from the server secret, so the server
+// does not need to store it
+pub fn client_server_key(server_secret: &str, client_hostname: &str) -> String {
+    hmac_hex(server_secret, client_hostname)
+}
+
+pub fn server_proof(server_key: &str, challenge: &str) -> String {
+    hmac_hex(server_key, challenge)
+}
+
+pub fn is_valid_server_proof(server_key: &str, challenge: &str, proof: &str) -> bool {
+    let expected = server_proof(server_key, challenge);
+
+    // Constant time comparison
+    proof.len() == expected.len()
+        && proof
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
 }
 
 #[derive(Serialize, Deserialize)]
@@ -120,7 +185,7 @@ pub struct StreamConfig {
 impl StreamConfigPacket {
     pub fn new(session: &SessionConfig, negotiated: NegotiatedStreamingConfig) -> Result<Self> {
         Ok(Self {
-            session: json::to_string(session)?,
+            session: json::to_string(&session.without_secrets())?,
             negotiated,
         })
     }
@@ -138,6 +203,12 @@ impl StreamConfigPacket {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DecoderInitializationConfigExt {
+    // Set when the encoder resolution changed after negotiation, e.g. by dynamic resolution
+    pub view_resolution: Option<UVec2>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DecoderInitializationConfig {
     pub codec: CodecType,
@@ -145,6 +216,25 @@ pub struct DecoderInitializationConfig {
     pub ext_str: String,
 }
 
+impl DecoderInitializationConfig {
+    pub fn with_ext(self, ext: DecoderInitializationConfigExt) -> Self {
+        Self {
+            ext_str: json::to_string(&ext).unwrap(),
+            ..self
+        }
+    }
+
+    pub fn ext(&self) -> Result<DecoderInitializationConfigExt> {
+        let ext_json = json::from_str::<json::Value>(&self.ext_str)?;
+
+        let view_resolution = ext_json
+            .get("view_resolution")
+            .and_then(|value| json::from_value(value.clone()).ok());
+
+        Ok(DecoderInitializationConfigExt { view_resolution })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum ServerControlPacket {
     StartStream,
@@ -152,6 +242,8 @@ pub enum ServerControlPacket {
     Restarting,
     KeepAlive,
     RealTimeConfig(RealTimeConfig),
+    // Enables or disables the recording of client trace events
+    TraceCapture(bool),
     Reserved(String),
     ReservedBuffer(Vec<u8>),
 }
@@ -274,9 +366,14 @@ impl From<usize> for PathSegment {
     }
 }
 
-// todo: support indices
+// Numeric segments are array indices, like in "dictionary.content.0.1"
 pub fn parse_path(path: &str) -> Vec<PathSegment> {
-    path.split('.').map(|s| s.into()).collect()
+    path.split('.')
+        .map(|s| {
+            s.parse::<usize>()
+                .map_or_else(|_| s.into(), PathSegment::Index)
+        })
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -291,6 +388,91 @@ pub enum ClientListAction {
     RemoveEntry,
     UpdateCurrentIp(Option<IpAddr>),
     SetConnectionState(ConnectionState),
+    // Partial session settings, validated against the settings schema
+    SetSettingsOverrides(Option<json::Value>),
+}
+
+// Performance notification level reported by the VR runtime for the CPU or GPU
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerformanceLevel {
+    Normal,
+    Warning,
+    Impaired,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PerformanceDomain {
+    Cpu,
+    Gpu,
+}
+
+// Mirrors the Android PowerManager thermal status
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThermalStatus {
+    None,
+    Light,
+    Moderate,
+    Severe,
+    Critical,
+    Emergency,
+    Shutdown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WifiLinkInfo {
+    pub rssi_dbm: Option<i32>,
+    pub link_speed_mbps: Option<u32>,
+    pub frequency_mhz: Option<u32>,
+    pub channel: Option<u32>,
+}
+
+impl WifiLinkInfo {
+    pub fn channel_from_frequency(frequency_mhz: u32) -> Option<u32> {
+        match frequency_mhz {
+            2484 => Some(14),
+            2412..=2472 => Some((frequency_mhz - 2407) / 5),
+            5150..=5895 => Some((frequency_mhz - 5000) / 5),
+            5955..=7115 => Some((frequency_mhz - 5950) / 5),
+            _ => None,
+        }
+    }
+
+    pub fn band_name(&self) -> Option<&'static str> {
+        match self.frequency_mhz? {
+            2400..=2500 => Some("2.4 GHz"),
+            4900..=5900 => Some("5 GHz"),
+            5925..=7125 => Some("6 GHz"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ClientTraceEvent {
+    pub name: String,
+    // Timeline the event belongs to, e.g. "Decoder"
+    pub track: String,
+    // Relative to the tracking input acquisition of the frame the event is reported with
+    pub start_offset_us: i64,
+    // None for instant events
+    pub duration_us: Option<u64>,
+}
+
+// Client telemetry, attached to the statistics of one frame every second or so, or to every frame
+// while a trace capture is running. Fields are None if the platform does not expose them.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClientStatisticsExt {
+    pub wifi: Option<WifiLinkInfo>,
+    // Frames submitted to the decoder and not yet decoded
+    pub decoder_queue_frames: Option<u32>,
+    // Frames received but never decoded, since the stream started
+    pub dropped_frames: Option<u64>,
+    pub thermal_status: Option<ThermalStatus>,
+    pub cpu_level: Option<PerformanceLevel>,
+    pub gpu_level: Option<PerformanceLevel>,
+    pub rendering_fps: Option<f32>,
+    // Recorded since the previous report, only while a trace capture is running
+    pub trace_events: Option<Vec<ClientTraceEvent>>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -302,6 +484,41 @@ pub struct ClientStatistics {
     pub rendering: Duration,
     pub vsync_queue: Duration,
     pub total_pipeline_latency: Duration,
+    // Video packet counts since the previous statistics report
+    pub video_packets_received: u32,
+    pub video_packets_lost: u32,
+    // Empty for most frames
+    pub ext_str: String,
+}
+
+impl ClientStatistics {
+    pub fn with_ext(self, ext: ClientStatisticsExt) -> Self {
+        Self {
+            ext_str: json::to_string(&ext).unwrap(),
+            ..self
+        }
+    }
+
+    pub fn ext(&self) -> Result<ClientStatisticsExt> {
+        let ext_json = json::from_str::<json::Value>(&self.ext_str)?;
+
+        fn field<T: serde::de::DeserializeOwned>(ext_json: &json::Value, name: &str) -> Option<T> {
+            ext_json
+                .get(name)
+                .and_then(|value| json::from_value(value.clone()).ok())
+        }
+
+        Ok(ClientStatisticsExt {
+            wifi: field(&ext_json, "wifi"),
+            decoder_queue_frames: field(&ext_json, "decoder_queue_frames"),
+            dropped_frames: field(&ext_json, "dropped_frames"),
+            thermal_status: field(&ext_json, "thermal_status"),
+            cpu_level: field(&ext_json, "cpu_level"),
+            gpu_level: field(&ext_json, "gpu_level"),
+            rendering_fps: field(&ext_json, "rendering_fps"),
+            trace_events: field(&ext_json, "trace_events"),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -316,6 +533,16 @@ pub enum FirewallRulesAction {
     Remove,
 }
 
+// Profiles contain partial session settings
+#[derive(Serialize, Deserialize, Debug)]
+pub enum SettingsProfileAction {
+    List,
+    Save { name: String, profile: json::Value },
+    Apply(String),
+    Rename { name: String, new_name: String },
+    Delete(String),
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ServerRequest {
     Log(LogEntry),
@@ -330,12 +557,55 @@ pub enum ServerRequest {
     InsertIdr,
     StartRecording,
     StopRecording,
+    StartFrameStatisticsLogging,
+    StopFrameStatisticsLogging,
+    StartTraceCapture,
+    StopTraceCapture,
     FirewallRules(FirewallRulesAction),
     RegisterNanvrDriver,
     UnregisterDriver(PathBuf),
     GetDriverList,
     RestartSteamvr,
     ShutdownSteamvr,
+    SettingsProfile(SettingsProfileAction),
+}
+
+// Unset fields don't filter. Event types are named as their serialized "id" field
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct EventsSubscription {
+    pub event_types: Option<Vec<String>>,
+    // Also applies to debug group events, which have Debug severity
+    pub min_log_severity: Option<LogSeverity>,
+    // Applies to StatisticsSummary and GraphStatistics separately
+    pub max_statistics_rate_hz: Option<f32>,
+}
+
+// Messages accepted by the events websocket
+#[derive(Serialize, Deserialize, Debug)]
+pub enum EventsWebsocketMessage {
+    Subscribe(EventsSubscription),
+    // The ID is chosen by the sender and is returned unchanged in the reply
+    ServerRequest {
+        id: json::Value,
+        request: ServerRequest,
+    },
+}
+
+// Sent on the events websocket after a ServerRequest is handled. The ID is null if the message
+// could not be parsed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServerRequestReply {
+    pub id: json::Value,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct RealTimeConfigExt {
+    // Ignored by the client if foveated encoding was not enabled during negotiation
+    pub foveated_encoding: Option<FoveatedEncodingConfig>,
+    pub game_audio_device: Option<CustomAudioDeviceConfig>,
+    pub microphone_device: Option<CustomAudioDeviceConfig>,
 }
 
 // Note: server sends a packet to the client at low frequency, binary encoding, without ensuring
@@ -356,7 +626,292 @@ impl RealTimeConfig {
                 .clientside_post_processing
                 .clone()
                 .into_option(),
-            ext_str: String::new(), // No extensions for now
+            ext_str: String::new(),
+        }
+        .with_ext(RealTimeConfigExt {
+            foveated_encoding: settings.video.foveated_encoding.clone().into_option(),
+            game_audio_device: settings
+                .audio
+                .game_audio
+                .as_option()
+                .and_then(|config| config.device.clone()),
+            microphone_device: settings
+                .audio
+                .microphone
+                .as_option()
+                .and_then(|config| config.device.clone()),
+        })
+    }
+
+    pub fn with_ext(self, ext: RealTimeConfigExt) -> Self {
+        Self {
+            ext_str: json::to_string(&ext).unwrap(),
+            ..self
+        }
+    }
+
+    pub fn ext(&self) -> Result<RealTimeConfigExt> {
+        let ext_json = json::from_str::<json::Value>(&self.ext_str)?;
+
+        let foveated_encoding = ext_json
+            .get("foveated_encoding")
+            .and_then(|value| json::from_value(value.clone()).ok());
+        let game_audio_device = ext_json
+            .get("game_audio_device")
+            .and_then(|value| json::from_value(value.clone()).ok());
+        let microphone_device = ext_json
+            .get("microphone_device")
+            .and_then(|value| json::from_value(value.clone()).ok());
+
+        Ok(RealTimeConfigExt {
+            foveated_encoding,
+            game_audio_device,
+            microphone_device,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configuration::settings_schema::{NumberType, SchemaNode};
+
+    // Real-time settings applied by the server (or the dashboard), which reads them from the
+    // current settings when needed. All other real-time settings must reach the client through
+    // RealTimeConfig. The encoder and audio gain application is tested in server_core and sound.
+    const SERVER_SIDE_PATHS: &[&str] = &[
+        "video.bitrate",
+        "video.enforce_server_frame_pacing",
+        "video.color_correction",
+        "audio.game_audio.content.gain",
+        "audio.microphone.content.gain",
+        "headset.face_tracking.content.sink",
+        "headset.body_tracking.content.sink",
+        "headset.body_tracking.content.tracked",
+        "headset.vmc.content",
+        "headset.controllers.content",
+        "headset.position_recentering_mode",
+        "headset.rotation_recentering_mode",
+        "connection.enable_on_disconnect_script",
+        "connection.allow_untrusted_http",
+        "connection.metrics_endpoint",
+        "extra.logging",
+        "extra.hooks",
+    ];
+
+    // Enables all switches and optionals, so that any nested setting is in effect
+    fn enable_all(session_settings: &mut json::Value, schema: &SchemaNode) {
+        match schema {
+            SchemaNode::Section { entries, .. } => {
+                for named_entry in entries {
+                    enable_all(
+                        &mut session_settings[&named_entry.name],
+                        &named_entry.content,
+                    );
+                }
+            }
+            SchemaNode::Choice { variants, .. } => {
+                for named_entry in variants {
+                    if let Some(data_schema) = &named_entry.content {
+                        enable_all(&mut session_settings[&named_entry.name], data_schema);
+                    }
+                }
+            }
+            SchemaNode::Optional { content, .. } => {
+                session_settings["set"] = json::Value::Bool(true);
+                enable_all(&mut session_settings["content"], content);
+            }
+            SchemaNode::Switch { content, .. } => {
+                session_settings["enabled"] = json::Value::Bool(true);
+                enable_all(&mut session_settings["content"], content);
+            }
+            _ => (),
+        }
+    }
+
+    fn join_path(path: &str, name: &str) -> String {
+        if path.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{path}.{name}")
+        }
+    }
+
+    fn collect_real_time_entries<'a>(
+        schema: &'a SchemaNode,
+        path: &str,
+        entries: &mut Vec<(String, &'a SchemaNode)>,
+    ) {
+        match schema {
+            SchemaNode::Section {
+                entries: section_entries,
+                ..
+            } => {
+                for named_entry in section_entries {
+                    let path = join_path(path, &named_entry.name);
+                    if named_entry.flags.contains("real-time") {
+                        entries.push((path, &named_entry.content));
+                    } else {
+                        collect_real_time_entries(&named_entry.content, &path, entries);
+                    }
+                }
+            }
+            SchemaNode::Choice { variants, .. } => {
+                for named_entry in variants {
+                    if let Some(data_schema) = &named_entry.content {
+                        let path = join_path(path, &named_entry.name);
+                        collect_real_time_entries(data_schema, &path, entries);
+                    }
+                }
+            }
+            SchemaNode::Optional { content, .. } | SchemaNode::Switch { content, .. } => {
+                collect_real_time_entries(content, &join_path(path, "content"), entries);
+            }
+            _ => (),
+        }
+    }
+
+    // Changes the value in a way that is reflected in the final settings
+    fn mutate(value: &mut json::Value, schema: &SchemaNode) {
+        match schema {
+            SchemaNode::Section { entries, .. } => {
+                let named_entry = entries.first().unwrap();
+                mutate(&mut value[&named_entry.name], &named_entry.content);
+            }
+            SchemaNode::Choice { variants, .. } => {
+                let variant = variants
+                    .iter()
+                    .find(|named_entry| value["variant"] != named_entry.name.as_str())
+                    .unwrap();
+                value["variant"] = json::Value::String(variant.name.clone());
+            }
+            SchemaNode::Optional { .. } => {
+                value["set"] = json::Value::Bool(!value["set"].as_bool().unwrap());
+            }
+            SchemaNode::Switch { .. } => {
+                value["enabled"] = json::Value::Bool(!value["enabled"].as_bool().unwrap());
+            }
+            SchemaNode::Boolean { .. } => *value = json::Value::Bool(!value.as_bool().unwrap()),
+            SchemaNode::Number { ty, .. } => {
+                *value = match ty {
+                    NumberType::Float => json::json!(value.as_f64().unwrap() + 0.5),
+                    _ => json::json!(value.as_u64().unwrap() + 1),
+                }
+            }
+            SchemaNode::Text { .. } => {
+                *value = json::Value::String(format!("{}_", value.as_str().unwrap()));
+            }
+            SchemaNode::Array(schemas) => mutate(&mut value["content"][0], &schemas[0]),
+            SchemaNode::Vector { .. } => {
+                let element = value["element"].clone();
+                value["content"].as_array_mut().unwrap().push(element);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn real_time_config(session_settings: &json::Value) -> RealTimeConfig {
+        let session = SessionConfig {
+            session_settings: json::from_value(session_settings.clone()).unwrap(),
+            ..SessionConfig::default()
+        };
+
+        RealTimeConfig::from_settings(&session.to_settings())
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            format!("{:?}", parse_path("session_settings.headset.vmc.content.0")),
+            "[session_settings, headset, vmc, content, [0]]"
+        );
+    }
+
+    #[test]
+    fn test_stream_config_packet_secrets() {
+        let session = SessionConfig::default();
+        let negotiated = NegotiatedStreamingConfig {
+            view_resolution: UVec2::new(1920, 1080),
+            refresh_rate_hint: 90.0,
+            game_audio_sample_rate: 44100,
+            enable_foveated_encoding: false,
+            encoding_gamma: 1.0,
+            enable_hdr: false,
+            wired: false,
+            ext_str: String::new(),
+        };
+
+        let packet = StreamConfigPacket::new(&session, negotiated).unwrap();
+        assert!(!packet.session.contains(&session.api_token));
+        assert!(!packet.session.contains(&session.server_secret));
+
+        let stream_config = packet.to_stream_config().unwrap();
+        assert_eq!(stream_config.server_version, session.server_version);
+    }
+
+    #[test]
+    fn test_server_proof() {
+        let key = client_server_key("secret", "1234.client.local.");
+        assert_ne!(key, client_server_key("secret", "5678.client.local."));
+        assert_ne!(key, client_server_key("other secret", "1234.client.local."));
+
+        let proof = server_proof(&key, "challenge");
+        assert!(is_valid_server_proof(&key, "challenge", &proof));
+        assert!(!is_valid_server_proof(&key, "other challenge", &proof));
+        assert!(!is_valid_server_proof(&key, "challenge", &proof[1..]));
+        assert!(!is_valid_server_proof(
+            &client_server_key("other secret", "1234.client.local."),
+            "challenge",
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_wifi_channel() {
+        assert_eq!(WifiLinkInfo::channel_from_frequency(2412), Some(1));
+        assert_eq!(WifiLinkInfo::channel_from_frequency(2484), Some(14));
+        assert_eq!(WifiLinkInfo::channel_from_frequency(5180), Some(36));
+        assert_eq!(WifiLinkInfo::channel_from_frequency(5955), Some(1));
+        assert_eq!(WifiLinkInfo::channel_from_frequency(60480), None);
+    }
+
+    #[test]
+    fn test_real_time_settings_propagate() {
+        let schema = Settings::schema(configuration::session_settings_default());
+        let mut session_settings =
+            json::to_value(configuration::session_settings_default()).unwrap();
+        enable_all(&mut session_settings, &schema);
+
+        let mut entries = vec![];
+        collect_real_time_entries(&schema, "", &mut entries);
+        assert!(!entries.is_empty());
+
+        for server_path in SERVER_SIDE_PATHS {
+            assert!(
+                entries
+                    .iter()
+                    .any(|(path, _)| path.starts_with(server_path)),
+                "{server_path} is not a real-time setting"
+            );
+        }
+
+        let base_config = real_time_config(&session_settings);
+        for (path, schema) in entries {
+            if SERVER_SIDE_PATHS
+                .iter()
+                .any(|server_path| path.starts_with(server_path))
+            {
+                continue;
+            }
+
+            let mut new_session_settings = session_settings.clone();
+            let pointer = format!("/{}", path.replace('.', "/"));
+            mutate(new_session_settings.pointer_mut(&pointer).unwrap(), schema);
+
+            assert!(
+                real_time_config(&new_session_settings) != base_config,
+                "{path} is not propagated to the client"
+            );
         }
     }
 }
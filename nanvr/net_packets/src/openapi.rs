@@ -0,0 +1,440 @@
+// OpenAPI 3.1 description of the web server: the versioned REST API, the websockets and the
+// unversioned HTTP endpoints
+
+use configuration::settings_json_schema;
+use serde_json as json;
+use shared::{NANVR_HIGH_NAME, NANVR_NAME, NANVR_VERSION};
+
+pub const REST_API_BASE_PATH: &str = "/api/v1";
+
+fn schema_ref(name: &str) -> json::Value {
+    json::json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+fn json_content(schema: json::Value) -> json::Value {
+    json::json!({ "application/json": { "schema": schema } })
+}
+
+fn operation(
+    summary: &str,
+    requires_token: bool,
+    request_schema: Option<json::Value>,
+    (success_code, response_schema): (&str, Option<json::Value>),
+    error_codes: &[&str],
+) -> json::Value {
+    let mut responses = json::Map::new();
+    responses.insert(
+        success_code.into(),
+        if let Some(schema) = response_schema {
+            json::json!({ "description": "Success", "content": json_content(schema) })
+        } else {
+            json::json!({ "description": "Success" })
+        },
+    );
+    for code in error_codes {
+        responses.insert(
+            (*code).into(),
+            json::json!({ "$ref": "#/components/responses/Error" }),
+        );
+    }
+    if requires_token {
+        responses.insert(
+            "401".into(),
+            json::json!({ "description": "Invalid API token" }),
+        );
+    }
+
+    let mut operation = json::json!({
+        "summary": summary,
+        "parameters": [{ "$ref": "#/components/parameters/NanvrHeader" }],
+        "responses": responses,
+    });
+    if let Some(schema) = request_schema {
+        operation["requestBody"] =
+            json::json!({ "required": true, "content": json_content(schema) });
+    }
+    if requires_token {
+        operation["security"] = json::json!([{ "bearerToken": [] }, { "queryToken": [] }]);
+    }
+
+    operation
+}
+
+// Websockets are described as GET requests answered with 101. OpenAPI cannot describe the
+// messages, their schemas are referenced by the x-client-messages and x-server-messages fields
+fn websocket_operation(
+    summary: &str,
+    nanvr_header: bool,
+    client_messages: Option<json::Value>,
+    server_messages: json::Value,
+) -> json::Value {
+    let mut operation = operation(summary, true, None, ("101", None), &[]);
+    operation["responses"]["101"]["description"] = "Switching to the websocket protocol".into();
+    if !nanvr_header {
+        operation["parameters"] = json::json!([]);
+    }
+    if let Some(schema) = client_messages {
+        operation["x-client-messages"] = schema;
+    }
+    operation["x-server-messages"] = server_messages;
+
+    operation
+}
+
+fn hostname_parameter() -> json::Value {
+    json::json!([{
+        "name": "hostname",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" },
+    }])
+}
+
+pub fn openapi_document() -> json::Value {
+    let object = json::json!({ "type": "object" });
+    let recording_status = schema_ref("RecordingStatus");
+    let drivers = json::json!({ "type": "array", "items": { "type": "string" } });
+
+    let mut session_settings_schema = settings_json_schema();
+    session_settings_schema
+        .as_object_mut()
+        .unwrap()
+        .remove("$schema");
+
+    let rest_api_paths = json::json!({
+        "/": { "get": operation("Server version", false, None, ("200", Some(schema_ref("Version"))), &[]) },
+        "/version": { "get": operation("Server version", false, None, ("200", Some(schema_ref("Version"))), &[]) },
+        "/openapi": { "get": operation("This document", false, None, ("200", Some(object.clone())), &[]) },
+        "/session": {
+            "get": operation("Session, including the API token", true, None, ("200", Some(object.clone())), &[]),
+            "patch": operation(
+                "Set session values by path, each value can be any session subtree",
+                true,
+                Some(json::json!({ "type": "array", "items": schema_ref("PathValuePair") })),
+                ("200", Some(object.clone())),
+                &["400", "422"],
+            ),
+        },
+        "/settings": {
+            "get": operation(
+                "Effective settings of the streaming client",
+                false,
+                None,
+                ("200", Some(object.clone())),
+                &[],
+            ),
+            "patch": operation(
+                "Merge partial session settings on top of the current ones",
+                true,
+                Some(schema_ref("SessionSettings")),
+                ("200", Some(schema_ref("SettingsPatchResponse"))),
+                &["400", "422"],
+            ),
+        },
+        "/settings/schema": {
+            "get": operation(
+                "JSON Schema of the session settings",
+                false,
+                None,
+                ("200", Some(object.clone())),
+                &[],
+            ),
+        },
+        "/clients": {
+            "get": operation("Known clients by hostname", false, None, ("200", Some(object.clone())), &[]),
+        },
+        "/clients/{hostname}": {
+            "parameters": hostname_parameter(),
+            "delete": operation("Remove a client", true, None, ("204", None), &["404"]),
+        },
+        "/clients/{hostname}/trust": {
+            "parameters": hostname_parameter(),
+            "post": operation("Trust a client", true, None, ("200", Some(object.clone())), &["404"]),
+        },
+        "/drivers": {
+            "get": operation("Registered OpenVR drivers", false, None, ("200", Some(drivers.clone())), &["500"]),
+            "delete": operation(
+                "Unregister an OpenVR driver",
+                true,
+                Some(schema_ref("DriverRequest")),
+                ("200", Some(drivers.clone())),
+                &["400", "404", "500"],
+            ),
+        },
+        "/drivers/nanvr": {
+            "post": operation(
+                format!("Register the {NANVR_NAME} driver").as_str(),
+                true,
+                None,
+                ("200", Some(drivers)),
+                &["500"],
+            ),
+        },
+        "/recording": {
+            "get": operation("Recording status", false, None, ("200", Some(recording_status.clone())), &[]),
+        },
+        "/recording/start": {
+            "post": operation("Start recording the video stream", true, None, ("200", Some(recording_status.clone())), &["409", "500"]),
+        },
+        "/recording/stop": {
+            "post": operation("Stop recording the video stream", true, None, ("200", Some(recording_status)), &[]),
+        },
+        "/video/capture-frame": {
+            "post": operation("Save the next encoded frame to a file", true, None, ("202", None), &[]),
+        },
+        "/video/idr": {
+            "post": operation("Request an IDR frame from the encoder", true, None, ("202", None), &[]),
+        },
+        "/steamvr/restart": {
+            "post": operation("Restart SteamVR", true, None, ("202", None), &[]),
+        },
+        "/steamvr/shutdown": {
+            "post": operation("Shut down SteamVR", true, None, ("202", None), &[]),
+        },
+        "/statistics": {
+            "get": operation(
+                "Latest statistics summary",
+                false,
+                None,
+                ("200", Some(object)),
+                &["404"],
+            ),
+        },
+    });
+
+    let mut paths = json::Map::new();
+    for (path, item) in rest_api_paths.as_object().unwrap() {
+        let path = if path == "/" {
+            REST_API_BASE_PATH.to_string()
+        } else {
+            format!("{REST_API_BASE_PATH}{path}")
+        };
+        paths.insert(path, item.clone());
+    }
+
+    let mut dashboard_request = operation(
+        "Handle a server request, like the ServerRequest messages of /api/events",
+        true,
+        Some(schema_ref("ServerRequest")),
+        ("200", None),
+        &[],
+    );
+    dashboard_request["responses"]["400"] = json::json!({ "description": "Invalid request" });
+
+    let metrics = json::json!({
+        "summary": "Prometheus metrics, if enabled in the settings",
+        "responses": {
+            "200": {
+                "description": "Success",
+                "content": { "text/plain": { "schema": { "type": "string" } } },
+            },
+            "403": { "description": "Remote access is not allowed" },
+            "404": { "description": "The metrics endpoint is disabled" },
+        },
+    });
+
+    paths.extend(
+        json::json!({
+            "/api/events": {
+                "get": websocket_operation(
+                    "Events, filtered by the last Subscribe message. The server sends a \
+                        ServerRequestReply for each ServerRequest message",
+                    true,
+                    Some(schema_ref("EventsWebsocketMessage")),
+                    json::json!({
+                        "oneOf": [schema_ref("Event"), schema_ref("ServerRequestReply")],
+                    }),
+                ),
+            },
+            "/api/dashboard-request": { "post": dashboard_request },
+            "/api/video-mirror": {
+                "get": websocket_operation(
+                    "Binary messages with the decoder configuration and then the NAL units of \
+                        each frame",
+                    true,
+                    None,
+                    json::json!({ "type": "string", "contentEncoding": "binary" }),
+                ),
+            },
+            "/api/video-mirror-mp4": {
+                "get": websocket_operation(
+                    "Fragmented MP4 of the video stream. A text message with the MIME type \
+                        precedes each binary init segment, the media fragments are binary",
+                    false,
+                    None,
+                    json::json!({
+                        "oneOf": [
+                            schema_ref("MirrorMimeType"),
+                            { "type": "string", "contentEncoding": "binary" },
+                        ],
+                    }),
+                ),
+            },
+            "/video-mirror": {
+                "get": {
+                    "summary": "Viewer page of /api/video-mirror-mp4",
+                    "responses": {
+                        "200": {
+                            "description": "Success",
+                            "content": { "text/html": { "schema": { "type": "string" } } },
+                        },
+                    },
+                },
+            },
+            "/metrics": { "get": metrics },
+        })
+        .as_object()
+        .unwrap()
+        .clone(),
+    );
+
+    json::json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": format!("{NANVR_NAME} web API"),
+            "version": NANVR_VERSION.to_string(),
+            "description": format!(
+                "Requests under {REST_API_BASE_PATH} are answered synchronously with JSON. \
+                    Events are pushed on the /api/events websocket instead."
+            ),
+        },
+        "paths": paths,
+        "components": {
+            "parameters": {
+                "NanvrHeader": {
+                    "name": format!("X-{NANVR_HIGH_NAME}"),
+                    "in": "header",
+                    "required": true,
+                    "schema": { "const": "true" },
+                },
+            },
+            "securitySchemes": {
+                "bearerToken": { "type": "http", "scheme": "bearer" },
+                "queryToken": { "type": "apiKey", "in": "query", "name": "token" },
+            },
+            "responses": {
+                "Error": {
+                    "description": "Error",
+                    "content": json_content(schema_ref("Error")),
+                },
+            },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "string" } },
+                    "required": ["error"],
+                },
+                "Version": {
+                    "type": "object",
+                    "properties": { "version": { "type": "string" } },
+                    "required": ["version"],
+                },
+                "PathValuePair": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "array",
+                            "items": {
+                                "oneOf": [
+                                    {
+                                        "type": "object",
+                                        "properties": { "Name": { "type": "string" } },
+                                        "required": ["Name"],
+                                    },
+                                    {
+                                        "type": "object",
+                                        "properties": { "Index": { "type": "integer", "minimum": 0 } },
+                                        "required": ["Index"],
+                                    },
+                                ],
+                            },
+                        },
+                        "value": {},
+                    },
+                    "required": ["path", "value"],
+                },
+                "SettingsPatchResponse": {
+                    "type": "object",
+                    "properties": {
+                        "steamvr_restart_paths": { "type": "array", "items": { "type": "string" } },
+                    },
+                    "required": ["steamvr_restart_paths"],
+                },
+                "DriverRequest": {
+                    "type": "object",
+                    "properties": { "path": { "type": "string" } },
+                    "required": ["path"],
+                },
+                "RecordingStatus": {
+                    "type": "object",
+                    "properties": { "recording": { "type": "boolean" } },
+                    "required": ["recording"],
+                },
+                "SessionSettings": session_settings_schema,
+                "EventsSubscription": {
+                    "type": "object",
+                    "properties": {
+                        "event_types": { "type": ["array", "null"], "items": { "type": "string" } },
+                        "min_log_severity": { "enum": ["Error", "Warning", "Info", "Debug", null] },
+                        "max_statistics_rate_hz": { "type": ["number", "null"] },
+                    },
+                },
+                "ServerRequest": {
+                    "description": "Externally tagged, like \"GetSession\" or {\"SetValues\": [...]}",
+                    "oneOf": [
+                        { "type": "string" },
+                        { "type": "object", "minProperties": 1, "maxProperties": 1 },
+                    ],
+                },
+                "EventsWebsocketMessage": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "properties": { "Subscribe": schema_ref("EventsSubscription") },
+                            "required": ["Subscribe"],
+                        },
+                        {
+                            "type": "object",
+                            "properties": {
+                                "ServerRequest": {
+                                    "type": "object",
+                                    "properties": {
+                                        "id": { "description": "Returned unchanged in the reply" },
+                                        "request": schema_ref("ServerRequest"),
+                                    },
+                                    "required": ["id", "request"],
+                                },
+                            },
+                            "required": ["ServerRequest"],
+                        },
+                    ],
+                },
+                "ServerRequestReply": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "description": "Null if the message could not be parsed" },
+                        "error": { "type": ["string", "null"] },
+                    },
+                    "required": ["id", "error"],
+                },
+                "Event": {
+                    "type": "object",
+                    "properties": {
+                        "timestamp": { "type": "string" },
+                        "event_type": {
+                            "type": "object",
+                            "properties": { "id": { "type": "string" }, "data": {} },
+                            "required": ["id"],
+                        },
+                    },
+                    "required": ["timestamp", "event_type"],
+                },
+                "MirrorMimeType": {
+                    "type": "object",
+                    "properties": { "mime_type": { "type": "string" } },
+                    "required": ["mime_type"],
+                },
+            },
+        },
+    })
+}
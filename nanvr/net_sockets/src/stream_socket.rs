@@ -155,13 +155,18 @@ impl<H: Serialize> StreamSender<H> {
 pub struct ReceiverData<H> {
     buffer: Option<Vec<u8>>,
     used_buffer_queue: mpsc::Sender<Vec<u8>>,
-    had_packet_loss: bool,
+    lost_packets_count: u32,
     _phantom: PhantomData<H>,
 }
 
 impl<H> ReceiverData<H> {
     pub fn had_packet_loss(&self) -> bool {
-        self.had_packet_loss
+        self.lost_packets_count > 0
+    }
+
+    // Number of packets lost between the previously received packet and this one
+    pub fn lost_packets_count(&self) -> u32 {
+        self.lost_packets_count
     }
 }
 
@@ -219,7 +224,7 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
             .recv_timeout(timeout)
             .handle_try_again()?;
 
-        let mut had_packet_loss = false;
+        let mut lost_packets_count = 0;
 
         if let Some(last_idx) = self.last_packet_index {
             // Use wrapping arithmetics
@@ -227,7 +232,7 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
                 Ordering::Equal => (),
                 Ordering::Greater => {
                     // Skipped some indices
-                    had_packet_loss = true
+                    lost_packets_count = packet.index.wrapping_sub(last_idx).wrapping_sub(1);
                 }
                 Ordering::Less => {
                     // Old packet, discard
@@ -241,7 +246,7 @@ impl<H: DeserializeOwned + Serialize> StreamReceiver<H> {
         Ok(ReceiverData {
             buffer: Some(packet.buffer),
             used_buffer_queue: self.used_buffer_queue.clone(),
-            had_packet_loss,
+            lost_packets_count,
             _phantom: PhantomData,
         })
     }
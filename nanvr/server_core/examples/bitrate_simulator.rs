@@ -0,0 +1,45 @@
+// Replays a network trace against the bitrate adaptation and writes the simulated frames.
+// Usage: bitrate_simulator <trace.json> <output.csv|output.json> [session.json]
+// Run it with `cargo xtask simulate-bitrate`.
+
+use configuration::SessionConfig;
+use server_core::bitrate_simulator::{self, NetworkTrace, SimulationConfig};
+use shared::anyhow::{Context, Result, bail};
+use std::{env, fs, path::PathBuf};
+
+fn main() -> Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let [trace_path, output_path, session_path @ ..] = &args[..] else {
+        bail!("Usage: bitrate_simulator <trace.json> <output.csv|output.json> [session.json]");
+    };
+
+    let trace = NetworkTrace::from_json(
+        &fs::read_to_string(trace_path).with_context(|| format!("Failed to read {trace_path}"))?,
+    )?;
+
+    // The bitrate settings of the session, or the defaults
+    let mut session = SessionConfig::default();
+    if let Some(session_path) = session_path.first() {
+        let session_json = serde_json::from_str(&fs::read_to_string(session_path)?)?;
+        session.merge_from_json(&session_json)?;
+    }
+    let bitrate_config = session.to_settings().video.bitrate;
+
+    let report = bitrate_simulator::simulate(&bitrate_config, &trace, &SimulationConfig::default());
+
+    let output_path = PathBuf::from(output_path);
+    let output = match output_path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => report.to_csv(),
+        Some("json") => report.to_json()?,
+        _ => bail!("The output file must be .csv or .json"),
+    };
+    fs::write(&output_path, output)?;
+
+    println!(
+        "Simulated {:.0}s, wrote {}",
+        trace.duration_s(),
+        output_path.display()
+    );
+
+    Ok(())
+}
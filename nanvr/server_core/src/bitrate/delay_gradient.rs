@@ -0,0 +1,456 @@
+// Delay-based and loss-based congestion controller, modeled after Google Congestion Control
+// (draft-ietf-rmcat-gcc-02). The delay-based part detects queue buildup from the trend of the
+// network latency and drives an increase/hold/decrease state machine, the loss-based part backs
+// off when the loss ratio is high. The final bitrate is the minimum of the two.
+
+use super::BitrateController;
+use configuration::BitrateMode;
+use events::BitrateDirectives;
+use std::{collections::VecDeque, mem, time::Duration};
+
+const TRENDLINE_WINDOW_SIZE: usize = 20;
+const TRENDLINE_SMOOTHING_COEFF: f32 = 0.9;
+const TRENDLINE_THRESHOLD_GAIN: f32 = 4.0;
+const MAX_TRENDLINE_SAMPLES: usize = 60;
+
+const OVERUSE_TIME_THRESHOLD_MS: f32 = 10.0;
+const THRESHOLD_GAIN_UP: f32 = 0.01;
+const THRESHOLD_GAIN_DOWN: f32 = 0.00018;
+const MAX_THRESHOLD_ADAPT_OFFSET_MS: f32 = 15.0;
+const MIN_THRESHOLD_MS: f32 = 6.0;
+const MAX_THRESHOLD_MS: f32 = 600.0;
+
+const RECEIVED_THROUGHPUT_WINDOW: Duration = Duration::from_millis(500);
+const MAX_INCREASE_OVER_RECEIVED_THROUGHPUT: f32 = 1.5;
+
+const LOSS_INCREASE_THRESHOLD: f32 = 0.02;
+const LOSS_DECREASE_THRESHOLD: f32 = 0.1;
+const LOSS_INCREASE_MULTIPLIER: f32 = 1.05;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BandwidthUsage {
+    Normal,
+    Underusing,
+    Overusing,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RateControlState {
+    Hold,
+    Increase,
+    Decrease,
+}
+
+pub struct DelayGradientController {
+    max_history_size: usize,
+    packet_bytes_history: VecDeque<(Duration, usize)>,
+    // (frame timestamp, size) of the frames that reached the client
+    received_history: VecDeque<(Duration, usize)>,
+
+    // Trendline estimator
+    first_arrival_ms: Option<f32>,
+    smoothed_latency_ms: Option<f32>,
+    // (arrival time, smoothed latency)
+    latency_history: VecDeque<(f32, f32)>,
+    trendline_sample_count: usize,
+
+    // Overuse detector
+    threshold_ms: f32,
+    previous_trend: f32,
+    last_detection_arrival_ms: Option<f32>,
+    overuse_time_ms: f32,
+    overuse_count: usize,
+    usage: BandwidthUsage,
+    // Overuse is latched until the next bitrate update, otherwise it might be missed
+    overuse_detected: bool,
+
+    // Rate controller
+    state: RateControlState,
+    delay_based_bps: f32,
+    loss_based_bps: f32,
+    received_packets: u32,
+    lost_packets: u32,
+    latest_timestamp: Duration,
+    last_update_timestamp: Option<Duration>,
+    update_requested: bool,
+}
+
+impl DelayGradientController {
+    pub fn new(max_history_size: usize, initial_bitrate_bps: f32, threshold_ms: f32) -> Self {
+        Self {
+            max_history_size,
+            packet_bytes_history: VecDeque::new(),
+            received_history: VecDeque::new(),
+            first_arrival_ms: None,
+            smoothed_latency_ms: None,
+            latency_history: VecDeque::new(),
+            trendline_sample_count: 0,
+            threshold_ms,
+            previous_trend: 0.0,
+            last_detection_arrival_ms: None,
+            overuse_time_ms: 0.0,
+            overuse_count: 0,
+            usage: BandwidthUsage::Normal,
+            overuse_detected: false,
+            state: RateControlState::Increase,
+            delay_based_bps: initial_bitrate_bps,
+            loss_based_bps: initial_bitrate_bps,
+            received_packets: 0,
+            lost_packets: 0,
+            latest_timestamp: Duration::ZERO,
+            last_update_timestamp: None,
+            update_requested: false,
+        }
+    }
+
+    // Returns the slope of the smoothed latency over arrival time, multiplied by a gain to
+    // increase the sensitivity
+    fn update_trendline(&mut self, arrival_ms: f32, latency_ms: f32) -> Option<f32> {
+        let first_arrival_ms = *self.first_arrival_ms.get_or_insert(arrival_ms);
+
+        let smoothed_latency_ms = match self.smoothed_latency_ms {
+            Some(smoothed) => {
+                TRENDLINE_SMOOTHING_COEFF * smoothed
+                    + (1.0 - TRENDLINE_SMOOTHING_COEFF) * latency_ms
+            }
+            None => latency_ms,
+        };
+        self.smoothed_latency_ms = Some(smoothed_latency_ms);
+
+        self.latency_history
+            .push_back((arrival_ms - first_arrival_ms, smoothed_latency_ms));
+        if self.latency_history.len() > TRENDLINE_WINDOW_SIZE {
+            self.latency_history.pop_front();
+        }
+        self.trendline_sample_count += 1;
+
+        if self.latency_history.len() < TRENDLINE_WINDOW_SIZE {
+            return None;
+        }
+
+        // Linear regression
+        let count = self.latency_history.len() as f32;
+        let mean_x = self.latency_history.iter().map(|(x, _)| x).sum::<f32>() / count;
+        let mean_y = self.latency_history.iter().map(|(_, y)| y).sum::<f32>() / count;
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for (x, y) in &self.latency_history {
+            numerator += (x - mean_x) * (y - mean_y);
+            denominator += (x - mean_x) * (x - mean_x);
+        }
+        if denominator == 0.0 {
+            return None;
+        }
+
+        Some(
+            numerator / denominator
+                * usize::min(self.trendline_sample_count, MAX_TRENDLINE_SAMPLES) as f32
+                * TRENDLINE_THRESHOLD_GAIN,
+        )
+    }
+
+    fn detect_usage(&mut self, arrival_ms: f32, trend: f32) {
+        let elapsed_ms = self
+            .last_detection_arrival_ms
+            .map_or(0.0, |last| arrival_ms - last);
+        self.last_detection_arrival_ms = Some(arrival_ms);
+
+        if trend > self.threshold_ms {
+            if self.overuse_count == 0 {
+                self.overuse_time_ms = elapsed_ms / 2.0;
+            } else {
+                self.overuse_time_ms += elapsed_ms;
+            }
+            self.overuse_count += 1;
+
+            if self.overuse_time_ms > OVERUSE_TIME_THRESHOLD_MS
+                && self.overuse_count > 1
+                && trend >= self.previous_trend
+            {
+                self.overuse_time_ms = 0.0;
+                self.overuse_count = 0;
+                self.usage = BandwidthUsage::Overusing;
+            }
+        } else if trend < -self.threshold_ms {
+            self.overuse_time_ms = 0.0;
+            self.overuse_count = 0;
+            self.usage = BandwidthUsage::Underusing;
+        } else {
+            self.overuse_time_ms = 0.0;
+            self.overuse_count = 0;
+            self.usage = BandwidthUsage::Normal;
+        }
+        self.previous_trend = trend;
+
+        if self.usage == BandwidthUsage::Overusing && !self.overuse_detected {
+            self.overuse_detected = true;
+            self.update_requested = true;
+        }
+
+        // Adaptive threshold. Avoid adapting to sudden spikes
+        if trend.abs() <= self.threshold_ms + MAX_THRESHOLD_ADAPT_OFFSET_MS {
+            let gain = if trend.abs() < self.threshold_ms {
+                THRESHOLD_GAIN_DOWN
+            } else {
+                THRESHOLD_GAIN_UP
+            };
+            self.threshold_ms += gain * (trend.abs() - self.threshold_ms) * elapsed_ms.min(100.0);
+            self.threshold_ms = self.threshold_ms.clamp(MIN_THRESHOLD_MS, MAX_THRESHOLD_MS);
+        }
+    }
+
+    fn received_throughput_bps(&self) -> Option<f32> {
+        let &(first_timestamp, _) = self.received_history.front()?;
+        let &(last_timestamp, _) = self.received_history.back()?;
+        let elapsed_s = (last_timestamp - first_timestamp).as_secs_f32();
+
+        if elapsed_s > 0.0 {
+            // Exclude the first frame, it was sent before the start of the interval
+            let bytes = self
+                .received_history
+                .iter()
+                .skip(1)
+                .map(|(_, size)| *size)
+                .sum::<usize>();
+
+            Some(bytes as f32 * 8.0 / elapsed_s)
+        } else {
+            None
+        }
+    }
+}
+
+impl BitrateController for DelayGradientController {
+    fn report_frame_encoded(&mut self, timestamp: Duration, _: Duration, size_bytes: usize) {
+        self.packet_bytes_history.push_back((timestamp, size_bytes));
+        if self.packet_bytes_history.len() > self.max_history_size {
+            self.packet_bytes_history.pop_front();
+        }
+    }
+
+    fn report_frame_latencies(
+        &mut self,
+        _: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        _: Duration,
+    ) {
+        while let Some((history_timestamp, size_bytes)) = self.packet_bytes_history.pop_front() {
+            if history_timestamp == timestamp {
+                self.received_history.push_back((timestamp, size_bytes));
+                break;
+            }
+        }
+        while let Some(&(history_timestamp, _)) = self.received_history.front() {
+            if history_timestamp + RECEIVED_THROUGHPUT_WINDOW < timestamp {
+                self.received_history.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.latest_timestamp = Duration::max(self.latest_timestamp, timestamp);
+
+        let latency_ms = network_latency.as_secs_f32() * 1000.0;
+        let arrival_ms = timestamp.as_secs_f32() * 1000.0 + latency_ms;
+        if let Some(trend) = self.update_trendline(arrival_ms, latency_ms) {
+            self.detect_usage(arrival_ms, trend);
+        }
+    }
+
+    fn report_packet_loss(&mut self, _: &BitrateMode, received_packets: u32, lost_packets: u32) {
+        self.received_packets += received_packets;
+        self.lost_packets += lost_packets;
+    }
+
+    fn take_update_request(&mut self) -> bool {
+        mem::take(&mut self.update_requested)
+    }
+
+    fn get_bitrate_bps(
+        &mut self,
+        config: &BitrateMode,
+        _: Duration,
+        directives: &mut BitrateDirectives,
+    ) -> Option<f32> {
+        let BitrateMode::DelayGradient {
+            max_throughput_mbps,
+            min_throughput_mbps,
+            increase_rate,
+            decrease_multiplier,
+            ..
+        } = config
+        else {
+            return None;
+        };
+
+        let elapsed_s = self.last_update_timestamp.map_or(0.0, |last| {
+            self.latest_timestamp.saturating_sub(last).as_secs_f32()
+        });
+        self.last_update_timestamp = Some(self.latest_timestamp);
+
+        let received_bps = self.received_throughput_bps();
+        directives.scaled_calculated_throughput_bps = received_bps;
+
+        let usage = if mem::take(&mut self.overuse_detected) {
+            BandwidthUsage::Overusing
+        } else {
+            self.usage
+        };
+        self.state = match (usage, self.state) {
+            (BandwidthUsage::Overusing, _) => RateControlState::Decrease,
+            (BandwidthUsage::Underusing, _) => RateControlState::Hold,
+            (BandwidthUsage::Normal, RateControlState::Decrease) => RateControlState::Hold,
+            (BandwidthUsage::Normal, _) => RateControlState::Increase,
+        };
+
+        match self.state {
+            RateControlState::Increase => {
+                let mut bitrate_bps = self.delay_based_bps * (1.0 + increase_rate).powf(elapsed_s);
+                if let Some(received_bps) = received_bps {
+                    bitrate_bps = f32::min(
+                        bitrate_bps,
+                        received_bps * MAX_INCREASE_OVER_RECEIVED_THROUGHPUT,
+                    );
+                }
+                // The cap above should never cause a decrease
+                self.delay_based_bps = f32::max(bitrate_bps, self.delay_based_bps);
+            }
+            RateControlState::Decrease => {
+                if let Some(received_bps) = received_bps {
+                    self.delay_based_bps =
+                        f32::min(self.delay_based_bps, received_bps * decrease_multiplier);
+                } else {
+                    self.delay_based_bps *= decrease_multiplier;
+                }
+                self.state = RateControlState::Hold;
+            }
+            RateControlState::Hold => (),
+        }
+
+        // Hold the loss-based bitrate if there were no reports since the last update
+        if self.received_packets + self.lost_packets > 0 {
+            let loss_ratio = super::loss_ratio(
+                mem::take(&mut self.received_packets),
+                mem::take(&mut self.lost_packets),
+            );
+
+            if loss_ratio > LOSS_DECREASE_THRESHOLD {
+                self.loss_based_bps *= 1.0 - 0.5 * loss_ratio;
+            } else if loss_ratio < LOSS_INCREASE_THRESHOLD {
+                self.loss_based_bps *= LOSS_INCREASE_MULTIPLIER;
+            }
+        }
+
+        let max_bps = *max_throughput_mbps as f32 * 1e6;
+        let min_bps = *min_throughput_mbps as f32 * 1e6;
+        directives.manual_max_throughput_bps = Some(max_bps);
+        directives.manual_min_throughput_bps = Some(min_bps);
+
+        // Don't use clamp() as it panics if min > max
+        self.delay_based_bps = f32::max(f32::min(self.delay_based_bps, max_bps), min_bps);
+        self.loss_based_bps = f32::max(f32::min(self.loss_based_bps, max_bps), min_bps);
+
+        Some(f32::min(self.delay_based_bps, self.loss_based_bps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitrate::{
+        simulator::Link,
+        tests::{FRAME_INTERVAL, link_conditions, run_trace},
+    };
+
+    fn delay_gradient_config() -> BitrateMode {
+        BitrateMode::DelayGradient {
+            initial_throughput_mbps: 10,
+            max_throughput_mbps: 500,
+            min_throughput_mbps: 5,
+            overuse_threshold_ms: 12.5,
+            increase_rate: 0.08,
+            decrease_multiplier: 0.85,
+        }
+    }
+
+    #[test]
+    fn test_ramps_up_on_idle_link() {
+        let config = delay_gradient_config();
+        let mut controller = DelayGradientController::new(256, 10e6, 12.5);
+        let mut link = Link::new(FRAME_INTERVAL, None);
+
+        let bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(200.0, 0.0),
+            20.0,
+            10e6,
+        );
+
+        assert!(bitrates.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(*bitrates.last().unwrap() > 15e6);
+    }
+
+    #[test]
+    fn test_backs_off_on_queue_buildup() {
+        let config = delay_gradient_config();
+        let mut controller = DelayGradientController::new(256, 60e6, 12.5);
+        let mut link = Link::new(FRAME_INTERVAL, None);
+
+        let bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(40.0, 0.0),
+            60.0,
+            60e6,
+        );
+
+        // After convergence the bitrate oscillates around the link capacity and the queue
+        // doesn't grow unbounded
+        let settled = &bitrates[bitrates.len() / 2..];
+        let average = settled.iter().sum::<f32>() / settled.len() as f32;
+        assert!(average < 40e6);
+        assert!(average > 20e6);
+        assert!(link.queue_s() < 0.1);
+    }
+
+    #[test]
+    fn test_backs_off_on_packet_loss() {
+        let config = delay_gradient_config();
+        let mut controller = DelayGradientController::new(256, 50e6, 12.5);
+        let mut link = Link::new(FRAME_INTERVAL, None);
+
+        let bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(200.0, 20.0),
+            20.0,
+            50e6,
+        );
+
+        assert!(*bitrates.last().unwrap() < 20e6);
+    }
+
+    #[test]
+    fn test_requests_update_on_overuse() {
+        let config = delay_gradient_config();
+        let mut controller = DelayGradientController::new(256, 10e6, 12.5);
+
+        for i in 0..100 {
+            let timestamp = FRAME_INTERVAL * i;
+            controller.report_frame_encoded(timestamp, Duration::ZERO, 10000);
+            controller.report_frame_latencies(
+                &config,
+                timestamp,
+                Duration::from_millis(3) + Duration::from_millis(2) * i,
+                Duration::ZERO,
+            );
+        }
+
+        assert!(controller.take_update_request());
+    }
+}
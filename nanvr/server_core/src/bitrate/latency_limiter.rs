@@ -0,0 +1,333 @@
+use super::BitrateController;
+use configuration::{BitrateMode, settings_schema::Switch};
+use events::BitrateDirectives;
+use shared::SlidingWindowAverage;
+use std::{collections::VecDeque, mem, time::Duration};
+
+// Estimates the network throughput from the frame sizes and network latencies, then limits it
+// using thresholds on the network, encoder and decoder latencies and on the packet loss
+pub struct LatencyLimiterController {
+    // note: why packet_sizes_bits_history is a queue and not a sliding average? Because some
+    // network samples will be dropped but not any packet size sample
+    packet_bytes_history: VecDeque<(Duration, usize)>,
+    packet_bytes_average: SlidingWindowAverage<f32>,
+    network_latency_average: SlidingWindowAverage<Duration>,
+    encoder_latency_average: SlidingWindowAverage<Duration>,
+    decoder_latency_overstep_count: usize,
+    dynamic_decoder_max_bytes_per_frame: f32,
+    received_packets: u32,
+    lost_packets: u32,
+    dynamic_packet_loss_max_bps: f32,
+    update_requested: bool,
+}
+
+impl LatencyLimiterController {
+    pub fn new(max_history_size: usize) -> Self {
+        Self {
+            packet_bytes_history: VecDeque::new(),
+            packet_bytes_average: SlidingWindowAverage::new(50000.0, max_history_size),
+            network_latency_average: SlidingWindowAverage::new(
+                Duration::from_millis(5),
+                max_history_size,
+            ),
+            encoder_latency_average: SlidingWindowAverage::new(
+                Duration::from_millis(5),
+                max_history_size,
+            ),
+            decoder_latency_overstep_count: 0,
+            dynamic_decoder_max_bytes_per_frame: f32::MAX,
+            received_packets: 0,
+            lost_packets: 0,
+            dynamic_packet_loss_max_bps: f32::MAX,
+            update_requested: false,
+        }
+    }
+}
+
+impl BitrateController for LatencyLimiterController {
+    fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    ) {
+        self.encoder_latency_average.submit_sample(encoder_latency);
+
+        self.packet_bytes_history.push_back((timestamp, size_bytes));
+    }
+
+    fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    ) {
+        while let Some(&(history_timestamp, size_bytes)) = self.packet_bytes_history.front() {
+            if history_timestamp == timestamp {
+                self.packet_bytes_average.submit_sample(size_bytes as f32);
+                self.network_latency_average.submit_sample(network_latency);
+
+                self.packet_bytes_history.pop_front();
+
+                break;
+            } else {
+                self.packet_bytes_history.pop_front();
+            }
+        }
+
+        if let BitrateMode::Adaptive {
+            decoder_latency_limiter: Switch::Enabled(config),
+            ..
+        } = &config
+        {
+            if decoder_latency > Duration::from_millis(config.max_decoder_latency_ms) {
+                self.decoder_latency_overstep_count += 1;
+
+                if self.decoder_latency_overstep_count == config.latency_overstep_frames {
+                    self.dynamic_decoder_max_bytes_per_frame = f32::min(
+                        self.packet_bytes_average.get_average(),
+                        self.dynamic_decoder_max_bytes_per_frame,
+                    ) * config
+                        .latency_overstep_multiplier;
+
+                    self.update_requested = true;
+
+                    self.decoder_latency_overstep_count = 0;
+                }
+            } else {
+                self.decoder_latency_overstep_count = 0;
+            }
+        }
+    }
+
+    fn report_packet_loss(&mut self, _: &BitrateMode, received_packets: u32, lost_packets: u32) {
+        self.received_packets += received_packets;
+        self.lost_packets += lost_packets;
+    }
+
+    fn take_update_request(&mut self) -> bool {
+        mem::take(&mut self.update_requested)
+    }
+
+    fn get_bitrate_bps(
+        &mut self,
+        config: &BitrateMode,
+        nominal_frame_interval: Duration,
+        directives: &mut BitrateDirectives,
+    ) -> Option<f32> {
+        let BitrateMode::Adaptive {
+            saturation_multiplier,
+            max_throughput_mbps,
+            min_throughput_mbps,
+            max_network_latency_ms,
+            encoder_latency_limiter,
+            decoder_latency_limiter,
+            packet_loss_limiter,
+        } = config
+        else {
+            return None;
+        };
+
+        let packet_bytes_average = self.packet_bytes_average.get_average();
+        let network_latency_average_s = self.network_latency_average.get_average().as_secs_f32();
+
+        let mut throughput_bps =
+            packet_bytes_average * 8.0 * saturation_multiplier / network_latency_average_s;
+        directives.scaled_calculated_throughput_bps = Some(throughput_bps);
+
+        if decoder_latency_limiter.enabled() {
+            throughput_bps = f32::min(throughput_bps, self.dynamic_decoder_max_bytes_per_frame);
+            directives.decoder_latency_limiter_bps = Some(self.dynamic_decoder_max_bytes_per_frame);
+        }
+
+        if let Switch::Enabled(max_ms) = max_network_latency_ms {
+            let max_bps = throughput_bps * (*max_ms as f32 / 1000.0) / network_latency_average_s;
+            throughput_bps = f32::min(throughput_bps, max_bps);
+
+            directives.network_latency_limiter_bps = Some(max_bps);
+        }
+
+        if let Switch::Enabled(config) = encoder_latency_limiter {
+            // Note: this assumes linear relationship between bitrate and encoder latency
+            // but this may not be the case
+            let saturation = self.encoder_latency_average.get_average().as_secs_f32()
+                / nominal_frame_interval.as_secs_f32();
+            let max_bps = throughput_bps * config.max_saturation_multiplier / saturation;
+            directives.encoder_latency_limiter_bps = Some(max_bps);
+
+            if saturation > config.max_saturation_multiplier {
+                throughput_bps = f32::min(throughput_bps, max_bps);
+            }
+        }
+
+        let loss_ratio = super::loss_ratio(
+            mem::take(&mut self.received_packets),
+            mem::take(&mut self.lost_packets),
+        );
+        if let Switch::Enabled(config) = packet_loss_limiter {
+            // Unlike the latency limiters, the loss limit is relaxed over time, since losses on
+            // wireless networks are often transient
+            if loss_ratio * 100.0 > config.max_packet_loss_percent {
+                self.dynamic_packet_loss_max_bps =
+                    f32::min(throughput_bps, self.dynamic_packet_loss_max_bps)
+                        * config.backoff_multiplier;
+            } else if self.dynamic_packet_loss_max_bps < throughput_bps {
+                self.dynamic_packet_loss_max_bps *= config.recovery_multiplier;
+            } else {
+                self.dynamic_packet_loss_max_bps = f32::MAX;
+            }
+
+            if self.dynamic_packet_loss_max_bps < throughput_bps {
+                throughput_bps = self.dynamic_packet_loss_max_bps;
+                directives.packet_loss_limiter_bps = Some(self.dynamic_packet_loss_max_bps);
+            }
+        } else {
+            self.dynamic_packet_loss_max_bps = f32::MAX;
+        }
+
+        if let Switch::Enabled(max) = max_throughput_mbps {
+            let max_bps = *max as f32 * 1e6;
+            throughput_bps = f32::min(throughput_bps, max_bps);
+
+            directives.manual_max_throughput_bps = Some(max_bps);
+        }
+        if let Switch::Enabled(min) = min_throughput_mbps {
+            let min_bps = *min as f32 * 1e6;
+            throughput_bps = f32::max(throughput_bps, min_bps);
+
+            directives.manual_min_throughput_bps = Some(min_bps);
+        }
+
+        // NB: Here we assign the calculated throughput to the requested bitrate. This is
+        // crucial for the working of the adaptive bitrate algorithm. The goal is to
+        // optimally occupy the available bandwidth, which is when the bitrate corresponds
+        // to the throughput.
+        Some(throughput_bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitrate::{
+        simulator::Link,
+        tests::{FRAME_INTERVAL, link_conditions, run_trace},
+    };
+    use configuration::{DecoderLatencyLimiter, EncoderLatencyLimiter, PacketLossLimiter};
+
+    fn adaptive_config(
+        decoder_latency_limiter: Switch<DecoderLatencyLimiter>,
+        packet_loss_limiter: Switch<PacketLossLimiter>,
+    ) -> BitrateMode {
+        BitrateMode::Adaptive {
+            saturation_multiplier: 0.95,
+            max_throughput_mbps: Switch::Disabled,
+            min_throughput_mbps: Switch::Disabled,
+            max_network_latency_ms: Switch::Disabled,
+            encoder_latency_limiter: Switch::Enabled(EncoderLatencyLimiter {
+                max_saturation_multiplier: 0.9,
+            }),
+            decoder_latency_limiter,
+            packet_loss_limiter,
+        }
+    }
+
+    #[test]
+    fn test_converges_below_link_capacity() {
+        let config = adaptive_config(Switch::Disabled, Switch::Disabled);
+        let mut controller = LatencyLimiterController::new(256);
+        let mut link = Link::new(FRAME_INTERVAL, None);
+
+        let bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(100.0, 0.0),
+            30.0,
+            30e6,
+        );
+        let final_bitrate = *bitrates.last().unwrap();
+
+        assert!(final_bitrate < 100e6);
+        assert!(final_bitrate > 50e6);
+        assert!(link.queue_s() < FRAME_INTERVAL.as_secs_f32());
+    }
+
+    #[test]
+    fn test_decoder_latency_limiter() {
+        let config = adaptive_config(
+            Switch::Enabled(DecoderLatencyLimiter {
+                max_decoder_latency_ms: 30,
+                latency_overstep_frames: 10,
+                latency_overstep_multiplier: 0.9,
+            }),
+            Switch::Disabled,
+        );
+        let mut controller = LatencyLimiterController::new(256);
+
+        for i in 0..10 {
+            let timestamp = FRAME_INTERVAL * i;
+            controller.report_frame_encoded(timestamp, Duration::from_millis(2), 10000);
+            controller.report_frame_latencies(
+                &config,
+                timestamp,
+                Duration::from_millis(5),
+                Duration::from_millis(40),
+            );
+        }
+
+        assert!(controller.take_update_request());
+        assert!(!controller.take_update_request());
+
+        let mut directives = BitrateDirectives::default();
+        controller.get_bitrate_bps(&config, FRAME_INTERVAL, &mut directives);
+        assert!(directives.decoder_latency_limiter_bps.unwrap() < f32::MAX);
+    }
+
+    #[test]
+    fn test_packet_loss_limiter() {
+        let config = adaptive_config(
+            Switch::Disabled,
+            Switch::Enabled(PacketLossLimiter {
+                max_packet_loss_percent: 1.0,
+                backoff_multiplier: 0.85,
+                recovery_multiplier: 1.05,
+            }),
+        );
+        let mut controller = LatencyLimiterController::new(256);
+
+        let mut link = Link::new(FRAME_INTERVAL, None);
+        let lossless_bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(100.0, 0.0),
+            10.0,
+            30e6,
+        );
+        let lossless_bitrate = *lossless_bitrates.last().unwrap();
+
+        // Loss without any latency increase
+        let lossy_bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(100.0, 5.0),
+            10.0,
+            lossless_bitrate,
+        );
+        let lossy_bitrate = *lossy_bitrates.last().unwrap();
+        assert!(lossy_bitrate < lossless_bitrate * 0.5);
+
+        let recovered_bitrates = run_trace(
+            &mut controller,
+            &config,
+            &mut link,
+            link_conditions(100.0, 0.0),
+            20.0,
+            lossy_bitrate,
+        );
+        assert!(*recovered_bitrates.last().unwrap() > lossy_bitrate * 1.5);
+    }
+}
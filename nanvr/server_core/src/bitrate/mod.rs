@@ -0,0 +1,430 @@
+mod delay_gradient;
+mod latency_limiter;
+pub mod simulator;
+
+use configuration::{
+    BitrateAdaptiveFramerateConfig, BitrateConfig, BitrateMode, DynamicResolutionConfig,
+    settings_schema::Switch,
+};
+use delay_gradient::DelayGradientController;
+use events::BitrateDirectives;
+use latency_limiter::LatencyLimiterController;
+use shared::SlidingWindowAverage;
+use std::time::{Duration, Instant};
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct DynamicEncoderParams {
+    pub bitrate_bps: f32,
+    pub framerate: f32,
+}
+
+// Estimates the bitrate to request to the encoder. All timestamps are frame target timestamps.
+pub trait BitrateController: Send {
+    fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    );
+
+    fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    );
+
+    // Video packets received and lost by the client since the previous report
+    fn report_packet_loss(
+        &mut self,
+        config: &BitrateMode,
+        received_packets: u32,
+        lost_packets: u32,
+    );
+
+    // Returns true if the bitrate should be recalculated without waiting for the next periodic
+    // update. The request is reset after this call.
+    fn take_update_request(&mut self) -> bool;
+
+    // Returns None if the bitrate mode is not handled by this controller
+    fn get_bitrate_bps(
+        &mut self,
+        config: &BitrateMode,
+        nominal_frame_interval: Duration,
+        directives: &mut BitrateDirectives,
+    ) -> Option<f32>;
+}
+
+fn loss_ratio(received_packets: u32, lost_packets: u32) -> f32 {
+    let total_packets = received_packets + lost_packets;
+    if total_packets > 0 {
+        lost_packets as f32 / total_packets as f32
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ControllerType {
+    LatencyLimiter,
+    DelayGradient,
+}
+
+pub struct BitrateManager {
+    max_history_size: usize,
+    nominal_frame_interval: Duration,
+    frame_interval_average: SlidingWindowAverage<Duration>,
+    controller: Box<dyn BitrateController>,
+    controller_type: ControllerType,
+    last_frame_instant: Instant,
+    last_update_instant: Instant,
+    previous_config: Option<BitrateConfig>,
+    update_needed: bool,
+    resolution_scale: f32,
+    low_bitrate_since: Option<Instant>,
+    high_bitrate_since: Option<Instant>,
+}
+
+impl BitrateManager {
+    pub fn new(max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            max_history_size,
+            nominal_frame_interval: Duration::from_secs_f32(1. / initial_framerate),
+            frame_interval_average: SlidingWindowAverage::new(
+                Duration::from_millis(16),
+                max_history_size,
+            ),
+            controller: Box::new(LatencyLimiterController::new(max_history_size)),
+            controller_type: ControllerType::LatencyLimiter,
+            last_frame_instant: Instant::now(),
+            last_update_instant: Instant::now(),
+            previous_config: None,
+            update_needed: true,
+            resolution_scale: 1.0,
+            low_bitrate_since: None,
+            high_bitrate_since: None,
+        }
+    }
+
+    // Replace the controller if the bitrate mode requires a different one. The constant mode
+    // keeps the current controller, so that it stays warm if the mode is switched back.
+    fn update_controller(&mut self, config: &BitrateMode) {
+        let controller_type = match config {
+            BitrateMode::ConstantMbps(_) => return,
+            BitrateMode::Adaptive { .. } => ControllerType::LatencyLimiter,
+            BitrateMode::DelayGradient { .. } => ControllerType::DelayGradient,
+        };
+
+        if controller_type != self.controller_type {
+            self.controller = match config {
+                BitrateMode::DelayGradient {
+                    initial_throughput_mbps,
+                    overuse_threshold_ms,
+                    ..
+                } => Box::new(DelayGradientController::new(
+                    self.max_history_size,
+                    *initial_throughput_mbps as f32 * 1e6,
+                    *overuse_threshold_ms,
+                )),
+                _ => Box::new(LatencyLimiterController::new(self.max_history_size)),
+            };
+            self.controller_type = controller_type;
+        }
+    }
+
+    // Fraction of the negotiated stream resolution the encoder should use
+    pub fn resolution_scale(&self) -> f32 {
+        self.resolution_scale
+    }
+
+    // Step the resolution scale when the bitrate stays low or recovers. Different thresholds and
+    // delays are used in the two directions to avoid oscillating between resolutions.
+    fn update_resolution_scale(
+        &mut self,
+        config: &Switch<DynamicResolutionConfig>,
+        bitrate_bps: f32,
+        now: Instant,
+    ) {
+        let Switch::Enabled(config) = config else {
+            self.resolution_scale = 1.0;
+            self.low_bitrate_since = None;
+            self.high_bitrate_since = None;
+
+            return;
+        };
+
+        let low_bitrate_bps = config.low_bitrate_threshold_mbps * 1e6;
+        if bitrate_bps <= low_bitrate_bps {
+            self.high_bitrate_since = None;
+            let since = *self.low_bitrate_since.get_or_insert(now);
+
+            if now >= since + Duration::from_secs_f32(config.downscale_delay_s)
+                && self.resolution_scale > config.min_scale
+            {
+                self.resolution_scale =
+                    f32::max(self.resolution_scale - config.scale_step, config.min_scale);
+                // Wait again before the next step, so that the bitrate can settle
+                self.low_bitrate_since = None;
+            }
+        } else if bitrate_bps >= low_bitrate_bps * config.upscale_bitrate_multiplier {
+            self.low_bitrate_since = None;
+            let since = *self.high_bitrate_since.get_or_insert(now);
+
+            if now >= since + Duration::from_secs_f32(config.upscale_delay_s)
+                && self.resolution_scale < 1.0
+            {
+                self.resolution_scale = f32::min(self.resolution_scale + config.scale_step, 1.0);
+                self.high_bitrate_since = None;
+            }
+        } else {
+            self.low_bitrate_since = None;
+            self.high_bitrate_since = None;
+        }
+    }
+
+    // Note: This is used to calculate the framerate/frame interval. The frame present is the most
+    // accurate event for this use.
+    pub fn report_frame_present(&mut self, config: &Switch<BitrateAdaptiveFramerateConfig>) {
+        self.report_frame_present_at(config, Instant::now());
+    }
+
+    fn report_frame_present_at(
+        &mut self,
+        config: &Switch<BitrateAdaptiveFramerateConfig>,
+        now: Instant,
+    ) {
+        let interval = now - self.last_frame_instant;
+        self.last_frame_instant = now;
+
+        if let Some(config) = config.as_option() {
+            let interval_ratio =
+                interval.as_secs_f32() / self.frame_interval_average.get_average().as_secs_f32();
+
+            self.frame_interval_average.submit_sample(interval);
+
+            if interval_ratio > config.framerate_reset_threshold_multiplier
+                || interval_ratio < 1.0 / config.framerate_reset_threshold_multiplier
+            {
+                // Clear most of the samples, keep some for stability
+                self.frame_interval_average.retain(5);
+                self.update_needed = true;
+            }
+        }
+    }
+
+    pub fn report_frame_encoded(
+        &mut self,
+        timestamp: Duration,
+        encoder_latency: Duration,
+        size_bytes: usize,
+    ) {
+        self.controller
+            .report_frame_encoded(timestamp, encoder_latency, size_bytes);
+    }
+
+    // decoder_latency is used to learn a suitable maximum bitrate bound to avoid decoder runaway
+    // latency
+    pub fn report_frame_latencies(
+        &mut self,
+        config: &BitrateMode,
+        timestamp: Duration,
+        network_latency: Duration,
+        decoder_latency: Duration,
+    ) {
+        if network_latency.is_zero() {
+            return;
+        }
+
+        self.update_controller(config);
+        self.controller
+            .report_frame_latencies(config, timestamp, network_latency, decoder_latency);
+    }
+
+    pub fn report_packet_loss(
+        &mut self,
+        config: &BitrateMode,
+        received_packets: u32,
+        lost_packets: u32,
+    ) {
+        self.update_controller(config);
+        self.controller
+            .report_packet_loss(config, received_packets, lost_packets);
+    }
+
+    pub fn get_encoder_params(
+        &mut self,
+        config: &BitrateConfig,
+    ) -> Option<(DynamicEncoderParams, BitrateDirectives)> {
+        self.get_encoder_params_at(config, Instant::now())
+    }
+
+    fn get_encoder_params_at(
+        &mut self,
+        config: &BitrateConfig,
+        now: Instant,
+    ) -> Option<(DynamicEncoderParams, BitrateDirectives)> {
+        self.update_controller(&config.mode);
+        if self.controller.take_update_request() {
+            self.update_needed = true;
+        }
+
+        if self.previous_config.as_ref() != Some(config) {
+            self.previous_config = Some(config.clone());
+            // Continue method. Always update bitrate in this case
+        } else if !self.update_needed
+            && (now < self.last_update_instant + UPDATE_INTERVAL
+                || matches!(config.mode, BitrateMode::ConstantMbps(_)))
+        {
+            return None;
+        }
+
+        self.last_update_instant = now;
+        self.update_needed = false;
+
+        let frame_interval = if config.adapt_to_framerate.enabled() {
+            self.frame_interval_average.get_average()
+        } else {
+            self.nominal_frame_interval
+        };
+
+        let mut bitrate_directives = BitrateDirectives::default();
+
+        let bitrate_bps = if let BitrateMode::ConstantMbps(bitrate_mbps) = &config.mode {
+            // The resolution is scaled only in response to a changing bitrate
+            self.update_resolution_scale(&Switch::Disabled, 0.0, now);
+
+            *bitrate_mbps as f32 * 1e6
+        } else {
+            let bitrate_bps = self.controller.get_bitrate_bps(
+                &config.mode,
+                self.nominal_frame_interval,
+                &mut bitrate_directives,
+            )?;
+            self.update_resolution_scale(&config.dynamic_resolution, bitrate_bps, now);
+
+            bitrate_bps
+        };
+
+        bitrate_directives.requested_bitrate_bps = bitrate_bps;
+
+        Some((
+            DynamicEncoderParams {
+                bitrate_bps,
+                framerate: 1.0 / f32::min(frame_interval.as_secs_f32(), 1.0),
+            },
+            bitrate_directives,
+        ))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::simulator::{Link, NetworkConditions};
+    use super::*;
+    use std::mem;
+
+    pub const FRAME_INTERVAL: Duration = Duration::from_nanos(11_111_111);
+    const FRAMES_PER_UPDATE: u32 = 90;
+
+    pub fn link_conditions(capacity_mbps: f32, loss_percent: f32) -> NetworkConditions {
+        NetworkConditions {
+            capacity_mbps,
+            latency_ms: 3.0,
+            loss_percent,
+        }
+    }
+
+    // Streams through the link of the simulator, using the bitrate requested by the controller.
+    // Returns the requested bitrate at each update.
+    pub fn run_trace(
+        controller: &mut dyn BitrateController,
+        config: &BitrateMode,
+        link: &mut Link,
+        conditions: NetworkConditions,
+        duration_s: f32,
+        initial_bitrate_bps: f32,
+    ) -> Vec<f32> {
+        let mut bitrate_bps = initial_bitrate_bps;
+        let mut bitrates = vec![];
+        let mut lost_packets = 0;
+
+        let frame_count = (duration_s / FRAME_INTERVAL.as_secs_f32()) as u32;
+        for i in 0..frame_count {
+            let timestamp = FRAME_INTERVAL * i;
+            let size_bytes = (bitrate_bps / 8.0 * FRAME_INTERVAL.as_secs_f32()) as usize;
+
+            controller.report_frame_encoded(timestamp, Duration::from_millis(2), size_bytes);
+            if let Some(network_latency) = link.send(&conditions, size_bytes) {
+                controller.report_frame_latencies(
+                    config,
+                    timestamp,
+                    network_latency,
+                    Duration::from_millis(5),
+                );
+                controller.report_packet_loss(config, 1, mem::take(&mut lost_packets));
+            } else {
+                lost_packets += 1;
+            }
+
+            if (i + 1) % FRAMES_PER_UPDATE == 0 || controller.take_update_request() {
+                let mut directives = BitrateDirectives::default();
+                if let Some(bps) =
+                    controller.get_bitrate_bps(config, FRAME_INTERVAL, &mut directives)
+                {
+                    bitrate_bps = bps;
+                    bitrates.push(bps);
+                }
+            }
+        }
+
+        bitrates
+    }
+
+    #[test]
+    fn test_resolution_scale_hysteresis() {
+        let config = Switch::Enabled(DynamicResolutionConfig {
+            min_scale: 0.7,
+            scale_step: 0.15,
+            low_bitrate_threshold_mbps: 15.0,
+            upscale_bitrate_multiplier: 1.5,
+            downscale_delay_s: 3.0,
+            upscale_delay_s: 10.0,
+        });
+        let mut manager = BitrateManager::new(256, 90.0);
+        let start = Instant::now();
+        let mut time_s = 0;
+
+        // Feed one bitrate per second, as the periodic update does
+        let mut run = |manager: &mut BitrateManager, bitrate_mbps: f32, duration_s: u64| {
+            for _ in 0..duration_s {
+                let now = start + Duration::from_secs(time_s);
+                manager.update_resolution_scale(&config, bitrate_mbps * 1e6, now);
+                time_s += 1;
+            }
+            manager.resolution_scale()
+        };
+
+        // Short dips don't change the resolution
+        for _ in 0..10 {
+            assert_eq!(run(&mut manager, 10.0, 2), 1.0);
+            assert_eq!(run(&mut manager, 30.0, 1), 1.0);
+        }
+
+        assert!((run(&mut manager, 10.0, 4) - 0.85).abs() < 1e-3);
+        assert!((run(&mut manager, 10.0, 4) - 0.7).abs() < 1e-3);
+        assert!((run(&mut manager, 10.0, 10) - 0.7).abs() < 1e-3);
+
+        // A bitrate between the two thresholds keeps the current resolution
+        assert!((run(&mut manager, 20.0, 30) - 0.7).abs() < 1e-3);
+
+        assert!((run(&mut manager, 30.0, 9) - 0.7).abs() < 1e-3);
+        assert!((run(&mut manager, 30.0, 2) - 0.85).abs() < 1e-3);
+        assert_eq!(run(&mut manager, 30.0, 11), 1.0);
+
+        manager.update_resolution_scale(&config, 10e6, start + Duration::from_secs(time_s));
+        manager.update_resolution_scale(&Switch::Disabled, 10e6, start + Duration::from_secs(60));
+        assert_eq!(manager.resolution_scale(), 1.0);
+    }
+}
@@ -0,0 +1,490 @@
+// Offline simulator for the bitrate adaptation. A network trace is replayed against
+// BitrateManager using simulated time, so that the algorithm can be tuned and regression-tested
+// without a headset.
+
+use super::BitrateManager;
+use configuration::BitrateConfig;
+use serde::{Deserialize, Serialize};
+use shared::anyhow::Result;
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    mem,
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct NetworkConditions {
+    pub capacity_mbps: f32,
+    pub latency_ms: f32,
+    pub loss_percent: f32,
+}
+
+impl NetworkConditions {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            capacity_mbps: self.capacity_mbps + (other.capacity_mbps - self.capacity_mbps) * t,
+            latency_ms: self.latency_ms + (other.latency_ms - self.latency_ms) * t,
+            loss_percent: self.loss_percent + (other.loss_percent - self.loss_percent) * t,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TraceSegment {
+    pub duration_s: f32,
+    pub start: NetworkConditions,
+    // If set, the conditions are linearly interpolated from start to end
+    pub end: Option<NetworkConditions>,
+}
+
+// A recorded trace can be converted to a list of constant segments
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NetworkTrace {
+    pub segments: Vec<TraceSegment>,
+}
+
+impl NetworkTrace {
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn constant(mut self, duration_s: f32, conditions: NetworkConditions) -> Self {
+        self.segments.push(TraceSegment {
+            duration_s,
+            start: conditions,
+            end: None,
+        });
+
+        self
+    }
+
+    pub fn ramp(
+        mut self,
+        duration_s: f32,
+        start: NetworkConditions,
+        end: NetworkConditions,
+    ) -> Self {
+        self.segments.push(TraceSegment {
+            duration_s,
+            start,
+            end: Some(end),
+        });
+
+        self
+    }
+
+    // A microwave oven turning on near the router: the 2.4GHz band gets heavy interference, the
+    // capacity drops and packets are lost, then everything goes back to normal
+    pub fn microwave_oven(base: NetworkConditions) -> Self {
+        let interference = NetworkConditions {
+            capacity_mbps: base.capacity_mbps * 0.4,
+            latency_ms: base.latency_ms + 2.0,
+            loss_percent: base.loss_percent + 5.0,
+        };
+
+        Self::default()
+            .constant(10.0, base)
+            .constant(10.0, interference)
+            .constant(10.0, base)
+    }
+
+    // Walking behind a wall: the signal degrades gradually, stays weak for a while and then
+    // recovers gradually
+    pub fn walk_behind_wall(base: NetworkConditions) -> Self {
+        let behind_wall = NetworkConditions {
+            capacity_mbps: base.capacity_mbps * 0.25,
+            latency_ms: base.latency_ms + 1.0,
+            loss_percent: base.loss_percent + 1.0,
+        };
+
+        Self::default()
+            .constant(10.0, base)
+            .ramp(5.0, base, behind_wall)
+            .constant(10.0, behind_wall)
+            .ramp(5.0, behind_wall, base)
+            .constant(10.0, base)
+    }
+
+    pub fn duration_s(&self) -> f32 {
+        self.segments.iter().map(|s| s.duration_s).sum()
+    }
+
+    pub fn conditions_at(&self, time_s: f32) -> Option<NetworkConditions> {
+        let mut segment_start_s = 0.0;
+        for segment in &self.segments {
+            let offset_s = time_s - segment_start_s;
+            if offset_s < segment.duration_s {
+                return Some(if let Some(end) = &segment.end {
+                    segment.start.lerp(end, offset_s / segment.duration_s)
+                } else {
+                    segment.start
+                });
+            }
+            segment_start_s += segment.duration_s;
+        }
+
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimulationConfig {
+    pub framerate: f32,
+    pub history_size: usize,
+    pub encoder_latency_ms: f32,
+    pub decoder_latency_ms: f32,
+    // Maximum relative deviation of the frame size from bitrate / framerate
+    pub frame_size_jitter: f32,
+    // Frames that would make the router queue grow above this are dropped
+    pub max_queue_ms: f32,
+    pub seed: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            framerate: 90.0,
+            history_size: 256,
+            encoder_latency_ms: 4.0,
+            decoder_latency_ms: 5.0,
+            frame_size_jitter: 0.2,
+            max_queue_ms: 100.0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimulationSample {
+    pub time_s: f32,
+    pub capacity_mbps: f32,
+    pub requested_bitrate_mbps: f32,
+    pub resolution_scale: f32,
+    pub frame_bytes: usize,
+    // None if the frame was dropped
+    pub network_latency_ms: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SimulationReport {
+    pub samples: Vec<SimulationSample>,
+}
+
+impl SimulationReport {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = concat!(
+            "time_s,capacity_mbps,requested_bitrate_mbps,resolution_scale,frame_bytes,",
+            "network_latency_ms,dropped\n"
+        )
+        .to_owned();
+        for sample in &self.samples {
+            writeln!(
+                csv,
+                "{:.4},{:.3},{:.3},{:.2},{},{},{}",
+                sample.time_s,
+                sample.capacity_mbps,
+                sample.requested_bitrate_mbps,
+                sample.resolution_scale,
+                sample.frame_bytes,
+                sample
+                    .network_latency_ms
+                    .map(|latency| format!("{latency:.3}"))
+                    .unwrap_or_default(),
+                sample.network_latency_ms.is_none() as u8,
+            )
+            .ok();
+        }
+
+        csv
+    }
+
+    fn samples_in(&self, start_s: f32, end_s: f32) -> impl Iterator<Item = &SimulationSample> {
+        self.samples
+            .iter()
+            .filter(move |s| s.time_s >= start_s && s.time_s < end_s)
+    }
+
+    pub fn average_bitrate_mbps(&self, start_s: f32, end_s: f32) -> f32 {
+        let (sum, count) = self
+            .samples_in(start_s, end_s)
+            .fold((0.0, 0), |(sum, count), s| {
+                (sum + s.requested_bitrate_mbps, count + 1)
+            });
+
+        sum / usize::max(count, 1) as f32
+    }
+
+    pub fn average_latency_ms(&self, start_s: f32, end_s: f32) -> f32 {
+        let (sum, count) = self
+            .samples_in(start_s, end_s)
+            .filter_map(|s| s.network_latency_ms)
+            .fold((0.0, 0), |(sum, count), latency| (sum + latency, count + 1));
+
+        sum / usize::max(count, 1) as f32
+    }
+
+    pub fn dropped_frames_ratio(&self, start_s: f32, end_s: f32) -> f32 {
+        let (dropped, count) = self
+            .samples_in(start_s, end_s)
+            .fold((0, 0), |(dropped, count), s| {
+                (dropped + s.network_latency_ms.is_none() as usize, count + 1)
+            });
+
+        dropped as f32 / usize::max(count, 1) as f32
+    }
+}
+
+// Single bottleneck link with a FIFO queue. Frames are sent one frame interval apart, each as a
+// single video packet.
+pub struct Link {
+    frame_interval: Duration,
+    // Frames that would make the queue grow above this are dropped
+    max_queue: Option<Duration>,
+    queue_s: f32,
+    loss_accumulator: f32,
+}
+
+impl Link {
+    pub fn new(frame_interval: Duration, max_queue: Option<Duration>) -> Self {
+        Self {
+            frame_interval,
+            max_queue,
+            queue_s: 0.0,
+            loss_accumulator: 0.0,
+        }
+    }
+
+    pub fn queue_s(&self) -> f32 {
+        self.queue_s
+    }
+
+    // Returns the network latency of the frame, or None if it was lost or dropped by the queue.
+    // Losses are spread evenly.
+    pub fn send(&mut self, conditions: &NetworkConditions, size_bytes: usize) -> Option<Duration> {
+        self.queue_s = f32::max(self.queue_s - self.frame_interval.as_secs_f32(), 0.0);
+        let transmission_s = size_bytes as f32 * 8.0 / (conditions.capacity_mbps * 1e6);
+        self.loss_accumulator += conditions.loss_percent / 100.0;
+
+        if self.loss_accumulator >= 1.0 {
+            self.loss_accumulator -= 1.0;
+
+            None
+        } else if self
+            .max_queue
+            .is_some_and(|max_queue| self.queue_s + transmission_s > max_queue.as_secs_f32())
+        {
+            None
+        } else {
+            self.queue_s += transmission_s;
+
+            Some(Duration::from_secs_f32(
+                conditions.latency_ms / 1000.0 + self.queue_s,
+            ))
+        }
+    }
+}
+
+struct Feedback {
+    arrival: Duration,
+    timestamp: Duration,
+    network_latency: Duration,
+    lost_packets: u32,
+}
+
+// xorshift64*, good enough for frame size noise and doesn't require a dependency
+struct Rng(u64);
+
+impl Rng {
+    // Returns a value in the range [-1, 1]
+    fn next_signed(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let value = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+        (value >> 40) as f32 / (1 << 23) as f32 - 1.0
+    }
+}
+
+// The client feedback (network latency and packet loss) is
+// delivered to the manager when it would arrive at the server.
+pub fn simulate(
+    bitrate_config: &BitrateConfig,
+    trace: &NetworkTrace,
+    config: &SimulationConfig,
+) -> SimulationReport {
+    let frame_interval = Duration::from_secs_f32(1.0 / config.framerate);
+    let encoder_latency = Duration::from_secs_f32(config.encoder_latency_ms / 1000.0);
+    let decoder_latency = Duration::from_secs_f32(config.decoder_latency_ms / 1000.0);
+
+    let mut manager = BitrateManager::new(config.history_size, config.framerate);
+    let start_instant = Instant::now();
+
+    let mut rng = Rng(config.seed.wrapping_add(0x9e37_79b9_7f4a_7c15));
+    let mut bitrate_bps = 0.0;
+    let mut link = Link::new(
+        frame_interval,
+        Some(Duration::from_secs_f32(config.max_queue_ms / 1000.0)),
+    );
+    let mut lost_packets = 0;
+    let mut pending_feedback = VecDeque::<Feedback>::new();
+
+    let mut report = SimulationReport::default();
+
+    for frame_index in 0.. {
+        let timestamp = frame_interval * frame_index;
+        let time_s = timestamp.as_secs_f32();
+        let Some(conditions) = trace.conditions_at(time_s) else {
+            break;
+        };
+        let now = start_instant + timestamp;
+
+        while let Some(feedback) = pending_feedback.front() {
+            if feedback.arrival > timestamp {
+                break;
+            }
+            manager.report_frame_latencies(
+                &bitrate_config.mode,
+                feedback.timestamp,
+                feedback.network_latency,
+                decoder_latency,
+            );
+            manager.report_packet_loss(&bitrate_config.mode, 1, feedback.lost_packets);
+            pending_feedback.pop_front();
+        }
+
+        manager.report_frame_present_at(&bitrate_config.adapt_to_framerate, now);
+        if let Some((params, _)) = manager.get_encoder_params_at(bitrate_config, now) {
+            bitrate_bps = params.bitrate_bps;
+        }
+
+        let frame_bytes = (bitrate_bps / 8.0 / config.framerate
+            * (1.0 + config.frame_size_jitter * rng.next_signed()))
+        .max(1.0) as usize;
+        manager.report_frame_encoded(timestamp, encoder_latency, frame_bytes);
+
+        let network_latency = link.send(&conditions, frame_bytes);
+
+        if let Some(network_latency) = network_latency {
+            // The statistics travel back to the server with the base latency
+            let arrival = timestamp
+                + encoder_latency
+                + network_latency
+                + decoder_latency
+                + Duration::from_secs_f32(conditions.latency_ms / 1000.0);
+
+            pending_feedback.push_back(Feedback {
+                arrival,
+                timestamp,
+                network_latency,
+                lost_packets: mem::take(&mut lost_packets),
+            });
+        } else {
+            lost_packets += 1;
+        }
+
+        report.samples.push(SimulationSample {
+            time_s,
+            capacity_mbps: conditions.capacity_mbps,
+            requested_bitrate_mbps: bitrate_bps / 1e6,
+            resolution_scale: manager.resolution_scale(),
+            frame_bytes,
+            network_latency_ms: network_latency.map(|latency| latency.as_secs_f32() * 1000.0),
+        });
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configuration::{BitrateMode, SessionConfig};
+
+    const BASE_CONDITIONS: NetworkConditions = NetworkConditions {
+        capacity_mbps: 200.0,
+        latency_ms: 3.0,
+        loss_percent: 0.0,
+    };
+
+    fn default_bitrate_config() -> BitrateConfig {
+        SessionConfig::default().to_settings().video.bitrate
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = NetworkTrace::walk_behind_wall(BASE_CONDITIONS);
+
+        assert_eq!(trace.duration_s(), 40.0);
+        assert_eq!(trace.conditions_at(5.0), Some(BASE_CONDITIONS));
+        assert_eq!(trace.conditions_at(12.5).unwrap().capacity_mbps, 125.0);
+        assert_eq!(trace.conditions_at(40.0), None);
+
+        let json = serde_json::to_string(&trace).unwrap();
+        assert_eq!(
+            NetworkTrace::from_json(&json).unwrap().conditions_at(27.5),
+            trace.conditions_at(27.5)
+        );
+    }
+
+    #[test]
+    fn test_report_csv() {
+        let trace = NetworkTrace::default().constant(1.0, BASE_CONDITIONS);
+        let report = simulate(
+            &default_bitrate_config(),
+            &trace,
+            &SimulationConfig::default(),
+        );
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), report.samples.len() + 1);
+        assert!(csv.starts_with("time_s,"));
+    }
+
+    #[test]
+    fn test_adaptive_microwave_oven() {
+        let trace = NetworkTrace::microwave_oven(BASE_CONDITIONS);
+        let report = simulate(
+            &default_bitrate_config(),
+            &trace,
+            &SimulationConfig::default(),
+        );
+
+        let before = report.average_bitrate_mbps(5.0, 10.0);
+        let during = report.average_bitrate_mbps(15.0, 20.0);
+
+        assert!(before < BASE_CONDITIONS.capacity_mbps);
+        assert!(during < before * 0.5);
+        // The packet loss limiter is relaxed once the interference stops
+        assert!(report.average_bitrate_mbps(28.0, 30.0) > report.average_bitrate_mbps(20.0, 22.0));
+        assert!(report.average_latency_ms(25.0, 30.0) < BASE_CONDITIONS.latency_ms + 1.0);
+        assert_eq!(report.dropped_frames_ratio(0.0, 10.0), 0.0);
+        assert_eq!(report.dropped_frames_ratio(20.0, 30.0), 0.0);
+    }
+
+    #[test]
+    fn test_delay_gradient_walk_behind_wall() {
+        let mut bitrate_config = default_bitrate_config();
+        bitrate_config.mode = BitrateMode::DelayGradient {
+            initial_throughput_mbps: 30,
+            max_throughput_mbps: 500,
+            min_throughput_mbps: 5,
+            overuse_threshold_ms: 12.5,
+            increase_rate: 0.08,
+            decrease_multiplier: 0.85,
+        };
+
+        let trace = NetworkTrace::walk_behind_wall(BASE_CONDITIONS);
+        let report = simulate(&bitrate_config, &trace, &SimulationConfig::default());
+
+        // Behind the wall the capacity is 50Mbps
+        assert!(report.average_bitrate_mbps(20.0, 25.0) < 50.0);
+        assert!(report.average_latency_ms(20.0, 25.0) < 30.0);
+        assert!(report.dropped_frames_ratio(20.0, 25.0) < 0.05);
+    }
+}
@@ -2,20 +2,22 @@ use crate::{
     ConnectionContext, FILESYSTEM_LAYOUT, SESSION_MANAGER, ServerCoreEvent,
     bitrate::BitrateManager,
     hand_gestures::HandGestureManager,
+    host_monitor::HostMonitor,
     input_mapping::ButtonMappingManager,
     sockets::WelcomeSocket,
     statistics::StatisticsManager,
     tracking::{self, TrackingManager},
 };
 use configuration::{
-    BodyTrackingSinkConfig, CodecType, ControllersEmulationMode, FrameSize, H264Profile,
-    OpenvrConfig, SessionConfig, SocketProtocol,
+    BodyTrackingSinkConfig, CodecType, ControllersConfig, ControllersEmulationMode, FrameSize,
+    H264Profile, OpenvrConfig, SessionConfig, SocketProtocol,
 };
 use events::{AdbEvent, ButtonEvent, EventType};
 use net_packets::{
     AUDIO, ClientConnectionResult, ClientControlPacket, ClientListAction, ClientStatistics,
-    HAPTICS, NegotiatedStreamingConfig, NegotiatedStreamingConfigExt, RealTimeConfig, STATISTICS,
-    ServerControlPacket, StreamConfigPacket, TRACKING, TrackingData, VIDEO, VideoPacketHeader,
+    HAPTICS, NegotiatedStreamingConfig, NegotiatedStreamingConfigExt, RealTimeConfig,
+    RealTimeConfigExt, STATISTICS, ServerControlPacket, StreamConfigPacket, TRACKING, TrackingData,
+    VIDEO, VideoPacketHeader,
 };
 use net_sockets::{
     CONTROL_PORT, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT, PeerType, ProtoControlSocket,
@@ -32,7 +34,7 @@ use shared::{
     warn,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     net::{IpAddr, Ipv4Addr},
     process::Command,
     sync::{Arc, mpsc::RecvTimeoutError},
@@ -45,6 +47,11 @@ const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const HANDSHAKE_ACTION_TIMEOUT: Duration = Duration::from_secs(2);
 pub const STREAMING_RECV_TIMEOUT: Duration = Duration::from_millis(500);
 const REAL_TIME_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+// Encoder settings must stay unchanged for this long before the encoder is recreated, so that
+// dragging a slider does not restart it at every step
+const ENCODER_RECONFIGURE_DELAY: Duration = Duration::from_secs(2);
+// Below this fraction of the render area, a smaller stream resolution resets the render size
+const MIN_REUSED_RENDER_AREA_RATIO: f32 = 0.5;
 
 const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
 
@@ -232,6 +239,77 @@ pub fn contruct_openvr_config(session: &SessionConfig) -> OpenvrConfig {
     }
 }
 
+fn controller_button_mapping_manager_from_config(
+    config: &ControllersConfig,
+    input_ids: &HashSet<u64>,
+    emulation_mode: &ControllersEmulationMode,
+) -> ButtonMappingManager {
+    if let Some(mappings) = &config.button_mappings {
+        ButtonMappingManager::new_manual(mappings)
+    } else {
+        ButtonMappingManager::new_automatic(
+            input_ids,
+            emulation_mode,
+            &config.button_mapping_config,
+        )
+    }
+}
+
+// Applies the encoder settings that can be changed while streaming. Other fields of the
+// negotiated config are left untouched.
+fn with_real_time_encoder_fields(config: &OpenvrConfig, new_config: &OpenvrConfig) -> OpenvrConfig {
+    // Foveated encoding can be toggled only when renegotiating the stream
+    let foveation_config = if new_config.enable_foveated_encoding {
+        new_config
+    } else {
+        config
+    };
+
+    OpenvrConfig {
+        foveation_center_size_x: foveation_config.foveation_center_size_x,
+        foveation_center_size_y: foveation_config.foveation_center_size_y,
+        foveation_center_shift_x: foveation_config.foveation_center_shift_x,
+        foveation_center_shift_y: foveation_config.foveation_center_shift_y,
+        foveation_edge_ratio_x: foveation_config.foveation_edge_ratio_x,
+        foveation_edge_ratio_y: foveation_config.foveation_edge_ratio_y,
+        enable_color_correction: new_config.enable_color_correction,
+        brightness: new_config.brightness,
+        contrast: new_config.contrast,
+        saturation: new_config.saturation,
+        gamma: new_config.gamma,
+        sharpening: new_config.sharpening,
+        ..config.clone()
+    }
+}
+
+// A resolution that fits in the render size already reported to SteamVR is obtained by
+// downscaling in the encoder, so SteamVR doesn't need to be restarted. The render size follows the
+// stream resolution when SteamVR is restarted anyway, or when most of it would be wasted.
+fn with_reused_render_size(old_config: &OpenvrConfig, config: OpenvrConfig) -> OpenvrConfig {
+    let old_area = old_config.eye_resolution_width * old_config.eye_resolution_height;
+    let area = config.eye_resolution_width * config.eye_resolution_height;
+    if config.eye_resolution_width > old_config.eye_resolution_width
+        || config.eye_resolution_height > old_config.eye_resolution_height
+        || (area as f32) < old_area as f32 * MIN_REUSED_RENDER_AREA_RATIO
+    {
+        return config;
+    }
+
+    let reused_config = OpenvrConfig {
+        eye_resolution_width: old_config.eye_resolution_width,
+        eye_resolution_height: old_config.eye_resolution_height,
+        target_eye_resolution_width: old_config.target_eye_resolution_width,
+        target_eye_resolution_height: old_config.target_eye_resolution_height,
+        ..config.clone()
+    };
+
+    if old_config.requires_driver_reload(&reused_config) {
+        config
+    } else {
+        reused_config
+    }
+}
+
 // Alternate connection trials with manual IPs and clients discovered on the local network
 pub fn handshake_loop(ctx: Arc<ConnectionContext>, lifecycle_state: Arc<RwLock<LifecycleState>>) {
     dbg_connection!("handshake_loop: Begin");
@@ -570,7 +648,11 @@ fn connection_pipeline(
 
     dbg_connection!("connection_pipeline: setting up negotiated streaming config");
 
-    let initial_settings = session_manager_lock.settings().clone();
+    // Session with the per-client settings overrides applied
+    let client_session = session_manager_lock
+        .session()
+        .with_client_overrides(&client_hostname);
+    let initial_settings = client_session.to_settings();
 
     fn get_view_res(config: FrameSize, default_res: UVec2) -> UVec2 {
         let res = match config {
@@ -690,9 +772,27 @@ fn connection_pipeline(
 
     let wired = client_ip.is_loopback();
 
+    // The client authenticates the server with the key it received on the first connection
+    let negotiated_config_ext = {
+        let session = session_manager_lock.session();
+        let server_key = net_packets::client_server_key(&session.server_secret, &client_hostname);
+        let streaming_caps_ext = streaming_caps.ext().ok();
+
+        NegotiatedStreamingConfigExt {
+            server_id: Some(session.server_id.clone()),
+            server_proof: streaming_caps_ext
+                .as_ref()
+                .and_then(|ext| ext.server_challenge.as_ref())
+                .map(|challenge| net_packets::server_proof(&server_key, challenge)),
+            server_key: streaming_caps_ext
+                .is_some_and(|ext| ext.request_server_key)
+                .then_some(server_key),
+        }
+    };
+
     dbg_connection!("connection_pipeline: send streaming config");
     let stream_config_packet = StreamConfigPacket::new(
-        session_manager_lock.session(),
+        &client_session,
         NegotiatedStreamingConfig {
             view_resolution: stream_view_resolution,
             refresh_rate_hint: fps,
@@ -703,16 +803,20 @@ fn connection_pipeline(
             wired,
             ext_str: String::new(),
         }
-        .with_ext(NegotiatedStreamingConfigExt {}),
+        .with_ext(negotiated_config_ext),
     )
     .to_con()?;
     proto_socket.send(&stream_config_packet).to_con()?;
+    *ctx.client_view_resolution.lock() = Some(stream_view_resolution);
 
     let (mut control_sender, mut control_receiver) =
         proto_socket.split(STREAMING_RECV_TIMEOUT).to_con()?;
 
+    let old_openvr_config = session_manager_lock.session().openvr_config.clone();
     let new_openvr_config = {
-        let mut config = contruct_openvr_config(session_manager_lock.session());
+        let mut config = contruct_openvr_config(&client_session);
+        config.encoding_eye_resolution_width = stream_view_resolution.x;
+        config.encoding_eye_resolution_height = stream_view_resolution.y;
         config.eye_resolution_width = stream_view_resolution.x;
         config.eye_resolution_height = stream_view_resolution.y;
         config.target_eye_resolution_width = target_view_resolution.x;
@@ -724,15 +828,25 @@ fn connection_pipeline(
         config.enable_hdr = enable_hdr;
         config.encoding_gamma = encoding_gamma;
         config.codec = codec as _;
-        config
+
+        with_reused_render_size(&old_openvr_config, config)
     };
 
-    if session_manager_lock.session().openvr_config != new_openvr_config {
+    if old_openvr_config != new_openvr_config {
+        let requires_driver_reload = old_openvr_config.requires_driver_reload(&new_openvr_config);
         session_manager_lock.session_mut().openvr_config = new_openvr_config;
 
-        control_sender.send(&ServerControlPacket::Restarting).ok();
+        if requires_driver_reload {
+            control_sender.send(&ServerControlPacket::Restarting).ok();
 
-        crate::notify_restart_driver();
+            crate::notify_restart_driver();
+        } else {
+            // The driver reads the new config from the session file, which is already saved
+            info!("Encoder settings changed, renegotiating stream");
+            ctx.events_sender
+                .send(ServerCoreEvent::ReconfigureStream)
+                .ok();
+        }
     }
 
     dbg_connection!("connection_pipeline: Send StartStream packet");
@@ -746,15 +860,40 @@ fn connection_pipeline(
     }
     dbg_connection!("connection_pipeline: Got StreamReady packet");
 
-    *ctx.statistics_manager.write() = Some(StatisticsManager::new(
-        initial_settings.connection.statistics_history_size,
-        Duration::from_secs_f32(1.0 / fps),
-        if let Switch::Enabled(config) = &initial_settings.headset.controllers {
-            config.steamvr_pipeline_frames
-        } else {
-            0.0
-        },
-    ));
+    {
+        // Samples on its own thread, so it never blocks the statistics lock
+        let host_monitor = initial_settings
+            .connection
+            .host_monitoring
+            .as_option()
+            .map(HostMonitor::new);
+
+        let mut statistics_manager_lock = ctx.statistics_manager.write();
+
+        // Keep logging frame statistics and capturing traces across reconnections
+        let frame_statistics_log = statistics_manager_lock
+            .as_mut()
+            .and_then(|stats| stats.take_frame_statistics_log());
+        let trace_capture = statistics_manager_lock
+            .as_mut()
+            .and_then(|stats| stats.take_trace_capture());
+
+        let mut stats = StatisticsManager::new(
+            initial_settings.connection.statistics_history_size,
+            Duration::from_secs_f32(1.0 / fps),
+            if let Switch::Enabled(config) = &initial_settings.headset.controllers {
+                config.steamvr_pipeline_frames
+            } else {
+                0.0
+            },
+            &initial_settings.connection.latency_distribution,
+        );
+        stats.set_frame_statistics_log(frame_statistics_log);
+        stats.set_trace_capture(trace_capture);
+        stats.set_host_monitor(host_monitor);
+
+        *statistics_manager_lock = Some(stats);
+    }
 
     *ctx.bitrate_manager.lock() =
         BitrateManager::new(initial_settings.video.bitrate.history_size, fps);
@@ -899,6 +1038,8 @@ fn connection_pipeline(
                 if let Some(stats) = &mut *ctx.statistics_manager.write() {
                     let timestamp = client_stats.target_timestamp;
                     let decoder_latency = client_stats.video_decode;
+                    let received_packets = client_stats.video_packets_received;
+                    let lost_packets = client_stats.video_packets_lost;
                     let (network_latency, game_latency) = stats.report_statistics(client_stats);
 
                     ctx.events_sender
@@ -906,32 +1047,137 @@ fn connection_pipeline(
                         .ok();
 
                     let session_manager_lock = SESSION_MANAGER.read();
-                    ctx.bitrate_manager.lock().report_frame_latencies(
-                        &session_manager_lock.settings().video.bitrate.mode,
+                    let bitrate_mode = &session_manager_lock.settings().video.bitrate.mode;
+                    let mut bitrate_manager = ctx.bitrate_manager.lock();
+                    bitrate_manager.report_frame_latencies(
+                        bitrate_mode,
                         timestamp,
                         network_latency,
                         decoder_latency,
                     );
+                    bitrate_manager.report_packet_loss(
+                        bitrate_mode,
+                        received_packets,
+                        lost_packets,
+                    );
                 }
             }
         }
     });
 
     let control_sender = Arc::new(Mutex::new(control_sender));
+    *ctx.control_sender.lock() = Some(Arc::clone(&control_sender));
+
+    // Resume the client side of a trace capture started before a reconnection
+    if ctx
+        .statistics_manager
+        .read()
+        .as_ref()
+        .is_some_and(|stats| stats.is_capturing_trace())
+    {
+        control_sender
+            .lock()
+            .send(&ServerControlPacket::TraceCapture(true))
+            .ok();
+    }
 
     let real_time_update_thread = thread::spawn({
+        let ctx = Arc::clone(&ctx);
         let control_sender = Arc::clone(&control_sender);
         let client_hostname = client_hostname.clone();
         move || {
             let mut previous_config = None;
+            let mut pending_openvr_config = None::<(OpenvrConfig, Instant)>;
+            // Foveation parameters sent to the client. They must match the ones used by the
+            // encoder, so they are updated only when the encoder is
+            let mut encoder_foveation = None;
             while is_streaming(&client_hostname) {
-                let config = {
+                let resolution_scale = ctx.bitrate_manager.lock().resolution_scale();
+
+                let (mut config, foveation, openvr_config, new_openvr_config) = {
                     let session_manager_lock = SESSION_MANAGER.read();
                     let settings = session_manager_lock.settings();
 
-                    RealTimeConfig::from_settings(settings)
+                    let game_audio_gain = settings
+                        .audio
+                        .game_audio
+                        .as_option()
+                        .map_or(1.0, |config| config.gain);
+                    let microphone_gain = settings
+                        .audio
+                        .microphone
+                        .as_option()
+                        .map_or(1.0, |config| config.gain);
+                    sound::linux::set_gains(game_audio_gain, microphone_gain);
+
+                    let openvr_config = session_manager_lock.session().openvr_config.clone();
+                    let mut new_openvr_config = with_real_time_encoder_fields(
+                        &openvr_config,
+                        &contruct_openvr_config(
+                            &session_manager_lock
+                                .session()
+                                .with_client_overrides(&client_hostname),
+                        ),
+                    );
+                    // The encoder downscales from the negotiated resolution, which SteamVR keeps
+                    // rendering at
+                    new_openvr_config.encoding_eye_resolution_width =
+                        align32(stream_view_resolution.x as f32 * resolution_scale);
+                    new_openvr_config.encoding_eye_resolution_height =
+                        align32(stream_view_resolution.y as f32 * resolution_scale);
+
+                    (
+                        RealTimeConfig::from_settings(settings),
+                        settings.video.foveated_encoding.clone().into_option(),
+                        openvr_config,
+                        new_openvr_config,
+                    )
                 };
 
+                let is_stable = pending_openvr_config
+                    .as_ref()
+                    .is_some_and(|(config, since)| {
+                        *config == new_openvr_config && since.elapsed() >= ENCODER_RECONFIGURE_DELAY
+                    });
+                if new_openvr_config == openvr_config {
+                    pending_openvr_config = None;
+                    encoder_foveation = foveation;
+                } else if is_stable {
+                    pending_openvr_config = None;
+
+                    if new_openvr_config.encoding_eye_resolution_width
+                        != openvr_config.encoding_eye_resolution_width
+                    {
+                        info!(
+                            "Scaling the stream resolution to {:.0}%",
+                            resolution_scale * 100.0
+                        );
+                    }
+
+                    SESSION_MANAGER.write().session_mut().openvr_config = new_openvr_config;
+                    encoder_foveation = foveation;
+
+                    info!("Encoder settings changed, renegotiating stream");
+                    if let Some(stats) = &mut *ctx.statistics_manager.write() {
+                        stats.report_trace_instant("Encoder", "Stream reconfigured");
+                    }
+                    ctx.events_sender
+                        .send(ServerCoreEvent::ReconfigureStream)
+                        .ok();
+                } else if pending_openvr_config
+                    .as_ref()
+                    .is_none_or(|(config, _)| *config != new_openvr_config)
+                {
+                    pending_openvr_config = Some((new_openvr_config, Instant::now()));
+                }
+
+                if let Ok(ext) = config.ext() {
+                    config = config.with_ext(RealTimeConfigExt {
+                        foveated_encoding: encoder_foveation.clone(),
+                        ..ext
+                    });
+                }
+
                 let same_config = previous_config.as_ref().is_some_and(|prev| config == *prev);
                 if !same_config {
                     previous_config = Some(config.clone());
@@ -969,27 +1215,21 @@ fn connection_pipeline(
     let control_receive_thread = thread::spawn({
         let ctx = Arc::clone(&ctx);
 
-        let controllers_config = session_manager_lock
+        let controllers_emulation_mode = session_manager_lock
             .settings()
             .headset
             .controllers
-            .as_option();
-        let mut controller_button_mapping_manager = controllers_config.map(|config| {
-            if let Some(mappings) = &config.button_mappings {
-                ButtonMappingManager::new_manual(mappings)
-            } else {
-                ButtonMappingManager::new_automatic(
-                    &CONTROLLER_PROFILE_INFO
-                        .get(&shared::hash_string(QUEST_CONTROLLER_PROFILE_PATH))
-                        .unwrap()
-                        .button_set,
-                    &config.emulation_mode,
-                    &config.button_mapping_config,
-                )
-            }
-        });
-        let controllers_emulation_mode =
-            controllers_config.map(|config| config.emulation_mode.clone());
+            .as_option()
+            .map(|config| config.emulation_mode.clone());
+        let mut controller_input_ids = CONTROLLER_PROFILE_INFO
+            .get(&shared::hash_string(QUEST_CONTROLLER_PROFILE_PATH))
+            .unwrap()
+            .button_set
+            .clone();
+        // Mapping settings used to build the current manager. The manager is rebuilt when they
+        // change
+        let mut button_mapping_settings = None;
+        let mut controller_button_mapping_manager = None;
 
         let disconnect_notif = Arc::clone(&disconnect_notif);
         let control_sender = Arc::clone(&control_sender);
@@ -1039,6 +1279,9 @@ fn connection_pipeline(
                         }
                     }
                     ClientControlPacket::RequestIdr => {
+                        if let Some(stats) = &mut *ctx.statistics_manager.write() {
+                            stats.report_trace_instant("Encoder", "IDR requested");
+                        }
                         if let Some(config) = ctx.decoder_config.lock().clone() {
                             control_sender
                                 .lock()
@@ -1089,6 +1332,25 @@ fn connection_pipeline(
                             }
                         }
 
+                        if let Some(emulation_mode) = &controllers_emulation_mode
+                            && let Switch::Enabled(config) =
+                                &SESSION_MANAGER.read().settings().headset.controllers
+                        {
+                            let new_settings = (
+                                config.button_mappings.clone(),
+                                config.button_mapping_config.clone(),
+                            );
+                            if button_mapping_settings.as_ref() != Some(&new_settings) {
+                                controller_button_mapping_manager =
+                                    Some(controller_button_mapping_manager_from_config(
+                                        config,
+                                        &controller_input_ids,
+                                        emulation_mode,
+                                    ));
+                                button_mapping_settings = Some(new_settings);
+                            }
+                        }
+
                         if let Some(manager) = &mut controller_button_mapping_manager {
                             let button_entries = entries
                                 .iter()
@@ -1103,23 +1365,9 @@ fn connection_pipeline(
                         };
                     }
                     ClientControlPacket::ActiveInteractionProfile { input_ids, .. } => {
-                        controller_button_mapping_manager = if let Switch::Enabled(config) =
-                            &SESSION_MANAGER.read().settings().headset.controllers
-                        {
-                            if let Some(mappings) = &config.button_mappings {
-                                Some(ButtonMappingManager::new_manual(mappings))
-                            } else {
-                                controllers_emulation_mode.as_ref().map(|emulation_mode| {
-                                    ButtonMappingManager::new_automatic(
-                                        &input_ids,
-                                        emulation_mode,
-                                        &config.button_mapping_config,
-                                    )
-                                })
-                            }
-                        } else {
-                            None
-                        };
+                        controller_input_ids = input_ids;
+                        // Rebuild the manager on the next buttons packet
+                        button_mapping_settings = None;
                     }
                     ClientControlPacket::Log { level, message } => {
                         info!("Client {client_hostname}: [{level:?}] {message}")
@@ -1191,9 +1439,24 @@ fn connection_pipeline(
 
     if initial_settings.extra.capture.startup_video_recording {
         info!("Creating recording file");
-        crate::create_recording_file(&ctx, session_manager_lock.settings());
+        crate::create_recording_file(&ctx, &initial_settings);
     }
 
+    let is_logging_frame_statistics = ctx
+        .statistics_manager
+        .read()
+        .as_ref()
+        .is_some_and(StatisticsManager::is_logging_frame_statistics);
+    if initial_settings
+        .extra
+        .capture
+        .startup_frame_statistics_logging
+        && !is_logging_frame_statistics
+    {
+        crate::start_frame_statistics_log(&ctx, &initial_settings);
+    }
+
+    session_manager_lock.set_active_client(Some(client_hostname.clone()));
     session_manager_lock.update_client_list(
         client_hostname.clone(),
         ClientListAction::SetConnectionState(ConnectionState::Streaming),
@@ -1209,6 +1472,7 @@ fn connection_pipeline(
 
     // This requests shutdown from threads
     *ctx.video_channel_sender.lock() = None;
+    *ctx.control_sender.lock() = None;
     *ctx.haptics_sender.lock() = None;
 
     *ctx.video_recording_file.lock() = None;
@@ -1217,6 +1481,7 @@ fn connection_pipeline(
         client_hostname,
         ClientListAction::SetConnectionState(ConnectionState::Disconnecting),
     );
+    session_manager_lock.set_active_client(None);
 
     let enable_on_disconnect_script = session_manager_lock
         .settings()
@@ -1262,3 +1527,85 @@ fn connection_pipeline(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configuration::SessionSettings;
+
+    #[test]
+    fn test_real_time_encoder_fields() {
+        let mut session = SessionConfig::default();
+        session.session_settings.video.foveated_encoding.enabled = true;
+        session.session_settings.video.color_correction.enabled = true;
+        let config = contruct_openvr_config(&session);
+
+        let mutations: [fn(&mut SessionSettings); 12] = [
+            |s| s.video.foveated_encoding.content.center_size_x += 0.1,
+            |s| s.video.foveated_encoding.content.center_size_y += 0.1,
+            |s| s.video.foveated_encoding.content.center_shift_x += 0.1,
+            |s| s.video.foveated_encoding.content.center_shift_y += 0.1,
+            |s| s.video.foveated_encoding.content.edge_ratio_x += 1.0,
+            |s| s.video.foveated_encoding.content.edge_ratio_y += 1.0,
+            |s| s.video.color_correction.enabled = false,
+            |s| s.video.color_correction.content.brightness += 0.1,
+            |s| s.video.color_correction.content.contrast += 0.1,
+            |s| s.video.color_correction.content.saturation += 0.1,
+            |s| s.video.color_correction.content.gamma += 0.1,
+            |s| s.video.color_correction.content.sharpening += 0.1,
+        ];
+        for (idx, mutate) in mutations.iter().enumerate() {
+            let mut new_session = session.clone();
+            mutate(&mut new_session.session_settings);
+            let new_config = contruct_openvr_config(&new_session);
+
+            assert_ne!(new_config, config, "mutation {idx} has no effect");
+            assert_eq!(
+                with_real_time_encoder_fields(&config, &new_config),
+                new_config,
+                "mutation {idx} is not applied to the encoder"
+            );
+        }
+
+        // Toggling foveated encoding requires a SteamVR restart
+        let mut new_session = session.clone();
+        new_session.session_settings.video.foveated_encoding.enabled = false;
+        let new_config = contruct_openvr_config(&new_session);
+        assert_eq!(with_real_time_encoder_fields(&config, &new_config), config);
+    }
+
+    #[test]
+    fn test_reused_render_size() {
+        let with_resolution = |width, height| OpenvrConfig {
+            eye_resolution_width: width,
+            eye_resolution_height: height,
+            target_eye_resolution_width: width,
+            target_eye_resolution_height: height,
+            encoding_eye_resolution_width: width,
+            encoding_eye_resolution_height: height,
+            ..OpenvrConfig::default()
+        };
+        let old_config = with_resolution(2000, 2000);
+
+        let config = with_reused_render_size(&old_config, with_resolution(1600, 1600));
+        assert_eq!(config.eye_resolution_width, 2000);
+        assert_eq!(config.encoding_eye_resolution_width, 1600);
+
+        // The render size can shrink
+        let config = with_reused_render_size(&old_config, with_resolution(1000, 1000));
+        assert_eq!(config.eye_resolution_width, 1000);
+
+        let config = with_reused_render_size(&old_config, with_resolution(2400, 1600));
+        assert_eq!(config.eye_resolution_width, 2400);
+
+        // SteamVR is restarted anyway
+        let config = with_reused_render_size(
+            &old_config,
+            OpenvrConfig {
+                refresh_rate: 120,
+                ..with_resolution(1600, 1600)
+            },
+        );
+        assert_eq!(config.eye_resolution_width, 1600);
+    }
+}
@@ -0,0 +1,137 @@
+use events::{Event, EventType};
+use net_packets::EventsSubscription;
+use shared::LogSeverity;
+use std::time::{Duration, Instant};
+
+// Per-subscriber filter of the events websocket. Without a subscription every event passes.
+#[derive(Default)]
+pub struct EventFilter {
+    subscription: EventsSubscription,
+    min_statistics_interval: Duration,
+    last_statistics_summary: Option<Instant>,
+    last_graph_statistics: Option<Instant>,
+}
+
+impl EventFilter {
+    pub fn new(subscription: EventsSubscription) -> Self {
+        let min_statistics_interval = subscription
+            .max_statistics_rate_hz
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f32(1.0 / rate))
+            .unwrap_or_default();
+
+        Self {
+            subscription,
+            min_statistics_interval,
+            last_statistics_summary: None,
+            last_graph_statistics: None,
+        }
+    }
+
+    pub fn accepts(&mut self, event: &Event, now: Instant) -> bool {
+        let event_type = &event.event_type;
+
+        if let Some(event_types) = &self.subscription.event_types
+            && !event_types.iter().any(|name| name == event_type.name())
+        {
+            return false;
+        }
+
+        if let Some(min_severity) = self.subscription.min_log_severity {
+            let severity = match event_type {
+                EventType::Log(entry) => Some(entry.severity),
+                EventType::DebugGroup { .. } => Some(LogSeverity::Debug),
+                _ => None,
+            };
+            if severity.is_some_and(|severity| severity < min_severity) {
+                return false;
+            }
+        }
+
+        let last_statistics = match event_type {
+            EventType::StatisticsSummary(_) => &mut self.last_statistics_summary,
+            EventType::GraphStatistics(_) => &mut self.last_graph_statistics,
+            _ => return true,
+        };
+        if last_statistics
+            .is_some_and(|last| now.saturating_duration_since(last) < self.min_statistics_interval)
+        {
+            return false;
+        }
+        *last_statistics = Some(now);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::GraphStatistics;
+    use shared::LogEntry;
+
+    fn event(event_type: EventType) -> Event {
+        Event {
+            timestamp: String::new(),
+            event_type,
+        }
+    }
+
+    fn log(severity: LogSeverity) -> Event {
+        event(EventType::Log(LogEntry {
+            severity,
+            content: String::new(),
+        }))
+    }
+
+    #[test]
+    fn test_no_subscription() {
+        let mut filter = EventFilter::default();
+        let now = Instant::now();
+
+        assert!(filter.accepts(&log(LogSeverity::Debug), now));
+        assert!(filter.accepts(&event(EventType::GraphStatistics(Default::default())), now));
+        assert!(filter.accepts(&event(EventType::GraphStatistics(Default::default())), now));
+    }
+
+    #[test]
+    fn test_event_types_and_severity() {
+        let mut filter = EventFilter::new(EventsSubscription {
+            event_types: Some(vec!["Log".into(), "DebugGroup".into()]),
+            min_log_severity: Some(LogSeverity::Warning),
+            max_statistics_rate_hz: None,
+        });
+        let now = Instant::now();
+
+        assert!(filter.accepts(&log(LogSeverity::Error), now));
+        assert!(filter.accepts(&log(LogSeverity::Warning), now));
+        assert!(!filter.accepts(&log(LogSeverity::Info), now));
+        assert!(!filter.accepts(
+            &event(EventType::DebugGroup {
+                group: "group".into(),
+                message: String::new(),
+            }),
+            now
+        ));
+        assert!(!filter.accepts(&event(EventType::ServerRequestsSelfRestart), now));
+    }
+
+    #[test]
+    fn test_statistics_rate() {
+        let mut filter = EventFilter::new(EventsSubscription {
+            max_statistics_rate_hz: Some(2.0),
+            ..Default::default()
+        });
+        let graph = event(EventType::GraphStatistics(GraphStatistics::default()));
+        let summary = event(EventType::StatisticsSummary(Default::default()));
+        let start = Instant::now();
+
+        assert!(filter.accepts(&graph, start));
+        assert!(!filter.accepts(&graph, start + Duration::from_millis(100)));
+        // Each statistics event type is limited separately
+        assert!(filter.accepts(&summary, start + Duration::from_millis(100)));
+        assert!(filter.accepts(&graph, start + Duration::from_millis(500)));
+        assert!(!filter.accepts(&graph, start + Duration::from_millis(900)));
+        assert!(filter.accepts(&log(LogSeverity::Info), start));
+    }
+}
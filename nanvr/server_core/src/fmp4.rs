@@ -0,0 +1,836 @@
+// Fragmented MP4 (ISO/IEC 14496-12) packaging of the encoded stream, for playback with Media
+// Source Extensions. Each frame is sent as a separate moof + mdat fragment.
+
+use configuration::CodecType;
+use net_packets::DecoderInitializationConfig;
+use shared::anyhow::{Result, bail};
+use std::time::Duration;
+
+const TIMESCALE: u32 = 90_000;
+const TRACK_ID: u32 = 1;
+// Used for the first frame, when the frame interval is unknown
+const DEFAULT_SAMPLE_DURATION: u32 = TIMESCALE / 90;
+
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+const IDENTITY_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+const H264_SPS: u8 = 7;
+const H264_PPS: u8 = 8;
+const H264_AUD: u8 = 9;
+const HEVC_VPS: u8 = 32;
+const HEVC_SPS: u8 = 33;
+const HEVC_PPS: u8 = 34;
+const HEVC_AUD: u8 = 35;
+
+#[derive(Clone)]
+pub enum Fmp4MirrorPacket {
+    Config(DecoderInitializationConfig),
+    Frame {
+        timestamp: Duration,
+        is_idr: bool,
+        nal_buffer: Vec<u8>,
+    },
+}
+
+// Annex B byte stream to NAL units, without start codes
+fn split_nals(buffer: &[u8]) -> Vec<&[u8]> {
+    let mut nals = vec![];
+    let mut nal_start = None;
+    let mut i = 0;
+    while i + 3 <= buffer.len() {
+        if buffer[i..i + 3] == [0, 0, 1] {
+            if let Some(start) = nal_start {
+                nals.push(&buffer[start..i]);
+            }
+            i += 3;
+            nal_start = Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    if let Some(start) = nal_start {
+        nals.push(&buffer[start..]);
+    }
+
+    // Strip the leading zero of 4 byte start codes
+    nals.into_iter()
+        .map(|nal| {
+            let end = nal.iter().rposition(|byte| *byte != 0).map_or(0, |i| i + 1);
+            &nal[..end]
+        })
+        .filter(|nal| !nal.is_empty())
+        .collect()
+}
+
+fn nal_type(codec: CodecType, nal: &[u8]) -> u8 {
+    if codec == CodecType::Hevc {
+        (nal[0] >> 1) & 0x3f
+    } else {
+        nal[0] & 0x1f
+    }
+}
+
+// Removes emulation prevention bytes
+fn to_rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+
+    rbsp
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn bit(&mut self) -> Result<u32> {
+        let Some(byte) = self.data.get(self.position / 8) else {
+            bail!("Unexpected end of parameter set");
+        };
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: usize) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()?;
+        }
+
+        Ok(value)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<()> {
+        self.bits(count).map(|_| ())
+    }
+
+    // Exp-Golomb unsigned
+    fn ue(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while self.bit()? == 0 {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                bail!("Invalid Exp-Golomb code");
+            }
+        }
+
+        Ok((1 << leading_zeros) - 1 + self.bits(leading_zeros)?)
+    }
+
+    fn se(&mut self) -> Result<i32> {
+        let value = self.ue()? as i64;
+
+        Ok(if value % 2 == 1 {
+            (value + 1) / 2
+        } else {
+            -value / 2
+        } as i32)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct SequenceInfo {
+    width: u32,
+    height: u32,
+    chroma_format_idc: u32,
+    bit_depth_luma_minus8: u32,
+    bit_depth_chroma_minus8: u32,
+}
+
+// Returns the horizontal and vertical chroma subsampling
+fn chroma_subsampling(chroma_format_idc: u32) -> (u32, u32) {
+    match chroma_format_idc {
+        1 => (2, 2),
+        2 => (2, 1),
+        _ => (1, 1),
+    }
+}
+
+fn parse_h264_sps(rbsp: &[u8]) -> Result<SequenceInfo> {
+    // Skip the NAL header
+    let mut reader = BitReader::new(rbsp.get(1..).unwrap_or_default());
+
+    let profile_idc = reader.bits(8)?;
+    reader.skip(16)?; // constraint flags, level_idc
+    reader.ue()?; // seq_parameter_set_id
+
+    let mut chroma_format_idc = 1;
+    let mut separate_colour_plane = false;
+    let mut bit_depth_luma_minus8 = 0;
+    let mut bit_depth_chroma_minus8 = 0;
+    if [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135].contains(&profile_idc) {
+        chroma_format_idc = reader.ue()?;
+        if chroma_format_idc == 3 {
+            separate_colour_plane = reader.bit()? == 1;
+        }
+        bit_depth_luma_minus8 = reader.ue()?;
+        bit_depth_chroma_minus8 = reader.ue()?;
+        reader.skip(1)?; // qpprime_y_zero_transform_bypass_flag
+
+        if reader.bit()? == 1 {
+            let list_count = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..list_count {
+                if reader.bit()? == 1 {
+                    let size = if i < 6 { 16 } else { 64 };
+                    let mut last_scale = 8;
+                    let mut next_scale = 8;
+                    for _ in 0..size {
+                        if next_scale != 0 {
+                            next_scale = (last_scale + reader.se()? + 256) % 256;
+                        }
+                        if next_scale != 0 {
+                            last_scale = next_scale;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    reader.ue()?; // log2_max_frame_num_minus4
+    match reader.ue()? {
+        0 => {
+            reader.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            reader.skip(1)?; // delta_pic_order_always_zero_flag
+            reader.se()?; // offset_for_non_ref_pic
+            reader.se()?; // offset_for_top_to_bottom_field
+            for _ in 0..reader.ue()? {
+                reader.se()?; // offset_for_ref_frame
+            }
+        }
+        _ => (),
+    }
+    reader.ue()?; // max_num_ref_frames
+    reader.skip(1)?; // gaps_in_frame_num_value_allowed_flag
+
+    let width_in_mbs = reader.ue()? + 1;
+    let height_in_map_units = reader.ue()? + 1;
+    let frame_mbs_only = reader.bit()?;
+    if frame_mbs_only == 0 {
+        reader.skip(1)?; // mb_adaptive_frame_field_flag
+    }
+    reader.skip(1)?; // direct_8x8_inference_flag
+
+    let (crop_unit_x, crop_unit_y) = if chroma_format_idc == 0 || separate_colour_plane {
+        (1, 1)
+    } else {
+        chroma_subsampling(chroma_format_idc)
+    };
+    let crop_unit_y = crop_unit_y * (2 - frame_mbs_only);
+
+    let mut crop = [0; 4];
+    if reader.bit()? == 1 {
+        for offset in &mut crop {
+            *offset = reader.ue()?;
+        }
+    }
+
+    Ok(SequenceInfo {
+        width: (width_in_mbs * 16).saturating_sub((crop[0] + crop[1]) * crop_unit_x),
+        height: ((2 - frame_mbs_only) * height_in_map_units * 16)
+            .saturating_sub((crop[2] + crop[3]) * crop_unit_y),
+        chroma_format_idc,
+        bit_depth_luma_minus8,
+        bit_depth_chroma_minus8,
+    })
+}
+
+fn parse_hevc_sps(rbsp: &[u8]) -> Result<SequenceInfo> {
+    // Skip the NAL header
+    let mut reader = BitReader::new(rbsp.get(2..).unwrap_or_default());
+
+    reader.skip(4)?; // sps_video_parameter_set_id
+    let max_sub_layers_minus1 = reader.bits(3)? as usize;
+    reader.skip(1)?; // sps_temporal_id_nesting_flag
+
+    // profile_tier_level()
+    reader.skip(96)?;
+    let mut sub_layers_present = vec![];
+    for _ in 0..max_sub_layers_minus1 {
+        sub_layers_present.push((reader.bit()? == 1, reader.bit()? == 1));
+    }
+    if max_sub_layers_minus1 > 0 {
+        reader.skip(2 * (8 - max_sub_layers_minus1))?;
+    }
+    for (profile_present, level_present) in sub_layers_present {
+        if profile_present {
+            reader.skip(88)?;
+        }
+        if level_present {
+            reader.skip(8)?;
+        }
+    }
+
+    reader.ue()?; // sps_seq_parameter_set_id
+    let chroma_format_idc = reader.ue()?;
+    let mut separate_colour_plane = false;
+    if chroma_format_idc == 3 {
+        separate_colour_plane = reader.bit()? == 1;
+    }
+    let mut width = reader.ue()?;
+    let mut height = reader.ue()?;
+
+    if reader.bit()? == 1 {
+        let (unit_x, unit_y) = if separate_colour_plane {
+            (1, 1)
+        } else {
+            chroma_subsampling(chroma_format_idc)
+        };
+        let (left, right, top, bottom) = (reader.ue()?, reader.ue()?, reader.ue()?, reader.ue()?);
+        width = width.saturating_sub((left + right) * unit_x);
+        height = height.saturating_sub((top + bottom) * unit_y);
+    }
+
+    Ok(SequenceInfo {
+        width,
+        height,
+        chroma_format_idc,
+        bit_depth_luma_minus8: reader.ue()?,
+        bit_depth_chroma_minus8: reader.ue()?,
+    })
+}
+
+fn write_box(buffer: &mut Vec<u8>, box_type: &[u8; 4], content: impl FnOnce(&mut Vec<u8>)) {
+    let start = buffer.len();
+    buffer.extend([0; 4]);
+    buffer.extend(box_type);
+    content(buffer);
+
+    let size = (buffer.len() - start) as u32;
+    buffer[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box(
+    buffer: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    content: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(buffer, box_type, |buffer| {
+        buffer.extend((((version as u32) << 24) | flags).to_be_bytes());
+        content(buffer);
+    })
+}
+
+fn write_nal_array(buffer: &mut Vec<u8>, nals: &[Vec<u8>]) {
+    for nal in nals {
+        buffer.extend((nal.len() as u16).to_be_bytes());
+        buffer.extend(nal);
+    }
+}
+
+fn write_matrix(buffer: &mut Vec<u8>) {
+    for value in IDENTITY_MATRIX {
+        buffer.extend(value.to_be_bytes());
+    }
+}
+
+pub struct Fmp4Muxer {
+    config_buffer: Vec<u8>,
+    codec: CodecType,
+    mime_type: String,
+    init_segment: Vec<u8>,
+    sequence_number: u32,
+    last_decode_time: Option<u64>,
+    last_sample_duration: u32,
+    waiting_for_idr: bool,
+}
+
+impl Fmp4Muxer {
+    pub fn new(config: &DecoderInitializationConfig) -> Result<Self> {
+        let codec = config.codec;
+        let nals = split_nals(&config.config_buffer);
+        let parameter_sets = |nal_type_filter: u8| {
+            nals.iter()
+                .filter(|nal| nal_type(codec, nal) == nal_type_filter)
+                .map(|nal| nal.to_vec())
+                .collect::<Vec<_>>()
+        };
+
+        let (sample_entry_type, sequence_info, codec_string, decoder_config_box) = match codec {
+            CodecType::H264 => {
+                let sps = parameter_sets(H264_SPS);
+                let pps = parameter_sets(H264_PPS);
+                let Some(first_sps) = sps.first().filter(|sps| sps.len() >= 4) else {
+                    bail!("Missing SPS in the decoder configuration");
+                };
+                let info = parse_h264_sps(&to_rbsp(first_sps))?;
+                let profile_idc = first_sps[1];
+
+                let codec_string = format!(
+                    "avc1.{:02x}{:02x}{:02x}",
+                    first_sps[1], first_sps[2], first_sps[3]
+                );
+
+                let mut avcc = vec![];
+                write_box(&mut avcc, b"avcC", |buffer| {
+                    buffer.extend([1, first_sps[1], first_sps[2], first_sps[3], 0xff]);
+                    buffer.push(0xe0 | sps.len() as u8);
+                    write_nal_array(buffer, &sps);
+                    buffer.push(pps.len() as u8);
+                    write_nal_array(buffer, &pps);
+
+                    if [100, 110, 122, 144].contains(&profile_idc) {
+                        buffer.extend([
+                            0xfc | info.chroma_format_idc as u8,
+                            0xf8 | info.bit_depth_luma_minus8 as u8,
+                            0xf8 | info.bit_depth_chroma_minus8 as u8,
+                            0,
+                        ]);
+                    }
+                });
+
+                (b"avc1", info, codec_string, avcc)
+            }
+            CodecType::Hevc => {
+                let vps = parameter_sets(HEVC_VPS);
+                let sps = parameter_sets(HEVC_SPS);
+                let pps = parameter_sets(HEVC_PPS);
+                let Some(first_sps) = sps.first() else {
+                    bail!("Missing SPS in the decoder configuration");
+                };
+                let sps_rbsp = to_rbsp(first_sps);
+                let info = parse_hevc_sps(&sps_rbsp)?;
+
+                // The general profile_tier_level() fields follow the NAL header and one byte
+                let profile_tier_level = &sps_rbsp[3..15];
+                let sub_layers_byte = sps_rbsp[2];
+
+                let profile_space = ["", "A", "B", "C"][profile_tier_level[0] as usize >> 6];
+                let tier = if profile_tier_level[0] & 0x20 != 0 {
+                    "H"
+                } else {
+                    "L"
+                };
+                let compatibility_flags =
+                    u32::from_be_bytes(profile_tier_level[1..5].try_into().unwrap());
+                let mut codec_string = format!(
+                    "hvc1.{profile_space}{}.{:X}.{tier}{}",
+                    profile_tier_level[0] & 0x1f,
+                    compatibility_flags.reverse_bits(),
+                    profile_tier_level[11],
+                );
+                let constraint_flags = &profile_tier_level[5..11];
+                let constraint_end = constraint_flags
+                    .iter()
+                    .rposition(|byte| *byte != 0)
+                    .map_or(0, |i| i + 1);
+                for byte in &constraint_flags[..constraint_end] {
+                    codec_string += &format!(".{byte:X}");
+                }
+
+                let mut hvcc = vec![];
+                write_box(&mut hvcc, b"hvcC", |buffer| {
+                    buffer.push(1);
+                    buffer.extend(profile_tier_level);
+                    buffer.extend([
+                        0xf0,
+                        0x00, // min_spatial_segmentation_idc
+                        0xfc, // parallelismType
+                        0xfc | info.chroma_format_idc as u8,
+                        0xf8 | info.bit_depth_luma_minus8 as u8,
+                        0xf8 | info.bit_depth_chroma_minus8 as u8,
+                        0x00,
+                        0x00, // avgFrameRate
+                    ]);
+                    // numTemporalLayers, temporalIdNested, lengthSizeMinusOne
+                    let sub_layers = ((sub_layers_byte >> 1) & 0x07) + 1;
+                    buffer.push((sub_layers << 3) | ((sub_layers_byte & 1) << 2) | 0x03);
+
+                    let arrays = [(HEVC_VPS, &vps), (HEVC_SPS, &sps), (HEVC_PPS, &pps)]
+                        .into_iter()
+                        .filter(|(_, nals)| !nals.is_empty())
+                        .collect::<Vec<_>>();
+                    buffer.push(arrays.len() as u8);
+                    for (nal_type, nals) in arrays {
+                        buffer.push(0x80 | nal_type);
+                        buffer.extend((nals.len() as u16).to_be_bytes());
+                        write_nal_array(buffer, nals);
+                    }
+                });
+
+                (b"hvc1", info, codec_string, hvcc)
+            }
+            CodecType::AV1 => bail!("AV1 is not supported by the MP4 mirror"),
+        };
+
+        let init_segment =
+            Self::build_init_segment(sample_entry_type, &sequence_info, &decoder_config_box);
+
+        Ok(Self {
+            config_buffer: config.config_buffer.clone(),
+            codec,
+            mime_type: format!("video/mp4; codecs=\"{codec_string}\""),
+            init_segment,
+            sequence_number: 0,
+            last_decode_time: None,
+            last_sample_duration: DEFAULT_SAMPLE_DURATION,
+            waiting_for_idr: true,
+        })
+    }
+
+    fn build_init_segment(
+        sample_entry_type: &[u8; 4],
+        info: &SequenceInfo,
+        decoder_config_box: &[u8],
+    ) -> Vec<u8> {
+        let mut buffer = vec![];
+
+        write_box(&mut buffer, b"ftyp", |buffer| {
+            buffer.extend(b"isom");
+            buffer.extend(0x200_u32.to_be_bytes());
+            buffer.extend(b"isomiso6mp41");
+            buffer.extend(sample_entry_type);
+        });
+
+        write_box(&mut buffer, b"moov", |buffer| {
+            write_full_box(buffer, b"mvhd", 0, 0, |buffer| {
+                buffer.extend([0; 8]); // creation and modification time
+                buffer.extend(TIMESCALE.to_be_bytes());
+                buffer.extend(0_u32.to_be_bytes()); // duration
+                buffer.extend(0x0001_0000_u32.to_be_bytes()); // rate
+                buffer.extend(0x0100_u16.to_be_bytes()); // volume
+                buffer.extend([0; 10]);
+                write_matrix(buffer);
+                buffer.extend([0; 24]);
+                buffer.extend((TRACK_ID + 1).to_be_bytes()); // next_track_ID
+            });
+
+            write_box(buffer, b"trak", |buffer| {
+                // Track enabled and in movie
+                write_full_box(buffer, b"tkhd", 0, 0x03, |buffer| {
+                    buffer.extend([0; 8]); // creation and modification time
+                    buffer.extend(TRACK_ID.to_be_bytes());
+                    buffer.extend([0; 4]);
+                    buffer.extend(0_u32.to_be_bytes()); // duration
+                    buffer.extend([0; 8]);
+                    buffer.extend([0; 4]); // layer, alternate_group
+                    buffer.extend([0; 4]); // volume
+                    write_matrix(buffer);
+                    buffer.extend((info.width << 16).to_be_bytes());
+                    buffer.extend((info.height << 16).to_be_bytes());
+                });
+
+                write_box(buffer, b"mdia", |buffer| {
+                    write_full_box(buffer, b"mdhd", 0, 0, |buffer| {
+                        buffer.extend([0; 8]); // creation and modification time
+                        buffer.extend(TIMESCALE.to_be_bytes());
+                        buffer.extend(0_u32.to_be_bytes()); // duration
+                        buffer.extend(0x55c4_u16.to_be_bytes()); // "und" language
+                        buffer.extend([0; 2]);
+                    });
+
+                    write_full_box(buffer, b"hdlr", 0, 0, |buffer| {
+                        buffer.extend([0; 4]);
+                        buffer.extend(b"vide");
+                        buffer.extend([0; 12]);
+                        buffer.extend(b"VideoHandler\0");
+                    });
+
+                    write_box(buffer, b"minf", |buffer| {
+                        write_full_box(buffer, b"vmhd", 0, 0x01, |buffer| {
+                            buffer.extend([0; 8]); // graphicsmode, opcolor
+                        });
+
+                        write_box(buffer, b"dinf", |buffer| {
+                            write_full_box(buffer, b"dref", 0, 0, |buffer| {
+                                buffer.extend(1_u32.to_be_bytes());
+                                // Media data is in the same file
+                                write_full_box(buffer, b"url ", 0, 0x01, |_| ());
+                            });
+                        });
+
+                        write_box(buffer, b"stbl", |buffer| {
+                            write_full_box(buffer, b"stsd", 0, 0, |buffer| {
+                                buffer.extend(1_u32.to_be_bytes());
+                                write_box(buffer, sample_entry_type, |buffer| {
+                                    buffer.extend([0; 6]);
+                                    buffer.extend(1_u16.to_be_bytes()); // data_reference_index
+                                    buffer.extend([0; 16]);
+                                    buffer.extend((info.width as u16).to_be_bytes());
+                                    buffer.extend((info.height as u16).to_be_bytes());
+                                    // 72 dpi
+                                    buffer.extend(0x0048_0000_u32.to_be_bytes());
+                                    buffer.extend(0x0048_0000_u32.to_be_bytes());
+                                    buffer.extend([0; 4]);
+                                    buffer.extend(1_u16.to_be_bytes()); // frame_count
+                                    buffer.extend([0; 32]); // compressorname
+                                    buffer.extend(0x0018_u16.to_be_bytes()); // depth
+                                    buffer.extend((-1_i16).to_be_bytes());
+                                    buffer.extend(decoder_config_box);
+                                });
+                            });
+
+                            // Samples are described in the fragments
+                            for box_type in [b"stts", b"stsc", b"stco"] {
+                                write_full_box(buffer, box_type, 0, 0, |buffer| {
+                                    buffer.extend(0_u32.to_be_bytes());
+                                });
+                            }
+                            write_full_box(buffer, b"stsz", 0, 0, |buffer| {
+                                buffer.extend([0; 8]);
+                            });
+                        });
+                    });
+                });
+            });
+
+            write_box(buffer, b"mvex", |buffer| {
+                write_full_box(buffer, b"trex", 0, 0, |buffer| {
+                    buffer.extend(TRACK_ID.to_be_bytes());
+                    buffer.extend(1_u32.to_be_bytes()); // default_sample_description_index
+                    buffer.extend([0; 12]);
+                });
+            });
+        });
+
+        buffer
+    }
+
+    pub fn config_buffer(&self) -> &[u8] {
+        &self.config_buffer
+    }
+
+    // For MediaSource.addSourceBuffer()
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn init_segment(&self) -> &[u8] {
+        &self.init_segment
+    }
+
+    // To be called when frames were skipped. Fragments are produced again from the next IDR
+    pub fn resync(&mut self) {
+        self.waiting_for_idr = true;
+    }
+
+    // The decode time must start near zero for the browser to start playing immediately. The
+    // sample duration is not known until the next frame, so the last frame interval is used.
+    pub fn fragment(
+        &mut self,
+        decode_time: Duration,
+        is_idr: bool,
+        nal_buffer: &[u8],
+    ) -> Option<Vec<u8>> {
+        if self.waiting_for_idr && !is_idr {
+            return None;
+        }
+        self.waiting_for_idr = false;
+
+        let mut decode_time = decode_time.as_micros() as u64 * TIMESCALE as u64 / 1_000_000;
+        if let Some(last_decode_time) = self.last_decode_time {
+            decode_time = decode_time.max(last_decode_time + 1);
+            self.last_sample_duration = (decode_time - last_decode_time) as u32;
+        }
+        self.last_decode_time = Some(decode_time);
+
+        // Parameter sets are in the init segment
+        let mut sample = Vec::with_capacity(nal_buffer.len());
+        for nal in split_nals(nal_buffer) {
+            let nal_type = nal_type(self.codec, nal);
+            if matches!(
+                (self.codec, nal_type),
+                (CodecType::H264, H264_SPS | H264_PPS | H264_AUD)
+                    | (CodecType::Hevc, HEVC_VPS | HEVC_SPS | HEVC_PPS | HEVC_AUD)
+            ) {
+                continue;
+            }
+
+            sample.extend((nal.len() as u32).to_be_bytes());
+            sample.extend(nal);
+        }
+
+        self.sequence_number += 1;
+
+        let mut buffer = vec![];
+        let mut data_offset_position = 0;
+        write_box(&mut buffer, b"moof", |buffer| {
+            write_full_box(buffer, b"mfhd", 0, 0, |buffer| {
+                buffer.extend(self.sequence_number.to_be_bytes());
+            });
+
+            write_box(buffer, b"traf", |buffer| {
+                // default-base-is-moof
+                write_full_box(buffer, b"tfhd", 0, 0x02_0000, |buffer| {
+                    buffer.extend(TRACK_ID.to_be_bytes());
+                });
+
+                write_full_box(buffer, b"tfdt", 1, 0, |buffer| {
+                    buffer.extend(decode_time.to_be_bytes());
+                });
+
+                // data-offset, sample-duration, sample-size and sample-flags present
+                write_full_box(buffer, b"trun", 0, 0x00_0701, |buffer| {
+                    buffer.extend(1_u32.to_be_bytes());
+                    data_offset_position = buffer.len();
+                    buffer.extend([0; 4]);
+                    buffer.extend(self.last_sample_duration.to_be_bytes());
+                    buffer.extend((sample.len() as u32).to_be_bytes());
+                    let flags = if is_idr {
+                        SYNC_SAMPLE_FLAGS
+                    } else {
+                        NON_SYNC_SAMPLE_FLAGS
+                    };
+                    buffer.extend(flags.to_be_bytes());
+                });
+            });
+        });
+
+        // The sample data starts after the mdat header
+        let data_offset = buffer.len() as u32 + 8;
+        buffer[data_offset_position..data_offset_position + 4]
+            .copy_from_slice(&data_offset.to_be_bytes());
+
+        write_box(&mut buffer, b"mdat", |buffer| buffer.extend(sample));
+
+        Some(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1920x1080 High profile, level 4.2
+    const H264_SPS_NAL: &[u8] = &[
+        0x67, 0x64, 0x00, 0x2a, 0xac, 0xb4, 0x03, 0xc0, 0x11, 0x3f, 0x2a,
+    ];
+    const H264_PPS_NAL: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+    // 1920x1080 Main profile, level 3.1, with emulation prevention bytes
+    const HEVC_SPS_NAL: &[u8] = &[
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x5d, 0xa0, 0x03, 0xc0, 0x80, 0x11, 0x07, 0xcb, 0xc0,
+    ];
+
+    fn annex_b(nals: &[&[u8]]) -> Vec<u8> {
+        nals.iter()
+            .flat_map(|nal| [&[0, 0, 0, 1][..], *nal].concat())
+            .collect()
+    }
+
+    // Returns the type and content of the top level boxes
+    fn parse_boxes(buffer: &[u8]) -> Vec<(String, &[u8])> {
+        let mut boxes = vec![];
+        let mut offset = 0;
+        while offset < buffer.len() {
+            let size = u32::from_be_bytes(buffer[offset..offset + 4].try_into().unwrap()) as usize;
+            let box_type = String::from_utf8_lossy(&buffer[offset + 4..offset + 8]).to_string();
+            boxes.push((box_type, &buffer[offset + 8..offset + size]));
+            offset += size;
+        }
+
+        boxes
+    }
+
+    #[test]
+    fn test_split_nals() {
+        let buffer = [
+            0, 0, 0, 1, 0x67, 0x01, 0, 0, 1, 0x68, 0x02, 0, 0, 0, 1, 0x65,
+        ];
+
+        assert_eq!(
+            split_nals(&buffer),
+            vec![&[0x67, 0x01][..], &[0x68, 0x02], &[0x65]]
+        );
+    }
+
+    #[test]
+    fn test_parse_sps() {
+        let expected = SequenceInfo {
+            width: 1920,
+            height: 1080,
+            chroma_format_idc: 1,
+            bit_depth_luma_minus8: 0,
+            bit_depth_chroma_minus8: 0,
+        };
+
+        assert_eq!(parse_h264_sps(&to_rbsp(H264_SPS_NAL)).unwrap(), expected);
+        assert_eq!(parse_hevc_sps(&to_rbsp(HEVC_SPS_NAL)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_h264_stream() {
+        let mut muxer = Fmp4Muxer::new(&DecoderInitializationConfig {
+            codec: CodecType::H264,
+            config_buffer: annex_b(&[H264_SPS_NAL, H264_PPS_NAL]),
+            ext_str: String::new(),
+        })
+        .unwrap();
+
+        assert_eq!(muxer.mime_type(), "video/mp4; codecs=\"avc1.64002a\"");
+        let init_boxes = parse_boxes(muxer.init_segment());
+        assert_eq!(init_boxes[0].0, "ftyp");
+        assert_eq!(init_boxes[1].0, "moov");
+
+        let slice = [0x41, 0x9a, 0x02];
+        assert!(
+            muxer
+                .fragment(Duration::ZERO, false, &annex_b(&[&slice]))
+                .is_none()
+        );
+
+        let idr = [0x65, 0x88, 0x84, 0x01];
+        let fragment = muxer
+            .fragment(Duration::ZERO, true, &annex_b(&[H264_SPS_NAL, &idr]))
+            .unwrap();
+        let boxes = parse_boxes(&fragment);
+        assert_eq!(boxes[0].0, "moof");
+        assert_eq!(boxes[1].0, "mdat");
+        // Only the IDR slice is kept, length prefixed
+        assert_eq!(boxes[1].1, [&[0, 0, 0, 4][..], &idr].concat());
+
+        // The data offset points to the start of the sample
+        let moof = &fragment[..boxes[0].1.len() + 8];
+        let trun_offset = moof.windows(4).position(|w| w == b"trun").unwrap();
+        let data_offset =
+            u32::from_be_bytes(moof[trun_offset + 12..trun_offset + 16].try_into().unwrap())
+                as usize;
+        assert_eq!(&fragment[data_offset..], boxes[1].1);
+
+        let fragment = muxer
+            .fragment(Duration::from_millis(10), false, &annex_b(&[&slice]))
+            .unwrap();
+        let trun_offset = fragment.windows(4).position(|w| w == b"trun").unwrap();
+        let duration = u32::from_be_bytes(
+            fragment[trun_offset + 16..trun_offset + 20]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(duration, 900);
+    }
+
+    #[test]
+    fn test_hevc_codec_string() {
+        let vps = [0x40, 0x01, 0x0c, 0x01];
+        let pps = [0x44, 0x01, 0xc1, 0x72];
+        let muxer = Fmp4Muxer::new(&DecoderInitializationConfig {
+            codec: CodecType::Hevc,
+            config_buffer: annex_b(&[&vps, HEVC_SPS_NAL, &pps]),
+            ext_str: String::new(),
+        })
+        .unwrap();
+
+        assert_eq!(muxer.mime_type(), "video/mp4; codecs=\"hvc1.1.6.L93.90\"");
+    }
+}
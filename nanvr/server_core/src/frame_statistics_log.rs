@@ -0,0 +1,248 @@
+use configuration::{FrameStatisticsFormat, FrameStatisticsLogConfig};
+use serde::Serialize;
+use shared::{anyhow::Result, info};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+const CSV_HEADER: &str = concat!(
+    "frame_index,target_timestamp_s,tracking_received_s,frame_present_s,frame_composed_s,",
+    "frame_encoded_s,game_latency_s,server_compositor_latency_s,encoder_latency_s,",
+    "network_latency_s,decoder_latency_s,decoder_queue_latency_s,client_compositor_latency_s,",
+    "vsync_queue_latency_s,total_pipeline_latency_s,frame_bytes,requested_bitrate_bps,",
+    "bitrate_bps,throughput_bps,client_frame_interval_s,video_packets_received,video_packets_lost",
+);
+
+// Server timestamps are relative to the start of the logging session. The frame index is assigned
+// by the log.
+#[derive(Serialize, Clone, Default)]
+pub struct FrameStatisticsRecord {
+    pub frame_index: u64,
+    pub target_timestamp_s: f64,
+    pub tracking_received_s: f64,
+    pub frame_present_s: f64,
+    pub frame_composed_s: f64,
+    pub frame_encoded_s: f64,
+    pub game_latency_s: f32,
+    pub server_compositor_latency_s: f32,
+    pub encoder_latency_s: f32,
+    pub network_latency_s: f32,
+    pub decoder_latency_s: f32,
+    pub decoder_queue_latency_s: f32,
+    pub client_compositor_latency_s: f32,
+    pub vsync_queue_latency_s: f32,
+    pub total_pipeline_latency_s: f32,
+    pub frame_bytes: usize,
+    pub requested_bitrate_bps: f32,
+    pub bitrate_bps: f32,
+    pub throughput_bps: f32,
+    pub client_frame_interval_s: f32,
+    pub video_packets_received: u32,
+    pub video_packets_lost: u32,
+}
+
+impl FrameStatisticsRecord {
+    fn to_csv_row(&self) -> String {
+        format!(
+            "{},{:.6},{:.6},{:.6},{:.6},{:.6},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.frame_index,
+            self.target_timestamp_s,
+            self.tracking_received_s,
+            self.frame_present_s,
+            self.frame_composed_s,
+            self.frame_encoded_s,
+            self.game_latency_s,
+            self.server_compositor_latency_s,
+            self.encoder_latency_s,
+            self.network_latency_s,
+            self.decoder_latency_s,
+            self.decoder_queue_latency_s,
+            self.client_compositor_latency_s,
+            self.vsync_queue_latency_s,
+            self.total_pipeline_latency_s,
+            self.frame_bytes,
+            self.requested_bitrate_bps,
+            self.bitrate_bps,
+            self.throughput_bps,
+            self.client_frame_interval_s,
+            self.video_packets_received,
+            self.video_packets_lost,
+        )
+    }
+}
+
+// Writes one record per frame, starting a new file when the current one grows too large
+pub struct FrameStatisticsLog {
+    start_instant: Instant,
+    frame_count: u64,
+    dir: PathBuf,
+    format: FrameStatisticsFormat,
+    max_file_bytes: u64,
+    max_files: usize,
+    session_name: String,
+    file: BufWriter<File>,
+    file_bytes: u64,
+    file_records: usize,
+    file_index: usize,
+    // Files of this logging session, oldest first
+    file_paths: VecDeque<PathBuf>,
+}
+
+impl FrameStatisticsLog {
+    pub fn new(dir: &Path, config: &FrameStatisticsLogConfig) -> Result<Self> {
+        let session_name = format!(
+            "frame_statistics.{}",
+            chrono::Local::now().format("%F.%H-%M-%S")
+        );
+        let (file, path) = Self::create_file(dir, &session_name, config.format, 0)?;
+
+        info!("Logging frame statistics to {}", path.display());
+
+        let mut this = Self {
+            start_instant: Instant::now(),
+            frame_count: 0,
+            dir: dir.to_owned(),
+            format: config.format,
+            max_file_bytes: config.max_file_size_mb * 1_000_000,
+            max_files: config.max_files,
+            session_name,
+            file,
+            file_bytes: 0,
+            file_records: 0,
+            file_index: 0,
+            file_paths: [path].into(),
+        };
+        this.write_header()?;
+
+        Ok(this)
+    }
+
+    pub fn seconds_since_start(&self, instant: Instant) -> f64 {
+        instant
+            .saturating_duration_since(self.start_instant)
+            .as_secs_f64()
+    }
+
+    fn create_file(
+        dir: &Path,
+        session_name: &str,
+        format: FrameStatisticsFormat,
+        index: usize,
+    ) -> Result<(BufWriter<File>, PathBuf)> {
+        let ext = match format {
+            FrameStatisticsFormat::Csv => "csv",
+            FrameStatisticsFormat::Jsonl => "jsonl",
+        };
+        let path = dir.join(format!("{session_name}.{index}.{ext}"));
+
+        Ok((BufWriter::new(File::create(&path)?), path))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.file, "{line}")?;
+        self.file_bytes += line.len() as u64 + 1;
+
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if self.format == FrameStatisticsFormat::Csv {
+            self.write_line(CSV_HEADER)?;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush()?;
+
+        self.file_index += 1;
+        let (file, path) =
+            Self::create_file(&self.dir, &self.session_name, self.format, self.file_index)?;
+        self.file = file;
+        self.file_bytes = 0;
+        self.file_records = 0;
+        self.file_paths.push_back(path);
+
+        while self.file_paths.len() > self.max_files {
+            if let Some(path) = self.file_paths.pop_front() {
+                fs::remove_file(path).ok();
+            }
+        }
+
+        self.write_header()
+    }
+
+    pub fn write(&mut self, mut record: FrameStatisticsRecord) -> Result<()> {
+        record.frame_index = self.frame_count;
+        self.frame_count += 1;
+
+        if self.file_records > 0 && self.file_bytes >= self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        let line = match self.format {
+            FrameStatisticsFormat::Csv => record.to_csv_row(),
+            FrameStatisticsFormat::Jsonl => serde_json::to_string(&record)?,
+        };
+
+        self.write_line(&line)?;
+        self.file_records += 1;
+
+        Ok(())
+    }
+}
+
+impl Drop for FrameStatisticsLog {
+    fn drop(&mut self) {
+        self.file.flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        let dir =
+            std::env::temp_dir().join(format!("frame_statistics_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut log = FrameStatisticsLog::new(
+            &dir,
+            &FrameStatisticsLogConfig {
+                format: FrameStatisticsFormat::Csv,
+                max_file_size_mb: 0,
+                max_files: 3,
+            },
+        )
+        .unwrap();
+        // With a zero size limit each file contains one record
+        for _ in 0..5 {
+            log.write(FrameStatisticsRecord::default()).unwrap();
+        }
+        drop(log);
+
+        let mut paths = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths.len(), 3);
+
+        let content = fs::read_to_string(paths.last().unwrap()).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("4,"));
+        assert_eq!(row.split(',').count(), CSV_HEADER.split(',').count());
+        assert_eq!(lines.next(), None);
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,393 @@
+// Hooks are triggered from the logging backend, so logs emitted from this module must never
+// trigger hooks themselves, and nothing here can lock SESSION_MANAGER (the session event is sent
+// while the write lock is held).
+
+use crate::ServerCoreEvent;
+use configuration::{HookConfig, HooksConfig};
+use events::EventType;
+use reqwest::{Url, redirect::Policy};
+use serde::Serialize;
+use serde_json as json;
+use shared::{
+    anyhow::{Result, bail},
+    info,
+    parking_lot::RwLock,
+    settings_schema::Switch,
+    warn,
+};
+use std::{
+    process::Stdio,
+    sync::{
+        LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net,
+    process::Command,
+    runtime::{self, Runtime},
+    time,
+};
+
+static CONFIG: LazyLock<RwLock<Switch<HooksConfig>>> =
+    LazyLock::new(|| RwLock::new(Switch::Disabled));
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("hooks")
+        .enable_all()
+        .build()
+        .unwrap()
+});
+
+static RUNNING_HOOKS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn is_hooks_log_target(target: &str) -> bool {
+    target.starts_with(module_path!())
+}
+
+pub fn set_config(config: Switch<HooksConfig>) {
+    *CONFIG.write() = config;
+}
+
+fn server_core_event_name(event: &ServerCoreEvent) -> &'static str {
+    match event {
+        ServerCoreEvent::SetOpenvrProperty { .. } => "SetOpenvrProperty",
+        ServerCoreEvent::ClientConnected => "ClientConnected",
+        ServerCoreEvent::ClientDisconnected => "ClientDisconnected",
+        ServerCoreEvent::Battery(_) => "Battery",
+        ServerCoreEvent::PlayspaceSync(_) => "PlayspaceSync",
+        ServerCoreEvent::LocalViewParams(_) => "LocalViewParams",
+        ServerCoreEvent::Tracking { .. } => "Tracking",
+        ServerCoreEvent::Buttons(_) => "Buttons",
+        ServerCoreEvent::RequestIDR => "RequestIDR",
+        ServerCoreEvent::ReconfigureStream => "ReconfigureStream",
+        ServerCoreEvent::CaptureFrame => "CaptureFrame",
+        ServerCoreEvent::GameRenderLatencyFeedback(_) => "GameRenderLatencyFeedback",
+        ServerCoreEvent::ShutdownPending => "ShutdownPending",
+        ServerCoreEvent::RestartPending => "RestartPending",
+    }
+}
+
+pub fn trigger_event(event_type: &EventType) {
+    if let EventType::Session(session) = event_type {
+        set_config(session.to_settings().extra.hooks);
+
+        // Hooks reach arbitrary scripts and URLs
+        let session = EventType::Session(Box::new(session.without_secrets()));
+        dispatch(event_type.name(), &session);
+    } else {
+        dispatch(event_type.name(), event_type);
+    }
+}
+
+pub fn trigger_server_core_event(event: &ServerCoreEvent) {
+    dispatch(server_core_event_name(event), event);
+}
+
+// Both event enums are serialized as {"id": <name>, "data": <data>}
+fn dispatch(name: &str, event: &impl Serialize) {
+    let (hooks, timeout, max_concurrent_hooks) = {
+        let Switch::Enabled(config) = &*CONFIG.read() else {
+            return;
+        };
+
+        let hooks = config
+            .hooks
+            .iter()
+            .filter(|hook| hook.event == name)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        (
+            hooks,
+            Duration::from_millis(config.timeout_ms),
+            config.max_concurrent_hooks,
+        )
+    };
+
+    // Serialize only if there is at least one hook for this event, since some events are very
+    // frequent
+    if hooks.is_empty() {
+        return;
+    }
+
+    let data = json::to_value(event)
+        .ok()
+        .and_then(|mut value| value.get_mut("data").map(|data| data.take()))
+        .unwrap_or(json::Value::Null);
+    let payload = json::json!({ "event": name, "data": data }).to_string();
+
+    for hook in hooks {
+        if RUNNING_HOOKS.fetch_add(1, Ordering::SeqCst) >= max_concurrent_hooks {
+            RUNNING_HOOKS.fetch_sub(1, Ordering::SeqCst);
+            warn!("Too many hooks running, skipping hook for event {name}");
+
+            continue;
+        }
+
+        let name = name.to_owned();
+        let payload = payload.clone();
+        RUNTIME.spawn(async move {
+            run_hook(&name, &hook, &payload, timeout).await;
+
+            RUNNING_HOOKS.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+async fn run_hook(name: &str, hook: &HookConfig, payload: &str, timeout: Duration) {
+    if let Some(script) = &hook.script {
+        match run_script(name, script, payload, timeout).await {
+            Ok(()) => info!("Hook script {script} for event {name} succeeded"),
+            Err(e) => warn!("Hook script {script} for event {name} failed: {e}"),
+        }
+    }
+
+    if let Some(url) = &hook.webhook_url {
+        match post_webhook(url, payload, timeout).await {
+            Ok(()) => info!("Webhook {url} for event {name} succeeded"),
+            Err(e) => warn!("Webhook {url} for event {name} failed: {e}"),
+        }
+    }
+}
+
+// The payload is passed only on stdin, since big events could exceed the environment size limit
+async fn run_script(name: &str, script: &str, payload: &str, timeout: Duration) -> Result<()> {
+    let mut child = Command::new(script)
+        .env("EVENT_NAME", name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // The child is killed if the timeout expires
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            // The script might not read stdin at all, so a broken pipe is not an error
+            stdin.write_all(payload.as_bytes()).await.ok();
+        }
+
+        child.wait().await
+    };
+    let Ok(status) = time::timeout(timeout, run).await else {
+        bail!("timed out");
+    };
+
+    let status = status?;
+    if !status.success() {
+        bail!("{status}");
+    }
+
+    Ok(())
+}
+
+// Events can contain private data, so only URLs that resolve to this machine are accepted. The
+// checked addresses are reused for the request, so a second DNS lookup cannot redirect it.
+async fn post_webhook(url: &str, payload: &str, timeout: Duration) -> Result<()> {
+    let parsed_url = Url::parse(url)?;
+    let Some(host) = parsed_url.host_str() else {
+        bail!("missing host");
+    };
+    let port = parsed_url.port_or_known_default().unwrap_or(80);
+
+    // IPv6 literals are enclosed in brackets
+    let host_ip = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses = net::lookup_host((host_ip, port)).await?.collect::<Vec<_>>();
+    if addresses.is_empty() || !addresses.iter().all(|address| address.ip().is_loopback()) {
+        bail!("{host} is not a local host");
+    }
+
+    let response = reqwest::Client::builder()
+        .resolve_to_addrs(host, &addresses)
+        .redirect(Policy::none())
+        .build()?
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(payload.to_owned())
+        .timeout(timeout)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        bail!("HTTP status {}", response.status());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env, fs,
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+        time::Instant,
+    };
+
+    fn hook_config(url: String, max_concurrent_hooks: usize) -> Switch<HooksConfig> {
+        Switch::Enabled(HooksConfig {
+            hooks: vec![HookConfig {
+                event: "ClientConnected".into(),
+                script: None,
+                webhook_url: Some(url),
+            }],
+            timeout_ms: 5000,
+            max_concurrent_hooks,
+        })
+    }
+
+    // Minimal HTTP stand-in that returns the body of the first request
+    fn spawn_http_server() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = vec![];
+            let mut buffer = [0; 1024];
+            loop {
+                let count = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..count]);
+
+                let request = String::from_utf8_lossy(&request);
+                if let Some((header, body)) = request.split_once("\r\n\r\n") {
+                    let content_length = header
+                        .lines()
+                        .find_map(|line| {
+                            let (key, value) = line.split_once(':')?;
+                            key.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= content_length {
+                        sender.send(body.to_owned()).unwrap();
+                        break;
+                    }
+                }
+            }
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+
+        (url, receiver)
+    }
+
+    // Tests share the global config, so they are run sequentially in a single test
+    #[test]
+    fn test_webhooks() {
+        let (url, receiver) = spawn_http_server();
+        set_config(hook_config(url, 4));
+
+        trigger_server_core_event(&ServerCoreEvent::ClientDisconnected);
+        trigger_server_core_event(&ServerCoreEvent::ClientConnected);
+
+        let body = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        let body = json::from_str::<json::Value>(&body).unwrap();
+        assert_eq!(body["event"], "ClientConnected");
+        assert!(body["data"].is_null());
+
+        while RUNNING_HOOKS.load(Ordering::SeqCst) > 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // With no available slots the hook must be skipped
+        let (url, receiver) = spawn_http_server();
+        set_config(hook_config(url, 0));
+
+        trigger_server_core_event(&ServerCoreEvent::ClientConnected);
+
+        assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+        assert_eq!(RUNNING_HOOKS.load(Ordering::SeqCst), 0);
+
+        set_config(Switch::Disabled);
+    }
+
+    #[test]
+    fn test_non_local_webhook() {
+        let result = RUNTIME.block_on(post_webhook(
+            "http://192.0.2.1/",
+            "{}",
+            Duration::from_secs(1),
+        ));
+        assert!(result.unwrap_err().to_string().contains("not a local host"));
+    }
+
+    #[cfg(unix)]
+    fn write_script(name: &str, content: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = env::temp_dir().join(format!("nanvr_hook_{name}_{}.sh", std::process::id()));
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        path.to_string_lossy().into_owned()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scripts() {
+        let output_path = env::temp_dir().join(format!("nanvr_hook_output_{}", std::process::id()));
+        let script = write_script(
+            "output",
+            &format!(
+                "#!/bin/sh\n{{ echo \"$EVENT_NAME\"; cat; }} > '{}'\n",
+                output_path.display()
+            ),
+        );
+        RUNTIME
+            .block_on(run_script(
+                "ClientConnected",
+                &script,
+                r#"{"event":"ClientConnected"}"#,
+                Duration::from_secs(5),
+            ))
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&output_path).unwrap(),
+            "ClientConnected\n{\"event\":\"ClientConnected\"}"
+        );
+
+        // The exit status is reported in the logged error
+        let failing_script = write_script("failing", "#!/bin/sh\nexit 3\n");
+        let error = RUNTIME
+            .block_on(run_script(
+                "ClientConnected",
+                &failing_script,
+                "{}",
+                Duration::from_secs(5),
+            ))
+            .unwrap_err();
+        assert!(error.to_string().contains('3'));
+
+        // The script is killed when the timeout expires
+        let slow_script = write_script("slow", "#!/bin/sh\nsleep 10\n");
+        let start = Instant::now();
+        let error = RUNTIME
+            .block_on(run_script(
+                "ClientConnected",
+                &slow_script,
+                "{}",
+                Duration::from_millis(100),
+            ))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "timed out");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        for path in [script, failing_script, slow_script] {
+            fs::remove_file(path).ok();
+        }
+        fs::remove_file(output_path).ok();
+    }
+}
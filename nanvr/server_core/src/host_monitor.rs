@@ -0,0 +1,359 @@
+use configuration::HostMonitoringConfig;
+use events::HostResources;
+use nvml_wrapper::{Nvml, enum_wrappers::device::TemperatureSensor};
+use shared::{debug, parking_lot::Mutex, warn};
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+use sysinfo::{Components, Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+// Consecutive samples over a threshold before warning, to ignore short spikes
+const WARNING_SAMPLES: u32 = 5;
+// How far below the threshold a value must go before the warning can be shown again
+const WARNING_HYSTERESIS: f32 = 5.0;
+
+const CPU_SENSOR_LABELS: &[&str] = &["cpu", "package", "tctl", "tdie", "k10temp", "coretemp"];
+
+fn max_option(a: Option<f32>, b: Option<f32>) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(f32::max(a, b)),
+        _ => a.or(b),
+    }
+}
+
+struct ThresholdWarning {
+    name: &'static str,
+    unit: &'static str,
+    threshold: f32,
+    samples_over: u32,
+    warned: bool,
+}
+
+impl ThresholdWarning {
+    fn new(name: &'static str, unit: &'static str, threshold: f32) -> Self {
+        Self {
+            name,
+            unit,
+            threshold,
+            samples_over: 0,
+            warned: false,
+        }
+    }
+
+    // Returns true if the warning was shown
+    fn update(&mut self, value: Option<f32>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+
+        if value > self.threshold {
+            self.samples_over += 1;
+        } else {
+            self.samples_over = 0;
+            if value < self.threshold - WARNING_HYSTERESIS {
+                self.warned = false;
+            }
+        }
+
+        if self.samples_over >= WARNING_SAMPLES && !self.warned {
+            warn!(
+                "{} is {value:.0}{}, over the threshold of {:.0}{}. Streaming performance may be affected",
+                self.name, self.unit, self.threshold, self.unit
+            );
+            self.warned = true;
+
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Default)]
+struct GpuSample {
+    usage_percent: Option<f32>,
+    encoder_percent: Option<f32>,
+    temperature_celsius: Option<f32>,
+}
+
+impl GpuSample {
+    fn merge(self, other: GpuSample) -> Self {
+        Self {
+            usage_percent: max_option(self.usage_percent, other.usage_percent),
+            encoder_percent: max_option(self.encoder_percent, other.encoder_percent),
+            temperature_celsius: max_option(self.temperature_celsius, other.temperature_celsius),
+        }
+    }
+}
+
+fn sample_nvidia_gpus(nvml: &Nvml) -> GpuSample {
+    let mut sample = GpuSample::default();
+
+    for index in 0..nvml.device_count().unwrap_or(0) {
+        let Ok(device) = nvml.device_by_index(index) else {
+            continue;
+        };
+
+        sample = sample.merge(GpuSample {
+            usage_percent: device
+                .utilization_rates()
+                .ok()
+                .map(|rates| rates.gpu as f32),
+            encoder_percent: device
+                .encoder_utilization()
+                .ok()
+                .map(|info| info.utilization as f32),
+            temperature_celsius: device
+                .temperature(TemperatureSensor::Gpu)
+                .ok()
+                .map(|temperature| temperature as f32),
+        });
+    }
+
+    sample
+}
+
+// AMD and Intel GPUs expose their load and temperature through the DRM sysfs interface. The
+// encoder load is not available this way.
+#[cfg(target_os = "linux")]
+fn sample_drm_gpus() -> GpuSample {
+    use std::{fs, path::Path};
+
+    fn read_number(path: &Path) -> Option<f32> {
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    let mut sample = GpuSample::default();
+
+    let Ok(entries) = fs::read_dir("/sys/class/drm") else {
+        return sample;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Skip connectors, like card0-DP-1
+        if !name.starts_with("card") || name.contains('-') {
+            continue;
+        }
+
+        let device_dir = entry.path().join("device");
+        sample.usage_percent = max_option(
+            sample.usage_percent,
+            read_number(&device_dir.join("gpu_busy_percent")),
+        );
+
+        for hwmon in fs::read_dir(device_dir.join("hwmon")).into_iter().flatten() {
+            let Ok(hwmon) = hwmon else {
+                continue;
+            };
+            // Millidegrees Celsius
+            let temperature = read_number(&hwmon.path().join("temp1_input"))
+                .map(|temperature| temperature / 1000.0);
+            sample.temperature_celsius = max_option(sample.temperature_celsius, temperature);
+        }
+    }
+
+    sample
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sample_drm_gpus() -> GpuSample {
+    GpuSample::default()
+}
+
+// Samples the resource usage of the PC. Each source is optional, if it is not available the
+// corresponding values are reported as None.
+struct HostSampler {
+    system: System,
+    components: Components,
+    pid: Option<Pid>,
+    nvml: Option<Nvml>,
+    cpu_warning: ThresholdWarning,
+    gpu_warning: ThresholdWarning,
+    encoder_warning: ThresholdWarning,
+    cpu_temperature_warning: ThresholdWarning,
+    gpu_temperature_warning: ThresholdWarning,
+}
+
+impl HostSampler {
+    fn new(config: &HostMonitoringConfig) -> Self {
+        let nvml = match Nvml::init() {
+            Ok(nvml) => Some(nvml),
+            Err(e) => {
+                debug!("NVML not available, NVIDIA GPU usage will not be monitored: {e}");
+                None
+            }
+        };
+
+        let mut this = Self {
+            system: System::new(),
+            components: Components::new_with_refreshed_list(),
+            pid: sysinfo::get_current_pid().ok(),
+            nvml,
+            cpu_warning: ThresholdWarning::new(
+                "PC CPU usage",
+                "%",
+                config.cpu_warning_threshold_percent,
+            ),
+            gpu_warning: ThresholdWarning::new(
+                "PC GPU usage",
+                "%",
+                config.gpu_warning_threshold_percent,
+            ),
+            encoder_warning: ThresholdWarning::new(
+                "Video encoder usage",
+                "%",
+                config.gpu_warning_threshold_percent,
+            ),
+            cpu_temperature_warning: ThresholdWarning::new(
+                "CPU temperature",
+                "°C",
+                config.temperature_warning_threshold_celsius,
+            ),
+            gpu_temperature_warning: ThresholdWarning::new(
+                "GPU temperature",
+                "°C",
+                config.temperature_warning_threshold_celsius,
+            ),
+        };
+
+        // CPU usage is measured between two refreshes
+        this.sample();
+
+        this
+    }
+
+    fn sample(&mut self) -> HostResources {
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+
+        if let Some(pid) = self.pid {
+            self.system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[pid]),
+                true,
+                ProcessRefreshKind::nothing().with_cpu().with_memory(),
+            );
+        }
+        let process = self.pid.and_then(|pid| self.system.process(pid));
+        // The process usage is relative to a single core
+        let cpu_count = usize::max(self.system.cpus().len(), 1) as f32;
+
+        for component in self.components.list_mut() {
+            component.refresh();
+        }
+        let cpu_temperature_celsius = self
+            .components
+            .list()
+            .iter()
+            .filter(|component| {
+                let label = component.label().to_lowercase();
+                CPU_SENSOR_LABELS.iter().any(|name| label.contains(name))
+            })
+            .fold(None, |acc, component| {
+                max_option(acc, component.temperature())
+            });
+
+        let mut gpu = sample_drm_gpus();
+        if let Some(nvml) = &self.nvml {
+            gpu = gpu.merge(sample_nvidia_gpus(nvml));
+        }
+
+        HostResources {
+            process_cpu_percent: process.map_or(0.0, |process| process.cpu_usage() / cpu_count),
+            system_cpu_percent: self.system.global_cpu_usage(),
+            process_memory_mb: process.map_or(0, |process| process.memory() / 1_000_000),
+            system_memory_used_mb: self.system.used_memory() / 1_000_000,
+            system_memory_total_mb: self.system.total_memory() / 1_000_000,
+            gpu_percent: gpu.usage_percent,
+            encoder_percent: gpu.encoder_percent,
+            gpu_temperature_celsius: gpu.temperature_celsius,
+            cpu_temperature_celsius,
+        }
+    }
+
+    // Shows warnings for the values that stay over their thresholds
+    fn update_warnings(&mut self, resources: &HostResources) {
+        self.cpu_warning.update(Some(resources.system_cpu_percent));
+        self.gpu_warning.update(resources.gpu_percent);
+        self.encoder_warning.update(resources.encoder_percent);
+        self.cpu_temperature_warning
+            .update(resources.cpu_temperature_celsius);
+        self.gpu_temperature_warning
+            .update(resources.gpu_temperature_celsius);
+    }
+}
+
+// Samples on its own thread, since reading the sensors can block. The statistics only copy the
+// latest sample. The thread stops when the monitor is dropped.
+pub struct HostMonitor {
+    resources: Arc<Mutex<HostResources>>,
+    _stop_sender: mpsc::Sender<()>,
+}
+
+impl HostMonitor {
+    pub fn new(config: &HostMonitoringConfig) -> Self {
+        let resources = Arc::new(Mutex::new(HostResources::default()));
+        let (stop_sender, stop_receiver) = mpsc::channel();
+
+        thread::spawn({
+            let config = config.clone();
+            let resources = Arc::clone(&resources);
+            move || {
+                let mut sampler = HostSampler::new(&config);
+
+                while let Err(RecvTimeoutError::Timeout) =
+                    stop_receiver.recv_timeout(SAMPLE_INTERVAL)
+                {
+                    let sample = sampler.sample();
+                    sampler.update_warnings(&sample);
+
+                    *resources.lock() = sample;
+                }
+            }
+        });
+
+        Self {
+            resources,
+            _stop_sender: stop_sender,
+        }
+    }
+
+    pub fn resources(&self) -> HostResources {
+        self.resources.lock().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_warning() {
+        let mut warning = ThresholdWarning::new("GPU usage", "%", 90.0);
+
+        // Short spikes and missing values are ignored
+        for _ in 0..WARNING_SAMPLES - 1 {
+            assert!(!warning.update(Some(99.0)));
+        }
+        assert!(!warning.update(None));
+        assert!(!warning.update(Some(80.0)));
+
+        let shown = (0..WARNING_SAMPLES)
+            .map(|_| warning.update(Some(99.0)))
+            .collect::<Vec<_>>();
+        assert_eq!(shown.iter().filter(|shown| **shown).count(), 1);
+        assert!(shown[WARNING_SAMPLES as usize - 1]);
+
+        // Dipping just below the threshold doesn't show the warning again
+        warning.update(Some(88.0));
+        assert!(!(0..WARNING_SAMPLES).any(|_| warning.update(Some(99.0))));
+
+        warning.update(Some(50.0));
+        assert!((0..WARNING_SAMPLES).any(|_| warning.update(Some(99.0))));
+    }
+}
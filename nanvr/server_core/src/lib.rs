@@ -1,15 +1,26 @@
 mod bitrate;
 mod c_api;
 mod connection;
+mod event_filter;
+mod fmp4;
+mod frame_statistics_log;
 mod hand_gestures;
 mod haptics;
+mod hooks;
+mod host_monitor;
 mod input_mapping;
 mod logging_backend;
+mod metrics;
+mod rest_api;
 mod sockets;
 mod statistics;
+#[cfg(any(test, feature = "test-server"))]
+pub mod test_server;
+mod trace_capture;
 mod tracking;
 mod web_server;
 
+pub use bitrate::simulator as bitrate_simulator;
 pub use c_api::*;
 pub use logging_backend::init_logging;
 pub use tracking::HandType;
@@ -18,17 +29,21 @@ use crate::connection::VideoPacket;
 use bitrate::{BitrateManager, DynamicEncoderParams};
 use configuration::{CodecType, OpenvrProperty, Settings};
 use events::{EventType, HapticsEvent};
+use fmp4::Fmp4MirrorPacket;
+use frame_statistics_log::FrameStatisticsLog;
 
 use net_packets::{
-    BatteryInfo, ButtonEntry, ClientListAction, DecoderInitializationConfig, Haptics,
-    VideoPacketHeader,
+    BatteryInfo, ButtonEntry, ClientListAction, DecoderInitializationConfig,
+    DecoderInitializationConfigExt, Haptics, ServerControlPacket, VideoPacketHeader,
 };
-use net_sockets::StreamSender;
+use net_sockets::{ControlSocketSender, StreamSender};
+use serde::Serialize;
 use server_io::ServerSessionManager;
 use shared::{
     ConnectionState, DEVICE_ID_TO_PATH, DeviceMotion, LifecycleState, Pose, RelaxedAtomic,
     ViewParams, dbg_server_core, error,
-    glam::Vec2,
+    glam::{UVec2, Vec2},
+    info,
     parking_lot::{Mutex, RwLock},
     settings_schema::Switch,
     warn,
@@ -49,6 +64,7 @@ use std::{
     time::{Duration, Instant},
 };
 use tokio::{runtime::Runtime, sync::broadcast};
+use trace_capture::TraceCapture;
 use tracking::TrackingManager;
 
 static FILESYSTEM_LAYOUT: OnceLock<filepaths::Layout> = OnceLock::new();
@@ -70,6 +86,8 @@ pub fn initialize_environment(layout: filepaths::Layout) {
     SESSION_MANAGER.write().session_mut();
 }
 
+#[derive(Serialize)]
+#[serde(tag = "id", content = "data")]
 pub enum ServerCoreEvent {
     SetOpenvrProperty {
         device_id: u64,
@@ -85,19 +103,36 @@ pub enum ServerCoreEvent {
     },
     Buttons(Vec<ButtonEntry>), // Note: this is after mapping
     RequestIDR,
+    // Recreate the encoder with the config stored in the session
+    ReconfigureStream,
     CaptureFrame,
     GameRenderLatencyFeedback(Duration), // only used for SteamVR
     ShutdownPending,
     RestartPending,
 }
 
+// Forwards events to the driver, triggering the configured hooks
+struct ServerCoreEventsSender(mpsc::Sender<ServerCoreEvent>);
+
+impl ServerCoreEventsSender {
+    fn send(&self, event: ServerCoreEvent) -> Result<(), mpsc::SendError<ServerCoreEvent>> {
+        hooks::trigger_server_core_event(&event);
+
+        self.0.send(event)
+    }
+}
+
 pub struct ConnectionContext {
-    events_sender: mpsc::Sender<ServerCoreEvent>,
+    events_sender: ServerCoreEventsSender,
     statistics_manager: RwLock<Option<StatisticsManager>>,
     bitrate_manager: Mutex<BitrateManager>,
     tracking_manager: RwLock<TrackingManager>,
     decoder_config: Mutex<Option<DecoderInitializationConfig>>,
+    // Resolution of the stream known by the client, negotiated or sent with a decoder config
+    client_view_resolution: Mutex<Option<UVec2>>,
+    control_sender: Mutex<Option<Arc<Mutex<ControlSocketSender<ServerControlPacket>>>>>,
     video_mirror_sender: Mutex<Option<broadcast::Sender<Vec<u8>>>>,
+    fmp4_mirror_sender: Mutex<Option<broadcast::Sender<Fmp4MirrorPacket>>>,
     video_recording_file: Mutex<Option<File>>,
     connection_threads: Mutex<Vec<JoinHandle<()>>>,
     clients_to_be_removed: Mutex<HashSet<String>>,
@@ -105,6 +140,41 @@ pub struct ConnectionContext {
     haptics_sender: Mutex<Option<StreamSender<Haptics>>>,
 }
 
+impl ConnectionContext {
+    // A temporary StatisticsManager is created, it is replaced when a headset connects
+    fn new(initial_settings: &Settings, events_sender: mpsc::Sender<ServerCoreEvent>) -> Self {
+        let stats = StatisticsManager::new(
+            initial_settings.connection.statistics_history_size,
+            Duration::from_secs_f32(1.0 / 90.0),
+            if let Switch::Enabled(config) = &initial_settings.headset.controllers {
+                config.steamvr_pipeline_frames
+            } else {
+                0.0
+            },
+            &initial_settings.connection.latency_distribution,
+        );
+
+        Self {
+            events_sender: ServerCoreEventsSender(events_sender),
+            statistics_manager: RwLock::new(Some(stats)),
+            bitrate_manager: Mutex::new(BitrateManager::new(256, 60.0)),
+            tracking_manager: RwLock::new(TrackingManager::new(
+                initial_settings.connection.statistics_history_size,
+            )),
+            decoder_config: Mutex::new(None),
+            client_view_resolution: Mutex::new(None),
+            control_sender: Mutex::new(None),
+            video_mirror_sender: Mutex::new(None),
+            fmp4_mirror_sender: Mutex::new(None),
+            video_recording_file: Mutex::new(None),
+            connection_threads: Mutex::new(Vec::new()),
+            clients_to_be_removed: Mutex::new(HashSet::new()),
+            video_channel_sender: Mutex::new(None),
+            haptics_sender: Mutex::new(None),
+        }
+    }
+}
+
 pub fn create_recording_file(connection_context: &ConnectionContext, settings: &Settings) {
     let codec = settings.video.preferred_codec;
     let ext = match codec {
@@ -137,6 +207,80 @@ pub fn create_recording_file(connection_context: &ConnectionContext, settings: &
     }
 }
 
+pub fn start_frame_statistics_log(connection_context: &ConnectionContext, settings: &Settings) {
+    if let Some(stats) = &mut *connection_context.statistics_manager.write() {
+        match FrameStatisticsLog::new(
+            &FILESYSTEM_LAYOUT.get().unwrap().log_dir,
+            &settings.extra.capture.frame_statistics_log,
+        ) {
+            Ok(log) => stats.set_frame_statistics_log(Some(log)),
+            Err(e) => error!("Failed to log frame statistics on disk: {e}"),
+        }
+    }
+}
+
+pub fn start_trace_capture(connection_context: &ConnectionContext) {
+    if let Some(stats) = &mut *connection_context.statistics_manager.write() {
+        stats.set_trace_capture(Some(TraceCapture::new()));
+        info!("Trace capture started");
+    }
+
+    if let Some(sender) = &*connection_context.control_sender.lock() {
+        sender
+            .lock()
+            .send(&ServerControlPacket::TraceCapture(true))
+            .ok();
+    }
+}
+
+pub fn stop_trace_capture(connection_context: &ConnectionContext) {
+    if let Some(sender) = &*connection_context.control_sender.lock() {
+        sender
+            .lock()
+            .send(&ServerControlPacket::TraceCapture(false))
+            .ok();
+    }
+
+    let maybe_capture = connection_context
+        .statistics_manager
+        .write()
+        .as_mut()
+        .and_then(|stats| stats.take_trace_capture());
+    if let Some(capture) = maybe_capture {
+        let path = FILESYSTEM_LAYOUT.get().unwrap().log_dir.join(format!(
+            "trace.{}.json",
+            chrono::Local::now().format("%F.%H-%M-%S")
+        ));
+        // Serializing a long capture takes seconds, don't block the caller
+        thread::spawn(move || match capture.write(&path) {
+            Ok(()) => info!("Trace saved to {}", path.display()),
+            Err(e) => error!("Failed to save trace: {e}"),
+        });
+    }
+}
+
+// Connected clients are disconnected before being removed from the list
+pub fn update_client_list(
+    connection_context: &ConnectionContext,
+    hostname: String,
+    mut action: ClientListAction,
+) {
+    let mut session_manager = SESSION_MANAGER.write();
+    if matches!(action, ClientListAction::RemoveEntry)
+        && let Some(entry) = session_manager.client_list().get(&hostname)
+        && entry.connection_state != ConnectionState::Disconnected
+    {
+        connection_context
+            .clients_to_be_removed
+            .lock()
+            .insert(hostname.clone());
+
+        action = ClientListAction::SetConnectionState(ConnectionState::Disconnecting);
+    }
+
+    session_manager.update_client_list(hostname, action);
+}
+
 pub fn notify_restart_driver() {
     if sysinfo::System::new_all()
         .processes_by_name(OsStr::new(&filepaths::dashboard_fname()))
@@ -189,43 +333,25 @@ impl ServerCoreContext {
 
         let (events_sender, events_receiver) = mpsc::channel();
 
-        // Create a temporary StatisticsManager until a headset connects
-        let initial_settings = SESSION_MANAGER.read().settings().clone();
-        let stats = StatisticsManager::new(
-            initial_settings.connection.statistics_history_size,
-            Duration::from_secs_f32(1.0 / 90.0),
-            if let Switch::Enabled(config) = &initial_settings.headset.controllers {
-                config.steamvr_pipeline_frames
-            } else {
-                0.0
-            },
-        );
-
-        let connection_context = Arc::new(ConnectionContext {
+        let connection_context = Arc::new(ConnectionContext::new(
+            SESSION_MANAGER.read().settings(),
             events_sender,
-            statistics_manager: RwLock::new(Some(stats)),
-            bitrate_manager: Mutex::new(BitrateManager::new(256, 60.0)),
-            tracking_manager: RwLock::new(TrackingManager::new(
-                initial_settings.connection.statistics_history_size,
-            )),
-            decoder_config: Mutex::new(None),
-            video_mirror_sender: Mutex::new(None),
-            video_recording_file: Mutex::new(None),
-            connection_threads: Mutex::new(Vec::new()),
-            clients_to_be_removed: Mutex::new(HashSet::new()),
-            video_channel_sender: Mutex::new(None),
-            haptics_sender: Mutex::new(None),
-        });
+        ));
+
+        let lifecycle_state = Arc::new(RwLock::new(LifecycleState::StartingUp));
 
         let webserver_runtime = Runtime::new().unwrap();
         webserver_runtime.spawn({
             let connection_context = Arc::clone(&connection_context);
-            async move { shared::show_err(web_server::web_server(connection_context).await) }
+            let lifecycle_state = Arc::clone(&lifecycle_state);
+            async move {
+                shared::show_err(web_server::web_server(connection_context, lifecycle_state).await)
+            }
         });
 
         (
             Self {
-                lifecycle_state: Arc::new(RwLock::new(LifecycleState::StartingUp)),
+                lifecycle_state,
                 is_restarting: RelaxedAtomic::new(false),
                 connection_context,
 
@@ -357,11 +483,52 @@ impl ServerCoreContext {
             file.write_all(&config_buffer).ok();
         }
 
-        *self.connection_context.decoder_config.lock() = Some(DecoderInitializationConfig {
+        // The encoder was (re)created with the resolution stored in the session. The client is
+        // only told about it if it changed.
+        let view_resolution = {
+            let openvr_config = &SESSION_MANAGER.read().session().openvr_config;
+            UVec2::new(
+                openvr_config.encoding_eye_resolution_width,
+                openvr_config.encoding_eye_resolution_height,
+            )
+        };
+        let changed_view_resolution = {
+            let mut client_view_resolution = self.connection_context.client_view_resolution.lock();
+            let changed = *client_view_resolution != Some(view_resolution);
+            *client_view_resolution = Some(view_resolution);
+
+            changed.then_some(view_resolution)
+        };
+
+        let config = DecoderInitializationConfig {
             codec,
             config_buffer,
             ext_str: String::new(),
+        }
+        .with_ext(DecoderInitializationConfigExt {
+            view_resolution: changed_view_resolution,
         });
+
+        if let Some(sender) = &*self.connection_context.fmp4_mirror_sender.lock() {
+            sender.send(Fmp4MirrorPacket::Config(config.clone())).ok();
+        }
+
+        let mut decoder_config_lock = self.connection_context.decoder_config.lock();
+
+        // When the stream is renegotiated the client must reinitialize its decoder before
+        // receiving the next IDR
+        if decoder_config_lock
+            .as_ref()
+            .is_some_and(|old_config| old_config.config_buffer != config.config_buffer)
+            && let Some(sender) = &*self.connection_context.control_sender.lock()
+        {
+            sender
+                .lock()
+                .send(&ServerControlPacket::DecoderConfig(config.clone()))
+                .ok();
+        }
+
+        *decoder_config_lock = Some(config);
     }
 
     pub fn send_video_nal(
@@ -408,6 +575,7 @@ impl ServerCoreContext {
                 }
             }
 
+            let mut frame_dropped = false;
             if !STREAM_CORRUPTED.load(Ordering::SeqCst)
                 || !SESSION_MANAGER
                     .read()
@@ -419,6 +587,27 @@ impl ServerCoreContext {
                     sender.send(nal_buffer.clone()).ok();
                 }
 
+                {
+                    let mut fmp4_mirror_sender_lock =
+                        self.connection_context.fmp4_mirror_sender.lock();
+                    // Drop the channel after the last viewer left instead of copying frames to it
+                    if fmp4_mirror_sender_lock
+                        .as_ref()
+                        .is_some_and(|sender| sender.receiver_count() == 0)
+                    {
+                        *fmp4_mirror_sender_lock = None;
+                    }
+                    if let Some(sender) = &*fmp4_mirror_sender_lock {
+                        sender
+                            .send(Fmp4MirrorPacket::Frame {
+                                timestamp,
+                                is_idr,
+                                nal_buffer: nal_buffer.clone(),
+                            })
+                            .ok();
+                    }
+                }
+
                 if let Some(file) = &mut *self.connection_context.video_recording_file.lock() {
                     file.write_all(&nal_buffer).ok();
                 }
@@ -438,14 +627,23 @@ impl ServerCoreContext {
                         .send(ServerCoreEvent::RequestIDR)
                         .ok();
                     warn!("Dropping video packet. Reason: Can't push to network");
+                    frame_dropped = true;
                 }
             } else {
                 warn!("Dropping video packet. Reason: Waiting for IDR frame");
+                frame_dropped = true;
             }
 
             if let Some(stats) = &mut *self.connection_context.statistics_manager.write() {
                 let encoder_latency = stats.report_frame_encoded(timestamp, buffer_size);
 
+                if is_idr {
+                    stats.report_trace_instant("Encoder", "IDR frame");
+                }
+                if frame_dropped {
+                    stats.report_trace_instant("Network", "Frame dropped");
+                }
+
                 self.connection_context
                     .bitrate_manager
                     .lock()
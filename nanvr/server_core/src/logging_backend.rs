@@ -1,4 +1,4 @@
-use crate::SESSION_MANAGER;
+use crate::{SESSION_MANAGER, hooks};
 use chrono::Local;
 use events::{Event, EventType};
 use fern::Dispatch;
@@ -11,13 +11,13 @@ pub static LOGGING_EVENTS_SENDER: LazyLock<broadcast::Sender<Event>> =
     LazyLock::new(|| broadcast::channel(CHANNEL_CAPACITY).0);
 
 pub fn init_logging(session_log_path: Option<PathBuf>, crash_log_path: Option<PathBuf>) {
-    let debug_groups_config = SESSION_MANAGER
-        .read()
-        .settings()
-        .extra
-        .logging
-        .debug_groups
-        .clone();
+    let (debug_groups_config, hooks_config) = {
+        let session_manager_lock = SESSION_MANAGER.read();
+        let extra = &session_manager_lock.settings().extra;
+
+        (extra.logging.debug_groups.clone(), extra.hooks.clone())
+    };
+    hooks::set_config(hooks_config);
 
     let mut log_dispatch = Dispatch::new()
         // Note: meta::target() is in the format <crate>::<module>
@@ -57,6 +57,10 @@ pub fn init_logging(session_log_path: Option<PathBuf>, crash_log_path: Option<Pa
                 event.message(),
             ));
 
+            if !hooks::is_hooks_log_target(record.target()) {
+                hooks::trigger_event(&event.event_type);
+            }
+
             LOGGING_EVENTS_SENDER.send(event).ok();
         });
 
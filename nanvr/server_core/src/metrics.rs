@@ -0,0 +1,646 @@
+// Statistics exported in the Prometheus text format, served by the web server at /metrics.
+// https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+
+use crate::statistics::StatisticsManager;
+use configuration::{ClientConnectionConfig, LatencyDistributionConfig};
+use events::{
+    FRAME_INTERVAL_DISTRIBUTIONS, GraphStatistics, LATENCY_COMPONENTS,
+    LATENCY_HISTOGRAM_BUCKET_COUNT, LATENCY_HISTOGRAM_BUCKET_MS, LatencyDistribution,
+};
+use shared::{ConnectionState, LifecycleState, NANVR_LOW_NAME};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+const LATENCY_BUCKETS_S: &[f64] = &[
+    0.001, 0.002, 0.004, 0.006, 0.008, 0.01, 0.015, 0.02, 0.03, 0.05, 0.075, 0.1, 0.15, 0.25, 0.5,
+];
+const FRAME_INTERVAL_BUCKETS_S: &[f64] = &[
+    0.005, 0.008, 0.01, 0.0115, 0.0125, 0.014, 0.0167, 0.02, 0.025, 0.033, 0.05, 0.1,
+];
+
+// Cumulative histogram of a latency component or frame interval, in seconds. It also keeps a
+// rolling window of the recent samples for the distributions shown in the dashboard.
+pub struct Histogram {
+    upper_bounds: &'static [f64],
+    // Non cumulative. The last bucket is +Inf
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+    recent_samples: VecDeque<f64>,
+    max_recent_samples: usize,
+    threshold: Option<f64>,
+    count_over_threshold: u64,
+}
+
+impl Histogram {
+    pub fn new(
+        upper_bounds: &'static [f64],
+        max_recent_samples: usize,
+        threshold: Option<f64>,
+    ) -> Self {
+        Self {
+            upper_bounds,
+            bucket_counts: vec![0; upper_bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+            recent_samples: VecDeque::new(),
+            max_recent_samples,
+            threshold,
+            count_over_threshold: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let index = self
+            .upper_bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.upper_bounds.len());
+        self.bucket_counts[index] += 1;
+        self.sum += value;
+        self.count += 1;
+
+        self.recent_samples.push_back(value);
+        if self.recent_samples.len() > self.max_recent_samples {
+            self.recent_samples.pop_front();
+        }
+
+        if self.threshold.is_some_and(|threshold| value > threshold) {
+            self.count_over_threshold += 1;
+        }
+    }
+
+    pub fn distribution(&self, name: &str) -> LatencyDistribution {
+        let mut samples_ms = self
+            .recent_samples
+            .iter()
+            .map(|sample| (sample * 1000.0) as f32)
+            .collect::<Vec<_>>();
+        samples_ms.sort_by(f32::total_cmp);
+
+        // Nearest-rank percentile
+        let percentile = |p: f32| {
+            let rank = (p * samples_ms.len() as f32).ceil() as usize;
+            samples_ms
+                .get(rank.clamp(1, usize::max(samples_ms.len(), 1)) - 1)
+                .copied()
+                .unwrap_or_default()
+        };
+
+        let mut histogram = vec![0; LATENCY_HISTOGRAM_BUCKET_COUNT];
+        for sample_ms in &samples_ms {
+            let bucket = (sample_ms / LATENCY_HISTOGRAM_BUCKET_MS) as usize;
+            histogram[usize::min(bucket, LATENCY_HISTOGRAM_BUCKET_COUNT - 1)] += 1;
+        }
+
+        LatencyDistribution {
+            name: name.to_owned(),
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+            p99_ms: percentile(0.99),
+            max_ms: samples_ms.last().copied().unwrap_or_default(),
+            histogram,
+            frames_over_threshold: self.threshold.map(|_| self.count_over_threshold),
+        }
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.into()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[derive(Default)]
+pub struct MetricsWriter {
+    text: String,
+}
+
+impl MetricsWriter {
+    fn header(&mut self, name: &str, help: &str, metric_type: &str) {
+        writeln!(self.text, "# HELP {NANVR_LOW_NAME}_{name} {help}").ok();
+        writeln!(self.text, "# TYPE {NANVR_LOW_NAME}_{name} {metric_type}").ok();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        write!(self.text, "{NANVR_LOW_NAME}_{name}").ok();
+        if !labels.is_empty() {
+            let labels = labels
+                .iter()
+                .map(|(key, value)| format!(r#"{key}="{}""#, escape_label_value(value)))
+                .collect::<Vec<_>>()
+                .join(",");
+            write!(self.text, "{{{labels}}}").ok();
+        }
+        writeln!(self.text, " {}", format_value(value)).ok();
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, help, "gauge");
+        self.sample(name, &[], value);
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, help, "counter");
+        self.sample(name, &[], value);
+    }
+
+    pub fn labeled_gauge<'a>(
+        &mut self,
+        name: &str,
+        help: &str,
+        samples: impl IntoIterator<Item = (&'a [(&'a str, &'a str)], f64)>,
+    ) {
+        self.header(name, help, "gauge");
+        for (labels, value) in samples {
+            self.sample(name, labels, value);
+        }
+    }
+
+    // Histograms of the same metric, distinguished by the value of one label
+    pub fn histograms<'a>(
+        &mut self,
+        name: &str,
+        help: &str,
+        label: &str,
+        histograms: impl IntoIterator<Item = (&'a str, &'a Histogram)>,
+    ) {
+        self.header(name, help, "histogram");
+        for (label_value, histogram) in histograms {
+            let mut cumulative_count = 0;
+            for (index, count) in histogram.bucket_counts.iter().enumerate() {
+                cumulative_count += count;
+                let upper_bound = histogram
+                    .upper_bounds
+                    .get(index)
+                    .map_or("+Inf".to_owned(), ToString::to_string);
+                self.sample(
+                    &format!("{name}_bucket"),
+                    &[(label, label_value), ("le", &upper_bound)],
+                    cumulative_count as f64,
+                );
+            }
+            self.sample(
+                &format!("{name}_sum"),
+                &[(label, label_value)],
+                histogram.sum,
+            );
+            self.sample(
+                &format!("{name}_count"),
+                &[(label, label_value)],
+                histogram.count as f64,
+            );
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+// Per-frame statistics of the current stream
+pub struct StreamMetrics {
+    // Same order as LATENCY_COMPONENTS
+    latency_histograms: Vec<Histogram>,
+    // Same order as FRAME_INTERVAL_DISTRIBUTIONS
+    frame_interval_histograms: Vec<Histogram>,
+    last_statistics: Option<GraphStatistics>,
+}
+
+impl StreamMetrics {
+    pub fn new(config: &LatencyDistributionConfig) -> Self {
+        let window = config.window_frames;
+        let total_threshold = config.total_latency_threshold_ms as f64 / 1000.0;
+        let interval_threshold = config.frame_interval_threshold_ms as f64 / 1000.0;
+
+        Self {
+            latency_histograms: LATENCY_COMPONENTS
+                .iter()
+                .map(|(component, _)| {
+                    let threshold = (*component == "total").then_some(total_threshold);
+                    Histogram::new(LATENCY_BUCKETS_S, window, threshold)
+                })
+                .collect(),
+            frame_interval_histograms: FRAME_INTERVAL_DISTRIBUTIONS
+                .iter()
+                .map(|_| Histogram::new(FRAME_INTERVAL_BUCKETS_S, window, Some(interval_threshold)))
+                .collect(),
+            last_statistics: None,
+        }
+    }
+
+    pub fn report(&mut self, stats: &GraphStatistics) {
+        let latencies = [
+            stats.total_pipeline_latency_s,
+            stats.game_time_s,
+            stats.server_compositor_s,
+            stats.encoder_s,
+            stats.network_s,
+            stats.decoder_s,
+            stats.decoder_queue_s,
+            stats.client_compositor_s,
+            stats.vsync_queue_s,
+        ];
+        for (histogram, latency) in self.latency_histograms.iter_mut().zip(latencies) {
+            histogram.observe(latency as f64);
+        }
+
+        for (histogram, fps) in self
+            .frame_interval_histograms
+            .iter_mut()
+            .zip([stats.client_fps, stats.server_fps])
+        {
+            histogram.observe(1.0 / fps as f64);
+        }
+
+        self.last_statistics = Some(stats.clone());
+    }
+
+    pub fn write(&self, writer: &mut MetricsWriter) {
+        writer.histograms(
+            "latency_seconds",
+            "Motion-to-photon latency and its components",
+            "component",
+            LATENCY_COMPONENTS
+                .iter()
+                .map(|(component, _)| *component)
+                .zip(&self.latency_histograms),
+        );
+        writer.histograms(
+            "frame_interval_seconds",
+            "Interval between frames on the client and the server",
+            "side",
+            ["client", "server"]
+                .into_iter()
+                .zip(&self.frame_interval_histograms),
+        );
+
+        let Some(stats) = &self.last_statistics else {
+            return;
+        };
+
+        writer.labeled_gauge(
+            "fps",
+            "Last reported framerate",
+            [
+                (&[("side", "client")][..], stats.client_fps as f64),
+                (&[("side", "server")][..], stats.server_fps as f64),
+            ],
+        );
+        writer.gauge(
+            "bitrate_bps",
+            "Bitrate of the last frame",
+            stats.bitrate_bps as f64,
+        );
+        writer.gauge(
+            "requested_bitrate_bps",
+            "Bitrate requested to the encoder",
+            stats.bitrate_directives.requested_bitrate_bps as f64,
+        );
+        writer.gauge(
+            "throughput_bps",
+            "Network throughput of the last frame",
+            stats.throughput_bps as f64,
+        );
+        writer.gauge(
+            "packet_loss_ratio",
+            "Ratio of video packets lost in the recent history",
+            stats.packet_loss_ratio as f64,
+        );
+    }
+
+    // Latency components followed by the frame intervals
+    pub fn latency_distributions(&self) -> Vec<LatencyDistribution> {
+        LATENCY_COMPONENTS
+            .iter()
+            .zip(&self.latency_histograms)
+            .chain(
+                FRAME_INTERVAL_DISTRIBUTIONS
+                    .iter()
+                    .zip(&self.frame_interval_histograms),
+            )
+            .map(|((name, _), histogram)| histogram.distribution(name))
+            .collect()
+    }
+}
+
+fn lifecycle_state_name(state: &LifecycleState) -> &'static str {
+    match state {
+        LifecycleState::StartingUp => "starting_up",
+        LifecycleState::Idle => "idle",
+        LifecycleState::Resumed => "resumed",
+        LifecycleState::ShuttingDown => "shutting_down",
+    }
+}
+
+fn connection_state_name(state: &ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Disconnected => "disconnected",
+        ConnectionState::Connecting => "connecting",
+        ConnectionState::Connected => "connected",
+        ConnectionState::Streaming => "streaming",
+        ConnectionState::Disconnecting => "disconnecting",
+    }
+}
+
+pub fn encode(
+    statistics: Option<&StatisticsManager>,
+    client_list: &HashMap<String, ClientConnectionConfig>,
+    lifecycle_state: &LifecycleState,
+) -> String {
+    let mut writer = MetricsWriter::default();
+
+    let current_state = lifecycle_state_name(lifecycle_state);
+    let states = [
+        LifecycleState::StartingUp,
+        LifecycleState::Idle,
+        LifecycleState::Resumed,
+        LifecycleState::ShuttingDown,
+    ]
+    .map(|state| lifecycle_state_name(&state));
+    let labels = states.map(|state| [("state", state)]);
+    writer.labeled_gauge(
+        "steamvr_state",
+        "Lifecycle state of the SteamVR driver",
+        labels
+            .iter()
+            .zip(states)
+            .map(|(labels, state)| (&labels[..], (state == current_state) as u8 as f64)),
+    );
+
+    let states = [
+        ConnectionState::Disconnected,
+        ConnectionState::Connecting,
+        ConnectionState::Connected,
+        ConnectionState::Streaming,
+        ConnectionState::Disconnecting,
+    ];
+    let mut hostnames = client_list.keys().collect::<Vec<_>>();
+    hostnames.sort();
+    let client_labels = hostnames
+        .iter()
+        .flat_map(|hostname| {
+            states.iter().map(|state| {
+                (
+                    [
+                        ("hostname", hostname.as_str()),
+                        ("state", connection_state_name(state)),
+                    ],
+                    (client_list[*hostname].connection_state == *state) as u8 as f64,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    writer.labeled_gauge(
+        "client_connection_state",
+        "Connection state of each known client",
+        client_labels
+            .iter()
+            .map(|(labels, value)| (&labels[..], *value)),
+    );
+
+    if let Some(statistics) = statistics {
+        statistics.write_metrics(&mut writer);
+    }
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionContext, SESSION_MANAGER, web_server};
+    use hyper::{Body, Request, StatusCode};
+    use net_packets::{ClientListAction, ClientStatistics, ClientStatisticsExt, WifiLinkInfo};
+    use shared::parking_lot::RwLock;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::{Arc, mpsc},
+        time::Duration,
+    };
+    use tokio::net::TcpListener;
+
+    fn sample_value<'a>(text: &'a str, sample: &str) -> Option<&'a str> {
+        text.lines()
+            .find_map(|line| line.strip_prefix(sample)?.strip_prefix(' '))
+    }
+
+    #[test]
+    fn test_histogram_distribution() {
+        let mut histogram = Histogram::new(LATENCY_BUCKETS_S, 100, Some(0.08));
+        // Samples from 1ms to 200ms, only the most recent 100 are kept
+        for ms in 1..=200 {
+            histogram.observe(ms as f64 / 1000.0);
+        }
+        assert_eq!(histogram.count, 200);
+
+        let distribution = histogram.distribution("total");
+        assert_eq!(distribution.name, "total");
+        assert!((distribution.p50_ms - 150.0).abs() < 0.01);
+        assert!((distribution.p90_ms - 190.0).abs() < 0.01);
+        assert!((distribution.p99_ms - 199.0).abs() < 0.01);
+        assert!((distribution.max_ms - 200.0).abs() < 0.01);
+        assert_eq!(distribution.frames_over_threshold, Some(120));
+
+        // All the retained samples are longer than the histogram range
+        assert_eq!(distribution.histogram.len(), LATENCY_HISTOGRAM_BUCKET_COUNT);
+        assert_eq!(
+            distribution.histogram[LATENCY_HISTOGRAM_BUCKET_COUNT - 1],
+            100
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scrape() {
+        let mut statistics = StatisticsManager::new(
+            256,
+            Duration::from_secs_f32(1.0 / 90.0),
+            0.0,
+            &LatencyDistributionConfig {
+                window_frames: 1000,
+                total_latency_threshold_ms: 80.0,
+                frame_interval_threshold_ms: 20.0,
+            },
+        );
+        for i in 0..10 {
+            let target_timestamp = Duration::from_millis(i * 11);
+            statistics.report_tracking_received(target_timestamp);
+            statistics.report_frame_encoded(target_timestamp, 50_000);
+            statistics.report_statistics(
+                ClientStatistics {
+                    target_timestamp,
+                    frame_interval: Duration::from_millis(11),
+                    video_decode: Duration::from_millis(3),
+                    total_pipeline_latency: Duration::from_millis(40),
+                    ..Default::default()
+                }
+                .with_ext(ClientStatisticsExt {
+                    wifi: Some(WifiLinkInfo {
+                        rssi_dbm: Some(-50),
+                        ..Default::default()
+                    }),
+                    dropped_frames: Some(i),
+                    ..Default::default()
+                }),
+            );
+        }
+        statistics.report_battery(*shared::HEAD_ID, 0.5, false);
+
+        let hostname = "metrics.client";
+        {
+            let mut session_manager = SESSION_MANAGER.write();
+            session_manager.update_client_list(
+                hostname.into(),
+                ClientListAction::AddIfMissing {
+                    trusted: true,
+                    manual_ips: vec![],
+                },
+            );
+            session_manager.update_client_list(
+                hostname.into(),
+                ClientListAction::SetConnectionState(ConnectionState::Streaming),
+            );
+        }
+
+        let (events_sender, _events_receiver) = mpsc::channel();
+        let connection_context = Arc::new(ConnectionContext::new(
+            SESSION_MANAGER.read().settings(),
+            events_sender,
+        ));
+        *connection_context.statistics_manager.write() = Some(statistics);
+        let lifecycle_state = Arc::new(RwLock::new(LifecycleState::Resumed));
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(web_server::serve(
+            listener,
+            false,
+            None,
+            Arc::clone(&connection_context),
+            Arc::clone(&lifecycle_state),
+        ));
+        let url = format!("http://{address}/metrics");
+
+        let set_endpoint = |enabled, allow_remote_access| {
+            let mut session_manager = SESSION_MANAGER.write();
+            let mut session = session_manager.session_mut();
+            let config = &mut session.session_settings.connection.metrics_endpoint;
+            config.enabled = enabled;
+            config.content.allow_remote_access = allow_remote_access;
+        };
+        // Requests from another host, which can't be made over the loopback listener
+        let remote_status = || async {
+            let request = Request::get("/metrics").body(Body::empty()).unwrap();
+            web_server::http_api(
+                &connection_context,
+                &lifecycle_state,
+                SocketAddr::new([192, 168, 1, 2].into(), 50000),
+                request,
+            )
+            .await
+            .unwrap()
+            .status()
+        };
+
+        set_endpoint(false, false);
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(remote_status().await, StatusCode::NOT_FOUND);
+
+        set_endpoint(true, false);
+        assert_eq!(remote_status().await, StatusCode::FORBIDDEN);
+
+        set_endpoint(true, true);
+        assert_eq!(remote_status().await, StatusCode::OK);
+
+        // Scrape from the loopback interface, which is allowed without remote access
+        set_endpoint(true, false);
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_TYPE],
+            CONTENT_TYPE
+        );
+        let text = response.text().await.unwrap();
+
+        assert_eq!(
+            sample_value(&text, r#"nanvr_steamvr_state{state="resumed"}"#),
+            Some("1")
+        );
+        assert_eq!(
+            sample_value(
+                &text,
+                r#"nanvr_client_connection_state{hostname="metrics.client",state="streaming"}"#
+            ),
+            Some("1")
+        );
+        assert_eq!(
+            sample_value(
+                &text,
+                r#"nanvr_client_connection_state{hostname="metrics.client",state="connected"}"#
+            ),
+            Some("0")
+        );
+        assert_eq!(
+            sample_value(
+                &text,
+                r#"nanvr_latency_seconds_bucket{component="total",le="0.03"}"#
+            ),
+            Some("0")
+        );
+        assert_eq!(
+            sample_value(
+                &text,
+                r#"nanvr_latency_seconds_bucket{component="total",le="0.05"}"#
+            ),
+            Some("10")
+        );
+        assert_eq!(
+            sample_value(&text, r#"nanvr_latency_seconds_count{component="decoder"}"#),
+            Some("10")
+        );
+        assert_eq!(
+            sample_value(&text, r#"nanvr_battery_level{device="/user/head"}"#),
+            Some("0.5")
+        );
+        assert_eq!(sample_value(&text, "nanvr_video_packets_total"), Some("10"));
+        assert_eq!(
+            sample_value(&text, "nanvr_client_wifi_rssi_dbm"),
+            Some("-50")
+        );
+        assert_eq!(
+            sample_value(&text, "nanvr_client_dropped_frames_total"),
+            Some("9")
+        );
+        assert_eq!(sample_value(&text, "nanvr_client_rendering_fps"), None);
+
+        // Every sample belongs to a declared metric
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().unwrap();
+            let base_name = name
+                .trim_end_matches("_bucket")
+                .trim_end_matches("_sum")
+                .trim_end_matches("_count");
+            assert!(
+                text.contains(&format!("# TYPE {base_name} "))
+                    || text.contains(&format!("# TYPE {name} ")),
+                "{line}"
+            );
+        }
+    }
+}
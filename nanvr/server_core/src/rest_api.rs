@@ -0,0 +1,557 @@
+use crate::{ConnectionContext, FILESYSTEM_LAYOUT, SESSION_MANAGER, ServerCoreEvent};
+use bytes::Buf;
+use hyper::{
+    Body, Method, Request, Response, StatusCode,
+    header::{ALLOW, CONTENT_TYPE},
+};
+use net_packets::{ClientListAction, PathValuePair};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json as json;
+use shared::anyhow::Result;
+use std::path::PathBuf;
+use tokio::task;
+
+pub const BASE_PATH: &str = net_packets::REST_API_BASE_PATH;
+
+fn json_response(code: StatusCode, value: &impl Serialize) -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(code)
+        .header(CONTENT_TYPE, "application/json")
+        .body(json::to_string(value)?.into())?)
+}
+
+fn error_response(code: StatusCode, message: impl ToString) -> Result<Response<Body>> {
+    json_response(code, &json::json!({ "error": message.to_string() }))
+}
+
+async fn parse_body<T: DeserializeOwned>(request: Request<Body>) -> Result<T, String> {
+    let body = hyper::body::aggregate(request)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    json::from_reader(body.reader()).map_err(|e| format!("Invalid request body: {e}"))
+}
+
+// Resources and the methods they accept, relative to BASE_PATH. Segments in braces match any
+// value. Used for the Allow header and checked against the OpenAPI document
+const ROUTES: &[(&str, &str)] = &[
+    ("/", "GET"),
+    ("/version", "GET"),
+    ("/openapi", "GET"),
+    ("/session", "GET, PATCH"),
+    ("/settings", "GET, PATCH"),
+    ("/settings/schema", "GET"),
+    ("/clients", "GET"),
+    ("/clients/{hostname}", "DELETE"),
+    ("/clients/{hostname}/trust", "POST"),
+    ("/drivers", "GET, DELETE"),
+    ("/drivers/nanvr", "POST"),
+    ("/recording", "GET"),
+    ("/recording/start", "POST"),
+    ("/recording/stop", "POST"),
+    ("/video/capture-frame", "POST"),
+    ("/video/idr", "POST"),
+    ("/steamvr/restart", "POST"),
+    ("/steamvr/shutdown", "POST"),
+    ("/statistics", "GET"),
+];
+
+fn route_matches(route: &str, segments: &[&str]) -> bool {
+    let route_segments = route
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    route_segments.len() == segments.len()
+        && route_segments
+            .iter()
+            .zip(segments)
+            .all(|(route_segment, segment)| {
+                route_segment.starts_with('{') || route_segment == segment
+            })
+}
+
+// Methods accepted by each resource, None if the resource doesn't exist
+fn allowed_methods(segments: &[&str]) -> Option<&'static str> {
+    ROUTES
+        .iter()
+        .find(|(route, _)| route_matches(route, segments))
+        .map(|(_, methods)| *methods)
+}
+
+#[derive(Serialize)]
+struct SettingsPatchResponse {
+    steamvr_restart_paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct DriverRequest {
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct RecordingStatus {
+    recording: bool,
+}
+
+// The drivers are read from and written to the OpenVR paths file, so they are accessed on the
+// blocking pool
+async fn drivers_response() -> Result<Response<Body>> {
+    match task::spawn_blocking(server_io::get_registered_drivers).await? {
+        Ok(list) => json_response(StatusCode::OK, &list),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+// Versioned API with synchronous JSON responses. Errors are returned as {"error": "<message>"}.
+pub async fn handle(
+    connection_context: &ConnectionContext,
+    request: Request<Body>,
+) -> Result<Response<Body>> {
+    let path = request.uri().path().to_owned();
+    let segments = path
+        .strip_prefix(BASE_PATH)
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    let method = request.method().clone();
+
+    match (&method, segments.as_slice()) {
+        (&Method::GET, []) | (&Method::GET, ["version"]) => json_response(
+            StatusCode::OK,
+            &json::json!({ "version": shared::NANVR_VERSION.to_string() }),
+        ),
+        (&Method::GET, ["openapi"]) => {
+            json_response(StatusCode::OK, &net_packets::openapi_document())
+        }
+        (&Method::GET, ["session"]) => {
+            json_response(StatusCode::OK, SESSION_MANAGER.read().session())
+        }
+        // Note: each value can be any session subtree
+        (&Method::PATCH, ["session"]) => {
+            let descs = match parse_body::<Vec<PathValuePair>>(request).await {
+                Ok(descs) => descs,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+            };
+
+            let mut session_manager = SESSION_MANAGER.write();
+            match session_manager.set_values(descs) {
+                Ok(()) => json_response(StatusCode::OK, session_manager.session()),
+                Err(e) => error_response(StatusCode::UNPROCESSABLE_ENTITY, e),
+            }
+        }
+        // Settings of the streaming client, with its overrides applied
+        (&Method::GET, ["settings"]) => {
+            json_response(StatusCode::OK, SESSION_MANAGER.read().settings())
+        }
+        (&Method::GET, ["settings", "schema"]) => {
+            json_response(StatusCode::OK, &configuration::settings_json_schema())
+        }
+        // Partial session settings, merged on top of the current ones
+        (&Method::PATCH, ["settings"]) => {
+            let overrides = match parse_body::<json::Value>(request).await {
+                Ok(overrides) => overrides,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+            };
+
+            match SESSION_MANAGER.write().apply_settings_overrides(&overrides) {
+                Ok(steamvr_restart_paths) => json_response(
+                    StatusCode::OK,
+                    &SettingsPatchResponse {
+                        steamvr_restart_paths,
+                    },
+                ),
+                Err(e) => error_response(StatusCode::UNPROCESSABLE_ENTITY, e),
+            }
+        }
+        (&Method::GET, ["clients"]) => {
+            json_response(StatusCode::OK, SESSION_MANAGER.read().client_list())
+        }
+        (&Method::POST, ["clients", hostname, "trust"]) => {
+            if !SESSION_MANAGER.read().client_list().contains_key(*hostname) {
+                return error_response(StatusCode::NOT_FOUND, "Client not found");
+            }
+
+            crate::update_client_list(
+                connection_context,
+                hostname.to_string(),
+                ClientListAction::Trust,
+            );
+
+            json_response(
+                StatusCode::OK,
+                &SESSION_MANAGER.read().client_list().get(*hostname),
+            )
+        }
+        (&Method::DELETE, ["clients", hostname]) => {
+            if !SESSION_MANAGER.read().client_list().contains_key(*hostname) {
+                return error_response(StatusCode::NOT_FOUND, "Client not found");
+            }
+
+            crate::update_client_list(
+                connection_context,
+                hostname.to_string(),
+                ClientListAction::RemoveEntry,
+            );
+
+            Ok(Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .body(Body::empty())?)
+        }
+        (&Method::GET, ["drivers"]) => drivers_response().await,
+        (&Method::POST, ["drivers", "nanvr"]) => {
+            let driver_dir = FILESYSTEM_LAYOUT
+                .get()
+                .unwrap()
+                .openvr_driver_root_dir
+                .clone();
+            let result =
+                task::spawn_blocking(move || server_io::driver_registration(&[driver_dir], true))
+                    .await?;
+            if let Err(e) = result {
+                return error_response(StatusCode::INTERNAL_SERVER_ERROR, e);
+            }
+
+            drivers_response().await
+        }
+        (&Method::DELETE, ["drivers"]) => {
+            let driver = match parse_body::<DriverRequest>(request).await {
+                Ok(driver) => driver,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, e),
+            };
+
+            // Returns false if the driver is not registered
+            let result = task::spawn_blocking(move || {
+                if !server_io::get_registered_drivers()
+                    .is_ok_and(|list| list.contains(&driver.path))
+                {
+                    return Ok(false);
+                }
+
+                server_io::driver_registration(&[driver.path], false).map(|()| true)
+            })
+            .await?;
+            match result {
+                Ok(true) => (),
+                Ok(false) => {
+                    return error_response(StatusCode::NOT_FOUND, "Driver not registered");
+                }
+                Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e),
+            }
+
+            drivers_response().await
+        }
+        (&Method::GET, ["recording"]) => json_response(
+            StatusCode::OK,
+            &RecordingStatus {
+                recording: connection_context.video_recording_file.lock().is_some(),
+            },
+        ),
+        (&Method::POST, ["recording", "start"]) => {
+            if connection_context.video_channel_sender.lock().is_none() {
+                return error_response(StatusCode::CONFLICT, "No client is streaming");
+            }
+
+            crate::create_recording_file(connection_context, SESSION_MANAGER.read().settings());
+
+            let recording = connection_context.video_recording_file.lock().is_some();
+            if recording {
+                json_response(StatusCode::OK, &RecordingStatus { recording })
+            } else {
+                error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to create the recording file",
+                )
+            }
+        }
+        (&Method::POST, ["recording", "stop"]) => {
+            *connection_context.video_recording_file.lock() = None;
+
+            json_response(StatusCode::OK, &RecordingStatus { recording: false })
+        }
+        // The frame is captured or the IDR is inserted asynchronously by the encoder
+        (&Method::POST, ["video", action @ ("capture-frame" | "idr")]) => {
+            let event = if *action == "idr" {
+                ServerCoreEvent::RequestIDR
+            } else {
+                ServerCoreEvent::CaptureFrame
+            };
+            connection_context.events_sender.send(event).ok();
+
+            Ok(Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(Body::empty())?)
+        }
+        // SteamVR is restarted or shut down asynchronously
+        (&Method::POST, ["steamvr", action @ ("restart" | "shutdown")]) => {
+            let event = if *action == "restart" {
+                ServerCoreEvent::RestartPending
+            } else {
+                ServerCoreEvent::ShutdownPending
+            };
+            connection_context.events_sender.send(event).ok();
+
+            Ok(Response::builder()
+                .status(StatusCode::ACCEPTED)
+                .body(Body::empty())?)
+        }
+        (&Method::GET, ["statistics"]) => {
+            let summary = connection_context
+                .statistics_manager
+                .read()
+                .as_ref()
+                .and_then(|stats| stats.last_summary().cloned());
+
+            if let Some(summary) = summary {
+                json_response(StatusCode::OK, &summary)
+            } else {
+                error_response(StatusCode::NOT_FOUND, "No client is streaming")
+            }
+        }
+        (_, segments) => {
+            if let Some(methods) = allowed_methods(segments) {
+                let mut response = error_response(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    format!("Method {method} not allowed"),
+                )?;
+                response.headers_mut().insert(ALLOW, methods.parse()?);
+
+                Ok(response)
+            } else {
+                error_response(StatusCode::NOT_FOUND, "Resource not found")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::HeaderMap;
+    use std::sync::mpsc;
+
+    fn connection_context() -> (ConnectionContext, mpsc::Receiver<ServerCoreEvent>) {
+        let (events_sender, events_receiver) = mpsc::channel();
+
+        (
+            ConnectionContext::new(SESSION_MANAGER.read().settings(), events_sender),
+            events_receiver,
+        )
+    }
+
+    async fn call(
+        connection_context: &ConnectionContext,
+        method: Method,
+        path: &str,
+        body: &str,
+    ) -> (StatusCode, HeaderMap, json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::from(body.to_owned()))
+            .unwrap();
+        let response = handle(connection_context, request).await.unwrap();
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let value = if body.is_empty() {
+            json::Value::Null
+        } else {
+            json::from_slice(&body).unwrap()
+        };
+
+        (status, headers, value)
+    }
+
+    #[tokio::test]
+    async fn test_routing() {
+        let (context, events_receiver) = connection_context();
+
+        for path in ["/api/v1", "/api/v1/", "/api/v1/version"] {
+            let (status, headers, body) = call(&context, Method::GET, path, "").await;
+            assert_eq!(status, StatusCode::OK, "{path}");
+            assert_eq!(headers[CONTENT_TYPE], "application/json");
+            assert_eq!(body["version"], shared::NANVR_VERSION);
+        }
+
+        let (status, _, body) = call(&context, Method::GET, "/api/v1/recording", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json::json!({ "recording": false }));
+
+        let (status, _, body) = call(&context, Method::POST, "/api/v1/video/idr", "").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body, json::Value::Null);
+        assert!(matches!(
+            events_receiver.try_recv(),
+            Ok(ServerCoreEvent::RequestIDR)
+        ));
+
+        let (status, _, body) = call(&context, Method::POST, "/api/v1/steamvr/restart", "").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body, json::Value::Null);
+        assert!(matches!(
+            events_receiver.try_recv(),
+            Ok(ServerCoreEvent::RestartPending)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_error_status_codes() {
+        let (context, _events_receiver) = connection_context();
+
+        let cases = [
+            (Method::GET, "/api/v1/unknown", "", StatusCode::NOT_FOUND),
+            (
+                Method::GET,
+                "/api/v1/version/extra",
+                "",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/clients/missing.client",
+                "",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                Method::POST,
+                "/api/v1/clients/missing.client/trust",
+                "",
+                StatusCode::NOT_FOUND,
+            ),
+            // No client is streaming
+            (Method::GET, "/api/v1/statistics", "", StatusCode::NOT_FOUND),
+            (
+                Method::POST,
+                "/api/v1/recording/start",
+                "",
+                StatusCode::CONFLICT,
+            ),
+            (
+                Method::PATCH,
+                "/api/v1/session",
+                "{",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Method::PATCH,
+                "/api/v1/session",
+                r#"[{"path": [{"Name": "missing"}], "value": 1}]"#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                Method::PATCH,
+                "/api/v1/settings",
+                "[",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Method::PATCH,
+                "/api/v1/settings",
+                r#"{"missing": 1}"#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/drivers",
+                "{}",
+                StatusCode::BAD_REQUEST,
+            ),
+        ];
+        for (method, path, body, expected_status) in cases {
+            let (status, headers, response) = call(&context, method.clone(), path, body).await;
+            assert_eq!(status, expected_status, "{method} {path}");
+            assert_eq!(headers[CONTENT_TYPE], "application/json");
+            assert!(response["error"].is_string(), "{method} {path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_method_not_allowed() {
+        let (context, _events_receiver) = connection_context();
+
+        let cases = [
+            (Method::POST, "/api/v1/version", "GET"),
+            (Method::PUT, "/api/v1/session", "GET, PATCH"),
+            (Method::GET, "/api/v1/clients/quest.client", "DELETE"),
+            (Method::GET, "/api/v1/recording/start", "POST"),
+            (Method::POST, "/api/v1/drivers", "GET, DELETE"),
+        ];
+        for (method, path, allowed) in cases {
+            let (status, headers, response) = call(&context, method.clone(), path, "").await;
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{method} {path}");
+            assert_eq!(headers[ALLOW], allowed, "{method} {path}");
+            assert!(response["error"].is_string());
+        }
+    }
+
+    fn sorted_methods(methods: impl Iterator<Item = String>) -> Vec<String> {
+        let mut methods = methods.collect::<Vec<_>>();
+        methods.sort();
+
+        methods
+    }
+
+    // The OpenAPI document must describe exactly the routed resources and methods
+    #[test]
+    fn test_openapi_matches_routes() {
+        let document = net_packets::openapi_document();
+        let paths = document["paths"].as_object().unwrap();
+
+        let documented_routes = paths
+            .keys()
+            .filter_map(|path| {
+                if path == BASE_PATH {
+                    Some("/")
+                } else {
+                    path.strip_prefix(BASE_PATH)
+                }
+            })
+            .collect::<Vec<_>>();
+        for route in &documented_routes {
+            assert!(
+                ROUTES.iter().any(|(routed, _)| routed == route),
+                "{route} is documented but not routed"
+            );
+        }
+
+        for (route, methods) in ROUTES {
+            assert!(
+                documented_routes.contains(route),
+                "{route} is routed but not documented"
+            );
+
+            let path = if *route == "/" {
+                BASE_PATH.to_string()
+            } else {
+                format!("{BASE_PATH}{route}")
+            };
+            let documented_methods = sorted_methods(
+                paths[&path]
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|key| *key != "parameters")
+                    .map(|method| method.to_uppercase()),
+            );
+            let routed_methods = sorted_methods(methods.split(", ").map(String::from));
+
+            assert_eq!(documented_methods, routed_methods, "{route}");
+        }
+    }
+
+    #[test]
+    fn test_allowed_methods() {
+        assert_eq!(allowed_methods(&[]), Some("GET"));
+        assert_eq!(allowed_methods(&["clients", "pico.client"]), Some("DELETE"));
+        assert_eq!(
+            allowed_methods(&["clients", "pico.client", "trust"]),
+            Some("POST")
+        );
+        assert_eq!(allowed_methods(&["settings", "schema"]), Some("GET"));
+        assert_eq!(allowed_methods(&["clients", "pico.client", "other"]), None);
+        assert_eq!(allowed_methods(&["unknown"]), None);
+    }
+}
@@ -1,6 +1,13 @@
-use events::{BitrateDirectives, EventType, GraphStatistics, StatisticsSummary};
-use net_packets::ClientStatistics;
-use shared::{HEAD_ID, SlidingWindowAverage};
+use crate::{
+    frame_statistics_log::{FrameStatisticsLog, FrameStatisticsRecord},
+    host_monitor::HostMonitor,
+    metrics::{MetricsWriter, StreamMetrics},
+    trace_capture::{TraceCapture, TraceProcess},
+};
+use configuration::LatencyDistributionConfig;
+use events::{BitrateDirectives, EventType, GraphStatistics, HostResources, StatisticsSummary};
+use net_packets::{ClientStatistics, ClientStatisticsExt};
+use shared::{DEVICE_ID_TO_PATH, HEAD_ID, SlidingWindowAverage, error};
 use std::{
     collections::{HashMap, VecDeque},
     time::{Duration, Instant},
@@ -56,6 +63,15 @@ pub struct StatisticsManager {
     last_vsync_time: Instant,
     frame_interval: Duration,
     last_throughput_directives: BitrateDirectives,
+    // (received, lost) video packets for each statistics report
+    packet_loss_history: VecDeque<(u32, u32)>,
+    metrics: StreamMetrics,
+    client_telemetry: Option<ClientStatisticsExt>,
+    host_monitor: Option<HostMonitor>,
+    host_resources: Option<HostResources>,
+    last_summary: Option<StatisticsSummary>,
+    frame_statistics_log: Option<FrameStatisticsLog>,
+    trace_capture: Option<TraceCapture>,
 }
 
 impl StatisticsManager {
@@ -64,6 +80,7 @@ impl StatisticsManager {
         max_history_size: usize,
         nominal_server_frame_interval: Duration,
         steamvr_pipeline_frames: f32,
+        latency_distribution: &LatencyDistributionConfig,
     ) -> Self {
         Self {
             history_buffer: VecDeque::new(),
@@ -86,6 +103,14 @@ impl StatisticsManager {
             last_vsync_time: Instant::now(),
             frame_interval: nominal_server_frame_interval,
             last_throughput_directives: BitrateDirectives::default(),
+            packet_loss_history: VecDeque::new(),
+            metrics: StreamMetrics::new(latency_distribution),
+            client_telemetry: None,
+            host_monitor: None,
+            host_resources: None,
+            last_summary: None,
+            frame_statistics_log: None,
+            trace_capture: None,
         }
     }
 
@@ -175,9 +200,26 @@ impl StatisticsManager {
     // Called every frame. Some statistics are reported once every frame
     // Returns (network latency, game time latency)
     pub fn report_statistics(&mut self, client_stats: ClientStatistics) -> (Duration, Duration) {
+        // The telemetry is attached only to some frames
+        let mut client_trace_events = None;
+        if !client_stats.ext_str.is_empty()
+            && let Ok(mut telemetry) = client_stats.ext()
+        {
+            client_trace_events = telemetry.trace_events.take();
+            self.client_telemetry = Some(telemetry);
+        }
+
         self.motion_to_photon_latency_average
             .submit_sample(client_stats.total_pipeline_latency);
 
+        self.packet_loss_history.push_back((
+            client_stats.video_packets_received,
+            client_stats.video_packets_lost,
+        ));
+        if self.packet_loss_history.len() > self.max_history_size {
+            self.packet_loss_history.pop_front();
+        }
+
         if let Some(frame) = self
             .history_buffer
             .iter_mut()
@@ -223,7 +265,9 @@ impl StatisticsManager {
 
                 let interval_secs = FULL_REPORT_INTERVAL.as_secs_f32();
 
-                events::send_event(EventType::StatisticsSummary(StatisticsSummary {
+                self.host_resources = self.host_monitor.as_ref().map(HostMonitor::resources);
+
+                let summary = StatisticsSummary {
                     video_packets_total: self.video_packets_total,
                     video_packets_per_sec: (self.video_packets_partial_sum as f32 / interval_secs)
                         as _,
@@ -250,7 +294,12 @@ impl StatisticsManager {
                         .cloned()
                         .unwrap_or_default()
                         .is_plugged,
-                }));
+                    latency_distributions: self.metrics.latency_distributions(),
+                    client_telemetry: self.client_telemetry.clone(),
+                    host_resources: self.host_resources.clone(),
+                };
+                self.last_summary = Some(summary.clone());
+                events::send_event(EventType::StatisticsSummary(summary));
 
                 self.video_packets_partial_sum = 0;
                 self.video_bytes_partial_sum = 0;
@@ -262,9 +311,18 @@ impl StatisticsManager {
             let bitrate_bps = packet_bits
                 / Duration::max(self.last_frame_present_interval, EPS_INTERVAL).as_secs_f32();
 
+            let (received_packets, lost_packets) = self.packet_loss_history.iter().fold(
+                (0, 0),
+                |(received_acc, lost_acc), (received, lost)| {
+                    (received_acc + received, lost_acc + lost)
+                },
+            );
+            let packet_loss_ratio =
+                lost_packets as f32 / u32::max(received_packets + lost_packets, 1) as f32;
+
             // todo: use target timestamp in nanoseconds. the dashboard needs to use the first
             // timestamp as the graph time origin.
-            events::send_event(EventType::GraphStatistics(GraphStatistics {
+            let graph_statistics = GraphStatistics {
                 total_pipeline_latency_s: client_stats.total_pipeline_latency.as_secs_f32(),
                 game_time_s: game_time_latency.as_secs_f32(),
                 server_compositor_s: server_compositor_latency.as_secs_f32(),
@@ -276,10 +334,84 @@ impl StatisticsManager {
                 vsync_queue_s: client_stats.vsync_queue.as_secs_f32(),
                 client_fps,
                 server_fps,
+                packet_loss_ratio,
                 bitrate_directives: self.last_throughput_directives.clone(),
                 throughput_bps,
                 bitrate_bps,
-            }));
+            };
+
+            self.metrics.report(&graph_statistics);
+
+            if let Some(trace) = &mut self.trace_capture {
+                let frame_timestamp = client_stats.target_timestamp;
+                for (track, name, start, duration) in [
+                    ("Game", "Render", frame.tracking_received, game_time_latency),
+                    (
+                        "Compositor",
+                        "Composite",
+                        frame.frame_present,
+                        server_compositor_latency,
+                    ),
+                    ("Encoder", "Encode", frame.frame_composed, encoder_latency),
+                    ("Network", "Transmit", frame.frame_encoded, network_latency),
+                ] {
+                    trace.span(
+                        TraceProcess::Streamer,
+                        track,
+                        name,
+                        start,
+                        duration,
+                        frame_timestamp,
+                    );
+                }
+
+                if let Some(events) = client_trace_events {
+                    // The network latency contains the transport of both the tracking and the
+                    // video packets. Half of it is the estimated offset between the client input
+                    // acquisition and the tracking being received by the streamer.
+                    let input_acquired = frame
+                        .tracking_received
+                        .checked_sub(network_latency / 2)
+                        .unwrap_or(frame.tracking_received);
+                    trace.add_client_events(input_acquired, frame_timestamp, events);
+                }
+            }
+
+            if let Some(log) = &mut self.frame_statistics_log {
+                let record = FrameStatisticsRecord {
+                    frame_index: 0,
+                    target_timestamp_s: client_stats.target_timestamp.as_secs_f64(),
+                    tracking_received_s: log.seconds_since_start(frame.tracking_received),
+                    frame_present_s: log.seconds_since_start(frame.frame_present),
+                    frame_composed_s: log.seconds_since_start(frame.frame_composed),
+                    frame_encoded_s: log.seconds_since_start(frame.frame_encoded),
+                    game_latency_s: graph_statistics.game_time_s,
+                    server_compositor_latency_s: graph_statistics.server_compositor_s,
+                    encoder_latency_s: graph_statistics.encoder_s,
+                    network_latency_s: graph_statistics.network_s,
+                    decoder_latency_s: graph_statistics.decoder_s,
+                    decoder_queue_latency_s: graph_statistics.decoder_queue_s,
+                    client_compositor_latency_s: graph_statistics.client_compositor_s,
+                    vsync_queue_latency_s: graph_statistics.vsync_queue_s,
+                    total_pipeline_latency_s: graph_statistics.total_pipeline_latency_s,
+                    frame_bytes: frame.video_packet_bytes,
+                    requested_bitrate_bps: graph_statistics
+                        .bitrate_directives
+                        .requested_bitrate_bps,
+                    bitrate_bps,
+                    throughput_bps,
+                    client_frame_interval_s: client_stats.frame_interval.as_secs_f32(),
+                    video_packets_received: client_stats.video_packets_received,
+                    video_packets_lost: client_stats.video_packets_lost,
+                };
+
+                if let Err(e) = log.write(record) {
+                    error!("Failed to write frame statistics, stopping: {e}");
+                    self.frame_statistics_log = None;
+                }
+            }
+
+            events::send_event(EventType::GraphStatistics(graph_statistics));
 
             (network_latency, game_time_latency)
         } else {
@@ -291,6 +423,184 @@ impl StatisticsManager {
         self.motion_to_photon_latency_average.get_average()
     }
 
+    pub fn last_summary(&self) -> Option<&StatisticsSummary> {
+        self.last_summary.as_ref()
+    }
+
+    pub fn set_host_monitor(&mut self, monitor: Option<HostMonitor>) {
+        self.host_monitor = monitor;
+    }
+
+    pub fn set_frame_statistics_log(&mut self, log: Option<FrameStatisticsLog>) {
+        self.frame_statistics_log = log;
+    }
+
+    pub fn is_logging_frame_statistics(&self) -> bool {
+        self.frame_statistics_log.is_some()
+    }
+
+    pub fn take_frame_statistics_log(&mut self) -> Option<FrameStatisticsLog> {
+        self.frame_statistics_log.take()
+    }
+
+    pub fn set_trace_capture(&mut self, capture: Option<TraceCapture>) {
+        self.trace_capture = capture;
+    }
+
+    pub fn is_capturing_trace(&self) -> bool {
+        self.trace_capture.is_some()
+    }
+
+    pub fn take_trace_capture(&mut self) -> Option<TraceCapture> {
+        self.trace_capture.take()
+    }
+
+    pub fn report_trace_instant(&mut self, track: &str, name: &str) {
+        if let Some(trace) = &mut self.trace_capture {
+            trace.instant(TraceProcess::Streamer, track, name, Instant::now());
+        }
+    }
+
+    pub fn write_metrics(&self, writer: &mut MetricsWriter) {
+        writer.counter(
+            "video_packets_total",
+            "Video frames sent to the client",
+            self.video_packets_total as f64,
+        );
+        writer.counter(
+            "video_bytes_total",
+            "Video bytes sent to the client",
+            self.video_bytes_total as f64,
+        );
+
+        let mut batteries = self
+            .battery_gauges
+            .iter()
+            .filter_map(|(id, data)| Some((*DEVICE_ID_TO_PATH.get(id)?, data)))
+            .collect::<Vec<_>>();
+        batteries.sort_by_key(|(path, _)| *path);
+        let labels = batteries
+            .iter()
+            .map(|(path, _)| [("device", *path)])
+            .collect::<Vec<_>>();
+        writer.labeled_gauge(
+            "battery_level",
+            "Battery level of each device, in the range [0, 1]",
+            labels
+                .iter()
+                .zip(&batteries)
+                .map(|(labels, (_, data))| (&labels[..], data.gauge_value as f64)),
+        );
+        writer.labeled_gauge(
+            "battery_plugged",
+            "Whether each device is charging",
+            labels
+                .iter()
+                .zip(&batteries)
+                .map(|(labels, (_, data))| (&labels[..], data.is_plugged as u8 as f64)),
+        );
+
+        if let Some(telemetry) = &self.client_telemetry {
+            let wifi = telemetry.wifi.clone().unwrap_or_default();
+            for (name, help, value) in [
+                (
+                    "client_wifi_rssi_dbm",
+                    "Wi-Fi signal strength of the client",
+                    wifi.rssi_dbm.map(f64::from),
+                ),
+                (
+                    "client_wifi_link_speed_mbps",
+                    "Wi-Fi link speed of the client",
+                    wifi.link_speed_mbps.map(f64::from),
+                ),
+                (
+                    "client_wifi_frequency_mhz",
+                    "Wi-Fi frequency of the client",
+                    wifi.frequency_mhz.map(f64::from),
+                ),
+                (
+                    "client_decoder_queue_frames",
+                    "Frames waiting in the client decoder",
+                    telemetry.decoder_queue_frames.map(f64::from),
+                ),
+                (
+                    "client_thermal_status",
+                    "Client thermal status, from 0 (none) to 6 (shutdown)",
+                    telemetry.thermal_status.map(|status| status as u8 as f64),
+                ),
+                (
+                    "client_rendering_fps",
+                    "Frames per second displayed by the client",
+                    telemetry.rendering_fps.map(f64::from),
+                ),
+            ] {
+                if let Some(value) = value {
+                    writer.gauge(name, help, value);
+                }
+            }
+
+            if let Some(dropped_frames) = telemetry.dropped_frames {
+                writer.counter(
+                    "client_dropped_frames_total",
+                    "Frames received by the client but never decoded",
+                    dropped_frames as f64,
+                );
+            }
+        }
+
+        if let Some(resources) = &self.host_resources {
+            for (name, help, value) in [
+                (
+                    "host_cpu_usage_percent",
+                    "CPU usage of the PC",
+                    Some(resources.system_cpu_percent),
+                ),
+                (
+                    "host_process_cpu_usage_percent",
+                    "CPU usage of the streamer process",
+                    Some(resources.process_cpu_percent),
+                ),
+                (
+                    "host_gpu_usage_percent",
+                    "GPU usage of the PC",
+                    resources.gpu_percent,
+                ),
+                (
+                    "host_encoder_usage_percent",
+                    "Video encoder usage of the PC",
+                    resources.encoder_percent,
+                ),
+                (
+                    "host_cpu_temperature_celsius",
+                    "CPU temperature of the PC",
+                    resources.cpu_temperature_celsius,
+                ),
+                (
+                    "host_gpu_temperature_celsius",
+                    "GPU temperature of the PC",
+                    resources.gpu_temperature_celsius,
+                ),
+            ] {
+                if let Some(value) = value {
+                    writer.gauge(name, help, value as f64);
+                }
+            }
+
+            writer.gauge(
+                "host_memory_used_bytes",
+                "Memory used by the PC",
+                resources.system_memory_used_mb as f64 * 1e6,
+            );
+            writer.gauge(
+                "host_process_memory_bytes",
+                "Memory used by the streamer process",
+                resources.process_memory_mb as f64 * 1e6,
+            );
+        }
+
+        self.metrics.write(writer);
+    }
+
     pub fn tracker_pose_time_offset(&self) -> Duration {
         // This is the opposite of the client's StatisticsManager::tracker_prediction_offset().
         self.steamvr_pipeline_latency
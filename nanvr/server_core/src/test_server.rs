@@ -0,0 +1,98 @@
+// Web server on an ephemeral loopback port, serving the in-memory session of the test process.
+// Used by the tests of the web server and of the API clients.
+
+use crate::{ConnectionContext, SESSION_MANAGER, ServerCoreEvent, logging_backend, web_server};
+use configuration::WebServerCertificate;
+use events::Event;
+use net_packets::ClientListAction;
+use shared::{LifecycleState, parking_lot::RwLock};
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddr},
+    process,
+    sync::{
+        Arc, mpsc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use tokio::{net::TcpListener, runtime::Runtime};
+
+pub struct TestWebServer {
+    pub address: SocketAddr,
+    // SHA-256 of the self-signed certificate, if TLS is enabled
+    pub tls_fingerprint: Option<String>,
+    pub events_receiver: mpsc::Receiver<ServerCoreEvent>,
+    _runtime: Runtime,
+}
+
+impl TestWebServer {
+    pub fn start(tls: bool) -> Self {
+        let (events_sender, events_receiver) = mpsc::channel();
+        let connection_context = Arc::new(ConnectionContext::new(
+            SESSION_MANAGER.read().settings(),
+            events_sender,
+        ));
+        let lifecycle_state = Arc::new(RwLock::new(LifecycleState::Resumed));
+
+        let (tls_acceptor, tls_fingerprint) = if tls {
+            // Each server gets its own certificate, so that concurrent tests don't race on the
+            // generated files
+            static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+            let dir = env::temp_dir().join(format!(
+                "test_web_server_{}_{}",
+                process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ));
+            let identity =
+                server_io::web_server_tls_identity(&dir, &WebServerCertificate::SelfSigned)
+                    .unwrap();
+            let fingerprint = identity.fingerprint.clone();
+
+            (
+                Some(web_server::tls_acceptor(identity).unwrap()),
+                Some(fingerprint),
+            )
+        } else {
+            (None, None)
+        };
+
+        let runtime = Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(TcpListener::bind((Ipv4Addr::LOCALHOST, 0)))
+            .unwrap();
+        let address = listener.local_addr().unwrap();
+        runtime.spawn(web_server::serve(
+            listener,
+            tls,
+            tls_acceptor,
+            connection_context,
+            lifecycle_state,
+        ));
+
+        Self {
+            address,
+            tls_fingerprint,
+            events_receiver,
+            _runtime: runtime,
+        }
+    }
+
+    pub fn api_token() -> String {
+        SESSION_MANAGER.read().session().api_token.clone()
+    }
+
+    pub fn add_client(hostname: &str, trusted: bool) {
+        SESSION_MANAGER.write().update_client_list(
+            hostname.into(),
+            ClientListAction::AddIfMissing {
+                trusted,
+                manual_ips: vec![],
+            },
+        );
+    }
+
+    // Delivered to the events websockets like the events of the logger
+    pub fn send_event(event: Event) {
+        logging_backend::LOGGING_EVENTS_SENDER.send(event).ok();
+    }
+}
@@ -0,0 +1,284 @@
+use net_packets::ClientTraceEvent;
+use serde::Serialize;
+use serde_json as json;
+use shared::{anyhow::Result, warn};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+// Limits the memory used by long captures
+const MAX_EVENTS: usize = 2_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TraceProcess {
+    Streamer = 1,
+    Client = 2,
+}
+
+#[derive(Serialize)]
+struct TraceEvent {
+    name: String,
+    ph: &'static str,
+    // Microseconds since the capture started
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    s: Option<&'static str>,
+    #[serde(skip_serializing_if = "json::Value::is_null")]
+    args: json::Value,
+}
+
+// Records spans and instant events of the streamer and the client, and writes them in the Chrome
+// trace event format. Captures can be opened with ui.perfetto.dev or chrome://tracing.
+pub struct TraceCapture {
+    start_instant: Instant,
+    events: Vec<TraceEvent>,
+    // The index of each track is its thread ID
+    tracks: Vec<(TraceProcess, String)>,
+    is_full: bool,
+}
+
+impl TraceCapture {
+    pub fn new() -> Self {
+        Self {
+            start_instant: Instant::now(),
+            events: vec![],
+            tracks: vec![],
+            is_full: false,
+        }
+    }
+
+    fn timestamp_us(&self, instant: Instant) -> f64 {
+        instant
+            .saturating_duration_since(self.start_instant)
+            .as_secs_f64()
+            * 1e6
+    }
+
+    fn track_id(&mut self, process: TraceProcess, track: &str) -> u32 {
+        let index = if let Some(index) = self
+            .tracks
+            .iter()
+            .position(|(p, name)| *p == process && name == track)
+        {
+            index
+        } else {
+            self.tracks.push((process, track.to_owned()));
+            self.tracks.len() - 1
+        };
+
+        index as u32 + 1
+    }
+
+    fn push(&mut self, event: TraceEvent) {
+        if self.events.len() < MAX_EVENTS {
+            self.events.push(event);
+        } else if !self.is_full {
+            warn!("Trace capture is full, new events are discarded");
+            self.is_full = true;
+        }
+    }
+
+    // The frame is identified by its target timestamp
+    pub fn span(
+        &mut self,
+        process: TraceProcess,
+        track: &str,
+        name: &str,
+        start: Instant,
+        duration: Duration,
+        frame: Duration,
+    ) {
+        let event = TraceEvent {
+            name: name.to_owned(),
+            ph: "X",
+            ts: self.timestamp_us(start),
+            dur: Some(duration.as_secs_f64() * 1e6),
+            pid: process as u32,
+            tid: self.track_id(process, track),
+            s: None,
+            args: json::json!({ "frame_ms": frame.as_secs_f64() * 1e3 }),
+        };
+        self.push(event);
+    }
+
+    pub fn instant(&mut self, process: TraceProcess, track: &str, name: &str, time: Instant) {
+        let event = TraceEvent {
+            name: name.to_owned(),
+            ph: "i",
+            ts: self.timestamp_us(time),
+            dur: None,
+            pid: process as u32,
+            tid: self.track_id(process, track),
+            s: Some("t"),
+            args: json::Value::Null,
+        };
+        self.push(event);
+    }
+
+    // Client events are relative to the client input acquisition of the frame, expressed here in
+    // the streamer clock
+    pub fn add_client_events(
+        &mut self,
+        input_acquired: Instant,
+        frame: Duration,
+        events: Vec<ClientTraceEvent>,
+    ) {
+        for event in events {
+            let offset = Duration::from_micros(event.start_offset_us.unsigned_abs());
+            let start = if event.start_offset_us >= 0 {
+                input_acquired + offset
+            } else {
+                input_acquired
+                    .checked_sub(offset)
+                    .unwrap_or(self.start_instant)
+            };
+
+            if let Some(duration_us) = event.duration_us {
+                self.span(
+                    TraceProcess::Client,
+                    &event.track,
+                    &event.name,
+                    start,
+                    Duration::from_micros(duration_us),
+                    frame,
+                );
+            } else {
+                self.instant(TraceProcess::Client, &event.track, &event.name, start);
+            }
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fn metadata(name: &str, pid: u32, tid: u32, args: json::Value) -> TraceEvent {
+            TraceEvent {
+                name: name.to_owned(),
+                ph: "M",
+                ts: 0.0,
+                dur: None,
+                pid,
+                tid,
+                s: None,
+                args,
+            }
+        }
+
+        let mut metadata_events = vec![];
+        for (process, name) in [
+            (TraceProcess::Streamer, "Streamer"),
+            (TraceProcess::Client, "Client"),
+        ] {
+            metadata_events.push(metadata(
+                "process_name",
+                process as u32,
+                0,
+                json::json!({ "name": name }),
+            ));
+        }
+        for (index, (process, name)) in self.tracks.iter().enumerate() {
+            let tid = index as u32 + 1;
+            metadata_events.push(metadata(
+                "thread_name",
+                *process as u32,
+                tid,
+                json::json!({ "name": name }),
+            ));
+            metadata_events.push(metadata(
+                "thread_sort_index",
+                *process as u32,
+                tid,
+                json::json!({ "sort_index": tid }),
+            ));
+        }
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct TraceFile<'a> {
+            trace_events: Vec<&'a TraceEvent>,
+            display_time_unit: &'static str,
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        json::to_writer(
+            &mut writer,
+            &TraceFile {
+                trace_events: metadata_events.iter().chain(&self.events).collect(),
+                display_time_unit: "ms",
+            },
+        )?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_file() {
+        let mut capture = TraceCapture::new();
+        let start = capture.start_instant;
+
+        capture.span(
+            TraceProcess::Streamer,
+            "Encoder",
+            "Encode",
+            start + Duration::from_millis(2),
+            Duration::from_millis(3),
+            Duration::from_millis(11),
+        );
+        capture.add_client_events(
+            start + Duration::from_millis(1),
+            Duration::from_millis(11),
+            vec![
+                ClientTraceEvent {
+                    name: "Decode".into(),
+                    track: "Decoder".into(),
+                    start_offset_us: 20_000,
+                    duration_us: Some(4_000),
+                },
+                ClientTraceEvent {
+                    name: "Packet loss".into(),
+                    track: "Network".into(),
+                    start_offset_us: -5_000,
+                    duration_us: None,
+                },
+            ],
+        );
+
+        let path = std::env::temp_dir().join(format!("trace_test_{}.json", std::process::id()));
+        capture.write(&path).unwrap();
+        let trace = json::from_reader::<_, json::Value>(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(path).ok();
+
+        let events = trace["traceEvents"].as_array().unwrap();
+        let find = |name: &str| events.iter().find(|event| event["name"] == name).unwrap();
+
+        let encode = find("Encode");
+        assert_eq!(encode["ph"], "X");
+        assert_eq!(encode["pid"], 1);
+        assert_eq!(encode["ts"], 2000.0);
+        assert_eq!(encode["dur"], 3000.0);
+
+        let decode = find("Decode");
+        assert_eq!(decode["pid"], 2);
+        assert_eq!(decode["ts"], 21000.0);
+
+        // Events before the capture start are clamped
+        let packet_loss = find("Packet loss");
+        assert_eq!(packet_loss["ph"], "i");
+        assert_eq!(packet_loss["ts"], 0.0);
+
+        assert!(events.iter().any(|event| event["name"] == "thread_name"
+            && event["pid"] == 2
+            && event["args"]["name"] == "Decoder"));
+    }
+}
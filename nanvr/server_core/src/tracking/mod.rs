@@ -316,21 +316,26 @@ pub fn tracking_loop(
                 )
             });
 
-    let mut face_tracking_sink = initial_settings
+    let osc_local_port = initial_settings.connection.osc_local_port;
+
+    // The sinks are recreated when their config changes
+    let mut face_tracking_sink_config = initial_settings
         .headset
         .face_tracking
-        .into_option()
-        .and_then(|config| {
-            FaceTrackingSink::new(config.sink, initial_settings.connection.osc_local_port).ok()
-        });
+        .as_option()
+        .map(|config| config.sink.clone());
+    let mut face_tracking_sink = face_tracking_sink_config
+        .clone()
+        .and_then(|config| FaceTrackingSink::new(config, osc_local_port).ok());
 
-    let mut body_tracking_sink = initial_settings
+    let mut body_tracking_sink_config = initial_settings
         .headset
         .body_tracking
-        .into_option()
-        .and_then(|config| {
-            BodyTrackingSink::new(config.sink, initial_settings.connection.osc_local_port).ok()
-        });
+        .as_option()
+        .map(|config| config.sink.clone());
+    let mut body_tracking_sink = body_tracking_sink_config
+        .clone()
+        .and_then(|config| BodyTrackingSink::new(config, osc_local_port).ok());
 
     let mut vmc_sink = initial_settings
         .headset
@@ -354,16 +359,40 @@ pub fn tracking_loop(
             stats.report_tracking_received(timestamp);
         }
 
-        let controllers_config = {
+        let (controllers_config, new_face_tracking_sink_config, new_body_tracking_sink_config) = {
             let data_lock = SESSION_MANAGER.read();
-            data_lock
-                .settings()
-                .headset
-                .controllers
-                .clone()
-                .into_option()
+            let headset_config = &data_lock.settings().headset;
+
+            (
+                headset_config.controllers.clone().into_option(),
+                headset_config
+                    .face_tracking
+                    .as_option()
+                    .map(|config| config.sink.clone()),
+                headset_config
+                    .body_tracking
+                    .as_option()
+                    .map(|config| config.sink.clone()),
+            )
         };
 
+        if new_face_tracking_sink_config != face_tracking_sink_config {
+            face_tracking_sink_config = new_face_tracking_sink_config;
+            // Release the local port before binding it again
+            face_tracking_sink.take();
+            face_tracking_sink = face_tracking_sink_config
+                .clone()
+                .and_then(|config| FaceTrackingSink::new(config, osc_local_port).ok());
+        }
+
+        if new_body_tracking_sink_config != body_tracking_sink_config {
+            body_tracking_sink_config = new_body_tracking_sink_config;
+            body_tracking_sink.take();
+            body_tracking_sink = body_tracking_sink_config
+                .clone()
+                .and_then(|config| BodyTrackingSink::new(config, osc_local_port).ok());
+        }
+
         let device_motion_keys = {
             let mut tracking_manager_lock = ctx.tracking_manager.write();
             let session_manager_lock = SESSION_MANAGER.read();
@@ -1,11 +1,15 @@
 use crate::{
     ConnectionContext, FILESYSTEM_LAYOUT, SESSION_MANAGER, ServerCoreEvent,
+    event_filter::EventFilter,
+    fmp4::{Fmp4MirrorPacket, Fmp4Muxer},
     logging_backend::LOGGING_EVENTS_SENDER,
+    metrics, rest_api,
 };
 use bytes::Buf;
+use configuration::WebServerCertificate;
 use const_format::formatcp;
 use events::{ButtonEvent, EventType};
-use futures::SinkExt;
+use futures::{SinkExt, StreamExt};
 use headers::{
     AccessControlAllowHeaders, AccessControlAllowMethods, AccessControlRequestHeaders,
     AccessControlRequestMethod, HeaderMapExt,
@@ -13,24 +17,60 @@ use headers::{
 use hyper::{
     Body, Method, Request, Response, StatusCode,
     header::{
-        self, ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE, HeaderName, HeaderValue,
+        self, ACCESS_CONTROL_ALLOW_ORIGIN, AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, HeaderName,
+        HeaderValue, WWW_AUTHENTICATE,
     },
     service,
+    upgrade::Upgraded,
+};
+use net_packets::{
+    ButtonEntry, DecoderInitializationConfig, EventsWebsocketMessage, ServerRequest,
+    ServerRequestReply,
 };
-use net_packets::{ButtonEntry, ClientListAction, ServerRequest};
 use serde::de::DeserializeOwned;
 use serde_json as json;
+use server_io::WebServerTlsIdentity;
 use shared::{
-    ConnectionState, NANVR_HIGH_NAME,
-    anyhow::{self, Result},
-    error, info, log,
+    LifecycleState, NANVR_HIGH_NAME,
+    anyhow::{self, Result, anyhow, bail},
+    debug, error, info, log,
+    parking_lot::RwLock,
+    settings_schema::Switch,
+    warn,
+};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    task,
+};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{ServerConfig, crypto::ring},
+    server::TlsStream,
 };
-use std::{net::SocketAddr, sync::Arc};
-use tokio::sync::broadcast::{self, error::RecvError};
 use tokio_tungstenite::{WebSocketStream, tungstenite::protocol};
+use tokio_util::either::Either;
 
 pub const WS_BROADCAST_CAPACITY: usize = 256;
 
+const VIDEO_MIRROR_PAGE: &str = include_str!("../resources/video_mirror.html");
+const FMP4_MIRROR_PATH: &str = "/api/video-mirror-mp4";
+
+// First byte of a TLS ClientHello record
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+const CONNECTION_SETUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+type WebStream = Either<TcpStream, TlsStream<TcpStream>>;
+
 fn reply(code: StatusCode) -> Result<Response<Body>> {
     Ok(Response::builder().status(code).body(Body::empty())?)
 }
@@ -41,37 +81,19 @@ async fn from_request_body<T: DeserializeOwned>(request: Request<Body>) -> Resul
     )?)
 }
 
-fn websocket<T: Clone + Send + 'static>(
+fn websocket<F: Future<Output = ()> + Send + 'static>(
     request: Request<Body>,
-    sender: broadcast::Sender<T>,
-    message_builder: impl Fn(T) -> protocol::Message + Send + Sync + 'static,
+    handler: impl FnOnce(WebSocketStream<Upgraded>) -> F + Send + 'static,
 ) -> Result<Response<Body>> {
     if let Some(key) = request.headers().typed_get::<headers::SecWebsocketKey>() {
         tokio::spawn(async move {
             match hyper::upgrade::on(request).await {
                 Ok(upgraded) => {
-                    let mut data_receiver = sender.subscribe();
-
-                    let mut ws =
+                    let ws =
                         WebSocketStream::from_raw_socket(upgraded, protocol::Role::Server, None)
                             .await;
 
-                    loop {
-                        match data_receiver.recv().await {
-                            Ok(data) => {
-                                if let Err(e) = ws.send(message_builder(data)).await {
-                                    info!("Failed to send log with websocket: {e}");
-                                    break;
-                                }
-
-                                ws.flush().await.ok();
-                            }
-                            Err(RecvError::Lagged(_)) => (),
-                            Err(RecvError::Closed) => break,
-                        }
-                    }
-
-                    ws.close(None).await.ok();
+                    handler(ws).await;
                 }
                 Err(e) => error!("{e}"),
             }
@@ -92,8 +114,379 @@ fn websocket<T: Clone + Send + 'static>(
     }
 }
 
-async fn http_api(
+fn broadcast_websocket<T: Clone + Send + 'static>(
+    request: Request<Body>,
+    sender: broadcast::Sender<T>,
+    message_builder: impl Fn(T) -> protocol::Message + Send + Sync + 'static,
+) -> Result<Response<Body>> {
+    let mut data_receiver = sender.subscribe();
+
+    websocket(request, move |mut ws| async move {
+        loop {
+            match data_receiver.recv().await {
+                Ok(data) => {
+                    if let Err(e) = ws.send(message_builder(data)).await {
+                        info!("Failed to send log with websocket: {e}");
+                        break;
+                    }
+
+                    ws.flush().await.ok();
+                }
+                Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => break,
+            }
+        }
+
+        ws.close(None).await.ok();
+    })
+}
+
+// Subscribers receive all events until they send a subscription. Server requests are answered
+// in order with a ServerRequestReply
+async fn events_websocket(
+    ws: WebSocketStream<Upgraded>,
+    connection_context: Arc<ConnectionContext>,
+) {
+    let mut events_receiver = LOGGING_EVENTS_SENDER.subscribe();
+    let (mut ws_sender, mut ws_receiver) = ws.split();
+    let mut filter = EventFilter::default();
+
+    // Requests can block (filesystem, scripts), so they run on the blocking pool and their replies
+    // are sent when ready, without stalling the events
+    let (reply_sender, mut reply_receiver) = mpsc::unbounded_channel::<ServerRequestReply>();
+
+    loop {
+        let message = tokio::select! {
+            res = events_receiver.recv() => match res {
+                Ok(event) => {
+                    if !filter.accepts(&event, Instant::now()) {
+                        continue;
+                    }
+
+                    json::to_string(&event).unwrap()
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            Some(reply) = reply_receiver.recv() => json::to_string(&reply).unwrap(),
+            message = ws_receiver.next() => match message {
+                Some(Ok(protocol::Message::Text(text))) => {
+                    let reply = match json::from_str::<EventsWebsocketMessage>(&text) {
+                        Ok(EventsWebsocketMessage::Subscribe(subscription)) => {
+                            filter = EventFilter::new(subscription);
+                            continue;
+                        }
+                        Ok(EventsWebsocketMessage::ServerRequest { id, request }) => {
+                            let connection_context = Arc::clone(&connection_context);
+                            let reply_sender = reply_sender.clone();
+                            task::spawn_blocking(move || {
+                                let error = handle_server_request(&connection_context, request)
+                                    .err()
+                                    .map(|e| e.to_string());
+
+                                // The websocket might have been closed in the meantime
+                                reply_sender.send(ServerRequestReply { id, error }).ok();
+                            });
+
+                            continue;
+                        }
+                        Err(e) => ServerRequestReply {
+                            id: json::Value::Null,
+                            error: Some(format!("Invalid message: {e}")),
+                        },
+                    };
+
+                    json::to_string(&reply).unwrap()
+                }
+                Some(Ok(protocol::Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+
+        if let Err(e) = ws_sender.send(protocol::Message::Text(message)).await {
+            info!("Failed to send event with websocket: {e}");
+            break;
+        }
+    }
+
+    ws_sender.close().await.ok();
+}
+
+// Each subscriber has its own muxer so that its first fragment is an IDR with decode time zero.
+// Before each init segment a text message {"mime_type": <type>} is sent.
+async fn fmp4_mirror_websocket(
+    mut ws: WebSocketStream<Upgraded>,
+    mut packet_receiver: broadcast::Receiver<Fmp4MirrorPacket>,
+    initial_config: Option<DecoderInitializationConfig>,
+    connection_context: Arc<ConnectionContext>,
+) {
+    let mut muxer: Option<Fmp4Muxer> = None;
+    let mut start_timestamp = None;
+    let mut next_config = initial_config;
+
+    'stream: loop {
+        let packet = if let Some(config) = next_config.take() {
+            Fmp4MirrorPacket::Config(config)
+        } else {
+            match packet_receiver.recv().await {
+                Ok(packet) => packet,
+                // The encoder sends IDRs only on request
+                Err(RecvError::Lagged(_)) => {
+                    if let Some(muxer) = &mut muxer {
+                        muxer.resync();
+                        connection_context
+                            .events_sender
+                            .send(ServerCoreEvent::RequestIDR)
+                            .ok();
+                    }
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            }
+        };
+
+        let messages = match packet {
+            Fmp4MirrorPacket::Config(config) => {
+                if muxer
+                    .as_ref()
+                    .is_some_and(|muxer| muxer.config_buffer() == config.config_buffer)
+                {
+                    continue;
+                }
+
+                match Fmp4Muxer::new(&config) {
+                    Ok(new_muxer) => {
+                        let messages = vec![
+                            protocol::Message::Text(
+                                json::json!({ "mime_type": new_muxer.mime_type() }).to_string(),
+                            ),
+                            protocol::Message::Binary(new_muxer.init_segment().to_vec()),
+                        ];
+                        muxer = Some(new_muxer);
+
+                        messages
+                    }
+                    Err(e) => {
+                        warn!("Cannot mirror the video stream: {e}");
+                        break;
+                    }
+                }
+            }
+            Fmp4MirrorPacket::Frame {
+                timestamp,
+                is_idr,
+                nal_buffer,
+            } => {
+                let Some(muxer) = &mut muxer else {
+                    continue;
+                };
+
+                let decode_time = timestamp.saturating_sub(start_timestamp.unwrap_or(timestamp));
+                let Some(fragment) = muxer.fragment(decode_time, is_idr, &nal_buffer) else {
+                    continue;
+                };
+                start_timestamp.get_or_insert(timestamp);
+
+                vec![protocol::Message::Binary(fragment)]
+            }
+        };
+
+        for message in messages {
+            if let Err(e) = ws.send(message).await {
+                info!("Failed to send video with websocket: {e}");
+                break 'stream;
+            }
+        }
+    }
+
+    ws.close(None).await.ok();
+}
+
+// Mutating requests, websockets and the session, which contains the API token
+fn requires_token(request: &Request<Body>) -> bool {
+    let path = request.uri().path();
+
+    request.method() != Method::GET
+        || request.headers().contains_key(header::UPGRADE)
+        || path == formatcp!("{}/session", rest_api::BASE_PATH)
+        || path == FMP4_MIRROR_PATH
+}
+
+// The token is accepted as a bearer token or, since browsers can't set headers on websockets, as
+// the "token" query parameter
+fn is_authorized(request: &Request<Body>, api_token: &str) -> bool {
+    let bearer_token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query_token = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|param| param.strip_prefix("token="))
+    });
+
+    // Constant time comparison
+    bearer_token.or(query_token).is_some_and(|token| {
+        token.len() == api_token.len()
+            && token
+                .bytes()
+                .zip(api_token.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    })
+}
+
+fn metrics_endpoint(
     connection_context: &ConnectionContext,
+    lifecycle_state: &RwLock<LifecycleState>,
+    remote_addr: SocketAddr,
+) -> Result<Response<Body>> {
+    let (config, client_list) = {
+        let session_manager_lock = SESSION_MANAGER.read();
+        (
+            session_manager_lock
+                .settings()
+                .connection
+                .metrics_endpoint
+                .clone(),
+            session_manager_lock.client_list().clone(),
+        )
+    };
+
+    let Switch::Enabled(config) = config else {
+        return reply(StatusCode::NOT_FOUND);
+    };
+    if !config.allow_remote_access && !remote_addr.ip().is_loopback() {
+        return reply(StatusCode::FORBIDDEN);
+    }
+
+    let text = metrics::encode(
+        connection_context.statistics_manager.read().as_ref(),
+        &client_list,
+        &lifecycle_state.read(),
+    );
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, metrics::CONTENT_TYPE)
+        .header(CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+        .body(text.into())?)
+}
+
+// Shared by the dashboard request endpoint and the events websocket
+fn handle_server_request(
+    connection_context: &ConnectionContext,
+    request: ServerRequest,
+) -> Result<()> {
+    match request {
+        ServerRequest::Log(event) => {
+            let level = event.severity.into_log_level();
+            log::log!(level, "{}", event.content);
+        }
+        ServerRequest::GetSession => {
+            events::send_event(EventType::Session(Box::new(
+                crate::SESSION_MANAGER.read().session().clone(),
+            )));
+        }
+        ServerRequest::UpdateSession(session) => *SESSION_MANAGER.write().session_mut() = *session,
+        ServerRequest::SetValues(descs) => SESSION_MANAGER.write().set_values(descs)?,
+        ServerRequest::UpdateClientList { hostname, action } => {
+            crate::update_client_list(connection_context, hostname, action)
+        }
+        ServerRequest::CaptureFrame => {
+            connection_context
+                .events_sender
+                .send(ServerCoreEvent::CaptureFrame)
+                .ok();
+        }
+        ServerRequest::InsertIdr => {
+            connection_context
+                .events_sender
+                .send(ServerCoreEvent::RequestIDR)
+                .ok();
+        }
+        ServerRequest::StartRecording => crate::create_recording_file(
+            connection_context,
+            crate::SESSION_MANAGER.read().settings(),
+        ),
+        ServerRequest::StopRecording => *connection_context.video_recording_file.lock() = None,
+        ServerRequest::StartFrameStatisticsLogging => crate::start_frame_statistics_log(
+            connection_context,
+            crate::SESSION_MANAGER.read().settings(),
+        ),
+        ServerRequest::StopFrameStatisticsLogging => {
+            if let Some(stats) = &mut *connection_context.statistics_manager.write() {
+                stats.set_frame_statistics_log(None);
+            }
+        }
+        ServerRequest::StartTraceCapture => crate::start_trace_capture(connection_context),
+        ServerRequest::StopTraceCapture => crate::stop_trace_capture(connection_context),
+        ServerRequest::FirewallRules(action) => {
+            if let Err(e) = server_io::firewall_rules(action, FILESYSTEM_LAYOUT.get().unwrap()) {
+                bail!("Setting firewall rules failed! code: {e}");
+            }
+
+            info!("Setting firewall rules succeeded!");
+        }
+        ServerRequest::RegisterNanvrDriver => {
+            let result = server_io::driver_registration(
+                &[FILESYSTEM_LAYOUT
+                    .get()
+                    .unwrap()
+                    .openvr_driver_root_dir
+                    .clone()],
+                true,
+            );
+
+            if let Ok(list) = server_io::get_registered_drivers() {
+                events::send_event(EventType::DriversList(list));
+            }
+
+            result?;
+        }
+        ServerRequest::UnregisterDriver(path) => {
+            let result = server_io::driver_registration(&[path], false);
+
+            if let Ok(list) = server_io::get_registered_drivers() {
+                events::send_event(EventType::DriversList(list));
+            }
+
+            result?;
+        }
+        ServerRequest::GetDriverList => {
+            events::send_event(EventType::DriversList(server_io::get_registered_drivers()?));
+        }
+        ServerRequest::RestartSteamvr => {
+            connection_context
+                .events_sender
+                .send(ServerCoreEvent::RestartPending)
+                .ok();
+        }
+        ServerRequest::ShutdownSteamvr => {
+            connection_context
+                .events_sender
+                .send(ServerCoreEvent::ShutdownPending)
+                .ok();
+        }
+        ServerRequest::SettingsProfile(action) => {
+            let event = server_io::settings_profile_action(
+                &mut SESSION_MANAGER.write(),
+                &FILESYSTEM_LAYOUT.get().unwrap().presets_dir(),
+                action,
+            )
+            .map_err(|e| anyhow!("Settings profile action failed: {e}"))?;
+
+            events::send_event(event);
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn http_api(
+    connection_context: &Arc<ConnectionContext>,
+    lifecycle_state: &RwLock<LifecycleState>,
+    remote_addr: SocketAddr,
     request: Request<Body>,
 ) -> Result<Response<Body>> {
     const X_NANVR: &str = formatcp!("X-{NANVR_HIGH_NAME}");
@@ -117,7 +510,10 @@ async fn http_api(
 
         if let Some(requested_method) = request.headers().typed_get::<AccessControlRequestMethod>()
         {
-            if requested_method != Method::GET.into() && requested_method != Method::POST.into() {
+            if ![Method::GET, Method::POST, Method::PATCH, Method::DELETE]
+                .into_iter()
+                .any(|method| requested_method == method.into())
+            {
                 return Ok(bad_request);
             }
         } else {
@@ -131,7 +527,7 @@ async fn http_api(
             for header in requested_headers.iter() {
                 if header == HeaderName::from_static(X_NANVR) {
                     found_x_nanvr = true;
-                } else if header != CONTENT_TYPE {
+                } else if header != CONTENT_TYPE && header != AUTHORIZATION {
                     return Ok(bad_request);
                 }
             }
@@ -145,12 +541,22 @@ async fn http_api(
             return Ok(bad_request);
         }
 
-        let allowed_methods = [Method::GET, Method::POST, Method::OPTIONS]
-            .into_iter()
-            .collect::<AccessControlAllowMethods>();
-        let allowed_headers = [CONTENT_TYPE, HeaderName::from_static(X_NANVR)]
-            .into_iter()
-            .collect::<AccessControlAllowHeaders>();
+        let allowed_methods = [
+            Method::GET,
+            Method::POST,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ]
+        .into_iter()
+        .collect::<AccessControlAllowMethods>();
+        let allowed_headers = [
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            HeaderName::from_static(X_NANVR),
+        ]
+        .into_iter()
+        .collect::<AccessControlAllowHeaders>();
 
         let mut response: Response<Body> = Response::builder()
             .status(StatusCode::OK)
@@ -165,7 +571,27 @@ async fn http_api(
         return Ok(response);
     }
 
-    if request.method() != Method::POST && request.method() != Method::GET {
+    // Scrapers cannot set the X-NANVR header, access is controlled by the settings instead
+    if request.method() == Method::GET && request.uri().path() == "/metrics" {
+        return metrics_endpoint(connection_context, lifecycle_state, remote_addr);
+    }
+
+    // The viewer page contains no data, and the websocket it opens requires the API token
+    if request.method() == Method::GET && request.uri().path() == "/video-mirror" {
+        return Ok(Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(CACHE_CONTROL, "no-cache, no-store, must-revalidate")
+            .body(VIDEO_MIRROR_PAGE.into())?);
+    }
+
+    let path = request.uri().path().to_owned();
+    let is_rest_api = path == rest_api::BASE_PATH
+        || path
+            .strip_prefix(rest_api::BASE_PATH)
+            .is_some_and(|subpath| subpath.starts_with('/'));
+
+    // The REST API returns its own status code for unsupported methods
+    if !is_rest_api && request.method() != Method::POST && request.method() != Method::GET {
         return Ok(Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body("invalid method".into())?);
@@ -177,120 +603,37 @@ async fn http_api(
     //
     // The dashboard can just set the header and be allowed through without the preflight
     // thus not getting blocked by allow_untrusted_http being disabled
-    if request.headers().get(X_NANVR) != Some(&HeaderValue::from_static("true")) {
+    //
+    // Browsers cannot set the header on websockets, the MP4 mirror is protected by the token only
+    if path != FMP4_MIRROR_PATH
+        && request.headers().get(X_NANVR) != Some(&HeaderValue::from_static("true"))
+    {
         return Ok(Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body(format!("missing X-{NANVR_HIGH_NAME} header").into())?);
     }
 
-    let mut response = match request.uri().path() {
+    if requires_token(&request)
+        && !is_authorized(&request, &SESSION_MANAGER.read().session().api_token)
+    {
+        return Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(WWW_AUTHENTICATE, "Bearer")
+            .body("missing or invalid API token".into())?);
+    }
+
+    let mut response = match path.as_str() {
+        _ if is_rest_api => rest_api::handle(connection_context, request).await?,
         // New unified requests
         "/api/dashboard-request" => {
             if let Ok(request) = from_request_body::<ServerRequest>(request).await {
-                match request {
-                    ServerRequest::Log(event) => {
-                        let level = event.severity.into_log_level();
-                        log::log!(level, "{}", event.content);
-                    }
-                    ServerRequest::GetSession => {
-                        events::send_event(EventType::Session(Box::new(
-                            crate::SESSION_MANAGER.read().session().clone(),
-                        )));
-                    }
-                    ServerRequest::UpdateSession(session) => {
-                        *SESSION_MANAGER.write().session_mut() = *session
-                    }
-                    ServerRequest::SetValues(descs) => {
-                        SESSION_MANAGER.write().set_values(descs).ok();
-                    }
-                    ServerRequest::UpdateClientList {
-                        hostname,
-                        mut action,
-                    } => {
-                        let mut session_manager = SESSION_MANAGER.write();
-                        if matches!(action, ClientListAction::RemoveEntry)
-                            && let Some(entry) = session_manager.client_list().get(&hostname)
-                            && entry.connection_state != ConnectionState::Disconnected
-                        {
-                            connection_context
-                                .clients_to_be_removed
-                                .lock()
-                                .insert(hostname.clone());
-
-                            action = ClientListAction::SetConnectionState(
-                                ConnectionState::Disconnecting,
-                            );
-                        }
-
-                        session_manager.update_client_list(hostname, action);
-                    }
-                    ServerRequest::CaptureFrame => {
-                        connection_context
-                            .events_sender
-                            .send(ServerCoreEvent::CaptureFrame)
-                            .ok();
-                    }
-                    ServerRequest::InsertIdr => {
-                        connection_context
-                            .events_sender
-                            .send(ServerCoreEvent::RequestIDR)
-                            .ok();
-                    }
-                    ServerRequest::StartRecording => crate::create_recording_file(
-                        connection_context,
-                        crate::SESSION_MANAGER.read().settings(),
-                    ),
-                    ServerRequest::StopRecording => {
-                        *connection_context.video_recording_file.lock() = None
-                    }
-                    ServerRequest::FirewallRules(action) => {
-                        if let Err(e) =
-                            server_io::firewall_rules(action, FILESYSTEM_LAYOUT.get().unwrap())
-                        {
-                            error!("Setting firewall rules failed! code: {e}");
-                        } else {
-                            info!("Setting firewall rules succeeded!");
-                        }
-                    }
-                    ServerRequest::RegisterNanvrDriver => {
-                        server_io::driver_registration(
-                            &[FILESYSTEM_LAYOUT
-                                .get()
-                                .unwrap()
-                                .openvr_driver_root_dir
-                                .clone()],
-                            true,
-                        )
-                        .ok();
-
-                        if let Ok(list) = server_io::get_registered_drivers() {
-                            events::send_event(EventType::DriversList(list));
-                        }
-                    }
-                    ServerRequest::UnregisterDriver(path) => {
-                        server_io::driver_registration(&[path], false).ok();
-
-                        if let Ok(list) = server_io::get_registered_drivers() {
-                            events::send_event(EventType::DriversList(list));
-                        }
-                    }
-                    ServerRequest::GetDriverList => {
-                        if let Ok(list) = server_io::get_registered_drivers() {
-                            events::send_event(EventType::DriversList(list));
-                        }
-                    }
-                    ServerRequest::RestartSteamvr => {
-                        connection_context
-                            .events_sender
-                            .send(ServerCoreEvent::RestartPending)
-                            .ok();
-                    }
-                    ServerRequest::ShutdownSteamvr => {
-                        connection_context
-                            .events_sender
-                            .send(ServerCoreEvent::ShutdownPending)
-                            .ok();
-                    }
+                let connection_context = Arc::clone(connection_context);
+                let result = task::spawn_blocking(move || {
+                    handle_server_request(&connection_context, request)
+                })
+                .await?;
+                if let Err(e) = result {
+                    error!("{e}");
                 }
 
                 reply(StatusCode::OK)?
@@ -298,9 +641,10 @@ async fn http_api(
                 reply(StatusCode::BAD_REQUEST)?
             }
         }
-        "/api/events" => websocket(request, LOGGING_EVENTS_SENDER.clone(), |e| {
-            protocol::Message::Text(json::to_string(&e).unwrap())
-        })?,
+        "/api/events" => {
+            let connection_context = Arc::clone(connection_context);
+            websocket(request, move |ws| events_websocket(ws, connection_context))?
+        }
         "/api/video-mirror" => {
             let sender = {
                 let mut sender_lock = connection_context.video_mirror_sender.lock();
@@ -318,7 +662,33 @@ async fn http_api(
                 sender.send(config.config_buffer.clone()).ok();
             }
 
-            let res = websocket(request, sender, protocol::Message::Binary)?;
+            let res = broadcast_websocket(request, sender, protocol::Message::Binary)?;
+
+            connection_context
+                .events_sender
+                .send(ServerCoreEvent::RequestIDR)
+                .ok();
+
+            res
+        }
+        FMP4_MIRROR_PATH => {
+            let packet_receiver = {
+                let mut sender_lock = connection_context.fmp4_mirror_sender.lock();
+                if let Some(sender) = &*sender_lock {
+                    sender.subscribe()
+                } else {
+                    let (sender, receiver) = broadcast::channel(WS_BROADCAST_CAPACITY);
+                    *sender_lock = Some(sender);
+
+                    receiver
+                }
+            };
+            let initial_config = connection_context.decoder_config.lock().clone();
+            let viewer_context = Arc::clone(connection_context);
+
+            let res = websocket(request, move |ws| {
+                fmp4_mirror_websocket(ws, packet_receiver, initial_config, viewer_context)
+            })?;
 
             connection_context
                 .events_sender
@@ -371,20 +741,156 @@ async fn http_api(
     Ok(response)
 }
 
-pub async fn web_server(connection_context: Arc<ConnectionContext>) -> Result<()> {
-    let web_server_port = crate::SESSION_MANAGER
-        .read()
-        .settings()
-        .connection
-        .web_server_port;
+fn load_tls_acceptor(certificate: &WebServerCertificate) -> Result<TlsAcceptor> {
+    let identity = server_io::web_server_tls_identity(
+        &FILESYSTEM_LAYOUT.get().unwrap().config_dir,
+        certificate,
+    )?;
+    info!(
+        "Web server certificate fingerprint (SHA-256): {}",
+        identity.fingerprint
+    );
+
+    tls_acceptor(identity)
+}
 
-    let service = service::make_service_fn(move |_| {
+pub(crate) fn tls_acceptor(identity: WebServerTlsIdentity) -> Result<TlsAcceptor> {
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(identity.certificate_chain, identity.private_key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+// When TLS is enabled, plain HTTP is only accepted from this PC, which is where the dashboard runs
+fn accepts_plain_connection(remote_ip: IpAddr, local_ip: IpAddr) -> bool {
+    remote_ip.is_loopback() || remote_ip == local_ip
+}
+
+async fn accept_connection(
+    stream: TcpStream,
+    tls_acceptor: Option<TlsAcceptor>,
+) -> io::Result<Option<WebStream>> {
+    let mut first_byte = [0];
+    stream.peek(&mut first_byte).await?;
+
+    if first_byte[0] == TLS_HANDSHAKE_RECORD {
+        if let Some(acceptor) = tls_acceptor {
+            return Ok(Some(Either::Right(acceptor.accept(stream).await?)));
+        }
+    } else {
+        if accepts_plain_connection(stream.peer_addr()?.ip(), stream.local_addr()?.ip()) {
+            return Ok(Some(Either::Left(stream)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn remote_addr(stream: &WebStream) -> SocketAddr {
+    match stream {
+        Either::Left(stream) => stream.peer_addr(),
+        Either::Right(stream) => stream.get_ref().0.peer_addr(),
+    }
+    .unwrap_or_else(|_| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+}
+
+pub async fn web_server(
+    connection_context: Arc<ConnectionContext>,
+    lifecycle_state: Arc<RwLock<LifecycleState>>,
+) -> Result<()> {
+    let (web_server_port, bind_address, tls_certificate) = {
+        let session_manager = crate::SESSION_MANAGER.read();
+        let connection = &session_manager.settings().connection;
+        let bind_address = connection.web_server_bind_address.ip().unwrap_or_else(|| {
+            warn!("Invalid web server bind address, listening on localhost only");
+            Ipv4Addr::LOCALHOST.into()
+        });
+
+        (
+            connection.web_server_port,
+            bind_address,
+            connection.web_server_tls.as_option().cloned(),
+        )
+    };
+
+    // If the certificate cannot be loaded, only plain local connections are accepted
+    let tls_acceptor = tls_certificate.as_ref().and_then(|certificate| {
+        load_tls_acceptor(certificate)
+            .map_err(|e| error!("Failed to set up web server TLS, remote access is disabled: {e}"))
+            .ok()
+    });
+
+    let listener = TcpListener::bind(SocketAddr::new(bind_address, web_server_port)).await?;
+
+    serve(
+        listener,
+        tls_certificate.is_some(),
+        tls_acceptor,
+        connection_context,
+        lifecycle_state,
+    )
+    .await
+}
+
+// When TLS is enabled, remote connections without a TLS acceptor are dropped
+pub(crate) async fn serve(
+    listener: TcpListener,
+    tls_enabled: bool,
+    tls_acceptor: Option<TlsAcceptor>,
+    connection_context: Arc<ConnectionContext>,
+    lifecycle_state: Arc<RwLock<LifecycleState>>,
+) -> Result<()> {
+    let (connection_sender, connection_receiver) = futures::channel::mpsc::unbounded();
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    debug!("Failed to accept web server connection: {e}");
+                    continue;
+                }
+            };
+
+            if !tls_enabled {
+                connection_sender
+                    .unbounded_send(Ok::<_, io::Error>(Either::Left(stream)))
+                    .ok();
+                continue;
+            }
+
+            let connection_sender = connection_sender.clone();
+            let tls_acceptor = tls_acceptor.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(
+                    CONNECTION_SETUP_TIMEOUT,
+                    accept_connection(stream, tls_acceptor),
+                )
+                .await
+                {
+                    Ok(Ok(Some(stream))) => {
+                        connection_sender.unbounded_send(Ok(stream)).ok();
+                    }
+                    Ok(Ok(None)) => (),
+                    Ok(Err(e)) => debug!("Web server TLS handshake failed: {e}"),
+                    Err(_) => debug!("Web server connection setup timed out"),
+                }
+            });
+        }
+    });
+
+    let service = service::make_service_fn(move |stream: &WebStream| {
         let connection_context = Arc::clone(&connection_context);
+        let lifecycle_state = Arc::clone(&lifecycle_state);
+        let remote_addr = remote_addr(stream);
         async move {
             Ok::<_, anyhow::Error>(service::service_fn(move |request| {
                 let connection_context = Arc::clone(&connection_context);
+                let lifecycle_state = Arc::clone(&lifecycle_state);
                 async move {
-                    let res = http_api(&connection_context, request).await;
+                    let res =
+                        http_api(&connection_context, &lifecycle_state, remote_addr, request).await;
                     if let Err(e) = &res {
                         shared::show_e(e);
                     }
@@ -395,10 +901,230 @@ pub async fn web_server(connection_context: Arc<ConnectionContext>) -> Result<()
         }
     });
 
-    Ok(hyper::Server::bind(&SocketAddr::new(
-        "0.0.0.0".parse().unwrap(),
-        web_server_port,
-    ))
-    .serve(service)
-    .await?)
+    Ok(
+        hyper::Server::builder(hyper::server::accept::from_stream(connection_receiver))
+            .serve(service)
+            .await?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process, sync::mpsc};
+    use tokio::io::AsyncWriteExt;
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{ClientConfig, RootCertStore, pki_types::ServerName},
+    };
+
+    fn request(method: Method, uri: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_requires_token() {
+        let cases = [
+            (Method::GET, "/api/v1/version", &[][..], false),
+            (Method::GET, "/api/v1/settings", &[], false),
+            (Method::GET, "/metrics", &[], false),
+            (Method::GET, "/api/v1/session", &[], true),
+            (Method::PATCH, "/api/v1/settings", &[], true),
+            (Method::POST, "/api/v1/video/idr", &[], true),
+            (Method::DELETE, "/api/v1/clients/quest.client", &[], true),
+            (Method::POST, "/api/dashboard-request", &[], true),
+            (
+                Method::GET,
+                "/api/events",
+                &[("Upgrade", "websocket")],
+                true,
+            ),
+            (
+                Method::GET,
+                "/api/video-mirror",
+                &[("Upgrade", "websocket")],
+                true,
+            ),
+            (Method::GET, FMP4_MIRROR_PATH, &[], true),
+        ];
+        for (method, path, headers, expected) in cases {
+            assert_eq!(
+                requires_token(&request(method.clone(), path, headers)),
+                expected,
+                "{method} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let token = "0123456789abcdef";
+
+        let cases = [
+            (
+                "/api/v1/session",
+                &[("Authorization", "Bearer 0123456789abcdef")][..],
+                true,
+            ),
+            ("/api/v1/session?token=0123456789abcdef", &[], true),
+            ("/api/v1/session?other=1&token=0123456789abcdef", &[], true),
+            ("/api/v1/session", &[], false),
+            (
+                "/api/v1/session",
+                &[("Authorization", "Bearer 0123456789abcdeX")],
+                false,
+            ),
+            (
+                "/api/v1/session",
+                &[("Authorization", "Bearer 0123456789abcde")],
+                false,
+            ),
+            (
+                "/api/v1/session",
+                &[("Authorization", "0123456789abcdef")],
+                false,
+            ),
+            ("/api/v1/session?token=wrong", &[], false),
+            ("/api/v1/session?xtoken=0123456789abcdef", &[], false),
+        ];
+        for (uri, headers, expected) in cases {
+            assert_eq!(
+                is_authorized(&request(Method::GET, uri, headers), token),
+                expected,
+                "{uri} {headers:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_responses() {
+        let (events_sender, _events_receiver) = mpsc::channel();
+        let connection_context = Arc::new(ConnectionContext::new(
+            SESSION_MANAGER.read().settings(),
+            events_sender,
+        ));
+        let lifecycle_state = RwLock::new(LifecycleState::Resumed);
+        let remote_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1234);
+        let api_token = SESSION_MANAGER.read().session().api_token.clone();
+        let x_nanvr = ("X-NANVR", "true");
+
+        let call = |request| http_api(&connection_context, &lifecycle_state, remote_addr, request);
+
+        for request in [
+            request(Method::GET, "/api/v1/session", &[x_nanvr]),
+            request(Method::POST, "/api/v1/video/idr", &[x_nanvr]),
+            request(
+                Method::POST,
+                "/api/v1/video/idr",
+                &[x_nanvr, ("Authorization", "Bearer wrong")],
+            ),
+        ] {
+            let response = call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+        }
+
+        let bearer = format!("Bearer {api_token}");
+        let response = call(request(
+            Method::GET,
+            "/api/v1/session",
+            &[x_nanvr, ("Authorization", bearer.as_str())],
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call(request(
+            Method::POST,
+            &format!("/api/v1/video/idr?token={api_token}"),
+            &[x_nanvr],
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // Reading the version doesn't need the token
+        let response = call(request(Method::GET, "/api/v1/version", &[x_nanvr]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_accepts_plain_connection() {
+        let local_ip = "192.168.1.20".parse().unwrap();
+
+        assert!(accepts_plain_connection(
+            Ipv4Addr::LOCALHOST.into(),
+            Ipv4Addr::LOCALHOST.into()
+        ));
+        assert!(accepts_plain_connection("::1".parse().unwrap(), local_ip));
+        assert!(accepts_plain_connection(local_ip, local_ip));
+        assert!(!accepts_plain_connection(
+            "192.168.1.30".parse().unwrap(),
+            local_ip
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_accept_connection() {
+        let dir = env::temp_dir().join(format!("web_server_accept_test_{}", process::id()));
+        let identity =
+            server_io::web_server_tls_identity(&dir, &WebServerCertificate::SelfSigned).unwrap();
+        let mut root_certificates = RootCertStore::empty();
+        root_certificates
+            .add(identity.certificate_chain[0].clone())
+            .unwrap();
+        let acceptor = tls_acceptor(identity).unwrap();
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Plain connections from this PC are accepted even if TLS is enabled
+        let mut plain_client = TcpStream::connect(address).await.unwrap();
+        plain_client
+            .write_all(b"GET / HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(matches!(
+            accept_connection(stream, Some(acceptor.clone())).await,
+            Ok(Some(Either::Left(_)))
+        ));
+
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(root_certificates)
+            .with_no_client_auth();
+        let tls_client = tokio::spawn(async move {
+            let stream = TcpStream::connect(address).await.unwrap();
+            TlsConnector::from(Arc::new(config))
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(matches!(
+            accept_connection(stream, Some(acceptor)).await,
+            Ok(Some(Either::Right(_)))
+        ));
+        assert!(tls_client.await.unwrap().is_ok());
+
+        // Without an acceptor TLS cannot be negotiated
+        let tls_client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(&[TLS_HANDSHAKE_RECORD]).await.unwrap();
+            stream
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(matches!(accept_connection(stream, None).await, Ok(None)));
+        tls_client.await.unwrap();
+
+        fs::remove_dir_all(dir).ok();
+    }
 }
@@ -1,10 +1,14 @@
 mod firewall;
 mod openvr_drivers;
 mod openvrpaths;
+mod profiles;
+mod web_server_tls;
 
 pub use firewall::*;
 pub use openvr_drivers::*;
 pub use openvrpaths::*;
+pub use profiles::*;
+pub use web_server_tls::*;
 
 use configuration::{ClientConnectionConfig, SessionConfig, Settings};
 use events::EventType;
@@ -29,11 +33,21 @@ fn save_session(session: &SessionConfig, path: &Path) -> Result<()> {
     Ok(())
 }
 
+// Settings of the active client have its overrides applied
+fn effective_settings(session: &SessionConfig, active_client: Option<&str>) -> Settings {
+    if let Some(hostname) = active_client {
+        session.with_client_overrides(hostname).to_settings()
+    } else {
+        session.to_settings()
+    }
+}
+
 // SessionConfig wrapper that saves session.json on destruction.
 pub struct SessionLock<'a> {
     session_desc: &'a mut SessionConfig,
     session_path: Option<&'a Path>,
     settings: &'a mut Settings,
+    active_client: Option<&'a str>,
 }
 
 impl Deref for SessionLock<'_> {
@@ -55,7 +69,7 @@ impl Drop for SessionLock<'_> {
             save_session(self.session_desc, session_path).ok();
         }
 
-        *self.settings = self.session_desc.to_settings();
+        *self.settings = effective_settings(self.session_desc, self.active_client);
         events::send_event(EventType::Session(Box::new(self.session_desc.clone())));
     }
 }
@@ -69,6 +83,7 @@ pub struct ServerSessionManager {
     session_config: SessionConfig,
     settings: Settings,
     session_path: Option<PathBuf>,
+    active_client: Option<String>,
 }
 
 impl ServerSessionManager {
@@ -85,6 +100,7 @@ impl ServerSessionManager {
             session_config: session_config.clone(),
             settings: session_config.to_settings(),
             session_path,
+            active_client: None,
         }
     }
 
@@ -145,13 +161,21 @@ impl ServerSessionManager {
             session_desc: &mut self.session_config,
             session_path: self.session_path.as_deref(),
             settings: &mut self.settings,
+            active_client: self.active_client.as_deref(),
         }
     }
 
+    // Settings of the streaming client, with its overrides applied
     pub fn settings(&self) -> &Settings {
         &self.settings
     }
 
+    // Set the client whose settings overrides are applied to settings()
+    pub fn set_active_client(&mut self, hostname: Option<String>) {
+        self.active_client = hostname;
+        self.settings = effective_settings(&self.session_config, self.active_client.as_deref());
+    }
+
     // Note: "value" can be any session subtree, in json format.
     pub fn set_values(&mut self, descs: Vec<PathValuePair>) -> Result<()> {
         let mut session_json = serde_json::to_value(self.session_config.clone()).unwrap();
@@ -181,7 +205,7 @@ impl ServerSessionManager {
 
         // session_json has been updated
         self.session_config = serde_json::from_value(session_json)?;
-        self.settings = self.session_config.to_settings();
+        self.settings = effective_settings(&self.session_config, self.active_client.as_deref());
 
         if let Some(session_path) = &self.session_path {
             save_session(&self.session_config, session_path)?;
@@ -192,6 +216,24 @@ impl ServerSessionManager {
         Ok(())
     }
 
+    // Merge partial session settings on top of the current ones. Returns the paths of the changed
+    // settings that require a SteamVR restart.
+    pub fn apply_settings_overrides(&mut self, overrides: &json::Value) -> Result<Vec<String>> {
+        configuration::validate_settings_overrides(overrides)?;
+
+        let session_settings = self
+            .session_config
+            .session_settings_with_overrides(overrides)?;
+        let steamvr_restart_paths = configuration::steamvr_restart_paths(
+            &self.session_config.session_settings,
+            &session_settings,
+        );
+
+        self.session_mut().session_settings = session_settings;
+
+        Ok(steamvr_restart_paths)
+    }
+
     pub fn client_list(&self) -> &HashMap<String, ClientConnectionConfig> {
         &self.session_config.client_connections
     }
@@ -214,6 +256,7 @@ impl ServerSessionManager {
                         manual_ips: manual_ips.into_iter().collect(),
                         trusted,
                         connection_state: ConnectionState::Disconnected,
+                        settings_overrides: None,
                     };
                     new_entry.insert(client_connection_desc);
 
@@ -263,6 +306,18 @@ impl ServerSessionManager {
                 {
                     entry.get_mut().connection_state = state;
 
+                    updated = true;
+                }
+            }
+            ClientListAction::SetSettingsOverrides(overrides) => {
+                if let Some(Err(e)) = overrides
+                    .as_ref()
+                    .map(configuration::validate_settings_overrides)
+                {
+                    error!("Invalid settings overrides: {e}");
+                } else if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    entry.get_mut().settings_overrides = overrides;
+
                     updated = true;
                 }
             }
@@ -270,6 +325,7 @@ impl ServerSessionManager {
 
         if updated {
             self.session_config.client_connections = client_connections;
+            self.settings = effective_settings(&self.session_config, self.active_client.as_deref());
 
             if let Some(session_path) = &self.session_path {
                 save_session(&self.session_config, session_path).ok();
@@ -0,0 +1,182 @@
+// Named settings profiles: partial session settings stored as <name>.json in the presets dir. They
+// use the same format as the per-client settings overrides.
+
+use crate::ServerSessionManager;
+use events::EventType;
+use net_packets::SettingsProfileAction;
+use serde_json as json;
+use shared::{
+    anyhow::{Result, bail},
+    info, warn,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const PROFILE_EXTENSION: &str = "json";
+
+fn profile_path(presets_dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.trim().is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\', ':'])
+        || name.chars().any(char::is_control)
+    {
+        bail!("Invalid profile name \"{name}\"");
+    }
+
+    Ok(presets_dir.join(format!("{name}.{PROFILE_EXTENSION}")))
+}
+
+pub fn list_profiles(presets_dir: &Path) -> Result<Vec<String>> {
+    if !presets_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut names = fs::read_dir(presets_dir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != PROFILE_EXTENSION {
+                return None;
+            }
+
+            Some(path.file_stem()?.to_str()?.to_owned())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+
+    Ok(names)
+}
+
+pub fn load_profile(presets_dir: &Path, name: &str) -> Result<json::Value> {
+    let overrides = json::from_str(&fs::read_to_string(profile_path(presets_dir, name)?)?)?;
+    configuration::validate_settings_overrides(&overrides)?;
+
+    Ok(overrides)
+}
+
+pub fn save_profile(presets_dir: &Path, name: &str, overrides: &json::Value) -> Result<()> {
+    configuration::validate_settings_overrides(overrides)?;
+
+    fs::create_dir_all(presets_dir)?;
+    fs::write(
+        profile_path(presets_dir, name)?,
+        json::to_string_pretty(overrides)?,
+    )?;
+
+    Ok(())
+}
+
+pub fn rename_profile(presets_dir: &Path, name: &str, new_name: &str) -> Result<()> {
+    let path = profile_path(presets_dir, name)?;
+    let new_path = profile_path(presets_dir, new_name)?;
+
+    if new_path.exists() {
+        bail!("Profile \"{new_name}\" already exists");
+    }
+
+    fs::rename(path, new_path)?;
+
+    Ok(())
+}
+
+pub fn delete_profile(presets_dir: &Path, name: &str) -> Result<()> {
+    fs::remove_file(profile_path(presets_dir, name)?)?;
+
+    Ok(())
+}
+
+// Returns the event to report back to the dashboard
+pub fn settings_profile_action(
+    session_manager: &mut ServerSessionManager,
+    presets_dir: &Path,
+    action: SettingsProfileAction,
+) -> Result<EventType> {
+    match action {
+        SettingsProfileAction::List => (),
+        SettingsProfileAction::Save { name, profile } => {
+            save_profile(presets_dir, &name, &profile)?;
+        }
+        SettingsProfileAction::Apply(name) => {
+            let steamvr_restart_paths =
+                session_manager.apply_settings_overrides(&load_profile(presets_dir, &name)?)?;
+
+            if steamvr_restart_paths.is_empty() {
+                info!("Applied settings profile \"{name}\"");
+            } else {
+                warn!(
+                    "Applied settings profile \"{name}\". SteamVR must be restarted to apply: {}",
+                    steamvr_restart_paths.join(", ")
+                );
+            }
+
+            return Ok(EventType::SettingsProfileApplied {
+                name,
+                steamvr_restart_paths,
+            });
+        }
+        SettingsProfileAction::Rename { name, new_name } => {
+            rename_profile(presets_dir, &name, &new_name)?;
+        }
+        SettingsProfileAction::Delete(name) => delete_profile(presets_dir, &name)?,
+    }
+
+    Ok(EventType::SettingsProfiles(list_profiles(presets_dir)?))
+}
+
+fn json_diff(base: &json::Value, current: &json::Value) -> Option<json::Value> {
+    if base == current {
+        return None;
+    }
+
+    if let (json::Value::Object(base), json::Value::Object(current)) = (base, current) {
+        Some(json::Value::Object(
+            current
+                .iter()
+                .filter_map(|(key, value)| {
+                    let diff = json_diff(base.get(key).unwrap_or(&json::Value::Null), value)?;
+
+                    Some((key.clone(), diff))
+                })
+                .collect(),
+        ))
+    } else {
+        Some(current.clone())
+    }
+}
+
+// Minimal partial session settings that, applied on top of base_session_settings, give
+// session_settings
+pub fn session_settings_diff(
+    base_session_settings: &json::Value,
+    session_settings: &json::Value,
+) -> json::Value {
+    json_diff(base_session_settings, session_settings).unwrap_or_else(|| json::json!({}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_profiles_lifecycle() {
+        let dir = env::temp_dir().join(format!("profiles_test_{}", std::process::id()));
+        let overrides = json::json!({ "video": { "preferred_fps": 90.0 } });
+
+        save_profile(&dir, "Sim racing", &overrides).unwrap();
+        assert_eq!(list_profiles(&dir).unwrap(), vec!["Sim racing".to_owned()]);
+
+        rename_profile(&dir, "Sim racing", "Beat Saber").unwrap();
+        assert_eq!(load_profile(&dir, "Beat Saber").unwrap(), overrides);
+        assert!(load_profile(&dir, "Sim racing").is_err());
+
+        assert!(save_profile(&dir, "../escape", &overrides).is_err());
+        assert!(save_profile(&dir, "Invalid", &json::json!({ "unknown": 1 })).is_err());
+
+        delete_profile(&dir, "Beat Saber").unwrap();
+        assert!(list_profiles(&dir).unwrap().is_empty());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
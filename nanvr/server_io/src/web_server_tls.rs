@@ -0,0 +1,190 @@
+// Certificate used by the web server when TLS is enabled. Remote tools can pin its SHA-256
+// fingerprint instead of trusting a certificate authority.
+
+use configuration::WebServerCertificate;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use sha2::{Digest, Sha256};
+use shared::{
+    anyhow::{Context, Result},
+    info,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+const SELF_SIGNED_CERTIFICATE_FNAME: &str = "web_server_certificate.pem";
+const SELF_SIGNED_PRIVATE_KEY_FNAME: &str = "web_server_private_key.pem";
+
+pub struct WebServerTlsIdentity {
+    pub certificate_chain: Vec<CertificateDer<'static>>,
+    pub private_key: PrivateKeyDer<'static>,
+    // SHA-256 of the leaf certificate, as colon separated hex bytes
+    pub fingerprint: String,
+}
+
+fn generate_self_signed_certificate(
+    certificate_path: &Path,
+    private_key_path: &Path,
+) -> Result<()> {
+    let names = ["localhost", "127.0.0.1", "::1"].map(String::from).to_vec();
+    let rcgen::CertifiedKey { cert, key_pair } = rcgen::generate_simple_self_signed(names)?;
+
+    if let Some(dir) = certificate_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // The key is created with restricted permissions, so it is never readable by other users. A
+    // leftover key is replaced rather than reused.
+    if private_key_path.exists() {
+        fs::remove_file(private_key_path)?;
+    }
+    let mut private_key_options = OpenOptions::new();
+    private_key_options.create_new(true).write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        private_key_options.mode(0o600);
+    }
+    private_key_options
+        .open(private_key_path)?
+        .write_all(key_pair.serialize_pem().as_bytes())?;
+
+    fs::write(certificate_path, cert.pem())?;
+
+    info!(
+        "Generated a self-signed web server certificate at {}",
+        certificate_path.display()
+    );
+
+    Ok(())
+}
+
+fn certificate_fingerprint(certificate: &CertificateDer) -> String {
+    Sha256::digest(certificate)
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn certificate_paths(config_dir: &Path, certificate: &WebServerCertificate) -> (PathBuf, PathBuf) {
+    match certificate {
+        WebServerCertificate::SelfSigned => (
+            config_dir.join(SELF_SIGNED_CERTIFICATE_FNAME),
+            config_dir.join(SELF_SIGNED_PRIVATE_KEY_FNAME),
+        ),
+        WebServerCertificate::Custom {
+            certificate_path,
+            private_key_path,
+        } => (
+            PathBuf::from(certificate_path),
+            PathBuf::from(private_key_path),
+        ),
+    }
+}
+
+// Returns the certificate chain and the fingerprint of the leaf certificate
+fn read_certificate_chain(
+    certificate_path: &Path,
+) -> Result<(Vec<CertificateDer<'static>>, String)> {
+    let certificate_file = File::open(certificate_path)
+        .with_context(|| format!("Cannot open {}", certificate_path.display()))?;
+    let certificate_chain = rustls_pemfile::certs(&mut BufReader::new(certificate_file))
+        .collect::<Result<Vec<_>, _>>()?;
+    let fingerprint = certificate_chain
+        .first()
+        .map(certificate_fingerprint)
+        .with_context(|| format!("No certificate found in {}", certificate_path.display()))?;
+
+    Ok((certificate_chain, fingerprint))
+}
+
+// Loads the configured certificate. The self-signed certificate is generated if it doesn't exist,
+// so only the web server should call this
+pub fn web_server_tls_identity(
+    config_dir: &Path,
+    certificate: &WebServerCertificate,
+) -> Result<WebServerTlsIdentity> {
+    let (certificate_path, private_key_path) = certificate_paths(config_dir, certificate);
+    if matches!(certificate, WebServerCertificate::SelfSigned)
+        && (!certificate_path.exists() || !private_key_path.exists())
+    {
+        generate_self_signed_certificate(&certificate_path, &private_key_path)?;
+    }
+
+    let (certificate_chain, fingerprint) = read_certificate_chain(&certificate_path)?;
+
+    let private_key_file = File::open(&private_key_path)
+        .with_context(|| format!("Cannot open {}", private_key_path.display()))?;
+    let private_key = rustls_pemfile::private_key(&mut BufReader::new(private_key_file))?
+        .with_context(|| format!("No private key found in {}", private_key_path.display()))?;
+
+    Ok(WebServerTlsIdentity {
+        certificate_chain,
+        private_key,
+        fingerprint,
+    })
+}
+
+// Fingerprint of the certificate used by the web server. None if the self-signed certificate has
+// not been generated yet, which happens when the web server starts with TLS enabled
+pub fn web_server_certificate_fingerprint(
+    config_dir: &Path,
+    certificate: &WebServerCertificate,
+) -> Result<Option<String>> {
+    let (certificate_path, _) = certificate_paths(config_dir, certificate);
+    if matches!(certificate, WebServerCertificate::SelfSigned) && !certificate_path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(read_certificate_chain(&certificate_path)?.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_self_signed_identity() {
+        let dir = env::temp_dir().join(format!("web_server_tls_test_{}", std::process::id()));
+
+        // Reading the fingerprint never generates the certificate
+        assert!(
+            web_server_certificate_fingerprint(&dir, &WebServerCertificate::SelfSigned)
+                .unwrap()
+                .is_none()
+        );
+
+        let identity = web_server_tls_identity(&dir, &WebServerCertificate::SelfSigned).unwrap();
+        assert_eq!(identity.certificate_chain.len(), 1);
+        assert_eq!(identity.fingerprint.len(), 32 * 3 - 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(dir.join(SELF_SIGNED_PRIVATE_KEY_FNAME)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+
+        // The certificate is reused, so pinned fingerprints stay valid
+        let reloaded = web_server_tls_identity(&dir, &WebServerCertificate::SelfSigned).unwrap();
+        assert_eq!(reloaded.fingerprint, identity.fingerprint);
+        assert_eq!(
+            web_server_certificate_fingerprint(&dir, &WebServerCertificate::SelfSigned).unwrap(),
+            Some(identity.fingerprint.clone())
+        );
+
+        let custom = WebServerCertificate::Custom {
+            certificate_path: dir
+                .join(SELF_SIGNED_CERTIFICATE_FNAME)
+                .to_string_lossy()
+                .into(),
+            private_key_path: dir.join("missing.pem").to_string_lossy().into(),
+        };
+        assert!(web_server_tls_identity(&dir, &custom).is_err());
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -38,6 +38,8 @@ void Settings::Load() {
         m_refreshRate = (int)config.get("refresh_rate").get<int64_t>();
         m_renderWidth = config.get("eye_resolution_width").get<int64_t>() * 2;
         m_renderHeight = config.get("eye_resolution_height").get<int64_t>();
+        m_encodingWidth = config.get("encoding_eye_resolution_width").get<int64_t>() * 2;
+        m_encodingHeight = config.get("encoding_eye_resolution_height").get<int64_t>();
         m_recommendedTargetWidth = config.get("target_eye_resolution_width").get<int64_t>() * 2;
         m_recommendedTargetHeight = config.get("target_eye_resolution_height").get<int64_t>();
         m_captureFrameDir = config.get("capture_frame_dir").get<std::string>();
@@ -115,3 +117,65 @@ void Settings::Load() {
         Error("Exception on parsing session config (%s): %hs\n", g_sessionPath, e.what());
     }
 }
+
+std::shared_ptr<Settings> Settings::LoadSnapshot() {
+    auto settings = std::shared_ptr<Settings>(new Settings());
+    settings->Load();
+
+    return settings->m_loaded ? settings : nullptr;
+}
+
+void Settings::ApplyEncoderSettings(const Settings& settings) {
+    m_encodingWidth = settings.m_encodingWidth;
+    m_encodingHeight = settings.m_encodingHeight;
+    m_captureFrameDir = settings.m_captureFrameDir;
+
+    m_enableFoveatedEncoding = settings.m_enableFoveatedEncoding;
+    m_foveationCenterSizeX = settings.m_foveationCenterSizeX;
+    m_foveationCenterSizeY = settings.m_foveationCenterSizeY;
+    m_foveationCenterShiftX = settings.m_foveationCenterShiftX;
+    m_foveationCenterShiftY = settings.m_foveationCenterShiftY;
+    m_foveationEdgeRatioX = settings.m_foveationEdgeRatioX;
+    m_foveationEdgeRatioY = settings.m_foveationEdgeRatioY;
+
+    m_enableColorCorrection = settings.m_enableColorCorrection;
+    m_brightness = settings.m_brightness;
+    m_contrast = settings.m_contrast;
+    m_saturation = settings.m_saturation;
+    m_gamma = settings.m_gamma;
+    m_sharpening = settings.m_sharpening;
+
+    m_codec = settings.m_codec;
+    m_h264Profile = settings.m_h264Profile;
+    m_fillerData = settings.m_fillerData;
+    m_entropyCoding = settings.m_entropyCoding;
+    m_use10bitEncoder = settings.m_use10bitEncoder;
+    m_encodingGamma = settings.m_encodingGamma;
+    m_enableHdr = settings.m_enableHdr;
+    m_forceHdrSrgbCorrection = settings.m_forceHdrSrgbCorrection;
+    m_clampHdrExtendedRange = settings.m_clampHdrExtendedRange;
+    m_enableVbaq = settings.m_enableVbaq;
+    m_encoderQualityPreset = settings.m_encoderQualityPreset;
+    m_amdBitrateCorruptionFix = settings.m_amdBitrateCorruptionFix;
+    m_nvencQualityPreset = settings.m_nvencQualityPreset;
+    m_force_sw_encoding = settings.m_force_sw_encoding;
+    m_swThreadCount = settings.m_swThreadCount;
+
+    m_nvencTuningPreset = settings.m_nvencTuningPreset;
+    m_nvencMultiPass = settings.m_nvencMultiPass;
+    m_nvencAdaptiveQuantizationMode = settings.m_nvencAdaptiveQuantizationMode;
+    m_nvencLowDelayKeyFrameScale = settings.m_nvencLowDelayKeyFrameScale;
+    m_nvencRefreshRate = settings.m_nvencRefreshRate;
+    m_nvencEnableIntraRefresh = settings.m_nvencEnableIntraRefresh;
+    m_nvencIntraRefreshPeriod = settings.m_nvencIntraRefreshPeriod;
+    m_nvencIntraRefreshCount = settings.m_nvencIntraRefreshCount;
+    m_nvencMaxNumRefFrames = settings.m_nvencMaxNumRefFrames;
+    m_nvencGopLength = settings.m_nvencGopLength;
+    m_nvencPFrameStrategy = settings.m_nvencPFrameStrategy;
+    m_nvencRateControlMode = settings.m_nvencRateControlMode;
+    m_nvencRcBufferSize = settings.m_nvencRcBufferSize;
+    m_nvencRcInitialDelay = settings.m_nvencRcInitialDelay;
+    m_nvencRcMaxBitrate = settings.m_nvencRcMaxBitrate;
+    m_nvencRcAverageBitrate = settings.m_nvencRcAverageBitrate;
+    m_nvencEnableWeightedPrediction = settings.m_nvencEnableWeightedPrediction;
+}
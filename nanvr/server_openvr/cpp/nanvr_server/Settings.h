@@ -1,6 +1,7 @@
 #pragma once
 
 #include <cstdint>
+#include <memory>
 #include <string>
 
 class Settings {
@@ -8,17 +9,28 @@ class Settings {
     bool m_loaded;
 
     Settings();
-    virtual ~Settings();
 
 public:
+    virtual ~Settings();
+
     void Load();
     static Settings& Instance() { return m_Instance; }
 
+    // Loads the session file into a new instance, without touching the one in use by other
+    // threads. Returns null on error.
+    static std::shared_ptr<Settings> LoadSnapshot();
+
+    // Copies the fields that can change by renegotiating the stream. They are read only by the
+    // encoder thread, so this must be called from it, or before it is started.
+    void ApplyEncoderSettings(const Settings& settings);
+
     bool IsLoaded() { return m_loaded; }
 
     int m_refreshRate;
     uint32_t m_renderWidth;
     uint32_t m_renderHeight;
+    uint32_t m_encodingWidth;
+    uint32_t m_encodingHeight;
     int32_t m_recommendedTargetWidth;
     int32_t m_recommendedTargetHeight;
     std::string m_captureFrameDir;
@@ -340,6 +340,21 @@ void RequestIDR() {
     }
 }
 
+void ReconfigureStream() {
+    // Other threads keep reading the current settings, the encoder thread applies the new ones
+    // between frames
+    auto settings = Settings::LoadSnapshot();
+    if (!settings) {
+        return;
+    }
+
+    if (g_driver_provider.hmd && g_driver_provider.hmd->m_encoder) {
+        g_driver_provider.hmd->m_encoder->Reconfigure(settings);
+    } else {
+        Settings::Instance().ApplyEncoderSettings(*settings);
+    }
+}
+
 void SetTracking(
     unsigned long long targetTimestampNs,
     float controllerPoseTimeOffsetS,
@@ -201,94 +201,115 @@ void CEncoder::Run() {
 
         nanvr::VkContext vk_ctx(init.device_uuid.data(), {});
 
-        FrameRender render(vk_ctx, init, m_fds);
-        auto output = render.CreateOutput();
-
-        nanvr::VkFrame frame(
-            vk_ctx, output.image, output.imageInfo, output.size, output.memory, output.drm
-        );
-        auto encode_pipeline = nanvr::EncodePipeline::Create(
-            &render,
-            vk_ctx,
-            frame,
-            output.imageInfo,
-            render.GetEncodingWidth(),
-            render.GetEncodingHeight()
-        );
-
-        bool valid_timestamps = true;
-
-        fprintf(stderr, "CEncoder starting to read present packets");
-        present_packet frame_info;
         while (not m_exiting) {
-            read_latest(client, (char*)&frame_info, sizeof(frame_info), m_exiting);
+            {
+                std::unique_lock lock(m_reconfigureMutex);
+                m_reconfigure = false;
+                if (m_pendingSettings) {
+                    Settings::Instance().ApplyEncoderSettings(*m_pendingSettings);
+                    m_pendingSettings = nullptr;
+                }
+            }
 
-            encode_pipeline->SetParams(GetDynamicEncoderParams());
+            // The renderer imports copies of the fds, the originals are kept for when the encoder
+            // is recreated
+            FrameRender render(vk_ctx, init, m_fds);
+            auto output = render.CreateOutput();
 
-            auto pose = m_poseHistory->GetBestPoseMatch((const vr::HmdMatrix34_t&)frame_info.pose);
-            if (!pose) {
-                continue;
-            }
+            nanvr::VkFrame frame(
+                vk_ctx, output.image, output.imageInfo, output.size, output.memory, output.drm
+            );
+            auto encode_pipeline = nanvr::EncodePipeline::Create(
+                &render,
+                vk_ctx,
+                frame,
+                output.imageInfo,
+                render.GetEncodingWidth(),
+                render.GetEncodingHeight()
+            );
 
-            if (m_captureFrame) {
-                m_captureFrame = false;
-                render.CaptureInputFrame(
-                    Settings::Instance().m_captureFrameDir + "/nanvr_frame_input.ppm"
-                );
-                render.CaptureOutputFrame(
-                    Settings::Instance().m_captureFrameDir + "/nanvr_frame_output.ppm"
-                );
-            }
+            bool valid_timestamps = true;
 
-            render.Render(frame_info.image, frame_info.semaphore_value);
+            fprintf(stderr, "CEncoder starting to read present packets");
+            present_packet frame_info;
+            while (not m_exiting and not m_reconfigure) {
+                read_latest(client, (char*)&frame_info, sizeof(frame_info), m_exiting);
 
-            if (!valid_timestamps) {
-                ReportPresent(pose->targetTimestampNs, 0);
-                ReportComposed(pose->targetTimestampNs, 0);
-            }
+                encode_pipeline->SetParams(GetDynamicEncoderParams());
 
-            encode_pipeline->PushFrame(pose->targetTimestampNs, m_scheduler.CheckIDRInsertion());
+                auto pose
+                    = m_poseHistory->GetBestPoseMatch((const vr::HmdMatrix34_t&)frame_info.pose);
+                if (!pose) {
+                    continue;
+                }
 
-            static_assert(sizeof(frame_info.pose) == sizeof(vr::HmdMatrix34_t&));
+                if (m_captureFrame) {
+                    m_captureFrame = false;
+                    render.CaptureInputFrame(
+                        Settings::Instance().m_captureFrameDir + "/nanvr_frame_input.ppm"
+                    );
+                    render.CaptureOutputFrame(
+                        Settings::Instance().m_captureFrameDir + "/nanvr_frame_output.ppm"
+                    );
+                }
 
-            nanvr::FramePacket packet;
-            if (!encode_pipeline->GetEncoded(packet)) {
-                Error("Failed to get encoded data!");
-                continue;
-            }
+                render.Render(frame_info.image, frame_info.semaphore_value);
 
-            if (valid_timestamps) {
-                auto render_timestamps = render.GetTimestamps();
-                auto encode_timestamp = encode_pipeline->GetTimestamp();
-
-                uint64_t present_offset = render_timestamps.now - render_timestamps.renderBegin;
-                uint64_t composed_offset = 0;
-
-                valid_timestamps = render_timestamps.now != 0;
-
-                if (encode_timestamp.gpu) {
-                    composed_offset = render_timestamps.now - encode_timestamp.gpu;
-                } else if (encode_timestamp.cpu) {
-                    auto now = std::chrono::duration_cast<std::chrono::nanoseconds>(
-                                   std::chrono::steady_clock::now().time_since_epoch()
-                    )
-                                   .count();
-                    composed_offset = now - encode_timestamp.cpu;
-                } else {
-                    composed_offset = render_timestamps.now - render_timestamps.renderComplete;
+                if (!valid_timestamps) {
+                    ReportPresent(pose->targetTimestampNs, 0);
+                    ReportComposed(pose->targetTimestampNs, 0);
                 }
 
-                if (present_offset < composed_offset) {
-                    present_offset = composed_offset;
+                encode_pipeline->PushFrame(
+                    pose->targetTimestampNs, m_scheduler.CheckIDRInsertion()
+                );
+
+                static_assert(sizeof(frame_info.pose) == sizeof(vr::HmdMatrix34_t&));
+
+                nanvr::FramePacket packet;
+                if (!encode_pipeline->GetEncoded(packet)) {
+                    Error("Failed to get encoded data!");
+                    continue;
+                }
+
+                if (valid_timestamps) {
+                    auto render_timestamps = render.GetTimestamps();
+                    auto encode_timestamp = encode_pipeline->GetTimestamp();
+
+                    uint64_t present_offset = render_timestamps.now - render_timestamps.renderBegin;
+                    uint64_t composed_offset = 0;
+
+                    valid_timestamps = render_timestamps.now != 0;
+
+                    if (encode_timestamp.gpu) {
+                        composed_offset = render_timestamps.now - encode_timestamp.gpu;
+                    } else if (encode_timestamp.cpu) {
+                        auto now = std::chrono::duration_cast<std::chrono::nanoseconds>(
+                                       std::chrono::steady_clock::now().time_since_epoch()
+                        )
+                                       .count();
+                        composed_offset = now - encode_timestamp.cpu;
+                    } else {
+                        composed_offset = render_timestamps.now - render_timestamps.renderComplete;
+                    }
+
+                    if (present_offset < composed_offset) {
+                        present_offset = composed_offset;
+                    }
+
+                    ReportPresent(pose->targetTimestampNs, present_offset);
+                    ReportComposed(pose->targetTimestampNs, composed_offset);
                 }
 
-                ReportPresent(pose->targetTimestampNs, present_offset);
-                ReportComposed(pose->targetTimestampNs, composed_offset);
+                ParseFrameNals(
+                    encode_pipeline->GetCodec(), packet.data, packet.size, packet.pts, packet.isIDR
+                );
             }
 
-            ParseFrameNals(
-                encode_pipeline->GetCodec(), packet.data, packet.size, packet.pts, packet.isIDR
-            );
+            if (m_reconfigure) {
+                Info("CEncoder: Recreating encoder with new settings\n");
+                m_scheduler.InsertIDR();
+            }
         }
     } catch (std::exception& e) {
         std::stringstream err;
@@ -296,6 +317,13 @@ void CEncoder::Run() {
         Error(err.str().c_str());
     }
 
+    for (int& fd : m_fds) {
+        if (fd != -1) {
+            close(fd);
+            fd = -1;
+        }
+    }
+
     client.events = POLLHUP;
     close(client.fd);
 }
@@ -311,4 +339,10 @@ void CEncoder::OnStreamStart() { m_scheduler.OnStreamStart(); }
 
 void CEncoder::InsertIDR() { m_scheduler.InsertIDR(); }
 
+void CEncoder::Reconfigure(std::shared_ptr<Settings> settings) {
+    std::unique_lock lock(m_reconfigureMutex);
+    m_pendingSettings = settings;
+    m_reconfigure = true;
+}
+
 void CEncoder::CaptureFrame() { m_captureFrame = true; }
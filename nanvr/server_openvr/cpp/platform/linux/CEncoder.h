@@ -4,10 +4,12 @@
 #include "../../shared/threadtools.h"
 #include <atomic>
 #include <memory>
+#include <mutex>
 #include <poll.h>
 #include <sys/types.h>
 
 class PoseHistory;
+class Settings;
 
 class CEncoder : public CThread {
 public:
@@ -19,6 +21,7 @@ public:
     void Stop();
     void OnStreamStart();
     void InsertIDR();
+    void Reconfigure(std::shared_ptr<Settings> settings);
     bool IsConnected() { return m_connected; }
     void CaptureFrame();
 
@@ -29,7 +32,10 @@ private:
     IDRScheduler m_scheduler;
     pollfd m_socket;
     std::string m_socketPath;
-    int m_fds[6];
+    int m_fds[6] = { -1, -1, -1, -1, -1, -1 };
     bool m_connected = false;
     std::atomic_bool m_captureFrame = false;
+    std::atomic_bool m_reconfigure = false;
+    std::mutex m_reconfigureMutex;
+    std::shared_ptr<Settings> m_pendingSettings;
 };
@@ -27,8 +27,8 @@ FrameRender::FrameRender(nanvr::VkContext& ctx, init_packet& init, int fds[])
         AddImage(init.image_create_info, init.mem_index, fds[2 * i], fds[2 * i + 1]);
     }
 
-    m_width = Settings::Instance().m_renderWidth;
-    m_height = Settings::Instance().m_renderHeight;
+    m_width = Settings::Instance().m_encodingWidth;
+    m_height = Settings::Instance().m_encodingHeight;
 
     Info("FrameRender: Input size %ux%u", m_width, m_height);
 
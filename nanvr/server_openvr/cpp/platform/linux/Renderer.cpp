@@ -6,6 +6,7 @@
 #include <cstring>
 #include <fstream>
 #include <iostream>
+#include <unistd.h>
 
 #ifndef DRM_FORMAT_INVALID
 #define DRM_FORMAT_INVALID 0
@@ -212,7 +213,9 @@ void Renderer::AddImage(
     importMemInfo.sType = VK_STRUCTURE_TYPE_IMPORT_MEMORY_FD_INFO_KHR;
     importMemInfo.pNext = &dedicatedMemInfo;
     importMemInfo.handleType = VK_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD_BIT;
-    importMemInfo.fd = imageFd;
+    // Vulkan takes ownership of imported fds on success, copies are imported so that the caller
+    // keeps the originals
+    importMemInfo.fd = dup(imageFd);
 
     VkMemoryAllocateInfo memAllocInfo = {};
     memAllocInfo.sType = VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO;
@@ -221,7 +224,11 @@ void Renderer::AddImage(
     memAllocInfo.memoryTypeIndex = memoryIndex;
 
     VkDeviceMemory mem;
-    VK_CHECK(vkAllocateMemory(m_dev, &memAllocInfo, nullptr, &mem));
+    VkResult memResult = vkAllocateMemory(m_dev, &memAllocInfo, nullptr, &mem);
+    if (memResult != VK_SUCCESS) {
+        close(importMemInfo.fd);
+    }
+    VK_CHECK(memResult);
     VK_CHECK(vkBindImageMemory(m_dev, image, mem, 0));
 
     VkSemaphoreTypeCreateInfo timelineInfo = {};
@@ -238,8 +245,12 @@ void Renderer::AddImage(
     impSemInfo.sType = VK_STRUCTURE_TYPE_IMPORT_SEMAPHORE_FD_INFO_KHR;
     impSemInfo.semaphore = semaphore;
     impSemInfo.handleType = VK_EXTERNAL_SEMAPHORE_HANDLE_TYPE_OPAQUE_FD_BIT;
-    impSemInfo.fd = semaphoreFd;
-    VK_CHECK(d.vkImportSemaphoreFdKHR(m_dev, &impSemInfo));
+    impSemInfo.fd = dup(semaphoreFd);
+    VkResult semResult = d.vkImportSemaphoreFdKHR(m_dev, &impSemInfo);
+    if (semResult != VK_SUCCESS) {
+        close(impSemInfo.fd);
+    }
+    VK_CHECK(semResult);
 
     VkImageViewCreateInfo viewInfo = {};
     viewInfo.sType = VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO;
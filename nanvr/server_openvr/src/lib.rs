@@ -253,6 +253,7 @@ fn event_loop(events_receiver: mpsc::Receiver<ServerCoreEvent>) {
                     }
                 }
                 ServerCoreEvent::RequestIDR => unsafe { RequestIDR() },
+                ServerCoreEvent::ReconfigureStream => unsafe { ReconfigureStream() },
                 ServerCoreEvent::CaptureFrame => unsafe { CaptureFrame() },
                 ServerCoreEvent::GameRenderLatencyFeedback(game_latency) => {
                     if game_latency.as_secs_f32() > 0.25 {
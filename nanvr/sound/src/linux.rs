@@ -10,7 +10,7 @@ use std::{
     path::Path,
     sync::{
         Arc,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
     },
     thread::{self, sleep},
     time::Duration,
@@ -81,6 +81,23 @@ struct Terminate;
 //  possibly related to fast state changes caused by pavucontrol
 static MIC_STREAMING: AtomicBool = AtomicBool::new(false);
 
+// Stored as f32 bits, so they can be changed while streaming
+static GAME_AUDIO_GAIN: AtomicU32 = AtomicU32::new(1.0_f32.to_bits());
+static MICROPHONE_GAIN: AtomicU32 = AtomicU32::new(1.0_f32.to_bits());
+
+pub fn set_gains(game_audio_gain: f32, microphone_gain: f32) {
+    GAME_AUDIO_GAIN.store(game_audio_gain.to_bits(), Ordering::Relaxed);
+    MICROPHONE_GAIN.store(microphone_gain.to_bits(), Ordering::Relaxed);
+}
+
+fn apply_gain_s16le(buffer: &mut [u8], gain: f32) {
+    for chunk in buffer.chunks_exact_mut(2) {
+        let sample = i16::from_le_bytes([chunk[0], chunk[1]]) as f32 * gain;
+        let sample = sample.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        chunk.copy_from_slice(&sample.to_le_bytes());
+    }
+}
+
 pub fn audio_loop(
     is_running: impl Fn() -> bool,
     sender: StreamSender<()>,
@@ -247,9 +264,14 @@ fn create_speaker_stream(
                     // Data is given as s16le in the correct layout by pipewire already,
                     // no need to do conversions
                     let mut buffer = sender.get_buffer(&()).unwrap();
-                    buffer
-                        .get_range_mut(0, size)
-                        .copy_from_slice(&data[0..size]);
+                    let samples = buffer.get_range_mut(0, size);
+                    samples.copy_from_slice(&data[0..size]);
+
+                    let gain = f32::from_bits(GAME_AUDIO_GAIN.load(Ordering::Relaxed));
+                    if gain != 1.0 {
+                        apply_gain_s16le(samples, gain);
+                    }
+
                     sender.send(buffer).ok();
                 }
             }
@@ -342,9 +364,12 @@ fn fill_pw_buf(
                 .take(requested * chan_count);
             let pw_sample_count = it.len();
 
+            let gain = f32::from_bits(MICROPHONE_GAIN.load(Ordering::Relaxed));
+
             let (front, back) = samples.as_slices();
-            let copy_sample =
-                |(chunk, sample): (&mut [u8], &f32)| chunk.copy_from_slice(&sample.to_le_bytes());
+            let copy_sample = |(chunk, sample): (&mut [u8], &f32)| {
+                chunk.copy_from_slice(&(sample * gain).to_le_bytes())
+            };
 
             // Split up so the compiler actually optimizes this properly
             it.by_ref().zip(front).for_each(copy_sample);
@@ -360,3 +385,27 @@ fn fill_pw_buf(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gains() {
+        set_gains(0.5, 2.0);
+        assert_eq!(f32::from_bits(GAME_AUDIO_GAIN.load(Ordering::Relaxed)), 0.5);
+        assert_eq!(f32::from_bits(MICROPHONE_GAIN.load(Ordering::Relaxed)), 2.0);
+
+        let mut buffer = [1000_i16, -1000, 30000, i16::MIN]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<_>>();
+        apply_gain_s16le(&mut buffer, 2.0);
+
+        let samples = buffer
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect::<Vec<_>>();
+        assert_eq!(samples, [2000, -2000, i16::MAX, i16::MIN]);
+    }
+}
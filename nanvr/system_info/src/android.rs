@@ -129,6 +129,62 @@ pub fn local_ip() -> IpAddr {
     IpAddr::V4(Ipv4Addr::new(ip_arr[0], ip_arr[1], ip_arr[2], ip_arr[3]))
 }
 
+// Returns (RSSI in dBm, link speed in Mbps, frequency in MHz). Values unknown to the platform are
+// None.
+pub fn get_wifi_link_info() -> Option<(Option<i32>, Option<u32>, Option<u32>)> {
+    let vm = vm();
+    let mut env = vm.attach_current_thread().ok()?;
+
+    let wifi_manager = get_system_service(&mut env, "wifi");
+    let wifi_info = env
+        .call_method(
+            wifi_manager,
+            "getConnectionInfo",
+            "()Landroid/net/wifi/WifiInfo;",
+            &[],
+        )
+        .ok()?
+        .l()
+        .ok()?;
+    if wifi_info.is_null() {
+        return None;
+    }
+
+    let mut get_int = |method: &str| {
+        env.call_method(&wifi_info, method, "()I", &[])
+            .ok()?
+            .i()
+            .ok()
+    };
+    let rssi = get_int("getRssi");
+    let link_speed = get_int("getLinkSpeed");
+    let frequency = get_int("getFrequency");
+
+    Some((
+        // -127 is WifiInfo.INVALID_RSSI
+        rssi.filter(|rssi| *rssi > -127 && *rssi < 0),
+        link_speed.and_then(|speed| u32::try_from(speed).ok()),
+        frequency.and_then(|frequency| u32::try_from(frequency).ok()),
+    ))
+}
+
+// Returns the PowerManager thermal status, from 0 (none) to 6 (shutdown)
+pub fn get_thermal_status() -> Option<i32> {
+    if get_api_level() < 29 {
+        return None;
+    }
+
+    let vm = vm();
+    let mut env = vm.attach_current_thread().unwrap();
+
+    let power_manager = get_system_service(&mut env, "power");
+
+    env.call_method(power_manager, "getCurrentThermalStatus", "()I", &[])
+        .ok()?
+        .i()
+        .ok()
+}
+
 // This is needed to avoid wifi scans that disrupt streaming.
 // Code inspired from https://github.com/Meumeu/WiVRn/blob/master/client/application.cpp
 pub fn set_wifi_lock(enabled: bool) {
@@ -0,0 +1,25 @@
+use serde_json as json;
+use std::{fs, path::PathBuf};
+
+fn write_json(path: PathBuf, value: &json::Value) {
+    fs::write(&path, json::to_string_pretty(value).unwrap() + "\n").unwrap();
+
+    println!("Wrote {}", path.display());
+}
+
+// The same documents are served by the web server at /api/v1/settings/schema and /api/v1/openapi
+pub fn export_api_schemas(output: Option<String>) {
+    let output_dir = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| filepaths::build_dir().join("api"));
+    fs::create_dir_all(&output_dir).unwrap();
+
+    write_json(
+        output_dir.join("settings.schema.json"),
+        &configuration::settings_json_schema(),
+    );
+    write_json(
+        output_dir.join("openapi.json"),
+        &net_packets::openapi_document(),
+    );
+}
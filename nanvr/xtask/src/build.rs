@@ -159,6 +159,20 @@ pub fn build_streamer(
         .unwrap();
     }
 
+    // Build command-line control tool
+    {
+        let _push_guard = sh.push_dir(filepaths::crate_dir("nanvrctl"));
+        cmd!(sh, "cargo build {common_flags_ref...}").run().unwrap();
+
+        sh.copy_file(
+            artifacts_dir.join(filepaths::exec_fname("nanvrctl")),
+            build_layout
+                .executables_dir
+                .join(filepaths::exec_fname("nanvrctl")),
+        )
+        .unwrap();
+    }
+
     // build compositor wrapper
     let _push_guard = sh.push_dir(filepaths::crate_dir("vrcompositor_wrapper"));
     cmd!(sh, "cargo build {common_flags_ref...}").run().unwrap();
@@ -1,3 +1,4 @@
+mod api_schemas;
 mod build;
 mod ci;
 mod command;
@@ -148,6 +149,23 @@ enum Commands {
     CiClippy,
     /// Verify MSRV version
     CheckMsrv,
+    /// Write the session settings JSON Schema and the web API OpenAPI document
+    ExportApiSchemas {
+        /// Output folder. Defaults to build/api
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Replay a network trace (JSON) against the bitrate adaptation and export the simulated
+    /// frames
+    SimulateBitrate {
+        trace: String,
+        /// Output file, .csv or .json. Defaults to build/bitrate_simulation.csv
+        #[arg(long)]
+        output: Option<String>,
+        /// Session file whose bitrate settings are used. Defaults to the default settings
+        #[arg(long)]
+        session: Option<String>,
+    },
 }
 
 #[derive(Clone, ValueEnum)]
@@ -188,6 +206,27 @@ pub fn run_launcher() {
     cmd!(sh, "{launcher_exe}").run().unwrap();
 }
 
+pub fn simulate_bitrate(trace: String, output: Option<String>, session: Option<String>) {
+    let sh = Shell::new().unwrap();
+
+    let output = output.unwrap_or_else(|| {
+        let build_dir = filepaths::build_dir();
+        fs::create_dir_all(&build_dir).unwrap();
+
+        build_dir
+            .join("bitrate_simulation.csv")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    cmd!(
+        sh,
+        "cargo run -p server_core --example bitrate_simulator -- {trace} {output} {session...}"
+    )
+    .run()
+    .unwrap();
+}
+
 pub fn clean() {
     fs::remove_dir_all(filepaths::build_dir()).ok();
     fs::remove_dir_all(filepaths::deps_dir()).ok();
@@ -282,6 +321,12 @@ fn main() {
         Commands::ChangeVersion { version } => version::bump_version(version),
         Commands::CiClippy => ci::clippy_ci(),
         Commands::CheckMsrv => version::check_msrv(),
+        Commands::ExportApiSchemas { output } => api_schemas::export_api_schemas(output),
+        Commands::SimulateBitrate {
+            trace,
+            output,
+            session,
+        } => simulate_bitrate(trace, output, session),
     }
     let elapsed_time = begin_time.elapsed();
 